@@ -0,0 +1,675 @@
+use mmtk::util::ObjectReference;
+
+use crate::runtime::{EvalResult, TypeTag};
+use crate::sorted_functions::{sorted_assoc, sorted_dissoc, sorted_get};
+use crate::value::{GcPtr, NativeContext, ValueRef};
+
+// Updates on persistent vectors, maps and sets. Vectors are 32-way tries and
+// maps and sets are hash tries, so each update copies O(log32 n) nodes and shares
+// the rest with the original. Transients batch several updates in place and are
+// frozen again with persistent!. Sorted maps and sets take the same updates and
+// copy O(log n) tree nodes; they have no transients.
+
+macro_rules! try_value {
+    ($expr:expr) => {
+        match $expr {
+            Ok(value) => value,
+            Err(error) => return EvalResult::Value(error),
+        }
+    };
+}
+
+fn heap_value(object: ObjectReference) -> ValueRef {
+    ValueRef::Heap(GcPtr::new(object))
+}
+
+fn collection_object(value: ValueRef) -> Option<(ObjectReference, TypeTag)> {
+    match value {
+        ValueRef::Heap(gc_ptr) => match gc_ptr.type_tag() {
+            tag @ (TypeTag::Vector | TypeTag::Map | TypeTag::Set | TypeTag::SortedMap | TypeTag::SortedSet) => {
+                Some((gc_ptr.0, tag))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn is_transient(ctx: &NativeContext, object: ObjectReference, tag: TypeTag) -> bool {
+    let vm = ctx.vm();
+    match tag {
+        TypeTag::Vector => vm.vector_is_transient(object),
+        TypeTag::Map => vm.hashmap_is_transient(object),
+        TypeTag::Set => vm.hashset_is_transient(object),
+        _ => false,
+    }
+}
+
+// A persistent vector, map or set; transients must go through the ! variants
+fn persistent_arg(ctx: &NativeContext, value: ValueRef, function_name: &str) -> Result<(ObjectReference, TypeTag), ValueRef> {
+    match collection_object(value) {
+        Some((object, tag)) if is_transient(ctx, object, tag) => Err(ctx.eval_error(&format!(
+            "{}: cannot update a transient {}, call persistent! first",
+            function_name,
+            tag.to_str()
+        ))),
+        Some(collection) => Ok(collection),
+        None => Err(ctx.type_error("a vector, map or set", ctx.type_name(value), function_name)),
+    }
+}
+
+fn transient_arg(ctx: &NativeContext, value: ValueRef, function_name: &str) -> Result<(ObjectReference, TypeTag), ValueRef> {
+    match collection_object(value) {
+        Some((object, tag)) if is_transient(ctx, object, tag) => Ok((object, tag)),
+        _ => Err(ctx.type_error("a transient", ctx.type_name(value), function_name)),
+    }
+}
+
+fn index_arg(ctx: &NativeContext, value: ValueRef, function_name: &str) -> Result<u32, ValueRef> {
+    match ctx.get_number(value) {
+        Some(n) if n >= 0.0 && n.fract() == 0.0 && n <= u32::MAX as f64 => Ok(n as u32),
+        _ => Err(ctx.type_error("a non-negative integer index", ctx.type_name(value), function_name)),
+    }
+}
+
+// Map entries are conj'ed as [key value] vectors
+fn entry_arg(ctx: &NativeContext, value: ValueRef, function_name: &str) -> Result<(ValueRef, ValueRef), ValueRef> {
+    match value.get_vec() {
+        Some(entry) if entry.len() == 2 => Ok((entry[0], entry[1])),
+        _ => Err(ctx.eval_error(&format!("{}: map entries must be [key value] vectors", function_name))),
+    }
+}
+
+fn key_value_pairs(ctx: &NativeContext, args: &[ValueRef], function_name: &str) -> Result<Vec<(ValueRef, ValueRef)>, ValueRef> {
+    if args.is_empty() || args.len() % 2 != 0 {
+        return Err(ctx.eval_error(&format!("{} expects keys and values in pairs", function_name)));
+    }
+    Ok(args.chunks_exact(2).map(|pair| (pair[0], pair[1])).collect())
+}
+
+fn assoc_pairs(ctx: &mut NativeContext, object: ObjectReference, tag: TypeTag, pairs: Vec<(ValueRef, ValueRef)>, function_name: &str) -> Result<ObjectReference, ValueRef> {
+    let vm = ctx.vm().clone();
+    let mut result = object;
+    for (key, value) in pairs {
+        result = match tag {
+            TypeTag::Vector => {
+                let index = index_arg(ctx, key, function_name)?;
+                vm.vector_assoc(result, index, value)
+                    .map_err(|message| ctx.eval_error(&format!("{}: {}", function_name, message)))?
+            }
+            TypeTag::Map => vm.hashmap_assoc(result, key, value),
+            TypeTag::SortedMap => sorted_assoc(ctx, result, tag, key, value)?,
+            _ => return Err(ctx.type_error("a vector or map", tag.to_str(), function_name)),
+        };
+    }
+    Ok(result)
+}
+
+pub fn native_conj(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    try_value!(ctx.require_min_arity(&args, 2, "conj"));
+
+    // Lists grow at the front, so (conj nil x) and (conj list x) prepend
+    if ctx.is_nil(args[0]) || args[0].get_list().is_some() {
+        let mut items = args[0].get_list().unwrap_or_default();
+        for item in &args[1..] {
+            items.insert(0, *item);
+        }
+        return EvalResult::Value(ctx.list(items));
+    }
+
+    let (mut result, tag) = try_value!(persistent_arg(ctx, args[0], "conj"));
+    let vm = ctx.vm().clone();
+    for item in &args[1..] {
+        result = match tag {
+            TypeTag::Vector => vm.vector_conj(result, *item),
+            TypeTag::Set => vm.hashset_conj(result, *item),
+            TypeTag::SortedSet => try_value!(sorted_assoc(ctx, result, tag, *item, ValueRef::nil())),
+            TypeTag::SortedMap => {
+                let (key, value) = try_value!(entry_arg(ctx, *item, "conj"));
+                try_value!(sorted_assoc(ctx, result, tag, key, value))
+            }
+            _ => {
+                let (key, value) = try_value!(entry_arg(ctx, *item, "conj"));
+                vm.hashmap_assoc(result, key, value)
+            }
+        };
+    }
+    EvalResult::Value(heap_value(result))
+}
+
+pub fn native_assoc(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    try_value!(ctx.require_min_arity(&args, 3, "assoc"));
+    let pairs = try_value!(key_value_pairs(ctx, &args[1..], "assoc"));
+
+    // (assoc nil k v) starts a new map
+    if ctx.is_nil(args[0]) {
+        return EvalResult::Value(ctx.hash_map(pairs));
+    }
+
+    let (object, tag) = try_value!(persistent_arg(ctx, args[0], "assoc"));
+    let result = try_value!(assoc_pairs(ctx, object, tag, pairs, "assoc"));
+    EvalResult::Value(heap_value(result))
+}
+
+pub fn native_dissoc(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    try_value!(ctx.require_min_arity(&args, 1, "dissoc"));
+    if ctx.is_nil(args[0]) {
+        return EvalResult::Value(ctx.nil());
+    }
+
+    let (mut result, tag) = try_value!(persistent_arg(ctx, args[0], "dissoc"));
+    if !matches!(tag, TypeTag::Map | TypeTag::SortedMap) {
+        return EvalResult::Value(ctx.type_error("a map", tag.to_str(), "dissoc"));
+    }
+    for key in &args[1..] {
+        result = match tag {
+            TypeTag::SortedMap => try_value!(sorted_dissoc(ctx, result, tag, *key)),
+            _ => ctx.vm().hashmap_dissoc(result, key),
+        };
+    }
+    EvalResult::Value(heap_value(result))
+}
+
+pub fn native_disj(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (disj set x ...) removes members from a set or sorted set
+    try_value!(ctx.require_min_arity(&args, 1, "disj"));
+    if ctx.is_nil(args[0]) {
+        return EvalResult::Value(ctx.nil());
+    }
+
+    let (mut result, tag) = try_value!(persistent_arg(ctx, args[0], "disj"));
+    if !matches!(tag, TypeTag::Set | TypeTag::SortedSet) {
+        return EvalResult::Value(ctx.type_error("a set", tag.to_str(), "disj"));
+    }
+    for member in &args[1..] {
+        result = match tag {
+            TypeTag::SortedSet => try_value!(sorted_dissoc(ctx, result, tag, *member)),
+            _ => ctx.vm().hashset_disj(result, member),
+        };
+    }
+    EvalResult::Value(heap_value(result))
+}
+
+pub fn native_update(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (update coll key f & args) replaces the value at key with (f old-value & args)
+    try_value!(ctx.require_min_arity(&args, 3, "update"));
+    let key = args[1];
+    let f = args[2];
+    if !ctx.is_callable(f) {
+        return EvalResult::Value(ctx.type_error("a function", ctx.type_name(f), "update"));
+    }
+
+    let (object, tag) = if ctx.is_nil(args[0]) {
+        let empty = ctx.hash_map(vec![]);
+        try_value!(persistent_arg(ctx, empty, "update"))
+    } else {
+        try_value!(persistent_arg(ctx, args[0], "update"))
+    };
+
+    let current = match tag {
+        TypeTag::Vector => {
+            let index = try_value!(index_arg(ctx, key, "update"));
+            ctx.vm().vector_get_at(object, index).unwrap_or_else(|_| ValueRef::nil())
+        }
+        TypeTag::Map => ctx.vm().hashmap_get(object, &key).unwrap_or_else(ValueRef::nil),
+        TypeTag::SortedMap => try_value!(sorted_get(ctx, object, key)).map_or_else(ValueRef::nil, |(_, value)| value),
+        _ => return EvalResult::Value(ctx.type_error("a vector or map", tag.to_str(), "update")),
+    };
+
+    // A map made for a nil collection is only held here while f runs
+    ctx.root(&[heap_value(object)]);
+    let mut call_args = vec![current];
+    call_args.extend_from_slice(&args[3..]);
+    let updated = try_value!(ctx.call(f, call_args));
+
+    let result = try_value!(assoc_pairs(ctx, object, tag, vec![(key, updated)], "update"));
+    EvalResult::Value(heap_value(result))
+}
+
+// === Transients ===
+
+pub fn native_transient(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    try_value!(ctx.require_arity(&args, 1, "transient"));
+    let (object, tag) = try_value!(persistent_arg(ctx, args[0], "transient"));
+    let vm = ctx.vm();
+    let transient = match tag {
+        TypeTag::Vector => vm.vector_transient(object),
+        TypeTag::Map => vm.hashmap_transient(object),
+        TypeTag::Set => vm.hashset_transient(object),
+        _ => return EvalResult::Value(ctx.type_error("a vector, map or set", tag.to_str(), "transient")),
+    };
+    EvalResult::Value(heap_value(transient))
+}
+
+pub fn native_persistent(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    try_value!(ctx.require_arity(&args, 1, "persistent!"));
+    let (object, tag) = try_value!(transient_arg(ctx, args[0], "persistent!"));
+    let vm = ctx.vm();
+    let frozen = match tag {
+        TypeTag::Vector => vm.vector_persistent(object),
+        TypeTag::Map => vm.hashmap_persistent(object),
+        _ => vm.hashset_persistent(object),
+    };
+    match frozen {
+        Ok(object) => EvalResult::Value(heap_value(object)),
+        Err(message) => EvalResult::Value(ctx.eval_error(&message)),
+    }
+}
+
+pub fn native_conj_mut(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    try_value!(ctx.require_min_arity(&args, 2, "conj!"));
+    let (object, tag) = try_value!(transient_arg(ctx, args[0], "conj!"));
+    let vm = ctx.vm().clone();
+    for item in &args[1..] {
+        let result = match tag {
+            TypeTag::Vector => vm.vector_conj_mut(object, *item),
+            TypeTag::Set => vm.hashset_conj_mut(object, *item),
+            _ => {
+                let (key, value) = try_value!(entry_arg(ctx, *item, "conj!"));
+                vm.hashmap_assoc_mut(object, key, value)
+            }
+        };
+        if let Err(message) = result {
+            return EvalResult::Value(ctx.eval_error(&message));
+        }
+    }
+    EvalResult::Value(args[0])
+}
+
+pub fn native_assoc_mut(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    try_value!(ctx.require_min_arity(&args, 3, "assoc!"));
+    let (object, tag) = try_value!(transient_arg(ctx, args[0], "assoc!"));
+    let pairs = try_value!(key_value_pairs(ctx, &args[1..], "assoc!"));
+    let vm = ctx.vm().clone();
+    for (key, value) in pairs {
+        let result = match tag {
+            TypeTag::Vector => {
+                let index = try_value!(index_arg(ctx, key, "assoc!"));
+                vm.vector_assoc_mut(object, index, value)
+            }
+            TypeTag::Map => vm.hashmap_assoc_mut(object, key, value),
+            _ => return EvalResult::Value(ctx.type_error("a transient vector or map", tag.to_str(), "assoc!")),
+        };
+        if let Err(message) = result {
+            return EvalResult::Value(ctx.eval_error(&format!("assoc!: {}", message)));
+        }
+    }
+    EvalResult::Value(args[0])
+}
+
+pub fn native_dissoc_mut(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    try_value!(ctx.require_min_arity(&args, 1, "dissoc!"));
+    let (object, tag) = try_value!(transient_arg(ctx, args[0], "dissoc!"));
+    if tag != TypeTag::Map {
+        return EvalResult::Value(ctx.type_error("a transient map", tag.to_str(), "dissoc!"));
+    }
+    for key in &args[1..] {
+        if let Err(message) = ctx.vm().hashmap_dissoc_mut(object, key) {
+            return EvalResult::Value(ctx.eval_error(&message));
+        }
+    }
+    EvalResult::Value(args[0])
+}
+
+// === Maps ===
+
+// Value stored under key in a map, vector or set, None when absent. Fails only
+// when a sorted collection's comparator does.
+fn lookup(ctx: &mut NativeContext, coll: ValueRef, key: ValueRef) -> Result<Option<ValueRef>, ValueRef> {
+    let Some((object, tag)) = collection_object(coll) else {
+        return Ok(None);
+    };
+    let vm = ctx.vm();
+    Ok(match tag {
+        TypeTag::Map => vm.hashmap_get(object, &key),
+        TypeTag::Set => vm.hashset_contains(object, &key).then_some(key),
+        TypeTag::SortedMap => sorted_get(ctx, object, key)?.map(|(_, value)| value),
+        TypeTag::SortedSet => sorted_get(ctx, object, key)?.map(|(member, _)| member),
+        _ => ctx
+            .get_number(key)
+            .filter(|n| *n >= 0.0 && n.fract() == 0.0)
+            .and_then(|index| vm.vector_get_at(object, index as u32).ok()),
+    })
+}
+
+fn assoc_one(ctx: &mut NativeContext, coll: ValueRef, key: ValueRef, value: ValueRef, function_name: &str) -> Result<ValueRef, ValueRef> {
+    if ctx.is_nil(coll) {
+        return Ok(ctx.hash_map(vec![(key, value)]));
+    }
+    let (object, tag) = persistent_arg(ctx, coll, function_name)?;
+    Ok(heap_value(assoc_pairs(ctx, object, tag, vec![(key, value)], function_name)?))
+}
+
+fn map_arg(ctx: &NativeContext, value: ValueRef, function_name: &str) -> Result<ObjectReference, ValueRef> {
+    match persistent_arg(ctx, value, function_name)? {
+        (object, TypeTag::Map) => Ok(object),
+        _ => Err(ctx.type_error("a map", ctx.type_name(value), function_name)),
+    }
+}
+
+fn path_arg(ctx: &mut NativeContext, value: ValueRef, function_name: &str) -> Result<Vec<ValueRef>, ValueRef> {
+    match ctx.seq_to_vec(value) {
+        Ok(path) if !path.is_empty() => Ok(path),
+        Ok(_) => Err(ctx.eval_error(&format!("{}: the key path is empty", function_name))),
+        Err(_) => Err(ctx.type_error("a vector of keys", ctx.type_name(value), function_name)),
+    }
+}
+
+fn assoc_path(ctx: &mut NativeContext, coll: ValueRef, path: &[ValueRef], value: ValueRef, function_name: &str) -> Result<ValueRef, ValueRef> {
+    let inner = match path {
+        [_] => value,
+        // Missing levels are created as maps
+        [key, rest @ ..] => {
+            let child = lookup(ctx, coll, *key)?.unwrap_or_else(ValueRef::nil);
+            assoc_path(ctx, child, rest, value, function_name)?
+        }
+        [] => unreachable!("key paths are never empty"),
+    };
+    assoc_one(ctx, coll, path[0], inner, function_name)
+}
+
+// Entries of a hash map, or of a sorted map in order
+fn map_entries(ctx: &NativeContext, value: ValueRef, function_name: &str) -> Result<Vec<(ValueRef, ValueRef)>, ValueRef> {
+    if let Some(sorted) = value.heap_object(TypeTag::SortedMap) {
+        let vm = ctx.vm();
+        return Ok(vm.sorted_entries(vm.sorted_root(sorted)));
+    }
+    Ok(ctx.vm().hashmap_entries(map_arg(ctx, value, function_name)?))
+}
+
+pub fn native_keys(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    try_value!(ctx.require_arity(&args, 1, "keys"));
+    if ctx.is_nil(args[0]) {
+        return EvalResult::Value(ctx.nil());
+    }
+    let entries = try_value!(map_entries(ctx, args[0], "keys"));
+    let keys: Vec<ValueRef> = entries.into_iter().map(|(key, _)| key).collect();
+    if keys.is_empty() {
+        return EvalResult::Value(ctx.nil());
+    }
+    EvalResult::Value(ctx.list(keys))
+}
+
+pub fn native_vals(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    try_value!(ctx.require_arity(&args, 1, "vals"));
+    if ctx.is_nil(args[0]) {
+        return EvalResult::Value(ctx.nil());
+    }
+    let entries = try_value!(map_entries(ctx, args[0], "vals"));
+    let vals: Vec<ValueRef> = entries.into_iter().map(|(_, value)| value).collect();
+    if vals.is_empty() {
+        return EvalResult::Value(ctx.nil());
+    }
+    EvalResult::Value(ctx.list(vals))
+}
+
+pub fn native_contains(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // Keys of a map, members of a set and indices of a vector are contained
+    try_value!(ctx.require_arity(&args, 2, "contains?"));
+    if ctx.is_nil(args[0]) {
+        return EvalResult::Value(ctx.bool(false));
+    }
+    let (object, tag) = match collection_object(args[0]) {
+        Some(collection) => collection,
+        None => return EvalResult::Value(ctx.type_error("a map, set or vector", ctx.type_name(args[0]), "contains?")),
+    };
+    let vm = ctx.vm();
+    let contained = match tag {
+        TypeTag::Map => vm.hashmap_contains_key(object, &args[1]),
+        TypeTag::Set => vm.hashset_contains(object, &args[1]),
+        TypeTag::SortedMap | TypeTag::SortedSet => try_value!(sorted_get(ctx, object, args[1])).is_some(),
+        _ => try_value!(lookup(ctx, args[0], args[1])).is_some(),
+    };
+    EvalResult::Value(ctx.bool(contained))
+}
+
+pub fn native_get_in(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (get-in coll [k1 k2 ...]) or (get-in coll keys not-found)
+    if args.len() != 2 && args.len() != 3 {
+        return EvalResult::Value(ctx.arity_error(2, args.len(), "get-in (or 3)"));
+    }
+    let not_found = args.get(2).copied().unwrap_or_else(ValueRef::nil);
+    let path = match ctx.seq_to_vec(args[1]) {
+        Ok(path) => path,
+        Err(_) => return EvalResult::Value(ctx.type_error("a vector of keys", ctx.type_name(args[1]), "get-in")),
+    };
+    let mut current = args[0];
+    for key in path {
+        match try_value!(lookup(ctx, current, key)) {
+            Some(value) => current = value,
+            None => return EvalResult::Value(not_found),
+        }
+    }
+    EvalResult::Value(current)
+}
+
+pub fn native_assoc_in(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (assoc-in coll [k1 k2 ...] value)
+    try_value!(ctx.require_arity(&args, 3, "assoc-in"));
+    let path = try_value!(path_arg(ctx, args[1], "assoc-in"));
+    EvalResult::Value(try_value!(assoc_path(ctx, args[0], &path, args[2], "assoc-in")))
+}
+
+pub fn native_update_in(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (update-in coll [k1 k2 ...] f & args)
+    try_value!(ctx.require_min_arity(&args, 3, "update-in"));
+    let path = try_value!(path_arg(ctx, args[1], "update-in"));
+    let f = args[2];
+    if !ctx.is_callable(f) {
+        return EvalResult::Value(ctx.type_error("a function", ctx.type_name(f), "update-in"));
+    }
+
+    let mut current = args[0];
+    for key in &path {
+        current = try_value!(lookup(ctx, current, *key)).unwrap_or_else(ValueRef::nil);
+    }
+    let mut call_args = vec![current];
+    call_args.extend_from_slice(&args[3..]);
+    let updated = try_value!(ctx.call(f, call_args));
+    EvalResult::Value(try_value!(assoc_path(ctx, args[0], &path, updated, "update-in")))
+}
+
+// Merge maps left to right into a transient copy of the first. `combine` decides
+// the value of a key found in both.
+fn merge_maps<F>(ctx: &mut NativeContext, maps: &[ValueRef], function_name: &str, mut combine: F) -> Result<ValueRef, ValueRef>
+where
+    F: FnMut(&mut NativeContext, ValueRef, ValueRef) -> Result<ValueRef, ValueRef>,
+{
+    let maps: Vec<ValueRef> = maps.iter().copied().filter(|map| !ctx.is_nil(*map)).collect();
+    let Some(first) = maps.first() else {
+        return Ok(ctx.nil());
+    };
+    let vm = ctx.vm().clone();
+    let merged = vm.hashmap_transient(map_arg(ctx, *first, function_name)?);
+    ctx.root(&[heap_value(merged)]);
+    for map in &maps[1..] {
+        let map = map_arg(ctx, *map, function_name)?;
+        for (key, value) in vm.hashmap_entries(map) {
+            let value = match vm.hashmap_get(merged, &key) {
+                Some(existing) => combine(ctx, existing, value)?,
+                None => value,
+            };
+            vm.hashmap_assoc_mut(merged, key, value).map_err(|message| ctx.eval_error(&message))?;
+        }
+    }
+    vm.hashmap_persistent(merged)
+        .map(heap_value)
+        .map_err(|message| ctx.eval_error(&message))
+}
+
+pub fn native_merge(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // Later maps win; nil arguments are skipped
+    EvalResult::Value(try_value!(merge_maps(ctx, &args, "merge", |_, _, value| Ok(value))))
+}
+
+pub fn native_merge_with(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (merge-with f map ...) combines values of shared keys with (f old new)
+    try_value!(ctx.require_min_arity(&args, 1, "merge-with"));
+    let f = args[0];
+    if !ctx.is_callable(f) {
+        return EvalResult::Value(ctx.type_error("a function", ctx.type_name(f), "merge-with"));
+    }
+    EvalResult::Value(try_value!(merge_maps(ctx, &args[1..], "merge-with", |ctx, old, new| ctx.call(f, vec![old, new]))))
+}
+
+pub fn native_select_keys(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    try_value!(ctx.require_arity(&args, 2, "select-keys"));
+    let keys = match ctx.seq_to_vec(args[1]) {
+        Ok(keys) => keys,
+        Err(_) => return EvalResult::Value(ctx.type_error("a collection of keys", ctx.type_name(args[1]), "select-keys")),
+    };
+    if ctx.is_nil(args[0]) {
+        return EvalResult::Value(ctx.hash_map(vec![]));
+    }
+    let map = try_value!(map_arg(ctx, args[0], "select-keys"));
+    let vm = ctx.vm();
+    let pairs = keys
+        .into_iter()
+        .filter_map(|key| vm.hashmap_get(map, &key).map(|value| (key, value)))
+        .collect();
+    EvalResult::Value(ctx.hash_map(pairs))
+}
+
+pub fn native_zipmap(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (zipmap keys vals) pairs them up until either runs out
+    try_value!(ctx.require_arity(&args, 2, "zipmap"));
+    let mut pairs = Vec::new();
+    let (mut keys, mut vals) = (args[0], args[1]);
+    while let (Some((key, rest_keys)), Some((value, rest_vals))) = (try_value!(ctx.seq_step(keys)), try_value!(ctx.seq_step(vals))) {
+        pairs.push((key, value));
+        keys = rest_keys;
+        vals = rest_vals;
+    }
+    EvalResult::Value(ctx.hash_map(pairs))
+}
+
+pub fn native_group_by(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (group-by f coll) maps each (f item) to a vector of the items giving it
+    try_value!(ctx.require_arity(&args, 2, "group-by"));
+    let f = args[0];
+    if !ctx.is_callable(f) {
+        return EvalResult::Value(ctx.type_error("a function", ctx.type_name(f), "group-by"));
+    }
+    let items = try_value!(ctx.seq_to_vec(args[1]));
+    let vm = ctx.vm().clone();
+    let empty = ctx.hash_map(vec![]);
+    let groups = vm.hashmap_transient(try_value!(map_arg(ctx, empty, "group-by")));
+    ctx.root(&items);
+    ctx.root(&[heap_value(groups)]);
+    for item in items {
+        let key = try_value!(ctx.call(f, vec![item]));
+        let group = match vm.hashmap_get(groups, &key) {
+            Some(group) => vm.vector_conj(group.heap_object(TypeTag::Vector).expect("groups are vectors"), item),
+            None => vm.alloc_vec(vec![item]),
+        };
+        if let Err(message) = vm.hashmap_assoc_mut(groups, key, heap_value(group)) {
+            return EvalResult::Value(ctx.eval_error(&message));
+        }
+    }
+    match vm.hashmap_persistent(groups) {
+        Ok(groups) => EvalResult::Value(heap_value(groups)),
+        Err(message) => EvalResult::Value(ctx.eval_error(&message)),
+    }
+}
+
+pub fn native_frequencies(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (frequencies coll) maps each distinct item to the number of times it occurs
+    try_value!(ctx.require_arity(&args, 1, "frequencies"));
+    let items = try_value!(ctx.seq_to_vec(args[0]));
+    let vm = ctx.vm().clone();
+    let empty = ctx.hash_map(vec![]);
+    let counts = vm.hashmap_transient(try_value!(map_arg(ctx, empty, "frequencies")));
+    for item in items {
+        let count = vm.hashmap_get(counts, &item).and_then(|count| count.get_number()).unwrap_or(0.0);
+        if let Err(message) = vm.hashmap_assoc_mut(counts, item, ctx.number(count + 1.0)) {
+            return EvalResult::Value(ctx.eval_error(&message));
+        }
+    }
+    match vm.hashmap_persistent(counts) {
+        Ok(counts) => EvalResult::Value(heap_value(counts)),
+        Err(message) => EvalResult::Value(ctx.eval_error(&message)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::ExecutionContext;
+    use crate::test_support::{context, eval, try_eval};
+
+    // Whether the code evaluates to a value equal to `expected`
+    fn returns(ctx: &mut ExecutionContext, code: &str, expected: &str) -> bool {
+        eval(ctx, &format!("(= {} {})", code, expected)) == "true"
+    }
+
+    // The message of an error, whether it was raised or returned as a value
+    fn error_message(ctx: &mut ExecutionContext, code: &str) -> String {
+        try_eval(ctx, code).unwrap_or_else(|error| error)
+    }
+
+    #[test]
+    fn nested_paths_read_and_update_inner_maps() {
+        let mut ctx = context("coll-nested");
+        eval(&mut ctx, "(def user {:name \"ann\" :address {:city \"Oslo\" :zip 150} :tags [:a :b]})");
+        assert!(returns(&mut ctx, "(get-in user [:address :city])", "\"Oslo\""));
+        assert!(returns(&mut ctx, "(get-in user [:tags 1])", ":b"));
+        assert!(returns(&mut ctx, "(get-in user [:address :country] :none)", ":none"));
+        assert_eq!(eval(&mut ctx, "(get-in user [:name :first])"), "nil");
+        assert!(returns(&mut ctx, "(get-in user [])", "user"));
+
+        assert!(returns(&mut ctx, "(get-in (assoc-in user [:address :city] \"Bergen\") [:address])", "{:city \"Bergen\" :zip 150}"));
+        // Missing levels are created as maps
+        assert!(returns(&mut ctx, "(assoc-in {} [:a :b :c] 1)", "{:a {:b {:c 1}}}"));
+        assert!(returns(&mut ctx, "(assoc-in nil [:a] 1)", "{:a 1}"));
+        assert!(returns(&mut ctx, "(assoc-in [[1 2] [3 4]] [1 0] :x)", "[[1 2] [:x 4]]"));
+        assert!(returns(&mut ctx, "(update-in user [:address :zip] + 10 1)", "(assoc-in user [:address :zip] 161)"));
+        assert!(returns(&mut ctx, "(update-in {} [:count] (fn [n] (if (nil? n) 1 (+ n 1))))", "{:count 1}"));
+        // The original map is unchanged
+        assert_eq!(eval(&mut ctx, "(get-in user [:address :zip])"), "150");
+    }
+
+    #[test]
+    fn maps_merge_and_select() {
+        let mut ctx = context("coll-merge");
+        assert!(returns(&mut ctx, "(merge {:a 1 :b 2} {:b 3} nil {:c 4})", "{:a 1 :b 3 :c 4}"));
+        assert_eq!(eval(&mut ctx, "(merge)"), "nil");
+        assert!(returns(&mut ctx, "(merge-with + {:a 1 :b 2} {:b 3 :c 4} {:b 1})", "{:a 1 :b 6 :c 4}"));
+        assert!(returns(&mut ctx, "(select-keys {:a 1 :b 2 :c 3} [:a :c :missing])", "{:a 1 :c 3}"));
+        assert!(returns(&mut ctx, "(select-keys nil [:a])", "{}"));
+        assert!(returns(&mut ctx, "(zipmap [:a :b :c] [1 2])", "{:a 1 :b 2}"));
+        assert!(returns(&mut ctx, "(zipmap [:a :b] (range))", "{:a 0 :b 1}"));
+        assert!(error_message(&mut ctx, "(merge {:a 1} [1 2])").contains("merge"));
+    }
+
+    #[test]
+    fn items_are_grouped_and_counted() {
+        let mut ctx = context("coll-group");
+        assert!(returns(&mut ctx, "(group-by (fn [x] (mod x 2)) [1 2 3 4 5])", "{1 [1 3 5] 0 [2 4]}"));
+        assert!(returns(&mut ctx, "(group-by :kind [{:kind :a :n 1} {:kind :b :n 2} {:kind :a :n 3}])",
+            "{:a [{:kind :a :n 1} {:kind :a :n 3}] :b [{:kind :b :n 2}]}"));
+        assert!(returns(&mut ctx, "(frequencies [:a :b :a \"s\" :a \"s\"])", "{:a 3 :b 1 \"s\" 2}"));
+        assert!(returns(&mut ctx, "(frequencies [])", "{}"));
+    }
+
+    #[test]
+    fn keywords_maps_and_sets_look_themselves_up() {
+        let mut ctx = context("coll-callable");
+        eval(&mut ctx, "(def m {:a 1 :b nil})");
+        assert_eq!(eval(&mut ctx, "(:a m)"), "1");
+        assert_eq!(eval(&mut ctx, "(:c m)"), "nil");
+        assert!(returns(&mut ctx, "(:c m :default)", ":default"));
+        assert_eq!(eval(&mut ctx, "(m :a)"), "1");
+        assert_eq!(eval(&mut ctx, "(m :c 0)"), "0");
+        assert_eq!(eval(&mut ctx, "(:a nil)"), "nil");
+        assert_eq!(eval(&mut ctx, "((set [1 2]) 2)"), "2");
+        assert_eq!(eval(&mut ctx, "((set [1 2]) 3)"), "nil");
+        assert_eq!(eval(&mut ctx, "(let [k :b lookup {:b 5}] (lookup k))"), "5");
+
+        // They can be passed wherever a function is expected
+        assert!(returns(&mut ctx, "(map :n [{:n 1} {:n 2}])", "'(1 2)"));
+        assert!(returns(&mut ctx, "(filter (set [2 3]) [1 2 3 4])", "'(2 3)"));
+        assert!(returns(&mut ctx, "(into [] (map {:x 10 :y 20}) [:y :x])", "[20 10]"));
+
+        assert!(error_message(&mut ctx, "(:a)").contains("1 or 2 arguments"));
+        assert!(error_message(&mut ctx, "(m :a 1 2)").contains("1 or 2 arguments"));
+    }
+}
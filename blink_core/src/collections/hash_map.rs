@@ -1,10 +1,10 @@
 use std::{collections::HashMap, fmt::Display};
 
-use crate::value::ValueRef;
+use crate::value::{ValueHashBuilder, ValueRef};
 
 #[derive(Debug)]
 pub struct BlinkHashMap {
-    map: HashMap<ValueRef, ValueRef>
+    map: HashMap<ValueRef, ValueRef, ValueHashBuilder>
 }
 
 impl Display for BlinkHashMap {
@@ -30,13 +30,13 @@ impl Iterator for BlinkHashMap {
 impl BlinkHashMap {
     pub fn new() -> Self {
         Self {
-            map: HashMap::new(),
+            map: HashMap::default(),
         }
     }
 
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            map: HashMap::with_capacity(capacity),
+            map: HashMap::with_capacity_and_hasher(capacity, ValueHashBuilder::default()),
         }
     }
 
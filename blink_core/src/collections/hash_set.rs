@@ -1,11 +1,11 @@
 use std::{collections::HashSet, fmt::Display};
 
-use crate::value::ValueRef;
+use crate::value::{ValueHashBuilder, ValueRef};
 
 /// Native HashSet for your language runtime - ValueRef values
 #[derive(Debug)]
 pub struct BlinkHashSet {
-    set: HashSet<ValueRef>,
+    set: HashSet<ValueRef, ValueHashBuilder>,
 }
 
 impl Display for BlinkHashSet {
@@ -32,13 +32,13 @@ impl Iterator for BlinkHashSet {
 impl BlinkHashSet {
     pub fn new() -> Self {
         Self {
-            set: HashSet::new(),
+            set: HashSet::default(),
         }
     }
 
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            set: HashSet::with_capacity(capacity),
+            set: HashSet::with_capacity_and_hasher(capacity, ValueHashBuilder::default()),
         }
     }
 
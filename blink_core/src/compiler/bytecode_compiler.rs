@@ -1,11 +1,27 @@
 use std::{collections::HashMap, sync::Arc};
 
 use crate::{
+    compiler::SyntaxQuote,
     error::BlinkError,
-    runtime::{BlinkVM, CompiledFunction, LabelPatch, Macro, Opcode},
-    value::{unpack_immediate, GcPtr, HeapValue, ImmediateValue, ValueRef},
+    runtime::{
+        BlinkVM, CompiledFunction, LabelPatch, LineTableEntry, LocalVarEntry, Opcode, TypeTag, IMPORT_REFER_ALL,
+        NO_LIST, NO_SYMBOL,
+    },
+    value::{unpack_immediate, GcPtr, HeapValue, ImmediateValue, SourceRange, ValueRef},
 };
 
+/// Forms the compiler handles itself rather than by calling a global
+pub(crate) const SPECIAL_FORMS: &[&str] = &[
+    "if", "let", "do", "quote", "var", "with-meta", "def", "defonce", "fn", "loop", "recur", "cond", "case*", "macro", "rmac",
+    "quasiquote", "unquote", "unquote-splicing", "future", "complete", "go", "deref", "lazy-seq", "lazy-cat",
+];
+
+/// Operators compiled to opcodes rather than calls
+pub(crate) const INLINE_OPERATORS: &[&str] = &["+", "-", "*", "/", "=", "<", ">", "<=", ">=", "and", "or", "not"];
+
+/// Module forms, compiled before globals are looked up
+pub(crate) const MODULE_FORMS: &[&str] = &["mod", "imp", "load", "reload"];
+
 #[derive(Debug, Clone)]
 struct LoopFrame {
     start_label: u16,
@@ -31,13 +47,13 @@ pub struct BytecodeCompiler {
     next_label: u16,
     label_positions: HashMap<u16, usize>,
     label_patches: Vec<LabelPatch>,
-}
 
-#[derive(Debug)]
-enum SplicePart {
-    Item(ValueRef),         // Regular item (gets quoted if symbol)
-    Splice(ValueRef),       // Expression to be spliced
-    UnquotedItem(ValueRef), // From unquote (never gets quoted)
+    // Debug info
+    line_table: Vec<LineTableEntry>,
+    position_stack: Vec<SourceRange>, // Positions of the forms currently being compiled
+    pending_fn_name: Option<u32>,     // Name from an enclosing def, for anonymous fns
+    local_vars: Vec<LocalVarEntry>,
+    open_locals: Vec<(usize, usize)>, // (index into local_vars, scope depth) not yet closed
 }
 
 impl BytecodeCompiler {
@@ -55,6 +71,11 @@ impl BytecodeCompiler {
             next_label: 0,
             label_positions: HashMap::new(),
             label_patches: Vec::new(),
+            line_table: Vec::new(),
+            position_stack: Vec::new(),
+            pending_fn_name: None,
+            local_vars: Vec::new(),
+            open_locals: Vec::new(),
         }
     }
 
@@ -66,6 +87,44 @@ impl BytecodeCompiler {
         self.scope_stack.push(HashMap::new());
         self.next_label = 0;
         self.label_patches.clear();
+        self.line_table.clear();
+        self.position_stack.clear();
+        self.pending_fn_name = None;
+        self.local_vars.clear();
+        self.open_locals.clear();
+    }
+
+    // LINE TABLE
+
+    fn source_position(&self, expr: ValueRef) -> Option<SourceRange> {
+        let id = expr.get_or_create_id()?;
+        self.vm.value_metadata.read().get_position(id)
+    }
+
+    fn is_fn_form(&self, expr: ValueRef) -> bool {
+        let head = match expr.get_list() {
+            Some(items) if !items.is_empty() => items[0],
+            _ => return false,
+        };
+        match head {
+            ValueRef::Immediate(packed) => match unpack_immediate(packed) {
+                ImmediateValue::Symbol(id) => {
+                    self.vm.symbol_table.read().get_symbol(id).as_deref() == Some("fn")
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Attribute code emitted from here on to `pos`
+    fn mark_position(&mut self, pos: SourceRange) {
+        let offset = self.bytecode.len() as u32;
+        match self.line_table.last_mut() {
+            Some(last) if last.offset == offset => last.pos = pos,
+            Some(last) if last.pos == pos => {}
+            _ => self.line_table.push(LineTableEntry { offset, pos }),
+        }
     }
 
     fn alloc_register(&mut self) -> u8 {
@@ -77,6 +136,19 @@ impl BytecodeCompiler {
         self.next_register - 1
     }
 
+    // Calls leave their result in register 0, which the next call reuses, so
+    // move it to a register of its own when it has to outlive another call
+    fn keep_result(&mut self, reg: u8) -> u8 {
+        if reg != 0 {
+            return reg;
+        }
+        let kept = self.alloc_register();
+        self.emit_u8(Opcode::LoadLocal as u8);
+        self.emit_u8(kept);
+        self.emit_u8(reg);
+        kept
+    }
+
     fn resolve_symbol_for_compilation(&self, symbol_id: u32) -> Result<ValueRef, String> {
         // First check local scope bindings
         for scope in self.scope_stack.iter().rev() {
@@ -262,12 +334,33 @@ impl BytecodeCompiler {
     }
 
     fn exit_scope(&mut self) {
+        self.close_locals(self.scope_stack.len());
         self.scope_stack.pop();
     }
 
     fn bind_local_symbol(&mut self, symbol_id: u32, register: u8) {
         if let Some(current_scope) = self.scope_stack.last_mut() {
             current_scope.insert(symbol_id, register);
+
+            self.open_locals.push((self.local_vars.len(), self.scope_stack.len()));
+            self.local_vars.push(LocalVarEntry {
+                symbol: symbol_id,
+                register,
+                start: self.bytecode.len() as u32,
+                end: u32::MAX,
+            });
+        }
+    }
+
+    /// End the live range of every local bound at `depth` or deeper
+    fn close_locals(&mut self, depth: usize) {
+        let end = self.bytecode.len() as u32;
+        while let Some(&(index, local_depth)) = self.open_locals.last() {
+            if local_depth < depth {
+                break;
+            }
+            self.local_vars[index].end = end;
+            self.open_locals.pop();
         }
     }
 
@@ -292,6 +385,46 @@ impl BytecodeCompiler {
     // MAIN COMPILATION METHODS
 
     fn compile_expression(&mut self, expr: ValueRef) -> Result<u8, String> {
+        self.at_position(expr, |compiler| compiler.compile_expression_inner(expr))
+    }
+
+    /// Attribute the code `compile` emits to the source position of `expr`
+    fn at_position<T>(&mut self, expr: ValueRef, compile: impl FnOnce(&mut Self) -> T) -> T {
+        let pos = self.source_position(expr);
+        if let Some(pos) = pos {
+            self.position_stack.push(pos);
+            self.mark_position(pos);
+        }
+
+        let result = compile(self);
+
+        // Code emitted after a nested form (e.g. the Call for its parent) belongs to the parent
+        if pos.is_some() {
+            self.position_stack.pop();
+            if let Some(&parent) = self.position_stack.last() {
+                self.mark_position(parent);
+            }
+        }
+
+        result
+    }
+
+    // Compile an operand that has to outlive the operands compiled after it.
+    // Calls leave their result in register 0, where the next call would
+    // overwrite it, so it is copied to a register of its own.
+    fn compile_operand(&mut self, expr: ValueRef) -> Result<u8, String> {
+        let reg = self.compile_expression(expr)?;
+        if reg != 0 {
+            return Ok(reg);
+        }
+        let saved_reg = self.alloc_register();
+        self.emit_u8(Opcode::LoadLocal as u8);
+        self.emit_u8(saved_reg);
+        self.emit_u8(reg);
+        Ok(saved_reg)
+    }
+
+    fn compile_expression_inner(&mut self, expr: ValueRef) -> Result<u8, String> {
         match expr {
             ValueRef::Immediate(packed) => {
                 let imm = unpack_immediate(packed);
@@ -338,8 +471,7 @@ impl BytecodeCompiler {
 
         self.enter_scope();
 
-        // STEP 1: First, allocate and bind ALL variables to their loop registers
-        // This ensures variable lookups always resolve to the loop registers
+        // STEP 1: First, allocate the loop registers for ALL variables
         for i in 0..binding_count {
             let symbol_idx = i * 2;
 
@@ -357,15 +489,12 @@ impl BytecodeCompiler {
             // Allocate the loop binding register FIRST
             let binding_reg = self.alloc_register();
 
-            // Bind the symbol to the loop register IMMEDIATELY
-            // This ensures all references to this symbol use the loop register
-            self.bind_local_symbol(symbol_id, binding_reg);
-
             binding_registers.push(binding_reg);
             binding_symbols.push(symbol_id);
         }
 
-        // STEP 2: Now compile initial values and store them in the loop registers
+        // STEP 2: Now compile initial values and store them in the loop registers. As in let,
+        // each value sees the bindings before it, so (loop [xs xs] ...) starts from the outer xs
         for i in 0..binding_count {
             let value_idx = i * 2 + 1;
             let binding_reg = binding_registers[i];
@@ -379,6 +508,9 @@ impl BytecodeCompiler {
                 self.emit_u8(binding_reg); // destination (loop register)
                 self.emit_u8(value_reg); // source (temporary register)
             }
+
+            // Bind the symbol to its loop register, so later lookups use the loop register
+            self.bind_local_symbol(binding_symbols[i], binding_reg);
         }
 
         // Create loop frame
@@ -433,7 +565,7 @@ impl BytecodeCompiler {
         let mut new_value_regs = Vec::new();
         for (i, &arg) in args.iter().enumerate() {
             let value_reg = self.compile_expression(arg)?;
-            new_value_regs.push(value_reg);
+            new_value_regs.push(self.keep_result(value_reg));
         }
 
         // Update binding registers with new values
@@ -475,6 +607,13 @@ impl BytecodeCompiler {
             // Only 2 args, must be anonymous: (fn [params] body)
             (None, 0)
         };
+        // A docstring before the parameters is only metadata for def
+        let params_index = match args.get(params_index) {
+            Some(doc) if doc.get_string().is_some() && args.len() > params_index + 2 => params_index + 1,
+            _ => params_index,
+        };
+        // Anonymous fns bound with def take the def'd name for stack traces
+        let debug_name = function_name.or(self.pending_fn_name.take());
 
         // Parse parameter list
         let params = args[params_index]
@@ -508,6 +647,11 @@ impl BytecodeCompiler {
         let saved_labels = std::mem::take(&mut self.label_positions);
         let saved_patches = std::mem::take(&mut self.label_patches);
         let saved_next_label = self.next_label;
+        let saved_line_table = std::mem::take(&mut self.line_table);
+        let saved_local_vars = std::mem::take(&mut self.local_vars);
+        let saved_open_locals = std::mem::take(&mut self.open_locals);
+        // Captures belong to the function being compiled, not the one around it
+        let saved_captures = std::mem::take(&mut self.captured_symbols);
 
         // Reset for function compilation
         self.next_register = 1; // Register 0 reserved for return value
@@ -556,8 +700,6 @@ impl BytecodeCompiler {
         let mut result_reg = self.alloc_register(); // Default return value
 
         for (i, &expr) in body_exprs.iter().enumerate() {
-            result_reg = self.compile_expression(expr)?;
-
             // Check for tail call optimization on final expression
             if i == body_exprs.len() - 1 {
                 if let Some(_tail_call_reg) = self.check_tail_call(expr)? {
@@ -568,6 +710,11 @@ impl BytecodeCompiler {
                     let function_bytecode = std::mem::take(&mut self.bytecode);
                     let function_constants = std::mem::take(&mut self.constants);
                     let function_registers = self.next_register;
+                    let function_line_table =
+                        std::mem::replace(&mut self.line_table, saved_line_table);
+                    let function_locals =
+                        std::mem::replace(&mut self.local_vars, saved_local_vars);
+                    self.open_locals = saved_open_locals;
 
                     // Restore parent compilation state
                     self.bytecode = saved_bytecode;
@@ -585,14 +732,25 @@ impl BytecodeCompiler {
                         module: self.current_module,
                         register_start: param_start_reg as u8,
                         has_self_reference: function_name.is_some(),
+                        name: debug_name,
+                        line_table: function_line_table,
+                        locals: function_locals,
                     };
 
-                    return self.create_closure_object(compiled_fn);
+                    let result = self.create_closure_object(compiled_fn);
+                    self.captured_symbols = saved_captures;
+                    return result;
                 }
             }
+
+            result_reg = self.compile_expression(expr)?;
         }
 
-        // Regular return
+        // Regular return, on the line of the code that computed the result rather
+        // than that of an enclosing form no code has been emitted for yet
+        if self.line_table.last().is_some_and(|last| last.offset as usize == self.bytecode.len()) {
+            self.line_table.pop();
+        }
         self.emit_u8(Opcode::Return as u8);
         self.emit_u8(result_reg);
         self.exit_scope();
@@ -601,6 +759,9 @@ impl BytecodeCompiler {
         let function_bytecode = std::mem::take(&mut self.bytecode);
         let function_constants = std::mem::take(&mut self.constants);
         let function_registers = self.next_register;
+        let function_line_table = std::mem::replace(&mut self.line_table, saved_line_table);
+        let function_locals = std::mem::replace(&mut self.local_vars, saved_local_vars);
+        self.open_locals = saved_open_locals;
 
         // Restore parent compilation state
         self.bytecode = saved_bytecode;
@@ -618,9 +779,14 @@ impl BytecodeCompiler {
             module: self.current_module,
             register_start: param_start_reg as u8,
             has_self_reference: function_name.is_some(),
+            name: debug_name,
+            line_table: function_line_table,
+            locals: function_locals,
         };
 
-        self.create_closure_object(compiled_fn)
+        let result = self.create_closure_object(compiled_fn);
+        self.captured_symbols = saved_captures;
+        result
     }
 
     fn analyze_closures(&mut self, exprs: &[ValueRef]) -> Result<(), String> {
@@ -696,36 +862,64 @@ impl BytecodeCompiler {
             return Err("macro expects at least 2 arguments: params and body".to_string());
         }
 
+        // A docstring before the parameters is only metadata for def
+        let args = match args {
+            [doc, rest @ ..] if doc.get_string().is_some() && rest.len() >= 2 => rest,
+            _ => args,
+        };
         let params = args[0];
         let body = &args[1..];
 
         // Parse parameters (same logic as fn)
         let param_symbols = self.extract_parameter_symbols(params)?;
         let (variadic_params, is_variadic) = self.detect_variadic_params(&param_symbols);
+        if variadic_params.len() > 253 {
+            return Err("macro cannot have more than 253 parameters".to_string());
+        }
 
-        // Create MacroDefinition with raw AST
-        let macro_def = Macro {
-            params: variadic_params,
-            body: body.to_vec(), // Store raw AST - no compilation!
-            is_variadic,
-            module: self.current_module,
+        // The body is an ordinary function of the call form, the caller's
+        // locals and the unevaluated arguments, with any rest arguments as a list
+        let (form_symbol, env_symbol) = {
+            let mut symbol_table = self.vm.symbol_table.write();
+            (symbol_table.intern("&form"), symbol_table.intern("&env"))
         };
+        let expander_params = [form_symbol, env_symbol]
+            .into_iter()
+            .chain(variadic_params.iter().copied())
+            .map(ValueRef::symbol)
+            .collect();
+        let mut expander_args = vec![self.vm.vector_value(expander_params)];
+        expander_args.extend_from_slice(body);
+        let expander_reg = self.compile_fn(&expander_args)?;
 
-        // Allocate to GC heap
-        let macro_obj = self.vm.alloc_macro(macro_def);
-        let macro_value = ValueRef::Heap(GcPtr::new(macro_obj));
-
-        // Store in constants and return register
-        let constant_idx = self.add_constant(macro_value);
         let result_reg = self.alloc_register();
-
-        self.emit_u8(Opcode::LoadImmConst as u8);
+        self.emit_u8(Opcode::MakeMacro as u8);
         self.emit_u8(result_reg);
-        self.emit_u8(constant_idx);
+        self.emit_u8(expander_reg);
+        self.emit_u8(is_variadic as u8);
+        self.emit_u8(variadic_params.len() as u8);
+        for param in variadic_params {
+            self.emit_u32(param);
+        }
 
         Ok(result_reg)
     }
 
+    // (rmac name) removes a macro defined in the current module
+    fn compile_rmac(&mut self, args: &[ValueRef]) -> Result<u8, String> {
+        let [name] = args else {
+            return Err("rmac expects 1 argument".to_string());
+        };
+        let symbol_id = name.get_symbol().ok_or("rmac: argument must be a symbol")?;
+
+        self.emit_u8(Opcode::RemoveMacro as u8);
+        self.emit_u32(symbol_id);
+
+        let result_reg = self.alloc_register();
+        self.emit_load_immediate(result_reg, ValueRef::nil());
+        Ok(result_reg)
+    }
+
     fn find_free_variables(&mut self, expr: ValueRef) -> Result<(), String> {
 
         match expr {
@@ -875,48 +1069,14 @@ impl BytecodeCompiler {
             return Err("= expects at least 2 arguments".to_string());
         }
 
-        if args.len() == 2 {
-            // Simple binary comparison
-            let left_reg = self.compile_expression(args[0])?;
-            let right_reg = self.compile_expression(args[1])?;
-            let result_reg = self.alloc_register();
-
-            self.emit_u8(Opcode::Eq as u8);
-            self.emit_u8(result_reg);
-            self.emit_u8(left_reg);
-            self.emit_u8(right_reg);
-
-            return Ok(result_reg);
-        } else {
-            // Chain multiple comparisons with AND logic
-            let first_reg = self.compile_expression(args[0])?;
-            let mut current_result = self.alloc_register();
-            self.emit_load_immediate(current_result, ValueRef::boolean(true));
-
-            for arg in &args[1..] {
-                let arg_reg = self.compile_expression(*arg)?;
-                let cmp_result = self.alloc_register();
-                let and_result = self.alloc_register();
-
-                // Compare first_reg == arg_reg
-                self.emit_u8(Opcode::Eq as u8);
-                self.emit_u8(cmp_result);
-                self.emit_u8(first_reg);
-                self.emit_u8(arg_reg);
-
-                // AND with previous result
-                // (You'd need to implement logical AND bytecode or use conditionals)
-                // For now, could fall back to native function for multi-arg =
-            }
-
-            return Ok(current_result);
-        }
+        // Equality is transitive, so comparing neighbours compares them all
+        self.compile_ordered_chain(args, Opcode::Eq)
     }
 
     fn compile_ordered_chain(&mut self, args: &[ValueRef], base_op: Opcode) -> Result<u8, String> {
         if args.len() == 2 {
             // Binary case
-            let left_reg = self.compile_expression(args[0])?;
+            let left_reg = self.compile_operand(args[0])?;
             let right_reg = self.compile_expression(args[1])?;
             let result_reg = self.alloc_register();
 
@@ -934,7 +1094,7 @@ impl BytecodeCompiler {
         let end_label = self.alloc_label();
 
         for i in 0..(args.len() - 1) {
-            let left_reg = self.compile_expression(args[i])?;
+            let left_reg = self.compile_operand(args[i])?;
             let right_reg = self.compile_expression(args[i + 1])?;
             let cmp_reg = self.alloc_register();
 
@@ -1045,6 +1205,51 @@ impl BytecodeCompiler {
         Ok(result_reg)
     }
 
+    // (case* expr table default clause...), which the case macro expands to. The table is a
+    // map from each constant to the index of its clause, and becomes a JumpTable constant.
+    fn compile_case(&mut self, args: &[ValueRef]) -> Result<u8, String> {
+        let [expr, table, default, clauses @ ..] = args else {
+            return Err("case* expects an expression, a table, a default and clauses".to_string());
+        };
+        if table.heap_object(TypeTag::Map).is_none() {
+            return Err("case* expects a map from constants to clause indexes".to_string());
+        }
+        if clauses.len() > u8::MAX as usize {
+            return Err(format!("case supports at most {} clauses", u8::MAX));
+        }
+
+        let value_reg = self.compile_expression(*expr)?;
+        let result_reg = self.alloc_register();
+        let end_label = self.alloc_label();
+        let labels: Vec<u16> = (0..=clauses.len()).map(|_| self.alloc_label()).collect();
+
+        let table_index = self.add_constant(*table);
+        self.emit_u8(Opcode::JumpTable as u8);
+        self.emit_u8(value_reg);
+        self.emit_u8(table_index);
+        self.emit_u8(clauses.len() as u8);
+        for &label in &labels {
+            self.label_patches.push(LabelPatch {
+                bytecode_offset: self.bytecode.len(),
+                label_id: label,
+            });
+            self.emit_i16(0); // Placeholder
+        }
+
+        // The clauses in order, then the default
+        for (&label, &body) in labels.iter().zip(clauses.iter().chain(std::iter::once(default))) {
+            self.emit_label(label);
+            let body_reg = self.compile_expression(body)?;
+            self.emit_u8(Opcode::LoadLocal as u8);
+            self.emit_u8(result_reg);
+            self.emit_u8(body_reg);
+            self.emit_jump(end_label);
+        }
+
+        self.emit_label(end_label);
+        Ok(result_reg)
+    }
+
     fn compile_symbol_reference(&mut self, symbol_id: u32) -> Result<u8, String> {
         let symbol_name = self
             .vm
@@ -1197,38 +1402,25 @@ impl BytecodeCompiler {
                     if let ImmediateValue::Symbol(symbol_id) = unpack_immediate(packed) {
                         // Don't tail-optimize special forms or arithmetic operators
                         if !self.is_special_form(symbol_id)
-                            && !self.is_arithmetic_operator(symbol_id)
+                            && !self.is_inline_operator(symbol_id)
+                            && !self.is_module_form(symbol_id, &list_items[1..])
                         {
                             // This is a regular function call - emit as tail call
-                            let func_reg = self.alloc_register();
-
-                            // Load function
-                            if let Some(local_reg) = self.resolve_any_local_symbol(symbol_id) {
-                                self.emit_u8(Opcode::LoadLocal as u8);
-                                self.emit_u8(func_reg);
-                                self.emit_u8(local_reg);
-                            } else if let Some(upvalue_idx) = self.resolve_upvalue(symbol_id) {
-                                self.emit_u8(Opcode::LoadUpvalue as u8);
-                                self.emit_u8(func_reg);
-                                self.emit_u8(upvalue_idx);
-                            } else {
-                                self.emit_u8(Opcode::LoadGlobal as u8);
-                                self.emit_u8(func_reg);
-                                self.emit_u32(symbol_id);
-                            }
+                            return self.at_position(expr, |compiler| {
+                                let func_reg = compiler.alloc_register();
 
-                            // Compile arguments
-                            let args = &list_items[1..];
-                            for arg in args {
-                                self.compile_expression(*arg)?;
-                            }
+                                compiler.emit_load_function(func_reg, symbol_id);
 
-                            // Emit tail call (no result register - direct return)
-                            self.emit_u8(Opcode::TailCall as u8);
-                            self.emit_u8(func_reg);
-                            self.emit_u8(args.len() as u8);
+                                let args = &list_items[1..];
+                                compiler.compile_call_arguments(func_reg, args)?;
 
-                            return Ok(Some(func_reg));
+                                // Emit tail call (no result register - direct return)
+                                compiler.emit_u8(Opcode::TailCall as u8);
+                                compiler.emit_u8(func_reg);
+                                compiler.emit_u8(args.len() as u8);
+
+                                Ok(Some(func_reg))
+                            });
                         }
                     }
                 }
@@ -1247,15 +1439,20 @@ impl BytecodeCompiler {
 
         match symbol_name.as_str() {
             "def" => self.compile_def(args),
+            "defonce" => self.compile_defonce(args),
             "if" => self.compile_if(args),
             "let" => self.compile_let(args),
             "do" => self.compile_do(args),
             "quote" => self.compile_quote(args),
+            "var" => self.compile_var(args),
+            "with-meta" => Err("with-meta: metadata can only be given to the name of a def".to_string()),
             "fn" => self.compile_fn(args),
             "loop" => self.compile_loop(args),
             "recur" => self.compile_recur(args),
             "cond" => self.compile_cond(args),
+            "case*" => self.compile_case(args),
             "macro" => self.compile_macro(args),
+            "rmac" => self.compile_rmac(args),
             "quasiquote" => self.compile_quasiquote(args),
             "unquote" => self.compile_unquote(args),
             "unquote-splicing" => self.compile_unquote_splicing(args),
@@ -1263,6 +1460,8 @@ impl BytecodeCompiler {
             "complete" => self.compile_complete(args),
             "go" => self.compile_go(args),
             "deref" => self.compile_deref(args),
+            "lazy-seq" => self.compile_lazy_seq(args),
+            "lazy-cat" => self.compile_lazy_cat(args),
             _ => Err(format!("Special form '{}' not implemented", symbol_name)),
         }
     }
@@ -1381,6 +1580,36 @@ impl BytecodeCompiler {
         self.compile_expression(synthetic_fn_call)
     }
 
+    // (lazy-seq body...) becomes (make-lazy-seq (fn [] body...)); the body runs
+    // the first time the seq is used and its result is cached
+    fn compile_lazy_seq(&mut self, args: &[ValueRef]) -> Result<u8, String> {
+        let thunk = self.lazy_thunk(args);
+        let make_lazy_seq = ValueRef::symbol(self.vm.symbol_table.write().intern("make-lazy-seq"));
+        let call = self.vm.list_value(vec![make_lazy_seq, thunk]);
+        self.compile_expression(call)
+    }
+
+    // (lazy-cat a b ...) becomes (lazy-concat (lazy-seq a) (lazy-seq b) ...), so
+    // no argument is evaluated until the concatenation reaches it
+    fn compile_lazy_cat(&mut self, args: &[ValueRef]) -> Result<u8, String> {
+        let lazy_concat = ValueRef::symbol(self.vm.symbol_table.write().intern("lazy-concat"));
+        let make_lazy_seq = ValueRef::symbol(self.vm.symbol_table.write().intern("make-lazy-seq"));
+        let mut call = vec![lazy_concat];
+        for arg in args {
+            let thunk = self.lazy_thunk(std::slice::from_ref(arg));
+            call.push(self.vm.list_value(vec![make_lazy_seq, thunk]));
+        }
+        let call = self.vm.list_value(call);
+        self.compile_expression(call)
+    }
+
+    fn lazy_thunk(&mut self, body: &[ValueRef]) -> ValueRef {
+        let fn_symbol = ValueRef::symbol(self.vm.symbol_table.write().intern("fn"));
+        let mut thunk = vec![fn_symbol, self.vm.vector_value(vec![])];
+        thunk.extend_from_slice(body);
+        self.vm.list_value(thunk)
+    }
+
     fn compile_deref(&mut self, args: &[ValueRef]) -> Result<u8, String> {
         if args.len() != 1 {
             return Err("deref expects exactly 1 argument".to_string());
@@ -1403,33 +1632,155 @@ impl BytecodeCompiler {
     }
 
     fn compile_def(&mut self, args: &[ValueRef]) -> Result<u8, String> {
-        if args.len() != 2 {
-            return Err("def expects exactly 2 arguments: name and value".to_string());
-        }
-
-        // First argument must be a symbol (the name to define)
-        let symbol_id = if let ValueRef::Immediate(packed) = args[0] {
-            if let ImmediateValue::Symbol(sym_id) = unpack_immediate(packed) {
-                sym_id
-            } else {
-                return Err("def: first argument must be a symbol".to_string());
-            }
-        } else {
-            return Err("def: first argument must be a symbol".to_string());
-        };
+        let (name, doc, value) = Self::definition_parts(args, "def")?;
+        let (symbol_id, meta) = self.definition_name(name, "def")?;
 
         // Compile the value expression
-        let value_reg = self.compile_expression(args[1])?;
+        if self.is_fn_form(value) {
+            self.pending_fn_name = Some(symbol_id);
+        }
+        let value_reg = self.compile_expression(value);
+        self.pending_fn_name = None;
+        let value_reg = value_reg?;
 
         // Store the value globally
         self.emit_u8(Opcode::StoreGlobal as u8);
         self.emit_u8(value_reg); // register first
         self.emit_u32(symbol_id); // symbol_id second
 
+        // Replace its metadata, clearing what an earlier def recorded
+        let (meta, private) = self.definition_meta(meta, doc, value);
+        let meta_reg = self.alloc_register();
+        self.emit_load_immediate(meta_reg, meta);
+        self.emit_u8(Opcode::StoreMeta as u8);
+        self.emit_u8(meta_reg);
+        self.emit_u32(symbol_id);
+        self.emit_u8(private as u8);
+
         // Return the value that was stored
         Ok(value_reg)
     }
 
+    // (def name value) or (def name "doc" value)
+    fn definition_parts(args: &[ValueRef], form_name: &str) -> Result<(ValueRef, Option<ValueRef>, ValueRef), String> {
+        match args {
+            [name, value] => Ok((*name, None, *value)),
+            [name, doc, value] if doc.get_string().is_some() => Ok((*name, Some(*doc), *value)),
+            _ => Err(format!("{} expects a name, an optional docstring and a value", form_name)),
+        }
+    }
+
+    // A definition's name and its ^{...} map, which the reader wraps as (with-meta name {...})
+    fn definition_name(&self, name: ValueRef, form_name: &str) -> Result<(u32, Option<ValueRef>), String> {
+        if let Some(symbol_id) = name.get_symbol() {
+            return Ok((symbol_id, None));
+        }
+        if let Some(items) = name.get_list() {
+            if let [head, target, meta] = items[..] {
+                if self.is_symbol_named(head, "with-meta") {
+                    if let Some(symbol_id) = target.get_symbol() {
+                        return Ok((symbol_id, Some(meta)));
+                    }
+                }
+            }
+        }
+        Err(format!("{}: first argument must be a symbol", form_name))
+    }
+
+    fn is_symbol_named(&self, value: ValueRef, name: &str) -> bool {
+        value
+            .get_symbol()
+            .is_some_and(|id| self.vm.symbol_table.read().get_symbol(id).as_deref() == Some(name))
+    }
+
+    // The metadata map a definition records, or nil: its ^{...} entries, then
+    // the docstring of the def or else of the fn or macro it binds, and that
+    // function's parameters as :arglists. Also whether it is :private.
+    fn definition_meta(&mut self, meta: Option<ValueRef>, doc: Option<ValueRef>, value: ValueRef) -> (ValueRef, bool) {
+        let keyword = |vm: &BlinkVM, name: &str| vm.symbol_table.write().intern(&format!(":{}", name));
+        let doc_key = keyword(&self.vm, "doc");
+        let arglists_key = keyword(&self.vm, "arglists");
+        let private_key = keyword(&self.vm, "private");
+
+        let mut pairs: Vec<(ValueRef, ValueRef)> = meta
+            .and_then(|meta| meta.get_map())
+            .map(|map| map.iter().map(|(k, v)| (*k, *v)).collect())
+            .unwrap_or_default();
+        let has_key = |pairs: &[(ValueRef, ValueRef)], key: u32| pairs.iter().any(|(k, _)| k.get_keyword() == Some(key));
+
+        let (fn_doc, params) = self.function_form_parts(value);
+        if let Some(doc) = doc.or(fn_doc) {
+            if !has_key(&pairs, doc_key) {
+                pairs.push((ValueRef::keyword(doc_key), doc));
+            }
+        }
+        if let Some(params) = params {
+            if !has_key(&pairs, arglists_key) {
+                pairs.push((ValueRef::keyword(arglists_key), self.vm.list_value(vec![params])));
+            }
+        }
+
+        let private = pairs
+            .iter()
+            .any(|(k, v)| k.get_keyword() == Some(private_key) && v.is_truthy());
+        if pairs.is_empty() {
+            (ValueRef::nil(), private)
+        } else {
+            (self.vm.map_value(pairs), private)
+        }
+    }
+
+    // The docstring and parameter vector of (fn name? "doc"? [params] ...) or
+    // (macro "doc"? [params] ...)
+    fn function_form_parts(&self, form: ValueRef) -> (Option<ValueRef>, Option<ValueRef>) {
+        let Some(items) = form.get_list() else {
+            return (None, None);
+        };
+        let Some((&head, mut args)) = items.split_first() else {
+            return (None, None);
+        };
+        if self.is_symbol_named(head, "fn") {
+            if args.len() >= 3 && args[0].get_symbol().is_some() {
+                args = &args[1..];
+            }
+        } else if !self.is_symbol_named(head, "macro") {
+            return (None, None);
+        }
+        let doc = match args {
+            [doc, params, ..] if doc.get_string().is_some() && params.get_vec().is_some() => {
+                args = &args[1..];
+                Some(*doc)
+            }
+            _ => None,
+        };
+        (doc, args.first().copied().filter(|params| params.get_vec().is_some()))
+    }
+
+    // (defonce name value) is def, unless the module already defines name: then
+    // value is not evaluated and the existing value is kept, so reloading a file
+    // does not reset its state. Each top-level form is compiled just before it
+    // runs, so the check is made when the form is compiled.
+    fn compile_defonce(&mut self, args: &[ValueRef]) -> Result<u8, String> {
+        let (name, _, _) = Self::definition_parts(args, "defonce")?;
+        let (symbol_id, _) = self.definition_name(name, "defonce")?;
+
+        let defined = self
+            .vm
+            .module_registry
+            .read()
+            .get_module(self.current_module)
+            .is_some_and(|module| module.exports.contains_key(&symbol_id));
+        if !defined {
+            return self.compile_def(args);
+        }
+
+        let result_reg = self.alloc_register();
+        self.emit_u8(Opcode::LoadGlobal as u8);
+        self.emit_u8(result_reg);
+        self.emit_u32(symbol_id);
+        Ok(result_reg)
+    }
+
     fn compile_function_call(&mut self, items: &[ValueRef]) -> Result<u8, String> {
         if items.is_empty() {
             // TODO empty should return nil
@@ -1443,11 +1794,19 @@ impl BytecodeCompiler {
                     return self.compile_special_form(symbol_id, &items[1..]);
                 }
 
+                if let Some(result) = self.try_compile_module_form(symbol_id, &items[1..]) {
+                    return result;
+                }
+
                 // Check for arithmetic operators
                 if let Ok(result) = self.try_compile_arithmetic(symbol_id, &items[1..]) {
                     return Ok(result);
                 }
 
+                if let Some(result) = self.try_compile_numeric_builtin(symbol_id, &items[1..]) {
+                    return result;
+                }
+
                 // Check for comparison
                 if let Ok(result) = self.try_compile_comparison(symbol_id, &items[1..]) {
                     return Ok(result);
@@ -1462,7 +1821,9 @@ impl BytecodeCompiler {
             }
         }
 
-        Err("Unsupported function call".to_string())
+        // Anything else in call position is evaluated for the function: ((fn ...) x),
+        // ((f) x), or a keyword, map or set that looks x up
+        self.compile_expression_call(items[0], &items[1..])
     }
 
     fn compile_if(&mut self, args: &[ValueRef]) -> Result<u8, String> {
@@ -1530,6 +1891,7 @@ impl BytecodeCompiler {
             };
 
             let value_reg = self.compile_expression(bindings[i + 1])?;
+            let value_reg = self.keep_result(value_reg);
             self.bind_local_symbol(symbol_id, value_reg);
         }
 
@@ -1566,6 +1928,202 @@ impl BytecodeCompiler {
         Ok(reg)
     }
 
+    // (var f) and #'f name the definition f resolves to, as a qualified symbol
+    fn compile_var(&mut self, args: &[ValueRef]) -> Result<u8, String> {
+        if args.len() != 1 {
+            return Err("var expects 1 argument".to_string());
+        }
+        let symbol_id = args[0].get_symbol().ok_or("var: argument must be a symbol")?;
+        let (module_id, name) = self
+            .vm
+            .locate_global_symbol(self.current_module, symbol_id)
+            .ok_or_else(|| {
+                let display = self.vm.symbol_table.read().get_symbol(symbol_id).unwrap_or_default();
+                format!("var: unable to resolve {}", display)
+            })?;
+        let qualified = self.vm.symbol_table.write().intern_qualified(module_id, name);
+
+        let reg = self.alloc_register();
+        self.emit_load_immediate(reg, ValueRef::symbol(qualified));
+        Ok(reg)
+    }
+
+    // MODULES
+
+    // (imp ...), (load ...) and (reload ...) always. (mod name) and (mod name :export [a b])
+    // declare a module; (mod n d) with any other arguments is still modulo, so
+    // it is left to the numeric builtins
+    fn is_module_form(&self, symbol_id: u32, args: &[ValueRef]) -> bool {
+        match self.vm.symbol_table.read().get_symbol(symbol_id).as_deref() {
+            Some("imp") | Some("load") | Some("reload") => true,
+            Some("mod") => {
+                args.first().is_some_and(|name| name.get_symbol().is_some())
+                    && args.get(1).map_or(true, |option| option.get_keyword().is_some())
+            }
+            _ => false,
+        }
+    }
+
+    fn try_compile_module_form(&mut self, symbol_id: u32, args: &[ValueRef]) -> Option<Result<u8, String>> {
+        if !self.is_module_form(symbol_id, args) {
+            return None;
+        }
+        let symbol_name = self.vm.symbol_table.read().get_symbol(symbol_id)?;
+        match symbol_name.as_str() {
+            "imp" => Some(self.compile_imp(args)),
+            "load" => Some(self.compile_load(args)),
+            "reload" => Some(self.compile_reload(args)),
+            _ => Some(self.compile_mod(args)),
+        }
+    }
+
+    // A module name: a symbol without a qualifier
+    fn module_name(&self, value: ValueRef, form: &str) -> Result<u32, String> {
+        match value.get_symbol() {
+            Some(id) if !self.vm.symbol_table.read().is_qualified(id) => Ok(id),
+            _ => Err(format!("{}: module name must be an unqualified symbol", form)),
+        }
+    }
+
+    // [a b] as symbol ids
+    fn symbol_list(&self, value: ValueRef, form: &str, option: &str) -> Result<Vec<u32>, String> {
+        let items = value
+            .get_vec()
+            .ok_or_else(|| format!("{}: {} expects a vector of symbols", form, option))?;
+        items
+            .iter()
+            .map(|item| item.get_symbol().ok_or_else(|| format!("{}: {} expects a vector of symbols", form, option)))
+            .collect()
+    }
+
+    fn option_name(&self, value: ValueRef, form: &str) -> Result<String, String> {
+        value
+            .get_keyword()
+            .and_then(|id| self.vm.symbol_table.read().get_symbol(id))
+            .ok_or_else(|| format!("{}: expected a keyword option, got {}", form, value))
+    }
+
+    fn emit_symbol_list(&mut self, symbols: Option<&[u32]>) -> Result<(), String> {
+        match symbols {
+            None => self.emit_u16(NO_LIST),
+            Some(symbols) => {
+                let count = u16::try_from(symbols.len())
+                    .ok()
+                    .filter(|count| *count != NO_LIST)
+                    .ok_or("Too many names in a module form")?;
+                self.emit_u16(count);
+                for &symbol in symbols {
+                    self.emit_u32(symbol);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // (mod name :export [a b]) makes `name` the current module, creating it if
+    // needed. Top-level forms after it compile and run in the module. With
+    // :export only the listed names are public; without it every definition is.
+    fn compile_mod(&mut self, args: &[ValueRef]) -> Result<u8, String> {
+        if self.scope_stack.len() > 1 || !self.upvalue_stack.is_empty() {
+            return Err("mod must be used at the top level".to_string());
+        }
+        let module_id = self.module_name(args[0], "mod")?;
+
+        let mut exports = None;
+        let mut options = args[1..].iter();
+        while let Some(&option) = options.next() {
+            match self.option_name(option, "mod")?.as_str() {
+                ":export" => {
+                    let list = options.next().ok_or("mod: :export expects a vector of symbols")?;
+                    exports = Some(self.symbol_list(*list, "mod", ":export")?);
+                }
+                other => return Err(format!("mod: unknown option {}", other)),
+            }
+        }
+
+        self.emit_u8(Opcode::EnterModule as u8);
+        self.emit_u32(module_id);
+        self.emit_symbol_list(exports.as_deref())?;
+
+        // Functions compiled from here on belong to the new module
+        self.current_module = module_id;
+
+        let result_reg = self.alloc_register();
+        self.emit_load_immediate(result_reg, ValueRef::nil());
+        Ok(result_reg)
+    }
+
+    // (load "path") evaluates a source file, once, and gives the value of its
+    // last form. The path is looked up relative to the file being loaded, then
+    // on the module path.
+    fn compile_load(&mut self, args: &[ValueRef]) -> Result<u8, String> {
+        if args.len() != 1 {
+            return Err(format!("load expects 1 argument, got {}", args.len()));
+        }
+        let path_reg = self.compile_expression(args[0])?;
+        let result_reg = self.alloc_register();
+        self.emit_u8(Opcode::LoadFile as u8);
+        self.emit_u8(result_reg);
+        self.emit_u8(path_reg);
+        Ok(result_reg)
+    }
+
+    // (reload 'name) evaluates the module's file again, then the files of the
+    // modules that import it, and gives the reloaded module names in order
+    fn compile_reload(&mut self, args: &[ValueRef]) -> Result<u8, String> {
+        if args.len() != 1 {
+            return Err(format!("reload expects 1 argument, got {}", args.len()));
+        }
+        let module_reg = self.compile_expression(args[0])?;
+        let result_reg = self.alloc_register();
+        self.emit_u8(Opcode::ReloadModule as u8);
+        self.emit_u8(result_reg);
+        self.emit_u8(module_reg);
+        Ok(result_reg)
+    }
+
+    // (imp name :as alias :only [a b]) or (imp name :refer-all). The module's
+    // public names are always reachable as name/a, or alias/a with :as; :only
+    // and :refer-all also make them usable unqualified.
+    fn compile_imp(&mut self, args: &[ValueRef]) -> Result<u8, String> {
+        let Some(&name) = args.first() else {
+            return Err("imp expects a module name".to_string());
+        };
+        let module_id = self.module_name(name, "imp")?;
+
+        let mut alias = None;
+        let mut only = None;
+        let mut refer_all = false;
+        let mut options = args[1..].iter();
+        while let Some(&option) = options.next() {
+            match self.option_name(option, "imp")?.as_str() {
+                ":as" => {
+                    let value = options.next().ok_or("imp: :as expects a symbol")?;
+                    alias = Some(self.module_name(*value, "imp :as")?);
+                }
+                ":only" => {
+                    let list = options.next().ok_or("imp: :only expects a vector of symbols")?;
+                    only = Some(self.symbol_list(*list, "imp", ":only")?);
+                }
+                ":refer-all" => refer_all = true,
+                other => return Err(format!("imp: unknown option {}", other)),
+            }
+        }
+        if refer_all && only.is_some() {
+            return Err("imp: :only and :refer-all cannot be combined".to_string());
+        }
+
+        self.emit_u8(Opcode::ImportModule as u8);
+        self.emit_u32(module_id);
+        self.emit_u32(alias.unwrap_or(NO_SYMBOL));
+        self.emit_u8(if refer_all { IMPORT_REFER_ALL } else { 0 });
+        self.emit_symbol_list(only.as_deref())?;
+
+        let result_reg = self.alloc_register();
+        self.emit_load_immediate(result_reg, ValueRef::nil());
+        Ok(result_reg)
+    }
+
     fn try_compile_arithmetic(&mut self, symbol_id: u32, args: &[ValueRef]) -> Result<u8, String> {
         let symbol_name = self
             .vm
@@ -1640,7 +2198,7 @@ impl BytecodeCompiler {
         };
 
         // Compile first argument as initial accumulator
-        let mut accumulator_reg = self.compile_expression(args[0])?;
+        let mut accumulator_reg = self.compile_operand(args[0])?;
 
         // Chain subsequent arguments
         for arg in &args[1..] {
@@ -1658,19 +2216,121 @@ impl BytecodeCompiler {
         Ok(accumulator_reg)
     }
 
+    // inc, dec and mod compile to opcodes while the name still means the core
+    // native. Other arities, and names that are shadowed or redefined, are
+    // compiled as ordinary calls.
+    fn try_compile_numeric_builtin(&mut self, symbol_id: u32, args: &[ValueRef]) -> Option<Result<u8, String>> {
+        let symbol_name = self.vm.symbol_table.read().get_symbol(symbol_id)?;
+        let opcode = match (symbol_name.as_str(), args.len()) {
+            ("inc", 1) => Opcode::AddImm8,
+            ("dec", 1) => Opcode::SubImm8,
+            ("mod", 2) => Opcode::Mod,
+            _ => return None,
+        };
+        if !self.refers_to_core_native(symbol_id) {
+            return None;
+        }
+        Some(self.compile_numeric_builtin(opcode, args))
+    }
+
+    fn compile_numeric_builtin(&mut self, opcode: Opcode, args: &[ValueRef]) -> Result<u8, String> {
+        let left_reg = self.compile_operand(args[0])?;
+        let right_reg = match args.get(1) {
+            Some(arg) => Some(self.compile_expression(*arg)?),
+            None => None,
+        };
+        let result_reg = self.alloc_register();
+
+        self.emit_u8(opcode as u8);
+        self.emit_u8(result_reg);
+        self.emit_u8(left_reg);
+        // The immediate forms take the 1 of inc and dec as their operand
+        self.emit_u8(right_reg.unwrap_or(1));
+
+        Ok(result_reg)
+    }
+
+    // True when a name is not bound locally and resolves to the native core registered
+    fn refers_to_core_native(&self, symbol_id: u32) -> bool {
+        if self.resolve_any_local_symbol(symbol_id).is_some() || self.resolve_upvalue(symbol_id).is_some() {
+            return false;
+        }
+        let Some(core_module) = self.vm.core_module else {
+            return false;
+        };
+        let registry = self.vm.module_registry.read();
+        match registry.resolve_symbol(core_module, symbol_id) {
+            Some(native) if native.is_native_fn() => registry
+                .resolve_symbol(self.current_module, symbol_id)
+                .map_or(true, |current| current == native),
+            _ => false,
+        }
+    }
+
     fn compile_regular_function_call(
         &mut self,
         symbol_id: u32,
         args: &[ValueRef],
     ) -> Result<u8, String> {
         let func_reg = self.alloc_register();
+        self.emit_load_function(func_reg, symbol_id);
 
-        // Load global function
-        self.emit_u8(Opcode::LoadGlobal as u8);
+        self.compile_call_arguments(func_reg, args)?;
+
+        let result_reg = self.alloc_register();
+
+        // Emit call - arguments are now in consecutive registers starting at first_arg_reg
+        self.emit_u8(Opcode::Call as u8);
         self.emit_u8(func_reg);
-        self.emit_u32(symbol_id);
+        self.emit_u8(args.len() as u8);
+        self.emit_u8(result_reg);
+
+        Ok(0) // ← Return register 0, where Call actually puts the result
+    }
+
+    fn compile_expression_call(&mut self, head: ValueRef, args: &[ValueRef]) -> Result<u8, String> {
+        let func_reg = self.alloc_register();
+        let head_reg = self.compile_expression(head)?;
+        if head_reg != func_reg {
+            self.emit_u8(Opcode::LoadLocal as u8);
+            self.emit_u8(func_reg);
+            self.emit_u8(head_reg);
+        }
 
-        // Compile arguments into consecutive registers
+        self.compile_call_arguments(func_reg, args)?;
+
+        let result_reg = self.alloc_register();
+        self.emit_u8(Opcode::Call as u8);
+        self.emit_u8(func_reg);
+        self.emit_u8(args.len() as u8);
+        self.emit_u8(result_reg);
+
+        Ok(0)
+    }
+
+    // Load the function named in call position: a local, an upvalue or a global
+    // Resolved like compile_symbol_reference: a name the function captured, such
+    // as the enclosing named fn, is an upvalue rather than the parent's register
+    fn emit_load_function(&mut self, func_reg: u8, symbol_id: u32) {
+        let local_reg = self.resolve_local_symbol(symbol_id);
+        let upvalue_idx = local_reg.is_none().then(|| self.resolve_upvalue(symbol_id)).flatten();
+        if let Some(upvalue_idx) = upvalue_idx {
+            self.emit_u8(Opcode::LoadUpvalue as u8);
+            self.emit_u8(func_reg);
+            self.emit_u8(upvalue_idx);
+        } else if let Some(local_reg) = local_reg.or_else(|| self.resolve_any_local_symbol(symbol_id)) {
+            self.emit_u8(Opcode::LoadLocal as u8);
+            self.emit_u8(func_reg);
+            self.emit_u8(local_reg);
+        } else {
+            self.emit_u8(Opcode::LoadGlobal as u8);
+            self.emit_u8(func_reg);
+            self.emit_u32(symbol_id);
+        }
+    }
+
+    // Compile call arguments into the consecutive registers after func_reg
+    fn compile_call_arguments(&mut self, func_reg: u8, args: &[ValueRef]) -> Result<(), String> {
         let mut arg_registers = Vec::new();
         for arg in args {
             let arg_reg = self.compile_expression(*arg)?; // This puts result in register 0
@@ -1694,50 +2354,24 @@ impl BytecodeCompiler {
                 self.emit_u8(arg_reg);
             }
         }
-
-        let result_reg = self.alloc_register();
-
-        // Emit call - arguments are now in consecutive registers starting at first_arg_reg
-        self.emit_u8(Opcode::Call as u8);
-        self.emit_u8(func_reg);
-        self.emit_u8(args.len() as u8);
-        self.emit_u8(result_reg);
-
-        Ok(0) // ← Return register 0, where Call actually puts the result
+        Ok(())
     }
 
     fn is_special_form(&self, symbol_id: u32) -> bool {
-        if let Some(symbol_name) = self.vm.symbol_table.read().get_symbol(symbol_id) {
-            matches!(
-                symbol_name.as_str(),
-                "if" | "let"
-                    | "do"
-                    | "quote"
-                    | "def"
-                    | "fn"
-                    | "loop"
-                    | "recur"
-                    | "cond"
-                    | "macro"
-                    | "quasiquote"
-                    | "unquote"
-                    | "unquote-splicing"
-                    | "future"
-                    | "complete"
-                    | "go"
-                    | "deref"
-            )
-        } else {
-            false
-        }
+        self.vm
+            .symbol_table
+            .read()
+            .get_symbol(symbol_id)
+            .is_some_and(|symbol_name| SPECIAL_FORMS.contains(&symbol_name.as_str()))
     }
 
-    fn is_arithmetic_operator(&self, symbol_id: u32) -> bool {
-        if let Some(symbol_name) = self.vm.symbol_table.read().get_symbol(symbol_id) {
-            matches!(symbol_name.as_str(), "+" | "-" | "*" | "/")
-        } else {
-            false
-        }
+    // Operators compiled to opcodes rather than calls
+    fn is_inline_operator(&self, symbol_id: u32) -> bool {
+        self.vm
+            .symbol_table
+            .read()
+            .get_symbol(symbol_id)
+            .is_some_and(|symbol_name| INLINE_OPERATORS.contains(&symbol_name.as_str()))
     }
 
     // MAIN COMPILATION ENTRY POINTS
@@ -1750,15 +2384,19 @@ impl BytecodeCompiler {
         // Emit return
         self.emit_u8(Opcode::Return as u8);
         self.emit_u8(result_reg);
+        self.close_locals(0);
 
         Ok(CompiledFunction {
             bytecode: self.bytecode.clone(),
             constants: self.constants.clone(), // Make sure this is actually copying
             parameter_count: 0,
             register_count: self.next_register,
-            module: 0,
+            module: self.current_module,
             register_start: 0,
             has_self_reference: false,
+            name: None,
+            line_table: self.line_table.clone(),
+            locals: self.local_vars.clone(),
         })
     }
 
@@ -2301,12 +2939,13 @@ impl BytecodeCompiler {
             return Err("quasiquote expects exactly 1 argument".to_string());
         }
 
-        if self.has_unquotes(args[0]) {
-            let processed = self.process_quasiquote(args[0], 1)?;
+        let template = SyntaxQuote::new(&self.vm, self.current_module).resolve(args[0]);
+        if self.has_unquotes(template) {
+            let processed = self.process_quasiquote(template, 1)?;
 
             self.compile_expression(processed)
         } else {
-            self.compile_quote(args)
+            self.compile_quote(&[template])
         }
     }
 
@@ -2363,210 +3002,94 @@ impl BytecodeCompiler {
         Err("unquote-splicing used outside quasiquote".to_string())
     }
 
-    /// Process a quasiquoted expression, handling nested quasiquotes
+    /// Code that builds a quasiquoted template when it runs: quoted parts
+    /// become (quote x), lists and vectors become calls to core/list, core/concat
+    /// and core/vec, and unquoted parts are left as the code they are.
     /// depth: current nesting level of quasiquotes (1 = top level)
     fn process_quasiquote(&mut self, expr: ValueRef, depth: i32) -> Result<ValueRef, String> {
-        match expr {
-            ValueRef::Immediate(packed) => {
-                // For immediate values (symbols, numbers, etc), just return them quoted
-                Ok(expr)
-            }
-            ValueRef::Heap(_) => {
-                if let Some(list_items) = expr.get_list() {
-                    self.process_quasiquote_list(&list_items, depth)
-                } else if let Some(vec_items) = expr.get_vec() {
-                    // Handle vectors specially
-                    let processed_items = self.process_quasiquote_list(&vec_items, depth)?;
-                    if let Some(processed_list) = processed_items.get_list() {
-                        // Convert the list back to a vector
-                        Ok(self.create_quoted_vector(processed_list))
-                    } else {
-                        Ok(processed_items)
+        if let Some(list_items) = expr.get_list() {
+            if let [head, inner] = list_items[..] {
+                let head_name = head.get_symbol().and_then(|symbol_id| self.vm.get_symbol_name(symbol_id));
+                let inner_depth = match head_name.as_deref() {
+                    Some("unquote") if depth == 1 => return Ok(inner),
+                    Some("unquote-splicing") if depth == 1 => {
+                        return Err("unquote-splicing used outside a list or vector".to_string());
                     }
-                } else {
-                    // For other heap values (maps, strings, etc), return as-is
-                    Ok(expr)
+                    Some("quasiquote") => Some(depth + 1),
+                    Some("unquote") | Some("unquote-splicing") => Some(depth - 1),
+                    _ => None,
+                };
+                if let Some(inner_depth) = inner_depth {
+                    // Nested: rebuild the form around its processed argument
+                    let inner = self.process_quasiquote(inner, inner_depth)?;
+                    let quoted_head = self.quoted(head);
+                    return Ok(self.core_call("list", vec![quoted_head, inner]));
                 }
             }
-            ValueRef::Handle(_) => Ok(expr),
+            return self.process_quasiquote_items(&list_items, depth);
         }
-    }
-
-    fn process_quasiquote_list(
-        &mut self,
-        items: &[ValueRef],
-        depth: i32,
-    ) -> Result<ValueRef, String> {
-        if items.is_empty() {
-            // Empty list - just return a quoted empty list
-            return Ok(self.create_quoted_list(vec![]));
+        if let Some(vec_items) = expr.get_vec() {
+            let items = self.process_quasiquote_items(&vec_items, depth)?;
+            return Ok(self.core_call("vec", vec![items]));
         }
-
-        // Check if first item is a special quasiquote form
-        if let ValueRef::Immediate(packed) = items[0] {
-            if let ImmediateValue::Symbol(symbol_id) = unpack_immediate(packed) {
-                let symbol_name = self.vm.symbol_table.read().get_symbol(symbol_id);
-                match symbol_name.as_deref() {
-                    Some("quasiquote") => {
-                        // Nested quasiquote - increase depth
-                        if items.len() != 2 {
-                            return Err("quasiquote expects exactly 1 argument".to_string());
-                        }
-                        let processed = self.process_quasiquote(items[1], depth + 1)?;
-                        return Ok(self.create_quoted_list(vec![items[0], processed]));
-                    }
-                    Some("unquote") => {
-                        if depth == 1 {
-                            // This is the unquote we should evaluate
-                            if items.len() != 2 {
-                                return Err("unquote expects exactly 1 argument".to_string());
-                            }
-                            return Ok(items[1]); // Return the unquoted expression for evaluation
-                        } else {
-                            // Nested unquote - decrease depth
-                            let processed = self.process_quasiquote(items[1], depth - 1)?;
-                            return Ok(self.create_quoted_list(vec![items[0], processed]));
-                        }
-                    }
-                    Some("unquote-splicing") => {
-                        return Err("unquote-splicing not allowed at top level of list".to_string());
-                    }
-                    _ => {
-                        // Regular symbol, fall through to process normally
-                    }
-                }
+        if let Some(map) = expr.get_map() {
+            let mut entries = Vec::new();
+            for (key, value) in map.iter() {
+                entries.push(self.process_quasiquote(*key, depth)?);
+                entries.push(self.process_quasiquote(*value, depth)?);
             }
+            return Ok(self.core_call("hash-map", entries));
         }
+        Ok(self.quoted(expr))
+    }
 
-        // Process all items, collecting them and checking for splicing
-        let mut result_parts = Vec::new();
-
-        for item in items {
-            // Check for unquote-splicing and unquote
-            if let Some(list_items) = item.get_list() {
-                if list_items.len() == 2 {
-                    if let ValueRef::Immediate(packed) = list_items[0] {
-                        if let ImmediateValue::Symbol(symbol_id) = unpack_immediate(packed) {
-                            let symbol_name = self.vm.symbol_table.read().get_symbol(symbol_id);
-                            if symbol_name.as_deref() == Some("unquote-splicing") {
-                                if depth == 1 {
-                                    // This is actual splicing
-                                    result_parts.push(SplicePart::Splice(list_items[1]));
-                                    continue;
-                                } else {
-                                    // Nested unquote-splicing - decrease depth
-                                    let processed =
-                                        self.process_quasiquote(list_items[1], depth - 1)?;
-                                    let new_item =
-                                        self.create_quoted_list(vec![list_items[0], processed]);
-                                    result_parts.push(SplicePart::Item(new_item));
-                                    continue;
-                                }
-                            }
-                            // ADD THIS: Handle regular unquote
-                            if symbol_name.as_deref() == Some("unquote") {
-                                if depth == 1 {
-                                    // This is an unquote - mark as unquoted (no quoting!)
-                                    result_parts.push(SplicePart::UnquotedItem(list_items[1]));
-                                    continue;
-                                } else {
-                                    // Nested unquote - decrease depth
-                                    let processed =
-                                        self.process_quasiquote(list_items[1], depth - 1)?;
-                                    let new_item =
-                                        self.create_quoted_list(vec![list_items[0], processed]);
-                                    result_parts.push(SplicePart::Item(new_item));
-                                    continue;
-                                }
-                            }
-                        }
+    /// Code for a list of template items: (core/list a b) or, when some are
+    /// spliced, (core/concat (core/list a) spliced (core/list b))
+    fn process_quasiquote_items(&mut self, items: &[ValueRef], depth: i32) -> Result<ValueRef, String> {
+        let mut segments = Vec::new();
+        let mut current = Vec::new();
+        for &item in items {
+            let spliced = item.get_list().and_then(|list_items| match list_items[..] {
+                [head, inner] if depth == 1 && self.is_symbol_named(head, "unquote-splicing") => Some(inner),
+                _ => None,
+            });
+            match spliced {
+                Some(inner) => {
+                    if !current.is_empty() {
+                        segments.push(self.core_call("list", std::mem::take(&mut current)));
                     }
+                    segments.push(inner);
                 }
+                None => current.push(self.process_quasiquote(item, depth)?),
             }
-
-            // Regular item - process recursively
-            let processed = self.process_quasiquote(*item, depth)?;
-
-            let final_item = processed;
-            result_parts.push(SplicePart::Item(final_item));
         }
-        self.build_spliced_list(result_parts)
-    }
 
-    fn create_quoted_list(&mut self, items: Vec<ValueRef>) -> ValueRef {
-        // Create a direct list structure for macro expansion
-        // This should create (if condition body nil) not (list 'if condition body 'nil)
-        ValueRef::Heap(GcPtr::new(
-            self.vm.alloc_list_from_items(items), // true = list
-        ))
+        if segments.is_empty() {
+            return Ok(self.core_call("list", current));
+        }
+        if !current.is_empty() {
+            segments.push(self.core_call("list", current));
+        }
+        Ok(self.core_call("concat", segments))
     }
 
-    fn create_quoted_vector(&mut self, items: Vec<ValueRef>) -> ValueRef {
-        // Create a simple quoted vector literal
-        ValueRef::Heap(GcPtr::new(self.vm.alloc_list_from_items(items)))
+    fn quoted(&self, value: ValueRef) -> ValueRef {
+        let quote = self.vm.symbol_table.write().intern("quote");
+        self.vm.list_value(vec![ValueRef::symbol(quote), value])
     }
 
-    fn build_spliced_list(&mut self, parts: Vec<SplicePart>) -> Result<ValueRef, String> {
-        // Check if we actually need splicing
-        let has_splice = parts.iter().any(|p| matches!(p, SplicePart::Splice(_)));
-
-        if !has_splice {
-            // No splicing - just build a regular quoted list
-            let items: Vec<ValueRef> = parts
-                .into_iter()
-                .map(|p| match p {
-                    SplicePart::Item(item) => item,
-                    SplicePart::Splice(_) => unreachable!(),
-                    SplicePart::UnquotedItem(item) => item,
-                })
-                .collect();
-            return Ok(self.create_quoted_list(items));
-        }
-
-        // We have splicing - need to build a runtime expression
-        // Transform into: (concat (list item1) spliced-expr (list item3) ...)
-
-        let concat_symbol = self.vm.symbol_table.write().intern("concat");
-        let concat_symbol_val = ValueRef::symbol(concat_symbol);
-        let list_symbol = self.vm.symbol_table.write().intern("list");
-        let list_symbol_val = ValueRef::symbol(list_symbol);
-
-        let mut concat_args = vec![concat_symbol_val];
-        let mut current_items = Vec::new();
-
-        for part in parts {
-            match part {
-                SplicePart::Item(item) => {
-                    current_items.push(item);
-                }
-                SplicePart::Splice(expr) => {
-                    // Flush any accumulated items as a list
-                    if !current_items.is_empty() {
-                        let mut list_call = vec![list_symbol_val];
-                        list_call.extend(current_items.drain(..));
-                        concat_args.push(ValueRef::Heap(GcPtr::new(
-                            self.vm.alloc_list_from_items(list_call),
-                        )));
-                    }
-                    // Add the spliced expression directly
-                    concat_args.push(expr);
-                }
-                SplicePart::UnquotedItem(item) => current_items.push(item),
+    // A call to a core function that the caller's locals cannot shadow
+    fn core_call(&self, name: &str, args: Vec<ValueRef>) -> ValueRef {
+        let function = {
+            let mut symbol_table = self.vm.symbol_table.write();
+            let name_id = symbol_table.intern(name);
+            match self.vm.core_module {
+                Some(core_module) => symbol_table.intern_qualified(core_module, name_id),
+                None => name_id,
             }
-        }
-
-        // Flush any remaining items
-        if !current_items.is_empty() {
-            let mut list_call = vec![list_symbol_val];
-            list_call.extend(current_items);
-            concat_args.push(ValueRef::Heap(GcPtr::new(
-                self.vm.alloc_list_from_items(list_call),
-            )));
-        }
-
-        // Return the concat expression
-        Ok(ValueRef::Heap(GcPtr::new(
-            self.vm.alloc_list_from_items(concat_args),
-        )))
+        };
+        let mut call = vec![ValueRef::symbol(function)];
+        call.extend(args);
+        self.vm.list_value(call)
     }
 }
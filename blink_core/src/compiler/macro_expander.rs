@@ -1,23 +1,32 @@
-use std::{collections::HashMap, sync::Arc};
+use std::sync::Arc;
 
 use crate::{
     runtime::{BlinkVM, Macro},
     value::unpack_immediate,
-    value::{HeapValue, ImmediateValue, ValueRef},
+    value::{FunctionCaller, HeapValue, ImmediateValue, ValueRef},
 };
 
-pub struct MacroExpander {
+pub struct MacroExpander<'a> {
     vm: Arc<BlinkVM>,
     current_module: u32,
+    // Runs the compiled macro bodies
+    caller: &'a mut dyn FunctionCaller,
+    // Locals bound around the form being expanded, which a macro sees as &env
+    locals: Vec<u32>,
+    // The macros expanded so far, as (module, name)
+    expanded_macros: Vec<(u32, u32)>,
     expansion_depth: usize,
     max_expansion_depth: usize,
 }
 
-impl MacroExpander {
-    pub fn new(vm: Arc<BlinkVM>, module: u32) -> Self {
+impl<'a> MacroExpander<'a> {
+    pub fn new(vm: Arc<BlinkVM>, module: u32, caller: &'a mut dyn FunctionCaller) -> Self {
         Self {
             vm,
             current_module: module,
+            caller,
+            locals: Vec::new(),
+            expanded_macros: Vec::new(),
             expansion_depth: 0,
             max_expansion_depth: 100,
         }
@@ -46,6 +55,15 @@ impl MacroExpander {
         }
     }
 
+    /// The macros this expander has expanded, as (module, name), for recording
+    /// which definitions depend on them
+    pub fn expanded_macros(&self) -> Vec<(u32, u32)> {
+        let mut macros = self.expanded_macros.clone();
+        macros.sort_unstable();
+        macros.dedup();
+        macros
+    }
+
     /// Single expansion pass
     fn expand_once(&mut self, expr: ValueRef) -> Result<ValueRef, String> {
         match expr {
@@ -55,7 +73,7 @@ impl MacroExpander {
             }
             ValueRef::Heap(gc_ptr) => {
                 match gc_ptr.to_heap_value() {
-                    HeapValue::List(list_obj) => self.expand_list(&list_obj),
+                    HeapValue::List(list_obj) => self.expand_list(expr, &list_obj),
                     HeapValue::Vector(vec_obj) => {
                         // Expand elements in vectors too
                         let mut expanded_items = Vec::new();
@@ -88,7 +106,7 @@ impl MacroExpander {
     }
 
     /// Expand a list (might be a macro call)
-    fn expand_list(&mut self, items: &[ValueRef]) -> Result<ValueRef, String> {
+    fn expand_list(&mut self, form: ValueRef, items: &[ValueRef]) -> Result<ValueRef, String> {
         if items.is_empty() {
             return Ok(self.vm.list_value(vec![]));
         }
@@ -97,7 +115,7 @@ impl MacroExpander {
         if let ValueRef::Immediate(packed) = items[0] {
             if let ImmediateValue::Symbol(symbol_id) = unpack_immediate(packed) {
                 // Try to expand as macro
-                if let Some(expanded) = self.try_expand_macro(symbol_id, &items[1..])? {
+                if let Some(expanded) = self.try_expand_macro(symbol_id, form, &items[1..])? {
                     return Ok(expanded);
                 }
 
@@ -121,15 +139,17 @@ impl MacroExpander {
     fn try_expand_macro(
         &mut self,
         symbol_id: u32,
+        form: ValueRef,
         args: &[ValueRef],
     ) -> Result<Option<ValueRef>, String> {
-        // Look up symbol in module registry
-        let symbol_value = match self
-            .vm
-            .module_registry
-            .read()
-            .resolve_symbol(self.current_module, symbol_id)
-        {
+        // A local of the same name shadows the macro
+        if self.locals.contains(&symbol_id) {
+            return Ok(None);
+        }
+
+        // Look up the symbol as the running code would: the current module,
+        // its imports, core, then module/name
+        let symbol_value = match self.vm.resolve_global_symbol(self.current_module, symbol_id) {
             Some(val) => val,
             None => return Ok(None), // Symbol not found
         };
@@ -137,22 +157,19 @@ impl MacroExpander {
         // Check if it's a macro
         if let ValueRef::Heap(gc_ptr) = symbol_value {
             if let HeapValue::Macro(macro_def) = gc_ptr.to_heap_value() {
+                let name = self.vm.get_symbol_name(symbol_id).unwrap_or_default();
+                if let Some(location) = self.vm.locate_global_symbol(self.current_module, symbol_id) {
+                    self.expanded_macros.push(location);
+                }
+
                 // Execute macro expansion
-                let expanded = self.expand_macro_call(&macro_def, args)?;
+                let expanded = self
+                    .expand_macro_call(&macro_def, form, args)
+                    .map_err(|e| format!("Expanding {}: {}", name, e))?;
 
                 println!(
                     "DEBUG: Macro {} expanded from ({} ...) to {}",
-                    self.vm
-                        .symbol_table
-                        .read()
-                        .get_symbol(symbol_id)
-                        .unwrap_or_default(),
-                    self.vm
-                        .symbol_table
-                        .read()
-                        .get_symbol(symbol_id)
-                        .unwrap_or_default(),
-                    expanded
+                    name, name, expanded
                 );
 
                 return Ok(Some(expanded));
@@ -162,51 +179,48 @@ impl MacroExpander {
         Ok(None)
     }
 
-    /// Expand a macro call
+    /// Expand a macro call by running the macro's compiled body on the
+    /// unevaluated arguments
     fn expand_macro_call(
         &mut self,
         macro_def: &Macro,
+        form: ValueRef,
         args: &[ValueRef],
     ) -> Result<ValueRef, String> {
         // Validate arity
         macro_def.validate_arity(args.len())?;
 
-        // Create parameter bindings
-        let mut bindings = HashMap::new();
-
-        // Bind regular parameters
-        for (i, &param_id) in macro_def.regular_params().iter().enumerate() {
-            bindings.insert(param_id, args[i]);
+        // The expander takes &form and &env, the regular arguments, then the
+        // rest of them as a list
+        let regular_count = macro_def.regular_params().len();
+        let mut call_args = vec![form, self.env_value()];
+        call_args.extend_from_slice(&args[..regular_count]);
+        if macro_def.is_variadic {
+            call_args.push(self.vm.list_value(args[regular_count..].to_vec()));
         }
 
-        // Bind variadic parameter (if any)
-        if let Some(variadic_param) = macro_def.variadic_param() {
-            let variadic_args: Vec<ValueRef> = args
-                .iter()
-                .skip(macro_def.regular_params().len())
-                .cloned()
-                .collect();
-            let variadic_list = self.vm.list_value(variadic_args);
-            bindings.insert(variadic_param, variadic_list);
-        }
-
-        // Expand macro body using substitution
-        if macro_def.body.len() == 1 {
-            self.substitute_in_ast(macro_def.body[0], &bindings)
+        self.expansion_depth += 1;
+        let result = if self.expansion_depth > self.max_expansion_depth {
+            Err("Maximum macro expansion depth exceeded".to_string())
         } else {
-            // Multiple body forms - wrap in 'do
-            let do_symbol = {
-                let mut symbol_table = self.vm.symbol_table.write();
-                ValueRef::symbol(symbol_table.intern("do"))
-            };
-
-            let mut expanded_body = vec![do_symbol];
-            for &body_expr in &macro_def.body {
-                expanded_body.push(self.substitute_in_ast(body_expr, &bindings)?);
+            // An error value from the body fails the expansion too
+            match self.caller.call_function(macro_def.expander, call_args) {
+                Ok(expansion) => expansion.get_error().map_or(Ok(expansion), |error| Err(error.message)),
+                Err(error) => Err(error.message),
             }
+        };
+        self.expansion_depth -= 1;
+        result
+    }
 
-            Ok(self.vm.list_value(expanded_body))
-        }
+    /// &env: a map from each local in scope to true
+    fn env_value(&self) -> ValueRef {
+        let pairs = self
+            .locals
+            .iter()
+            .map(|&symbol_id| (ValueRef::symbol(symbol_id), ValueRef::boolean(true)))
+            .collect();
+        self.vm.map_value(pairs)
     }
 
     /// Check if two values are equal (for fixpoint detection)
@@ -224,7 +238,9 @@ impl MacroExpander {
                     | "fn"
                     | "if"
                     | "let"
+                    | "loop"
                     | "quote"
+                    | "quasiquote"
                     | "do"
                     | "try"
                     | "macro"
@@ -238,202 +254,8 @@ impl MacroExpander {
         }
     }
 
-    fn substitute_in_ast(
-        &mut self,
-        expr: ValueRef,
-        bindings: &HashMap<u32, ValueRef>,
-    ) -> Result<ValueRef, String> {
-        match expr {
-            ValueRef::Immediate(packed) => {
-                if let ImmediateValue::Symbol(symbol_id) = unpack_immediate(packed) {
-                    // If this symbol is a parameter, substitute it
-                    if let Some(&replacement) = bindings.get(&symbol_id) {
-                        Ok(replacement)
-                    } else {
-                        Ok(expr) // Not a parameter, keep as-is
-                    }
-                } else {
-                    Ok(expr) // Numbers, bools, etc. - no substitution needed
-                }
-            }
-            ValueRef::Heap(gc_ptr) => {
-                match gc_ptr.to_heap_value() {
-                    HeapValue::List(list_obj) => self.substitute_in_list(&list_obj, bindings),
-                    HeapValue::Vector(vec_obj) => {
-                        let mut substituted = Vec::new();
-                        for &item in &vec_obj {
-                            substituted.push(self.substitute_in_ast(item, bindings)?);
-                        }
-                        Ok(self.vm.vector_value(substituted))
-                    }
-                    HeapValue::Map(map_obj) => {
-                        let mut substituted_pairs = Vec::new();
-                        for &(key, value) in &map_obj.iter().collect::<Vec<_>>() {
-                            let new_key = self.substitute_in_ast(*key, bindings)?;
-                            let new_value = self.substitute_in_ast(*value, bindings)?;
-                            substituted_pairs.push((new_key, new_value));
-                        }
-                        Ok(self.vm.map_value(substituted_pairs))
-                    }
-                    _ => Ok(expr), // Strings, functions, etc. - no substitution needed
-                }
-            }
-            ValueRef::Handle(_) => Ok(expr), // Native functions - no substitution
-        }
-    }
-
-    /// Handle list substitution with special quasiquote support
-    fn substitute_in_list(
-        &mut self,
-        items: &[ValueRef],
-        bindings: &HashMap<u32, ValueRef>,
-    ) -> Result<ValueRef, String> {
-        if items.is_empty() {
-            return Ok(self.vm.list_value(vec![]));
-        }
-
-        // Check for quasiquote special forms first
-        if let ValueRef::Immediate(packed) = items[0] {
-            if let ImmediateValue::Symbol(symbol_id) = unpack_immediate(packed) {
-                let symbol_opt = self.vm.symbol_table.read().get_symbol(symbol_id);
-                if let Some(symbol_name) = symbol_opt {
-                    match symbol_name.as_str() {
-                        "quasiquote" => {
-                            if items.len() == 2 {
-                                return self.expand_quasiquote(items[1], bindings);
-                            }
-                        }
-                        "unquote" => {
-                            if items.len() == 2 {
-                                // ~expr - substitute the expression
-                                return self.substitute_in_ast(items[1], bindings);
-                            }
-                        }
-                        "unquote-splicing" => {
-                            // ~@expr should only appear inside quasiquote
-                            return Err("unquote-splicing used outside quasiquote".to_string());
-                        }
-                        _ => {} // Regular list
-                    }
-                }
-            }
-        }
-
-        // Regular list - substitute all elements
-        let mut substituted = Vec::new();
-        for &item in items {
-            substituted.push(self.substitute_in_ast(item, bindings)?);
-        }
-
-        Ok(self.vm.list_value(substituted))
-    }
-
-    /// Expand quasiquote template with unquote and unquote-splicing support
-    fn expand_quasiquote(
-        &mut self,
-        template: ValueRef,
-        bindings: &HashMap<u32, ValueRef>,
-    ) -> Result<ValueRef, String> {
-        match template {
-            ValueRef::Immediate(packed) => {
-                if let ImmediateValue::Symbol(symbol_id) = unpack_immediate(packed) {
-                    // In quasiquote, symbols are quoted unless explicitly unquoted
-                    // Check if this symbol should be substituted (but it won't be unless unquoted)
-                    Ok(template)
-                } else {
-                    Ok(template) // Numbers, bools, etc. are literal
-                }
-            }
-            ValueRef::Heap(gc_ptr) => {
-                match gc_ptr.to_heap_value() {
-                    HeapValue::List(list_obj) => self.expand_quasiquote_list(&list_obj, bindings),
-                    HeapValue::Vector(vec_obj) => {
-                        let mut expanded = Vec::new();
-                        for &item in &vec_obj {
-                            expanded.push(self.expand_quasiquote(item, bindings)?);
-                        }
-                        Ok(self.vm.vector_value(expanded))
-                    }
-                    _ => Ok(template), // Strings, etc. are literal
-                }
-            }
-            ValueRef::Handle(_) => Ok(template),
-        }
-    }
-
-    /// Expand quasiquote list with unquote and splicing support
-    fn expand_quasiquote_list(
-        &mut self,
-        items: &[ValueRef],
-        bindings: &HashMap<u32, ValueRef>,
-    ) -> Result<ValueRef, String> {
-        if items.is_empty() {
-            return Ok(self.vm.list_value(vec![]));
-        }
-
-        let mut result = Vec::new();
-        let mut i = 0;
-
-        while i < items.len() {
-            let item = items[i];
-
-            // Check for unquote or unquote-splicing
-            if let Some(list_items) = item.get_list() {
-                if !list_items.is_empty() {
-                    if let ValueRef::Immediate(packed) = list_items[0] {
-                        if let ImmediateValue::Symbol(symbol_id) = unpack_immediate(packed) {
-                            let symbol_opt = self.vm.symbol_table.read().get_symbol(symbol_id);
-                            if let Some(symbol_name) = symbol_opt {
-                                match symbol_name.as_str() {
-                                    "unquote" => {
-                                        // ~expr - evaluate and substitute
-                                        if list_items.len() == 2 {
-                                            let substituted =
-                                                self.substitute_in_ast(list_items[1], bindings)?;
-                                            result.push(substituted);
-                                            i += 1;
-                                            continue;
-                                        }
-                                    }
-                                    "unquote-splicing" => {
-                                        // ~@expr - evaluate and splice
-                                        if list_items.len() == 2 {
-                                            let substituted =
-                                                self.substitute_in_ast(list_items[1], bindings)?;
-
-                                            // The substituted value should be a list to splice
-                                            if let Some(splice_items) = substituted.get_list() {
-                                                result.extend_from_slice(&splice_items);
-                                            } else if let Some(splice_items) = substituted.get_vec()
-                                            {
-                                                result.extend_from_slice(&splice_items);
-                                            } else {
-                                                return Err(
-                                                    "unquote-splicing requires a list or vector"
-                                                        .to_string(),
-                                                );
-                                            }
-                                            i += 1;
-                                            continue;
-                                        }
-                                    }
-                                    _ => {} // Regular list
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-
-            // Regular item - recursively expand within quasiquote context
-            result.push(self.expand_quasiquote(item, bindings)?);
-            i += 1;
-        }
-
-        Ok(self.vm.list_value(result))
-    }
-
-    /// Handle let bindings carefully during expansion
+    /// Handle let bindings carefully during expansion; each name is in scope
+    /// for the bindings after it
     fn expand_let_bindings(&mut self, bindings: ValueRef) -> Result<ValueRef, String> {
         if let Some(binding_vec) = bindings.get_vec() {
             let mut expanded_bindings = Vec::new();
@@ -447,6 +269,7 @@ impl MacroExpander {
                 // Symbol doesn't get expanded, but value does
                 expanded_bindings.push(chunk[0]); // binding symbol
                 expanded_bindings.push(self.expand_once(chunk[1])?); // binding value
+                self.locals.extend(chunk[0].get_symbol());
             }
 
             Ok(self.vm.vector_value(expanded_bindings))
@@ -455,6 +278,41 @@ impl MacroExpander {
         }
     }
 
+    /// Expand the unquoted parts of a quasiquote template, which are code;
+    /// depth counts the quasiquotes around the form less the unquotes
+    fn expand_unquoted(&mut self, form: ValueRef, depth: usize) -> Result<ValueRef, String> {
+        if depth == 0 {
+            return self.expand_once(form);
+        }
+        if let Some(items) = form.get_list() {
+            if let [head, inner] = items[..] {
+                let name = head.get_symbol().and_then(|symbol_id| self.vm.get_symbol_name(symbol_id));
+                let inner_depth = match name.as_deref() {
+                    Some("quasiquote") => Some(depth + 1),
+                    Some("unquote") | Some("unquote-splicing") => Some(depth - 1),
+                    _ => None,
+                };
+                if let Some(inner_depth) = inner_depth {
+                    let inner = self.expand_unquoted(inner, inner_depth)?;
+                    return Ok(self.vm.list_value(vec![head, inner]));
+                }
+            }
+            let items = items
+                .into_iter()
+                .map(|item| self.expand_unquoted(item, depth))
+                .collect::<Result<Vec<_>, _>>()?;
+            return Ok(self.vm.list_value(items));
+        }
+        if let Some(items) = form.get_vec() {
+            let items = items
+                .into_iter()
+                .map(|item| self.expand_unquoted(item, depth))
+                .collect::<Result<Vec<_>, _>>()?;
+            return Ok(self.vm.vector_value(items));
+        }
+        Ok(form)
+    }
+
     /// Update expand_special_form to handle quasiquote properly
     fn expand_special_form(
         &mut self,
@@ -476,45 +334,64 @@ impl MacroExpander {
                 Ok(self.vm.list_value(result))
             }
             "quasiquote" => {
-                // Quasiquote needs special handling but isn't a macro call
-                // This should be handled during macro template expansion, not here
-                // For now, treat it like quote
+                // The template is data, but what it unquotes is code
                 let mut result = vec![ValueRef::symbol(symbol_id)];
-                result.extend_from_slice(args);
+                for &arg in args {
+                    result.push(self.expand_unquoted(arg, 1)?);
+                }
                 Ok(self.vm.list_value(result))
             }
             "unquote" | "unquote-splicing" => {
                 // These should only appear inside quasiquote
                 Err(format!("{} used outside quasiquote", symbol_name))
             }
-            "let" => {
+            "let" | "loop" => {
                 if args.len() < 2 {
-                    return Err("let expects at least 2 arguments".to_string());
+                    return Err(format!("{} expects at least 2 arguments", symbol_name));
                 }
 
                 // Expand binding values but not symbols
-                let bindings = self.expand_let_bindings(args[0])?;
+                let scope = self.locals.len();
+                let bindings = self.expand_let_bindings(args[0]);
 
                 // Expand body
-                let mut expanded_body = Vec::new();
-                for &body_expr in &args[1..] {
-                    expanded_body.push(self.expand_once(body_expr)?);
-                }
+                let expanded_body: Result<Vec<ValueRef>, String> = bindings.and_then(|bindings| {
+                    let mut expanded = vec![ValueRef::symbol(symbol_id), bindings];
+                    for &body_expr in &args[1..] {
+                        expanded.push(self.expand_once(body_expr)?);
+                    }
+                    Ok(expanded)
+                });
+                self.locals.truncate(scope);
 
-                let mut result = vec![ValueRef::symbol(symbol_id), bindings];
-                result.extend(expanded_body);
-                Ok(self.vm.list_value(result))
+                Ok(self.vm.list_value(expanded_body?))
             }
-            "fn" => {
+            "fn" | "macro" => {
                 if args.len() < 2 {
-                    return Err("fn expects at least 2 arguments".to_string());
+                    return Err(format!("{} expects at least 2 arguments", symbol_name));
                 }
 
-                // Params don't get expanded, body does
-                let mut result = vec![ValueRef::symbol(symbol_id), args[0]];
-                for &body_expr in &args[1..] {
-                    result.push(self.expand_once(body_expr)?);
+                // The name, docstring and params don't get expanded, the body
+                // does with the name and params in scope
+                let Some(params_index) = args.iter().position(|arg| arg.get_vec().is_some()) else {
+                    return Err(format!("{} parameter list must be a vector", symbol_name));
+                };
+                let scope = self.locals.len();
+                if symbol_name == "fn" {
+                    self.locals.extend(args[0].get_symbol());
+                } else {
+                    let mut symbol_table = self.vm.symbol_table.write();
+                    self.locals.extend([symbol_table.intern("&form"), symbol_table.intern("&env")]);
                 }
+                let params = args[params_index].get_vec().unwrap_or_default();
+                self.locals.extend(params.iter().filter_map(|param| param.get_symbol()));
+
+                let mut result = vec![ValueRef::symbol(symbol_id)];
+                result.extend_from_slice(&args[..=params_index]);
+                let body: Result<Vec<ValueRef>, String> =
+                    args[params_index + 1..].iter().map(|&body_expr| self.expand_once(body_expr)).collect();
+                self.locals.truncate(scope);
+                result.extend(body?);
 
                 Ok(self.vm.list_value(result))
             }
@@ -529,3 +406,142 @@ impl MacroExpander {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{context, eval, try_eval};
+
+    #[test]
+    fn gensyms_are_unique() {
+        let mut ctx = context("hygiene-gensym");
+        assert_ne!(eval(&mut ctx, "(gensym)"), eval(&mut ctx, "(gensym)"));
+        assert!(eval(&mut ctx, "(gensym \"tmp\")").starts_with("tmp__"));
+    }
+
+    #[test]
+    fn auto_gensyms_are_shared_within_a_template() {
+        let mut ctx = context("hygiene-auto");
+        let names = eval(&mut ctx, "(def names `[a# a# b#]) names");
+        let names: Vec<&str> = names.trim_matches(|c| c == '[' || c == ']').split_whitespace().collect();
+        assert_eq!(names.len(), 3);
+        assert_eq!(names[0], names[1]);
+        assert_ne!(names[0], names[2]);
+        assert_ne!(names[0], eval(&mut ctx, "(first `[a#])"));
+    }
+
+    #[test]
+    fn template_locals_do_not_capture_caller_bindings() {
+        let mut ctx = context("hygiene-capture");
+        let code = "(def unsafe-add (macro [a b] `(let [tmp ~a] (+ tmp ~b))))
+                    (let [tmp 10] (unsafe-add 1 tmp))";
+        assert_eq!(eval(&mut ctx, code), "2");
+
+        let code = "(def safe-add (macro [a b] `(let [tmp# ~a] (+ tmp# ~b))))
+                    (let [tmp 10] (safe-add 1 tmp))";
+        assert_eq!(eval(&mut ctx, code), "11");
+    }
+
+    #[test]
+    fn caller_locals_do_not_shadow_template_globals() {
+        let mut ctx = context("hygiene-shadow");
+        assert_eq!(eval(&mut ctx, "`(map inc xs)"), "(core/map core/inc xs )");
+
+        let code = "(def twice (macro [x] `(list ~x ~x)))
+                    (let [list (fn [a b] :shadowed)] (twice 2))";
+        assert_eq!(eval(&mut ctx, code), "(2 2 )");
+    }
+
+    #[test]
+    fn macro_bodies_run_as_code() {
+        let mut ctx = context("compiled-macros");
+        let code = "(def double-of (fn [x] (list '* x 2)))
+                    (def twice (macro [x] (let [form (double-of x)] form)))
+                    (twice 21)";
+        assert_eq!(eval(&mut ctx, code), "42");
+
+        let code = "(def unless (macro [test & body] `(if ~test nil (do ~@body))))
+                    (unless false 1 2 3)";
+        assert_eq!(eval(&mut ctx, code), "3");
+    }
+
+    #[test]
+    fn macros_see_their_call_form_and_locals() {
+        let mut ctx = context("macro-form-env");
+        let code = "(def whole (macro [x] (list 'quote &form)))
+                    (whole (+ 1 2))";
+        assert_eq!(eval(&mut ctx, code), "(whole (+ 1 2 ) )");
+
+        let code = "(def bound? (macro [name] (contains? &env name)))
+                    (let [a 1] (list (bound? a) (bound? b)))";
+        assert_eq!(eval(&mut ctx, code), "(true false )");
+    }
+
+    #[test]
+    fn prelude_conditionals_and_threading() {
+        let mut ctx = context("prelude-threading");
+        assert_eq!(eval(&mut ctx, "(list (when true 1 2) (when false 1) (unless false 3))"), "(2 nil 3 )");
+        assert_eq!(eval(&mut ctx, "(list (if-let [x 1] (+ x 1) 0) (if-let [x nil] x 0) (when-let [x 5] (* x 2)))"), "(2 0 10 )");
+        assert_eq!(eval(&mut ctx, "(list (-> 1 (+ 2) (* 3) inc) (->> [1 2 3] (map inc) (reduce +)))"), "(10 9 )");
+        assert_eq!(eval(&mut ctx, "(list (as-> 2 x (+ x 1) (* x x)) (cond-> 1 true inc false (* 10) true (* 3)))"), "(9 6 )");
+    }
+
+    #[test]
+    fn case_dispatches_on_constants() {
+        let mut ctx = context("prelude-case");
+        let code = "(def describe (fn [x] (case x 1 \"one\" \"two\" 2 (a b) \"a or b\" \"other\")))
+                    (list (describe 1) (describe \"two\") (describe 'b) (describe 3))";
+        assert_eq!(eval(&mut ctx, code), "(one 2 a or b other )");
+        assert_eq!(eval(&mut ctx, "(case 3 1 :one)"), "nil");
+    }
+
+    #[test]
+    fn iteration_macros() {
+        let mut ctx = context("prelude-iteration");
+        assert_eq!(eval(&mut ctx, "(for [x [1 2 3] y [10 20] :when (> y 10) :let [z (+ x y)]] z)"), "[21 22 23 ]");
+        assert_eq!(eval(&mut ctx, "(for [x (range 4)] (* x x))"), "[0 1 4 9 ]");
+        assert_eq!(eval(&mut ctx, "(list (dotimes [i 3] i) (doseq [x [1 2]] x))"), "(nil nil )");
+    }
+
+    #[test]
+    fn definitions_record_the_macros_they_expanded() {
+        let mut ctx = context("macro-dependents");
+        let vm = ctx.vm.clone();
+        let module = ctx.current_module;
+        let symbol = |name: &str| vm.symbol_table.write().intern(name);
+        let dependents = |name: &str| vm.module_registry.read().macro_dependents((module, symbol(name)));
+
+        eval(&mut ctx, "(def twice (macro [x] `(* 2 ~x))) (def f (fn [] (twice 5))) (def g (fn [] 1))");
+        assert_eq!(dependents("twice"), vec![(module, symbol("f"))]);
+
+        assert!(ctx.take_warnings().is_empty());
+
+        // Redefining the macro keeps the record until f is evaluated again
+        eval(&mut ctx, "(def twice (macro [x] `(* 3 ~x)))");
+        assert_eq!(
+            ctx.take_warnings(),
+            vec!["macro macro-dependents/twice was redefined; macro-dependents/f were compiled with its old expansion. Evaluate them again to use the new one.".to_string()]
+        );
+        assert_eq!(eval(&mut ctx, "(f)"), "10");
+        assert_eq!(dependents("twice"), vec![(module, symbol("f"))]);
+
+        eval(&mut ctx, "(def f (fn [] 5))");
+        assert!(dependents("twice").is_empty());
+        eval(&mut ctx, "(def twice (macro [x] x))");
+        assert!(ctx.take_warnings().is_empty());
+    }
+
+    #[test]
+    fn rmac_removes_only_macros() {
+        let mut ctx = context("macro-removal");
+        eval(&mut ctx, "(def twice (macro [x] `(* 2 ~x))) (def f (fn [] (twice 5))) (rmac twice)");
+        assert!(try_eval(&mut ctx, "(twice 1)").is_err());
+        assert_eq!(eval(&mut ctx, "(f)"), "10");
+        let warnings = ctx.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("macro macro-removal/twice was removed; macro-removal/f"), "{}", warnings[0]);
+
+        let error = try_eval(&mut ctx, "(rmac f)").unwrap_err();
+        assert!(error.contains("rmac: f is not a macro defined in macro-removal"), "{}", error);
+        assert_eq!(eval(&mut ctx, "(f)"), "10");
+    }
+}
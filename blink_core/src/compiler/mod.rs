@@ -1,7 +1,9 @@
 mod arithmetic_optimizer;
 mod bytecode_compiler;
 mod macro_expander;
+mod syntax_quote;
 
 pub use arithmetic_optimizer::*;
 pub use bytecode_compiler::*;
-pub use macro_expander::*;
\ No newline at end of file
+pub use macro_expander::*;
+pub use syntax_quote::*;
\ No newline at end of file
@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use crate::{
+    compiler::{INLINE_OPERATORS, MODULE_FORMS, SPECIAL_FORMS},
+    runtime::BlinkVM,
+    value::ValueRef,
+};
+
+/// Suffix that asks syntax-quote for a fresh name: `tmp#`
+const AUTO_GENSYM_SUFFIX: char = '#';
+
+/// Makes a quasiquoted template hygienic before it is expanded. Within the
+/// quoted parts:
+///
+/// - `name#` becomes a fresh symbol, the same one everywhere in the template,
+///   so bindings the template introduces cannot capture the caller's names
+/// - a symbol naming a global becomes `module/name`, resolved in the module
+///   the template was written in, so the caller's locals cannot shadow it
+///
+/// Unquoted parts are the caller's code and are left alone, as are special
+/// forms, inline operators and names that are not globals.
+pub struct SyntaxQuote<'a> {
+    vm: &'a BlinkVM,
+    module: u32,
+    gensyms: HashMap<u32, u32>,
+}
+
+impl<'a> SyntaxQuote<'a> {
+    pub fn new(vm: &'a BlinkVM, module: u32) -> Self {
+        Self {
+            vm,
+            module,
+            gensyms: HashMap::new(),
+        }
+    }
+
+    /// The template of `(quasiquote template)`, resolved
+    pub fn resolve(&mut self, template: ValueRef) -> ValueRef {
+        self.resolve_at(template, 1)
+    }
+
+    // depth counts the quasiquotes around a form less the unquotes; at 0 it is
+    // code, not template
+    fn resolve_at(&mut self, form: ValueRef, depth: usize) -> ValueRef {
+        if let Some(symbol_id) = form.get_symbol() {
+            return ValueRef::symbol(self.resolve_symbol(symbol_id));
+        }
+        if let Some(items) = form.get_list() {
+            if let [head, inner] = items[..] {
+                let inner_depth = if Self::is_named(self.vm, head, "quasiquote") {
+                    Some(depth + 1)
+                } else if Self::is_named(self.vm, head, "unquote") || Self::is_named(self.vm, head, "unquote-splicing") {
+                    Some(depth - 1)
+                } else {
+                    None
+                };
+                match inner_depth {
+                    Some(0) => return form,
+                    Some(inner_depth) => return self.vm.list_value(vec![head, self.resolve_at(inner, inner_depth)]),
+                    None => {}
+                }
+            }
+            let items = items.into_iter().map(|item| self.resolve_at(item, depth)).collect();
+            return self.vm.list_value(items);
+        }
+        if let Some(items) = form.get_vec() {
+            let items = items.into_iter().map(|item| self.resolve_at(item, depth)).collect();
+            return self.vm.vector_value(items);
+        }
+        form
+    }
+
+    fn resolve_symbol(&mut self, symbol_id: u32) -> u32 {
+        let name = self.vm.get_symbol_name(symbol_id).unwrap_or_default();
+        if let Some(prefix) = name.strip_suffix(AUTO_GENSYM_SUFFIX).filter(|prefix| !prefix.is_empty()) {
+            return *self
+                .gensyms
+                .entry(symbol_id)
+                .or_insert_with(|| self.vm.gensym(&format!("{}__auto", prefix)));
+        }
+
+        let compiled_form = SPECIAL_FORMS.contains(&name.as_str())
+            || INLINE_OPERATORS.contains(&name.as_str())
+            || MODULE_FORMS.contains(&name.as_str());
+        if compiled_form || name == "&" || self.vm.symbol_table.read().is_qualified(symbol_id) {
+            return symbol_id;
+        }
+        match self.vm.locate_global_symbol(self.module, symbol_id) {
+            Some((module_id, name_id)) => self.vm.symbol_table.write().intern_qualified(module_id, name_id),
+            None => symbol_id,
+        }
+    }
+
+    fn is_named(vm: &BlinkVM, value: ValueRef, name: &str) -> bool {
+        value
+            .get_symbol()
+            .is_some_and(|symbol_id| vm.get_symbol_name(symbol_id).as_deref() == Some(name))
+    }
+}
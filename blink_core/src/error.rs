@@ -7,6 +7,30 @@ pub struct BlinkError {
     pub message: String,
     pub pos: Option<SourceRange>,
     pub error_type: BlinkErrorType,
+    pub stack_trace: Vec<StackFrame>, // Innermost frame first; empty unless raised at runtime
+}
+
+// One frame of a runtime stack trace
+#[derive(Debug, Clone)]
+pub struct StackFrame {
+    pub function: Option<String>,
+    pub module: String,
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub col: Option<usize>,
+}
+
+impl Display for StackFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at {} ({}", self.function.as_deref().unwrap_or("<anonymous>"), self.module)?;
+        if let Some(file) = &self.file {
+            write!(f, " {}", file)?;
+        }
+        if let (Some(line), Some(col)) = (self.line, self.col) {
+            write!(f, ":{}:{}", line, col)?;
+        }
+        write!(f, ")")
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -43,6 +67,25 @@ impl Hash for ParseErrorType {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LimitKind {
+    Fuel,
+    Deadline,
+    CallDepth,
+    Allocation,
+}
+
+impl Display for LimitKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitKind::Fuel => write!(f, "instruction fuel"),
+            LimitKind::Deadline => write!(f, "deadline"),
+            LimitKind::CallDepth => write!(f, "call depth"),
+            LimitKind::Allocation => write!(f, "allocation"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum BlinkErrorType {
     Tokenizer,
@@ -62,6 +105,10 @@ pub enum BlinkErrorType {
     UserDefined {
         data: Option<ValueRef>,
     },
+    LimitExceeded {
+        limit: LimitKind,
+    },
+    Interrupted,
 }
 
 impl Display for BlinkErrorType {   
@@ -82,6 +129,10 @@ impl Display for BlinkErrorType {
             BlinkErrorType::UserDefined { data } => {
                 write!(f, "User defined error: {}", data.unwrap_or_else(|| ValueRef::nil()))
             },
+            BlinkErrorType::LimitExceeded { limit } => {
+                write!(f, "Execution limit exceeded: {}", limit)
+            },
+            BlinkErrorType::Interrupted => write!(f, "Interrupted"),
         }
     }
 }
@@ -121,6 +172,11 @@ impl Hash for BlinkErrorType {
                     "nil".hash(state);
                 }
             }
+            BlinkErrorType::LimitExceeded { limit } => {
+                "LimitExceeded".hash(state);
+                limit.hash(state);
+            }
+            BlinkErrorType::Interrupted => "Interrupted".hash(state),
         }
     }
 }
@@ -134,6 +190,7 @@ impl BlinkError {
                 start: pos,
                 end: pos,
             }),
+            stack_trace: Vec::new(),
             error_type: BlinkErrorType::Tokenizer,
         }
     }
@@ -145,6 +202,7 @@ impl BlinkError {
                 start: pos,
                 end: pos,
             }),
+            stack_trace: Vec::new(),
             error_type: BlinkErrorType::UnexpectedToken {
                 token: token.to_string(),
             },
@@ -155,6 +213,7 @@ impl BlinkError {
         Self {
             message: format!("Undefined symbol '{}'", name),
             pos: None,
+            stack_trace: Vec::new(),
             error_type: BlinkErrorType::UndefinedSymbol {
                 name: name.to_string(),
             },
@@ -165,6 +224,7 @@ impl BlinkError {
         Self {
             message: message.into(),
             pos: Some(pos),
+            stack_trace: Vec::new(),
             error_type: BlinkErrorType::Parse(error_type),
         }
     }
@@ -201,6 +261,7 @@ impl BlinkError {
         Self {
             message: message.into(),
             pos: None,
+            stack_trace: Vec::new(),
             error_type: BlinkErrorType::Eval,
         }
     }
@@ -214,6 +275,7 @@ impl BlinkError {
                 got
             ),
             pos: None,
+            stack_trace: Vec::new(),
             error_type: BlinkErrorType::ArityMismatch {
                 expected,
                 got,
@@ -222,6 +284,29 @@ impl BlinkError {
         }
     }
 
+    pub fn limit_exceeded(limit: LimitKind, message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            pos: None,
+            stack_trace: Vec::new(),
+            error_type: BlinkErrorType::LimitExceeded { limit },
+        }
+    }
+
+    pub fn interrupted(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            pos: None,
+            stack_trace: Vec::new(),
+            error_type: BlinkErrorType::Interrupted,
+        }
+    }
+
+    pub fn with_stack_trace(mut self, stack_trace: Vec<StackFrame>) -> Self {
+        self.stack_trace = stack_trace;
+        self
+    }
+
     pub fn with_pos(mut self, pos: Option<SourceRange>) -> Self {
         self.pos = pos;
         self
@@ -295,10 +380,17 @@ impl fmt::Display for BlinkError {
             BlinkErrorType::UserDefined { data: _ } => {
                 write!(f, "User defined error: {}", self.message)
             }
+            BlinkErrorType::LimitExceeded { limit } => {
+                write!(f, "Execution limit exceeded ({}): {}", limit, self.message)
+            }
+            BlinkErrorType::Interrupted => write!(f, "Interrupted: {}", self.message),
         };
         if let Some(pos) = self.pos {
             write!(f, " at {}", pos);
         }
+        for frame in &self.stack_trace {
+            write!(f, "\n    {}", frame)?;
+        }
 
         Ok(())
     }
@@ -2,15 +2,25 @@ pub mod env;
 pub mod error;
 pub mod eval;
 pub mod native_functions;
+pub mod math_functions;
+pub mod sequence_functions;
+pub mod collection_functions;
+pub mod transducer_functions;
+pub mod sorted_functions;
+pub mod string_functions;
+pub mod type_functions;
 pub mod parser;
 pub mod repl;
 pub mod telemetry;
 pub mod value;
 pub mod module;
+pub mod project;
 pub mod collections;
 pub mod compiler;
 pub mod runtime;
 mod output_manager;
+#[cfg(test)]
+mod test_support;
 
 pub use env::Env;
 // TODO expose value creation
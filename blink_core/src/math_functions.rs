@@ -0,0 +1,327 @@
+use crate::runtime::EvalResult;
+use crate::value::{NativeContext, ValueRef};
+
+// Numeric functions and random numbers. inc, dec, mod, rem, quot, abs, min,
+// max, floor, ceil and round are in core as well as the `math` module; the
+// rest are called qualified, e.g. (math/sqrt 2). Calls to inc, dec and mod with
+// the usual arity are compiled to opcodes unless the name has been redefined.
+//
+// Random numbers come from the VM's generator. (math/rand-seed! n) fixes the
+// seed, after which rand, rand-int, rand-nth and shuffle repeat the same results.
+
+macro_rules! try_value {
+    ($expr:expr) => {
+        match $expr {
+            Ok(value) => value,
+            Err(error) => return EvalResult::Value(error),
+        }
+    };
+}
+
+/// Modulo with the sign of the divisor, so (mod -1 3) is 2
+pub fn floored_mod(dividend: f64, divisor: f64) -> f64 {
+    let remainder = dividend % divisor;
+    if remainder != 0.0 && (remainder < 0.0) != (divisor < 0.0) {
+        remainder + divisor
+    } else {
+        remainder
+    }
+}
+
+fn number_args(ctx: &NativeContext, args: &[ValueRef], count: usize, function_name: &str) -> Result<Vec<f64>, ValueRef> {
+    ctx.require_arity(args, count, function_name)?;
+    ctx.extract_numbers(args, function_name)
+}
+
+fn unary(args: Vec<ValueRef>, ctx: &mut NativeContext, function_name: &str, f: fn(f64) -> f64) -> EvalResult {
+    let n = try_value!(number_args(ctx, &args, 1, function_name));
+    EvalResult::Value(ctx.number(f(n[0])))
+}
+
+// A binary operation that fails on a zero divisor
+fn division(args: Vec<ValueRef>, ctx: &mut NativeContext, function_name: &str, f: fn(f64, f64) -> f64) -> EvalResult {
+    let n = try_value!(number_args(ctx, &args, 2, function_name));
+    if n[1] == 0.0 {
+        return EvalResult::Value(ctx.eval_error(&format!("{}: division by zero", function_name)));
+    }
+    EvalResult::Value(ctx.number(f(n[0], n[1])))
+}
+
+pub fn native_inc(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    unary(args, ctx, "inc", |n| n + 1.0)
+}
+
+pub fn native_dec(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    unary(args, ctx, "dec", |n| n - 1.0)
+}
+
+pub fn native_mod(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    division(args, ctx, "mod", floored_mod)
+}
+
+pub fn native_rem(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // Remainder with the sign of the dividend, so (rem -1 3) is -1
+    division(args, ctx, "rem", |a, b| a % b)
+}
+
+pub fn native_quot(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // Quotient rounded toward zero
+    division(args, ctx, "quot", |a, b| (a / b).trunc())
+}
+
+pub fn native_abs(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    unary(args, ctx, "abs", f64::abs)
+}
+
+fn extreme(args: Vec<ValueRef>, ctx: &mut NativeContext, function_name: &str, pick: fn(f64, f64) -> f64) -> EvalResult {
+    try_value!(ctx.require_min_arity(&args, 1, function_name));
+    let numbers = try_value!(ctx.extract_numbers(&args, function_name));
+    // NaN is contagious rather than skipped
+    if numbers.iter().any(|n| n.is_nan()) {
+        return EvalResult::Value(ctx.number(f64::NAN));
+    }
+    let result = numbers.into_iter().reduce(pick).unwrap_or_default();
+    EvalResult::Value(ctx.number(result))
+}
+
+pub fn native_min(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    extreme(args, ctx, "min", f64::min)
+}
+
+pub fn native_max(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    extreme(args, ctx, "max", f64::max)
+}
+
+pub fn native_floor(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    unary(args, ctx, "floor", f64::floor)
+}
+
+pub fn native_ceil(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    unary(args, ctx, "ceil", f64::ceil)
+}
+
+pub fn native_round(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // Halves round away from zero
+    unary(args, ctx, "round", f64::round)
+}
+
+pub fn native_sqrt(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    unary(args, ctx, "sqrt", f64::sqrt)
+}
+
+pub fn native_exp(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    unary(args, ctx, "exp", f64::exp)
+}
+
+pub fn native_log(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // Natural logarithm
+    unary(args, ctx, "log", f64::ln)
+}
+
+pub fn native_log10(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    unary(args, ctx, "log10", f64::log10)
+}
+
+pub fn native_sin(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    unary(args, ctx, "sin", f64::sin)
+}
+
+pub fn native_cos(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    unary(args, ctx, "cos", f64::cos)
+}
+
+pub fn native_tan(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    unary(args, ctx, "tan", f64::tan)
+}
+
+pub fn native_asin(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    unary(args, ctx, "asin", f64::asin)
+}
+
+pub fn native_acos(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    unary(args, ctx, "acos", f64::acos)
+}
+
+pub fn native_atan(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    unary(args, ctx, "atan", f64::atan)
+}
+
+pub fn native_atan2(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (math/atan2 y x)
+    let n = try_value!(number_args(ctx, &args, 2, "atan2"));
+    EvalResult::Value(ctx.number(n[0].atan2(n[1])))
+}
+
+pub fn native_pow(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (math/pow base exponent)
+    let n = try_value!(number_args(ctx, &args, 2, "pow"));
+    EvalResult::Value(ctx.number(n[0].powf(n[1])))
+}
+
+// Random numbers
+
+fn seed_arg(ctx: &NativeContext, value: ValueRef) -> Result<u64, ValueRef> {
+    match ctx.get_number(value) {
+        Some(n) if n.is_finite() && n.fract() == 0.0 => Ok(n as i64 as u64),
+        _ => Err(ctx.type_error("an integer seed", ctx.type_name(value), "rand-seed!")),
+    }
+}
+
+pub fn native_rand_seed(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (math/rand-seed! n) restarts the generator from seed n
+    try_value!(ctx.require_arity(&args, 1, "rand-seed!"));
+    let seed = try_value!(seed_arg(ctx, args[0]));
+    ctx.vm().rng.lock().reseed(seed);
+    EvalResult::Value(ctx.nil())
+}
+
+pub fn native_rand(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (rand) is in [0, 1) and (rand n) in [0, n)
+    let scale = match args.len() {
+        0 => 1.0,
+        1 => try_value!(ctx.extract_numbers(&args, "rand"))[0],
+        n => return EvalResult::Value(ctx.arity_error(0, n, "rand (or 1)")),
+    };
+    let draw = ctx.vm().rng.lock().next_f64();
+    EvalResult::Value(ctx.number(draw * scale))
+}
+
+// A random index below a count
+fn random_index(ctx: &NativeContext, count: usize) -> usize {
+    ctx.vm().rng.lock().below(count as u64) as usize
+}
+
+pub fn native_rand_int(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (rand-int n) is a whole number in [0, n)
+    let n = try_value!(number_args(ctx, &args, 1, "rand-int"))[0];
+    if !(n >= 1.0 && n.fract() == 0.0 && n <= (1u64 << 53) as f64) {
+        return EvalResult::Value(ctx.eval_error(&format!("rand-int: bound must be a positive integer, got {}", n)));
+    }
+    EvalResult::Value(ctx.number(random_index(ctx, n as usize) as f64))
+}
+
+pub fn native_rand_nth(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (rand-nth coll) is a random item of a non-empty collection
+    try_value!(ctx.require_arity(&args, 1, "rand-nth"));
+    let items = match ctx.seq_to_vec(args[0]) {
+        Ok(items) => items,
+        Err(_) if !ctx.is_lazy_seq(args[0]) => {
+            return EvalResult::Value(ctx.type_error("a collection", ctx.type_name(args[0]), "rand-nth"))
+        }
+        Err(error) => return EvalResult::Value(error),
+    };
+    if items.is_empty() {
+        return EvalResult::Value(ctx.eval_error("rand-nth: the collection is empty"));
+    }
+    EvalResult::Value(items[random_index(ctx, items.len())])
+}
+
+pub fn native_shuffle(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (shuffle coll) is a vector of the items in random order
+    try_value!(ctx.require_arity(&args, 1, "shuffle"));
+    let mut items = match ctx.seq_to_vec(args[0]) {
+        Ok(items) => items,
+        Err(_) if !ctx.is_lazy_seq(args[0]) => {
+            return EvalResult::Value(ctx.type_error("a collection", ctx.type_name(args[0]), "shuffle"))
+        }
+        Err(error) => return EvalResult::Value(error),
+    };
+    // Fisher-Yates
+    for i in (1..items.len()).rev() {
+        let j = random_index(ctx, i + 1);
+        items.swap(i, j);
+    }
+    EvalResult::Value(ctx.vector(items))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::BytecodeCompiler;
+    use crate::parser::parse_all;
+    use crate::runtime::{disassemble_bytecode, ExecutionContext};
+    use crate::test_support::{context, eval, try_eval};
+
+    // Whether the code evaluates to a value equal to `expected`
+    fn returns(ctx: &mut ExecutionContext, code: &str, expected: &str) -> bool {
+        eval(ctx, &format!("(= {} {})", code, expected)) == "true"
+    }
+
+    // The disassembled bytecode of a single top-level form, compiled in ctx's module
+    fn disassemble(ctx: &mut ExecutionContext, code: &str) -> String {
+        let vm = ctx.vm.clone();
+        let forms = {
+            let mut symbol_table = vm.symbol_table.write();
+            let mut reader_macros = vm.reader_macros.write();
+            parse_all(code, &mut reader_macros, &mut symbol_table).unwrap()
+        };
+        let form = vm.alloc_parsed_value(forms.into_iter().next().unwrap());
+        let compiled = BytecodeCompiler::new(vm, ctx.current_module).compile_for_storage(form).unwrap();
+        disassemble_bytecode(&compiled.bytecode, &compiled.constants)
+    }
+
+    #[test]
+    fn inc_dec_and_mod_compile_to_opcodes() {
+        let mut ctx = context("math-opcodes");
+        assert!(disassemble(&mut ctx, "(inc 5)").contains(": AddImm8 "));
+        assert!(disassemble(&mut ctx, "(dec 5)").contains(": SubImm8 "));
+        assert!(disassemble(&mut ctx, "(mod 5 3)").contains(": Mod "));
+        // Other arities are calls to the natives
+        assert!(!disassemble(&mut ctx, "(mod 5)").contains(": Mod "));
+
+        assert_eq!(eval(&mut ctx, "(inc 1.5)"), "2.5");
+        assert_eq!(eval(&mut ctx, "(dec 0)"), "-1");
+        // The sign follows the divisor, as in the native
+        assert_eq!(eval(&mut ctx, "(mod -1 3)"), "2");
+        assert_eq!(eval(&mut ctx, "(mod 7 -3)"), "-2");
+        assert_eq!(eval(&mut ctx, "(mod 5.5 2)"), "1.5");
+        assert_eq!(eval(&mut ctx, "(let [f mod] (f -1 3))"), "2");
+        assert!(try_eval(&mut ctx, "(mod 1 0)").unwrap_or_else(|error| error).contains("zero"));
+        assert!(try_eval(&mut ctx, "(inc :k)").is_err());
+    }
+
+    #[test]
+    fn opcode_operands_may_both_be_calls() {
+        let mut ctx = context("math-operands");
+        eval(&mut ctx, "(defn f [x] (* x 10))");
+        eval(&mut ctx, "(defn g [x] (+ x 3))");
+        // The right operand's call must not overwrite the left one's result
+        assert_eq!(eval(&mut ctx, "(mod (f 5) (g 4))"), "1");
+        assert_eq!(eval(&mut ctx, "(inc (f 2))"), "21");
+        assert_eq!(eval(&mut ctx, "(dec (g (inc 1)))"), "4");
+        assert_eq!(eval(&mut ctx, "(mod (f (g 1)) (mod (g 5) (f 1)))"), "0");
+        assert_eq!(eval(&mut ctx, "(+ (mod (f 5) (g 4)) (inc (g 0)))"), "5");
+    }
+
+    #[test]
+    fn redefined_names_are_called_instead() {
+        let mut ctx = context("math-redefined");
+        assert!(!disassemble(&mut ctx, "(let [inc (fn [x] 0)] (inc 5))").contains(": AddImm8 "));
+        assert_eq!(eval(&mut ctx, "(let [inc (fn [x] 0)] (inc 5))"), "0");
+        eval(&mut ctx, "(defn mod [a b] (+ a b))");
+        assert!(!disassemble(&mut ctx, "(mod 5 3)").contains(": Mod "));
+        assert_eq!(eval(&mut ctx, "(mod 5 3)"), "8");
+        // Other modules still get the core one
+        let mut other = context("math-redefined-other");
+        assert_eq!(eval(&mut other, "(mod 5 3)"), "2");
+    }
+
+    #[test]
+    fn a_seed_fixes_the_random_sequence() {
+        let mut ctx = context("math-random");
+        let draws = "(vector (rand) (rand 10) (vec (map (fn [_] (rand-int 1000)) (range 5))) (rand-nth [:a :b :c :d]) (shuffle (range 10)))";
+        eval(&mut ctx, "(math/rand-seed! 42)");
+        eval(&mut ctx, &format!("(def first-run {})", draws));
+        eval(&mut ctx, "(math/rand-seed! 42)");
+        assert!(returns(&mut ctx, draws, "first-run"));
+        eval(&mut ctx, "(math/rand-seed! 43)");
+        assert!(!returns(&mut ctx, draws, "first-run"));
+
+        // Draws stay in range and shuffles keep every item
+        eval(&mut ctx, "(math/rand-seed! 7)");
+        assert_eq!(eval(&mut ctx, "(reduce (fn [ok _] (and ok (let [n (rand-int 3)] (and (>= n 0) (< n 3) (int? n))))) true (range 200))"), "true");
+        assert_eq!(eval(&mut ctx, "(reduce (fn [ok _] (and ok (< (rand) 1))) true (range 200))"), "true");
+        assert!(returns(&mut ctx, "(sort (shuffle (range 20)))", "(sort (range 20))"));
+        assert!(try_eval(&mut ctx, "(rand-int 0)").unwrap_or_else(|error| error).contains("positive integer"));
+        assert!(try_eval(&mut ctx, "(math/rand-seed! 1.5)").unwrap_or_else(|error| error).contains("integer seed"));
+    }
+}
@@ -1,4 +1,6 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::time::SystemTime;
 use libloading::Library;
 use crate::value::ValueRef;
 
@@ -22,11 +24,53 @@ pub enum SerializedModuleSource {
 pub struct Module {
     pub name: u32,
     pub imports: HashMap<u32, (u32, u32)>, // alias -> (module_id, symbol_id)
+    pub aliases: HashMap<u32, u32>, // alias -> module_id, from (imp m :as alias)
     pub exports: HashMap<u32, ValueRef>,
+    pub public: Option<HashSet<u32>>, // Names other modules may use; None makes every definition public
+    pub private: HashSet<u32>, // Definitions marked ^:private, hidden even without an export list
+    pub metadata: HashMap<u32, ValueRef>, // Metadata map of each definition that has one
+    pub dependencies: HashSet<u32>, // Modules this one imported, for reloading its dependents
     pub source: SerializedModuleSource, // Simplified for heap storage
     pub ready: bool,
 }
 
+impl Module {
+    pub fn new(name: u32, source: SerializedModuleSource) -> Self {
+        Module {
+            name,
+            imports: HashMap::new(),
+            aliases: HashMap::new(),
+            exports: HashMap::new(),
+            public: None,
+            private: HashSet::new(),
+            metadata: HashMap::new(),
+            dependencies: HashSet::new(),
+            source,
+            ready: true,
+        }
+    }
+
+    pub fn is_public(&self, symbol_id: u32) -> bool {
+        !self.private.contains(&symbol_id) && self.public.as_ref().map_or(true, |public| public.contains(&symbol_id))
+    }
+}
+
+/// What (imp ...) brings into the importing module besides qualified access
+#[derive(Clone, Debug)]
+pub enum ImportNames {
+    None,
+    Only(Vec<u32>),
+    All,
+}
+
+/// How a file was last evaluated, so it can be evaluated again when it changes
+#[derive(Clone, Debug)]
+pub struct FileLoad {
+    pub path: PathBuf,
+    pub module: u32, // The module its first form ran in
+    pub modified: Option<SystemTime>,
+}
+
 /// Registry supporting all module types
 #[derive(Debug)]
 pub struct ModuleRegistry {
@@ -39,9 +83,13 @@ pub struct ModuleRegistry {
     /// File -> module IDs mapping (for multi-module .blink files)
     file_modules: HashMap<u32, Vec<u32>>,
     module_files: HashMap<u32, u32>,
+    file_loads: HashMap<u32, FileLoad>,
     
     /// Native libraries that have been loaded
     loaded_libraries: HashMap<u32, libloading::Library>,
+
+    /// Macro -> the definitions compiled with its expansion, both as (module, name)
+    macro_dependents: HashMap<(u32, u32), BTreeSet<(u32, u32)>>,
 }
 
 impl ModuleRegistry {
@@ -51,7 +99,9 @@ impl ModuleRegistry {
             evaluated_files: HashSet::new(),
             file_modules: HashMap::new(),
             module_files: HashMap::new(),
+            file_loads: HashMap::new(),
             loaded_libraries: HashMap::new(),
+            macro_dependents: HashMap::new(),
         }
     }
 
@@ -93,7 +143,34 @@ impl ModuleRegistry {
     pub fn is_file_evaluated(&self, path: u32) -> bool {
         self.evaluated_files.contains(&path)
     }
+
+    /// Let a file be evaluated again
+    pub fn unmark_file_evaluated(&mut self, path: u32) {
+        self.evaluated_files.remove(&path);
+    }
+
+    pub fn record_file_load(&mut self, path: u32, load: FileLoad) {
+        self.file_loads.insert(path, load);
+    }
+
+    pub fn file_load(&self, path: u32) -> Option<&FileLoad> {
+        self.file_loads.get(&path)
+    }
+
+    /// Every evaluated file, with how it was loaded
+    pub fn file_loads(&self) -> impl Iterator<Item = (u32, &FileLoad)> {
+        self.file_loads.iter().map(|(path, load)| (*path, load))
+    }
     
+    /// Record that `file` defines `module_id`
+    pub fn record_module_file(&mut self, module_id: u32, file: u32) {
+        self.module_files.insert(module_id, file);
+        let modules = self.file_modules.entry(file).or_default();
+        if !modules.contains(&module_id) {
+            modules.push(module_id);
+        }
+    }
+
     /// Get all modules defined in a file
     pub fn modules_in_file(&self, file: u32) -> Vec<u32> {
         self.file_modules.get(&file).cloned().unwrap_or_default()
@@ -105,17 +182,331 @@ impl ModuleRegistry {
     }
     
 
+    /// A name as seen from inside a module: its own definitions, then the names it imported
     pub fn resolve_symbol(&self, module_id: u32, symbol_id: u32) -> Option<ValueRef> {
-        //TODO overhaul qualified symbols
         let module = self.modules.get(&module_id)?;
         match module.exports.get(&symbol_id) {
             Some(val) => Some(*val),
-            None => {
-                let module = self.modules.get(&module_id)?;
-                module.imports.get(&symbol_id).and_then(|(module_id, symbol_id)| self.resolve_symbol(*module_id, *symbol_id))
+            None => module
+                .imports
+                .get(&symbol_id)
+                .and_then(|(module_id, symbol_id)| self.resolve_public(*module_id, *symbol_id)),
+        }
+    }
+
+    /// Replace the metadata of a definition, from its docstring and ^{...}.
+    /// `private` hides it from other modules.
+    pub fn set_metadata(&mut self, module_id: u32, symbol_id: u32, meta: Option<ValueRef>, private: bool) {
+        let Some(module) = self.modules.get_mut(&module_id) else {
+            return;
+        };
+        match meta {
+            Some(meta) => module.metadata.insert(symbol_id, meta),
+            None => module.metadata.remove(&symbol_id),
+        };
+        if private {
+            module.private.insert(symbol_id);
+        } else {
+            module.private.remove(&symbol_id);
+        }
+    }
+
+    /// The metadata map of a definition, if it has one
+    pub fn metadata(&self, module_id: u32, symbol_id: u32) -> Option<ValueRef> {
+        self.modules.get(&module_id)?.metadata.get(&symbol_id).copied()
+    }
+
+    /// The module and name a symbol refers to from inside `module_id`: one of
+    /// its own definitions, or the public definition it imported
+    pub fn locate_symbol(&self, module_id: u32, symbol_id: u32) -> Option<(u32, u32)> {
+        let module = self.modules.get(&module_id)?;
+        if module.exports.contains_key(&symbol_id) {
+            return Some((module_id, symbol_id));
+        }
+        let &(from, name) = module.imports.get(&symbol_id)?;
+        self.resolve_public(from, name).map(|_| (from, name))
+    }
+
+    /// Definitions other modules may use, by symbol id
+    pub fn public_exports(&self, module_id: u32) -> Vec<(u32, ValueRef)> {
+        self.modules.get(&module_id).map_or_else(Vec::new, |module| {
+            module
+                .exports
+                .iter()
+                .filter(|(symbol_id, _)| module.is_public(**symbol_id))
+                .map(|(symbol_id, value)| (*symbol_id, *value))
+                .collect()
+        })
+    }
+
+    /// Modules a module imported, each with the names it referred from it
+    pub fn imports_of(&self, module_id: u32) -> Vec<(u32, Vec<u32>)> {
+        let Some(module) = self.modules.get(&module_id) else {
+            return Vec::new();
+        };
+        module
+            .dependencies
+            .iter()
+            .map(|from| {
+                let referred = module
+                    .imports
+                    .iter()
+                    .filter(|(_, (module, _))| module == from)
+                    .map(|(_, (_, name))| *name)
+                    .collect();
+                (*from, referred)
+            })
+            .collect()
+    }
+
+    /// A name as seen from another module: only public definitions, and not
+    /// the module's own imports
+    pub fn resolve_public(&self, module_id: u32, symbol_id: u32) -> Option<ValueRef> {
+        let module = self.modules.get(&module_id)?;
+        if !module.is_public(symbol_id) {
+            return None;
+        }
+        module.exports.get(&symbol_id).copied()
+    }
+
+    /// The module a qualifier names from inside `module_id`: an alias, or a module's own name
+    pub fn resolve_alias(&self, module_id: u32, qualifier: u32) -> u32 {
+        self.modules
+            .get(&module_id)
+            .and_then(|module| module.aliases.get(&qualifier).copied())
+            .unwrap_or(qualifier)
+    }
+
+    /// Make `module_id` current, creating it from `source` if needed. An export
+    /// list replaces the module's public names.
+    pub fn enter_module(&mut self, module_id: u32, public: Option<Vec<u32>>, source: SerializedModuleSource) {
+        let module = self
+            .modules
+            .entry(module_id)
+            .or_insert_with(|| Module::new(module_id, source));
+        if let Some(public) = public {
+            module.public = Some(public.into_iter().collect());
+        }
+    }
+
+    /// Import `from` into `into`, under an optional alias, referring the given names.
+    /// Errors name the symbol id that failed, for the caller to describe.
+    pub fn import(
+        &mut self,
+        into: u32,
+        from: u32,
+        alias: Option<u32>,
+        names: ImportNames,
+    ) -> Result<(), ImportError> {
+        let source = self.modules.get(&from).ok_or(ImportError::NoModule(from))?;
+        let referred = match names {
+            ImportNames::None => Vec::new(),
+            ImportNames::All => source
+                .exports
+                .keys()
+                .copied()
+                .filter(|symbol_id| source.is_public(*symbol_id))
+                .collect(),
+            ImportNames::Only(names) => {
+                for &name in &names {
+                    if !source.exports.contains_key(&name) {
+                        return Err(ImportError::Undefined(from, name));
+                    }
+                    if !source.is_public(name) {
+                        return Err(ImportError::Private(from, name));
+                    }
+                }
+                names
             }
+        };
+        let module = self.modules.get_mut(&into).ok_or(ImportError::NoModule(into))?;
+        if into != from {
+            module.dependencies.insert(from);
+        }
+        if let Some(alias) = alias {
+            module.aliases.insert(alias, from);
+        }
+        for name in referred {
+            module.imports.insert(name, (from, name));
         }
+        Ok(())
+    }
+
+    /// `roots` and every module that depends on them, directly or not, with
+    /// each module after the ones it imports. Modules in an import cycle come
+    /// last, in name order.
+    pub fn dependents_in_order(&self, roots: &[u32], name_of: impl Fn(u32) -> String) -> Vec<u32> {
+        let mut affected: HashSet<u32> = roots.iter().copied().collect();
+        loop {
+            let before = affected.len();
+            for module in self.modules.values() {
+                if module.dependencies.iter().any(|dependency| affected.contains(dependency)) {
+                    affected.insert(module.name);
+                }
+            }
+            if affected.len() == before {
+                break;
+            }
+        }
+
+        let mut pending: Vec<u32> = affected.iter().copied().collect();
+        pending.sort_by_key(|module| name_of(*module));
+        let imports_within = |module: u32| -> usize {
+            self.modules.get(&module).map_or(0, |module| {
+                module.dependencies.iter().filter(|dependency| affected.contains(dependency)).count()
+            })
+        };
+
+        // Kahn's algorithm, taking ready modules in name order
+        let mut waiting: HashMap<u32, usize> = pending.iter().map(|module| (*module, imports_within(*module))).collect();
+        let mut ready: VecDeque<u32> = pending.iter().copied().filter(|module| waiting[module] == 0).collect();
+        let mut order = Vec::with_capacity(pending.len());
+        while let Some(module) = ready.pop_front() {
+            order.push(module);
+            for &dependent in &pending {
+                let depends = self.modules.get(&dependent).is_some_and(|m| m.dependencies.contains(&module));
+                if depends && dependent != module {
+                    let count = waiting.get_mut(&dependent).unwrap();
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.push_back(dependent);
+                    }
+                }
+            }
+        }
+        pending.retain(|module| !order.contains(module));
+        order.extend(pending);
+        order
+    }
+
+    /// Remove a definition with its metadata, returning its value
+    pub fn remove_definition(&mut self, module_id: u32, symbol_id: u32) -> Option<ValueRef> {
+        let module = self.modules.get_mut(&module_id)?;
+        module.metadata.remove(&symbol_id);
+        module.private.remove(&symbol_id);
+        module.exports.remove(&symbol_id)
+    }
+
+    /// Record the macros a definition expanded when it was compiled, replacing
+    /// what its previous definition expanded
+    pub fn record_macro_uses(&mut self, definition: (u32, u32), macros: &[(u32, u32)]) {
+        self.macro_dependents.retain(|_, dependents| {
+            dependents.remove(&definition);
+            !dependents.is_empty()
+        });
+        for &macro_name in macros {
+            self.macro_dependents.entry(macro_name).or_default().insert(definition);
+        }
+    }
+
+    /// The definitions compiled with a macro's expansion
+    pub fn macro_dependents(&self, macro_name: (u32, u32)) -> Vec<(u32, u32)> {
+        self.macro_dependents
+            .get(&macro_name)
+            .map_or_else(Vec::new, |dependents| dependents.iter().copied().collect())
+    }
+
+    /// Forget the dependents of a macro that was removed
+    pub fn forget_macro(&mut self, macro_name: (u32, u32)) {
+        self.macro_dependents.remove(&macro_name);
+    }
+
+    /// Whether a name is defined in a module but hidden from other modules
+    pub fn is_private(&self, module_id: u32, symbol_id: u32) -> bool {
+        self.modules
+            .get(&module_id)
+            .is_some_and(|module| module.exports.contains_key(&symbol_id) && !module.is_public(symbol_id))
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+pub enum ImportError {
+    NoModule(u32),
+    Undefined(u32, u32),
+    Private(u32, u32),
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::ExecutionContext;
+    use crate::test_support::{context, eval, try_eval};
+
+    fn error_message(ctx: &mut ExecutionContext, code: &str) -> String {
+        try_eval(ctx, code).unwrap_or_else(|error| error)
+    }
+
+    // Define `library` with two public functions and a private one, then go
+    // back to `user`
+    fn define_library(ctx: &mut ExecutionContext, library: &str, user: &str) {
+        eval(
+            ctx,
+            &format!(
+                "(mod {library} :export [greet wave])
+                 (def secret 42)
+                 (def ^:private hidden 7)
+                 (def greet (fn [] (+ secret hidden)))
+                 (def wave (fn [] 2))
+                 (mod {user})"
+            ),
+        );
+    }
+
+    #[test]
+    fn imports_reach_public_names_through_the_module_or_an_alias() {
+        let mut ctx = context("imp-as-user");
+        define_library(&mut ctx, "imp-as-lib", "imp-as-user");
+        eval(&mut ctx, "(imp imp-as-lib :as lib)");
+        assert_eq!(eval(&mut ctx, "(lib/greet)"), "49");
+        assert_eq!(eval(&mut ctx, "(imp-as-lib/wave)"), "2");
+        assert!(error_message(&mut ctx, "(greet)").contains("not found"));
+    }
+
+    #[test]
+    fn only_and_refer_all_make_names_usable_unqualified() {
+        let mut ctx = context("imp-only-user");
+        define_library(&mut ctx, "imp-only-lib", "imp-only-user");
+        eval(&mut ctx, "(imp imp-only-lib :only [greet])");
+        assert_eq!(eval(&mut ctx, "(greet)"), "49");
+        assert!(error_message(&mut ctx, "(wave)").contains("not found"));
+        assert_eq!(eval(&mut ctx, "(imp-only-lib/wave)"), "2");
+
+        let mut ctx = context("imp-refer-all-user");
+        define_library(&mut ctx, "imp-refer-all-lib", "imp-refer-all-user");
+        eval(&mut ctx, "(imp imp-refer-all-lib :refer-all)");
+        assert_eq!(eval(&mut ctx, "(+ (greet) (wave))"), "51");
+
+        let message = error_message(&mut ctx, "(imp imp-refer-all-lib :only [greet] :refer-all)");
+        assert!(message.contains("cannot be combined"), "{}", message);
+        let message = error_message(&mut ctx, "(imp imp-refer-all-lib :only [missing])");
+        assert!(message.contains("does not define missing"), "{}", message);
+    }
 
+    #[test]
+    fn private_definitions_are_hidden_from_other_modules() {
+        let mut ctx = context("imp-private-user");
+        define_library(&mut ctx, "imp-private-lib", "imp-private-user");
+        eval(&mut ctx, "(imp imp-private-lib :refer-all)");
+        // Left out of :export, or marked ^:private
+        for name in ["secret", "hidden"] {
+            let message = error_message(&mut ctx, &format!("imp-private-lib/{}", name));
+            assert!(message.contains("private to module imp-private-lib"), "{}", message);
+            assert!(error_message(&mut ctx, name).contains("not found"));
+            let message = error_message(&mut ctx, &format!("(imp imp-private-lib :only [{}])", name));
+            assert!(message.contains("private to module"), "{}", message);
+        }
+        // The module itself still uses them
+        assert_eq!(eval(&mut ctx, "(greet)"), "49");
+    }
+
+    #[test]
+    fn mod_with_operands_is_still_modulo() {
+        let mut ctx = context("mod-modulo");
+        assert_eq!(eval(&mut ctx, "(mod 7 3)"), "1");
+        assert_eq!(eval(&mut ctx, "(let [x 7 y 3] (mod x y))"), "1");
+        assert_eq!(eval(&mut ctx, "(let [x 8] (mod x 3))"), "2");
+        eval(&mut ctx, "(def x 9) (def y 4)");
+        assert_eq!(eval(&mut ctx, "(mod x y)"), "1");
+    }
+}
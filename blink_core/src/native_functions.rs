@@ -4,8 +4,9 @@ use std::sync::Arc;
 use parking_lot::RwLock;
 
 use crate::error::{BlinkError, BlinkErrorType};
-use crate::runtime::{EvalResult, GLOBAL_VM};
-use crate::value::{unpack_immediate, ImmediateValue, NativeContext, ValueRef};
+use crate::runtime::{format_profile_report, EvalResult, SeqGenerator, TypeTag, GLOBAL_VM};
+use crate::sorted_functions::{sorted_get, sorted_object};
+use crate::value::{unpack_immediate, value_hash, ImmediateValue, NativeContext, ValueRef};
 
 
 pub fn native_add(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
@@ -92,15 +93,77 @@ pub fn native_div(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
 }
 
 pub fn native_eq(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
-    let result = if let Some((first, rest)) = args.split_first() {
-        rest.iter().all(|arg| arg == first)
+    if let Some((first, rest)) = args.split_first() {
+        for arg in rest {
+            match ctx.values_equal(*first, *arg) {
+                Ok(true) => {}
+                Ok(false) => return EvalResult::Value(ctx.bool(false)),
+                Err(error) => return EvalResult::Value(error),
+            }
+        }
+    }
+
+    EvalResult::Value(ctx.bool(true))
+}
+
+pub fn native_identical(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (identical? a b) compares representations, unlike =: the same heap object,
+    // or bit-identical immediates, so (identical? 0 -0) is false
+    if args.len() != 2 {
+        return EvalResult::Value(ctx.arity_error(2, args.len(), "identical?"));
+    }
+    EvalResult::Value(ctx.bool(args[0].identical(&args[1])))
+}
+
+pub fn native_hash(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (hash x) agrees with =: equal values have equal hashes
+    if args.len() != 1 {
+        return EvalResult::Value(ctx.arity_error(1, args.len(), "hash"));
+    }
+    // = compares a lazy seq item by item, so it hashes like a list of its items
+    let value = if ctx.is_lazy_seq(args[0]) {
+        match ctx.seq_to_vec(args[0]) {
+            Ok(items) => ctx.list(items),
+            Err(error) => return EvalResult::Value(error),
+        }
     } else {
-        true
+        args[0]
     };
+    // Keep 53 bits so the hash is an exact whole number
+    let hash = value_hash(&value) >> 11;
+    EvalResult::Value(ctx.number(hash as f64))
+}
 
+// <, >, <= and >= are compiled inline when called by name; these natives let
+// them be passed as values, e.g. to sort or subseq
+fn ordered_chain(args: &[ValueRef], ctx: &NativeContext, name: &str, holds: fn(f64, f64) -> bool) -> EvalResult {
+    let mut numbers = Vec::with_capacity(args.len());
+    for arg in args {
+        match ctx.get_number(*arg) {
+            Some(n) => numbers.push(n),
+            None => return EvalResult::Value(ctx.eval_error(&format!("{} expects numbers, got {}", name, arg.type_tag()))),
+        }
+    }
+    let result = numbers.windows(2).all(|pair| holds(pair[0], pair[1]));
     EvalResult::Value(ctx.bool(result))
 }
 
+pub fn native_lt(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    ordered_chain(&args, ctx, "<", |a, b| a < b)
+}
+
+pub fn native_gt(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    ordered_chain(&args, ctx, ">", |a, b| a > b)
+}
+
+pub fn native_lte(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    ordered_chain(&args, ctx, "<=", |a, b| a <= b)
+}
+
+pub fn native_gte(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    ordered_chain(&args, ctx, ">=", |a, b| a >= b)
+}
+
 pub fn native_not(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
     
     if args.len() != 1 {
@@ -199,12 +262,20 @@ pub fn native_cons(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
         } else {
             EvalResult::Value(ctx.eval_error("Invalid vector reference"))
         }
+    } else if ctx.is_lazy_seq(collection) {
+        // Consing onto a lazy seq must not realise it
+        EvalResult::Value(ctx.seq_cell(element, collection))
     } else {
-        EvalResult::Value(ctx.eval_error("second argument to cons must be a list or vector"))
+        EvalResult::Value(ctx.eval_error("second argument to cons must be a list, vector or lazy seq"))
     }
 }
 
 pub fn native_concat(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    if args.iter().any(|arg| ctx.is_lazy_seq(*arg)) {
+        let seqables = ctx.vector(args);
+        return EvalResult::Value(ctx.lazy_seq(SeqGenerator::Concat, seqables));
+    }
+
     let mut all_items = Vec::new();
     
     for arg in args {
@@ -225,6 +296,12 @@ pub fn native_concat(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult
                     }
                 }
             }
+        } else {
+            // Strings, maps, sets and nil concatenate as their seq
+            match ctx.seq_items(arg) {
+                Some(items) => all_items.extend(items),
+                None => return EvalResult::Value(ctx.type_error("a collection", ctx.type_name(arg), "concat")),
+            }
         }
     }
     
@@ -258,8 +335,17 @@ pub fn native_first(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult
         } else {
             EvalResult::Value(ctx.eval_error("Invalid vector reference"))
         }
+    } else if ctx.is_nil(collection) || ctx.is_lazy_seq(collection) {
+        match ctx.seq_step(collection) {
+            Ok(Some((first, _))) => EvalResult::Value(first),
+            Ok(None) => EvalResult::Value(ctx.nil()),
+            Err(error) => EvalResult::Value(error),
+        }
     } else {
-        EvalResult::Value(ctx.eval_error("first expects a list or vector"))
+        match ctx.seq_items(collection) {
+            Some(items) => EvalResult::Value(items.first().copied().unwrap_or(ctx.nil())),
+            None => EvalResult::Value(ctx.eval_error("first expects a collection")),
+        }
     }
 }
 
@@ -268,17 +354,26 @@ pub fn native_rest(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
         return EvalResult::Value(ctx.arity_error(1, args.len(), "rest"));
     }
     
+    // The rest of a lazy seq stays lazy; only its first cell is realised
+    if ctx.is_lazy_seq(args[0]) {
+        return match ctx.seq_step(args[0]) {
+            Ok(Some((_, rest))) if !ctx.is_nil(rest) => EvalResult::Value(rest),
+            Ok(_) => EvalResult::Value(ctx.list(vec![])),
+            Err(error) => EvalResult::Value(error),
+        };
+    }
+
     match args[0].get_list() {
         Some(list) => {
             let rest_items: Vec<ValueRef> = list.iter().skip(1).cloned().collect();
             EvalResult::Value(ctx.list(rest_items))
         },
-        None => match args[0].get_vec() {
-            Some(vec) => {
-                let rest_items: Vec<ValueRef> = vec.iter().skip(1).cloned().collect();
+        None => match ctx.seq_items(args[0]) {
+            Some(items) => {
+                let rest_items: Vec<ValueRef> = items.iter().skip(1).cloned().collect();
                 EvalResult::Value(ctx.list(rest_items)) // Note: returns list, not vector
             },
-            None => EvalResult::Value(ctx.eval_error("rest expects a list or vector"))
+            None => EvalResult::Value(ctx.eval_error("rest expects a collection"))
         }
     }
 }
@@ -288,13 +383,21 @@ pub fn native_empty_q(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResul
         return EvalResult::Value(ctx.arity_error(1, args.len(), "empty?"));
     }
     
+    if ctx.is_lazy_seq(args[0]) {
+        return match ctx.seq_step(args[0]) {
+            Ok(cell) => EvalResult::Value(ctx.bool(cell.is_none())),
+            Err(error) => EvalResult::Value(error),
+        };
+    }
+
     let is_empty = match args[0].get_list() {
         Some(list) => list.is_empty(),
         None => match args[0].get_vec() {
             Some(vec) => vec.is_empty(),
-            None => {
-                return EvalResult::Value(ctx.eval_error("empty? expects a list or vector"));
-            }
+            None => match ctx.count(args[0]) {
+                Some(count) => count == 0,
+                None => return EvalResult::Value(ctx.eval_error("empty? expects a collection or lazy seq")),
+            },
         }
     };
     
@@ -306,13 +409,23 @@ pub fn native_count(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult
         return EvalResult::Value(ctx.arity_error(1, args.len(), "count"));
     }
     
+    // Counting a lazy seq realises all of it
+    if ctx.is_lazy_seq(args[0]) {
+        return match ctx.seq_to_vec(args[0]) {
+            Ok(items) => EvalResult::Value(ctx.number(items.len() as f64)),
+            Err(error) => EvalResult::Value(error),
+        };
+    }
+
     let count = match args[0].get_list() {
         Some(list) => list.len(),
         None => match args[0].get_vec() {
             Some(vec) => vec.len(),
-            None => {
-                return EvalResult::Value(ctx.eval_error("count expects a list or vector"));
-            }
+            // Maps, sets, strings and sorted collections
+            None => match ctx.count(args[0]) {
+                Some(count) => count,
+                None => return EvalResult::Value(ctx.eval_error("count expects a collection or lazy seq")),
+            },
         }
     };
     
@@ -366,11 +479,11 @@ pub fn native_get(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
     
     let target_ref = target_val;    
 
-    if let Some(target) = target_val.get_vec(){
+    if let Some(target) = target_val.heap_object(TypeTag::Vector) {
         if let Some(n) = ctx.get_number(*key_val){
-            let idx = n as usize;
-            if let Some(val) = target.get(idx) {
-                return EvalResult::Value(*val);
+            // Vectors are tries, so look the index up instead of copying the items out
+            if let Ok(val) = ctx.vm().vector_get_at(target, n as u32) {
+                return EvalResult::Value(val);
             } else if let Some(default) = fallback_val {
                 return EvalResult::Value(default);
             } else {
@@ -392,17 +505,31 @@ pub fn native_get(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
         } else {
             return EvalResult::Value(ctx.eval_error("get expects a number as second argument"));
         }
-    } else if let Some(target) = target_val.get_map(){
-        let res = target.get(key_val);
+    } else if let Some(target) = target_val.heap_object(TypeTag::Map) {
+        let res = ctx.vm().hashmap_get(target, key_val);
         if let Some(val) = res {
-            return EvalResult::Value(*val);
+            return EvalResult::Value(val);
         } else if let Some(default) = fallback_val {
             return EvalResult::Value(default);
         } else {
             return EvalResult::Value(ctx.nil());
         }
+    } else if let Some(target) = target_val.heap_object(TypeTag::Set) {
+        if ctx.vm().hashset_contains(target, key_val) {
+            return EvalResult::Value(*key_val);
+        }
+        return EvalResult::Value(fallback_val.unwrap_or(ctx.nil()));
+    } else if let Some((target, tag)) = sorted_object(*target_val) {
+        // A sorted map gives the value, a sorted set the member equal to the key
+        return match sorted_get(ctx, target, *key_val) {
+            Ok(Some((member, value))) => EvalResult::Value(if tag == TypeTag::SortedMap { value } else { member }),
+            Ok(None) => EvalResult::Value(fallback_val.unwrap_or(ctx.nil())),
+            Err(error) => EvalResult::Value(error),
+        };
+    } else if ctx.is_nil(*target_val) {
+        return EvalResult::Value(fallback_val.unwrap_or(ctx.nil()));
     } else {
-        return EvalResult::Value(ctx.eval_error("get expects a list, vector, or map"));
+        return EvalResult::Value(ctx.eval_error("get expects a list, vector, map or set"));
     }
 }
 
@@ -438,6 +565,7 @@ pub fn native_error(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult
         pos,
         message,
         error_type: BlinkErrorType::UserDefined { data },
+        stack_trace: Vec::new(),
     };
     EvalResult::Value(ctx.error(error))
 }
@@ -455,3 +583,295 @@ pub fn native_run_scheduler(_args: Vec<ValueRef>, ctx: &mut NativeContext) -> Ev
     }
 }
 
+
+pub fn native_profile_start(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    if !args.is_empty() {
+        return EvalResult::Value(ctx.arity_error(0, args.len(), "profile-start"));
+    }
+
+    let vm = ctx.vm().clone();
+    vm.value_metadata.write().clear_profile();
+    vm.profiler.start();
+    EvalResult::Value(ctx.nil())
+}
+
+/// Stops profiling and returns the per-function report
+pub fn native_profile_stop(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    if !args.is_empty() {
+        return EvalResult::Value(ctx.arity_error(0, args.len(), "profile-stop"));
+    }
+
+    let vm = ctx.vm().clone();
+    vm.profiler.stop();
+    let report = format_profile_report(&vm.value_metadata.read().profile_report());
+    EvalResult::Value(ctx.string(&report))
+}
+
+pub fn native_profile_report(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    if !args.is_empty() {
+        return EvalResult::Value(ctx.arity_error(0, args.len(), "profile-report"));
+    }
+
+    let vm = ctx.vm().clone();
+    let report = format_profile_report(&vm.value_metadata.read().profile_report());
+    EvalResult::Value(ctx.string(&report))
+}
+
+/// Collapsed stacks for flamegraph tools; with a path, writes them to that file
+pub fn native_profile_collapsed(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    if args.len() > 1 {
+        return EvalResult::Value(ctx.arity_error(1, args.len(), "profile-collapsed"));
+    }
+
+    let collapsed = ctx.vm().profiler.collapsed_stacks();
+    match args.first() {
+        None => EvalResult::Value(ctx.string(&collapsed)),
+        Some(path_val) => {
+            let Some(path) = ctx.get_string(*path_val) else {
+                return EvalResult::Value(ctx.eval_error("profile-collapsed expects a file path string"));
+            };
+            match std::fs::write(&path, collapsed) {
+                Ok(()) => EvalResult::Value(ctx.string(&path)),
+                Err(e) => EvalResult::Value(ctx.eval_error(&format!("Could not write {}: {}", path, e))),
+            }
+        }
+    }
+}
+
+/// The directories searched for module files, in order
+pub fn native_module_path(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    if !args.is_empty() {
+        return EvalResult::Value(ctx.arity_error(0, args.len(), "module-path"));
+    }
+
+    let dirs: Vec<String> = ctx
+        .vm()
+        .module_path
+        .read()
+        .dirs()
+        .iter()
+        .map(|dir| dir.display().to_string())
+        .collect();
+    let dirs = dirs.iter().map(|dir| ctx.string(dir)).collect();
+    EvalResult::Value(ctx.vector(dirs))
+}
+
+/// Append a directory to the module path
+pub fn native_add_module_path(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    if args.len() != 1 {
+        return EvalResult::Value(ctx.arity_error(1, args.len(), "add-module-path!"));
+    }
+    let Some(dir) = ctx.get_string(args[0]) else {
+        return EvalResult::Value(ctx.type_error("a directory string", ctx.type_name(args[0]), "add-module-path!"));
+    };
+
+    ctx.vm().module_path.write().add(dir);
+    native_module_path(Vec::new(), ctx)
+}
+
+/// The module a quoted module name refers to, if it exists
+fn module_arg(value: ValueRef, ctx: &NativeContext, name: &str) -> Result<u32, ValueRef> {
+    let Some(module_id) = value.get_symbol() else {
+        return Err(ctx.type_error("a module symbol", ctx.type_name(value), name));
+    };
+    if ctx.vm().module_registry.read().get_module(module_id).is_none() {
+        let module_name = ctx.symbol_name(module_id).unwrap_or_default();
+        return Err(ctx.eval_error(&format!("{}: no module named {}", name, module_name)));
+    }
+    Ok(module_id)
+}
+
+/// Symbols sorted by name
+fn sorted_symbols(ids: impl IntoIterator<Item = u32>, ctx: &NativeContext) -> Vec<ValueRef> {
+    let mut named: Vec<(String, u32)> = ids
+        .into_iter()
+        .map(|id| (ctx.symbol_name(id).unwrap_or_default(), id))
+        .collect();
+    named.sort();
+    named.into_iter().map(|(_, id)| ValueRef::symbol(id)).collect()
+}
+
+/// Names of every registered module
+pub fn native_all_modules(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    if !args.is_empty() {
+        return EvalResult::Value(ctx.arity_error(0, args.len(), "all-modules"));
+    }
+    let modules: Vec<u32> = ctx.vm().module_registry.read().modules.keys().copied().collect();
+    EvalResult::Value(ctx.vector(sorted_symbols(modules, ctx)))
+}
+
+/// A module's public definitions, as a map of name to value
+pub fn native_module_exports(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    if args.len() != 1 {
+        return EvalResult::Value(ctx.arity_error(1, args.len(), "module-exports"));
+    }
+    let module_id = match module_arg(args[0], ctx, "module-exports") {
+        Ok(module_id) => module_id,
+        Err(error) => return EvalResult::Value(error),
+    };
+
+    let exports = ctx.vm().module_registry.read().public_exports(module_id);
+    let pairs = exports
+        .into_iter()
+        .map(|(name, value)| (ValueRef::symbol(name), value))
+        .collect();
+    EvalResult::Value(ctx.hash_map(pairs))
+}
+
+/// The modules a module imported, as a map of module name to the names it referred
+pub fn native_module_imports(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    if args.len() != 1 {
+        return EvalResult::Value(ctx.arity_error(1, args.len(), "module-imports"));
+    }
+    let module_id = match module_arg(args[0], ctx, "module-imports") {
+        Ok(module_id) => module_id,
+        Err(error) => return EvalResult::Value(error),
+    };
+
+    let imports = ctx.vm().module_registry.read().imports_of(module_id);
+    let pairs = imports
+        .into_iter()
+        .map(|(from, names)| (ValueRef::symbol(from), ctx.vector(sorted_symbols(names, ctx))))
+        .collect();
+    EvalResult::Value(ctx.hash_map(pairs))
+}
+
+/// The qualified name a symbol resolves to in the calling module, or nil
+pub fn native_resolve(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    if args.len() != 1 {
+        return EvalResult::Value(ctx.arity_error(1, args.len(), "resolve"));
+    }
+    let Some(symbol_id) = args[0].get_symbol() else {
+        return EvalResult::Value(ctx.type_error("a symbol", ctx.type_name(args[0]), "resolve"));
+    };
+
+    match ctx.vm().locate_global_symbol(ctx.current_module(), symbol_id) {
+        Some((module_id, name)) => {
+            let qualified = ctx.vm().symbol_table.write().intern_qualified(module_id, name);
+            EvalResult::Value(ValueRef::symbol(qualified))
+        }
+        None => EvalResult::Value(ctx.nil()),
+    }
+}
+
+/// What is known about a definition named by a var, `#'f` or `'m/f`: its
+/// name, module, type, whether it is private and where it was defined, along
+/// with its docstring, arglists and ^{...} entries. Anything else has no metadata.
+pub fn native_meta(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    if args.len() != 1 {
+        return EvalResult::Value(ctx.arity_error(1, args.len(), "meta"));
+    }
+    let Some(symbol_id) = args[0].get_symbol() else {
+        return EvalResult::Value(ctx.nil());
+    };
+
+    let vm = ctx.vm().clone();
+    // A qualified name is looked up directly, so private definitions have metadata too
+    let located = match vm.symbol_table.read().get_qualified(symbol_id) {
+        Some((module_id, name)) if vm.module_registry.read().resolve_symbol(module_id, name).is_some() => {
+            Some((module_id, name))
+        }
+        _ => None,
+    };
+    let Some((module_id, name)) = located.or_else(|| vm.locate_global_symbol(ctx.current_module(), symbol_id)) else {
+        return EvalResult::Value(ctx.nil());
+    };
+
+    let (value, private, file, meta) = {
+        let registry = vm.module_registry.read();
+        let value = registry.resolve_symbol(module_id, name).unwrap_or_else(ValueRef::nil);
+        let meta = registry.metadata(module_id, name).and_then(|meta| meta.get_map());
+        (value, registry.is_private(module_id, name), registry.find_module_file(module_id), meta)
+    };
+    let mut pairs = vec![
+        (ctx.keyword("name"), ValueRef::symbol(name)),
+        (ctx.keyword("module"), ValueRef::symbol(module_id)),
+        (ctx.keyword("type"), ctx.string(ctx.type_name(value))),
+        (ctx.keyword("private"), ctx.bool(private)),
+    ];
+    if let Some(file) = file.and_then(|file| ctx.symbol_name(file)) {
+        pairs.push((ctx.keyword("file"), ctx.string(&file)));
+    }
+    if let Some(pos) = ctx.get_pos(value) {
+        pairs.push((ctx.keyword("line"), ctx.number(pos.start.line as f64)));
+    }
+    for (key, value) in meta.iter().flat_map(|meta| meta.iter()) {
+        if !pairs.iter().any(|(existing, _)| existing.get_keyword().is_some_and(|id| key.get_keyword() == Some(id))) {
+            pairs.push((*key, *value));
+        }
+    }
+    EvalResult::Value(ctx.hash_map(pairs))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::ExecutionContext;
+    use crate::test_support::{context, eval, try_eval};
+
+    // Whether the code evaluates to a value equal to `expected`
+    fn returns(ctx: &mut ExecutionContext, code: &str, expected: &str) -> bool {
+        eval(ctx, &format!("(= {} {})", code, expected)) == "true"
+    }
+
+    fn error_message(ctx: &mut ExecutionContext, code: &str) -> String {
+        try_eval(ctx, code).unwrap_or_else(|error| error)
+    }
+
+    // A module with a public function and a private value, imported into `user`
+    fn define_library(ctx: &mut ExecutionContext, library: &str, user: &str) {
+        eval(
+            ctx,
+            &format!(
+                "(mod {library} :export [area])
+                 (def area (fn [r] (* r r)))
+                 (def helper 1)
+                 (mod {user})
+                 (imp {library} :only [area])"
+            ),
+        );
+    }
+
+    #[test]
+    fn module_exports_and_imports_describe_modules() {
+        let mut ctx = context("intro-modules");
+        define_library(&mut ctx, "intro-modules-lib", "intro-modules");
+        assert_eq!(eval(&mut ctx, "(count (module-exports 'intro-modules-lib))"), "1");
+        assert_eq!(eval(&mut ctx, "((get (module-exports 'intro-modules-lib) 'area) 3)"), "9");
+        assert_eq!(eval(&mut ctx, "(get (module-exports 'intro-modules-lib) 'helper)"), "nil");
+        assert!(returns(&mut ctx, "(keys (module-imports 'intro-modules))", "'(intro-modules-lib)"));
+        assert!(returns(&mut ctx, "(get (module-imports 'intro-modules) 'intro-modules-lib)", "'(area)"));
+        assert_eq!(eval(&mut ctx, "(count (filter (fn [m] (= m 'intro-modules-lib)) (all-modules)))"), "1");
+        let message = error_message(&mut ctx, "(module-exports 'intro-no-such-module)");
+        assert!(message.contains("no module named intro-no-such-module"), "{}", message);
+    }
+
+    #[test]
+    fn resolve_names_the_definition_a_symbol_refers_to() {
+        let mut ctx = context("intro-resolve");
+        define_library(&mut ctx, "intro-resolve-lib", "intro-resolve");
+        eval(&mut ctx, "(def local 2)");
+        assert!(returns(&mut ctx, "(resolve 'area)", "'intro-resolve-lib/area"));
+        assert!(returns(&mut ctx, "(resolve 'local)", "'intro-resolve/local"));
+        assert!(returns(&mut ctx, "(resolve 'intro-resolve-lib/area)", "'intro-resolve-lib/area"));
+        assert!(returns(&mut ctx, "(resolve 'map)", "'core/map"));
+        assert_eq!(eval(&mut ctx, "(resolve 'helper)"), "nil");
+        assert_eq!(eval(&mut ctx, "(resolve 'intro-nothing)"), "nil");
+    }
+
+    #[test]
+    fn meta_describes_definitions() {
+        let mut ctx = context("intro-meta");
+        define_library(&mut ctx, "intro-meta-lib", "intro-meta");
+        assert!(returns(&mut ctx, "(:name (meta 'area))", "'area"));
+        assert!(returns(&mut ctx, "(:module (meta #'area))", "'intro-meta-lib"));
+        assert!(returns(&mut ctx, "(:arglists (meta 'area))", "'([r])"));
+        assert_eq!(eval(&mut ctx, "(:private (meta 'area))"), "false");
+        // Qualified names reach private definitions too
+        assert_eq!(eval(&mut ctx, "(:private (meta 'intro-meta-lib/helper))"), "true");
+        assert!(returns(&mut ctx, "(:type (meta 'intro-meta-lib/helper))", "\"number\""));
+        eval(&mut ctx, "(def ^{:added \"1.2\"} tagged 3)");
+        assert!(returns(&mut ctx, "(:added (meta 'tagged))", "\"1.2\""));
+        assert_eq!(eval(&mut ctx, "(meta 'intro-undefined)"), "nil");
+        assert_eq!(eval(&mut ctx, "(meta 42)"), "nil");
+    }
+}
@@ -1,4 +1,4 @@
-use crate::error::BlinkError;
+use crate::error::{BlinkError, ParseErrorType};
 use crate::runtime::SymbolTable;
 use crate::value::{ParsedValue, ParsedValueWithPos, SourcePos, SourceRange};
 use std::collections::HashMap;
@@ -66,7 +66,14 @@ pub fn tokenize_at(
         }
 
         match c {
-            '(' | ')' | '[' | ']' | '{' | '}' => {
+            ';' if !in_str => {
+                if !current.is_empty() {
+                    tokens.push((current.clone(), SourcePos { line, col }));
+                    current.clear();
+                }
+                in_comment = true;
+            }
+            '(' | ')' | '[' | ']' | '{' | '}' if !in_str => {
                 if !current.is_empty() {
                     tokens.push((current.clone(), SourcePos { line, col }));
                     current.clear();
@@ -242,6 +249,47 @@ pub fn parse(
             Err(BlinkError::unexpected_token(&token, start_pos).with_pos(Some(range)))
         }
 
+        // ^{:doc "..."} form, or ^:private form for {:private true}
+        _ if token.starts_with('^') => {
+            let rest = &token[1..];
+            let meta_form = if rest.is_empty() {
+                if tokens.is_empty() {
+                    let pos = SourceRange { start: start_pos, end: start_pos };
+                    return Err(BlinkError::parse_unexpected_eof(pos));
+                }
+                parse(tokens, reader_ctx, symbol_table)?
+            } else {
+                let mut rest_tokens = tokenize(rest)?;
+                parse(&mut rest_tokens, reader_ctx, symbol_table)?
+            };
+            let meta_pos = meta_form.pos;
+            let meta = match meta_form.value {
+                ParsedValue::Map(_) => meta_form,
+                ParsedValue::Keyword(_) => {
+                    let flag = ParsedValueWithPos::new(ParsedValue::Bool(true), meta_pos);
+                    ParsedValueWithPos::new(ParsedValue::Map(vec![(meta_form, flag)]), meta_pos)
+                }
+                _ => {
+                    let pos = meta_pos.unwrap_or(SourceRange { start: start_pos, end: start_pos });
+                    return Err(BlinkError::parse(
+                        "Metadata must be a map or a keyword",
+                        pos,
+                        ParseErrorType::UnexpectedToken(token.clone()),
+                    ));
+                }
+            };
+
+            if tokens.is_empty() {
+                let pos = SourceRange { start: start_pos, end: start_pos };
+                return Err(BlinkError::parse_unexpected_eof(pos));
+            }
+            let target = parse(tokens, reader_ctx, symbol_table)?;
+            let end_pos = target.pos.as_ref().map(|r| r.end).unwrap_or(start_pos);
+            let range = Some(SourceRange { start: start_pos, end: end_pos });
+            let with_meta = ParsedValueWithPos::new(ParsedValue::Symbol(symbol_table.intern("with-meta")), range);
+            Ok(ParsedValueWithPos::new(ParsedValue::List(vec![with_meta, target, meta]), range))
+        }
+
         _ => {
             // Check for reader macros
             let mut matched_macro: Option<(String, u32)> = None;
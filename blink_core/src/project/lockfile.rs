@@ -0,0 +1,69 @@
+use std::fs;
+use std::path::Path;
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::project::LOCK_FILE;
+
+const LOCK_HEADER: &str = "# Written by the Blink resolver from blink.toml. Do not edit by hand.\n\n";
+
+/// Contents of `blink.lock`: every package the project depends on, directly or
+/// not, at the version it resolved to
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Lockfile {
+    #[serde(default, rename = "package", skip_serializing_if = "Vec::is_empty")]
+    pub packages: Vec<LockedPackage>,
+    #[serde(default, rename = "plugin", skip_serializing_if = "Vec::is_empty")]
+    pub plugins: Vec<LockedPlugin>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: String,
+    /// `path+../utils`, `registry`, `git+url#rev` or `tarball+url`
+    pub source: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LockedPlugin {
+    pub name: String,
+    pub package: String,
+    pub path: String,
+}
+
+impl Lockfile {
+    /// `blink.lock` of a project, if it has one
+    pub fn read(root: &Path) -> Result<Option<Self>, String> {
+        let path = root.join(LOCK_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let text = fs::read_to_string(&path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        toml::from_str(&text)
+            .map(Some)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Write `blink.lock`, leaving the file alone when nothing changed
+    pub fn write(&self, root: &Path) -> Result<(), String> {
+        if Self::read(root).ok().flatten().as_ref() == Some(self) {
+            return Ok(());
+        }
+        let path = root.join(LOCK_FILE);
+        let body = toml::to_string(self).map_err(|e| e.to_string())?;
+        fs::write(&path, format!("{}{}", LOCK_HEADER, body))
+            .map_err(|e| format!("Could not write {}: {}", path.display(), e))
+    }
+
+    /// The version a registry package was locked at
+    pub fn registry_version(&self, name: &str) -> Option<Version> {
+        self.packages
+            .iter()
+            .find(|package| package.name == name && package.source == "registry")
+            .and_then(|package| Version::parse(&package.version).ok())
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+
+use crate::project::MANIFEST_FILE;
+
+/// Source directories used when the manifest does not list any
+const DEFAULT_SOURCE_PATHS: &[&str] = &["src", "."];
+
+/// A parsed `blink.toml`:
+///
+/// ```toml
+/// [package]
+/// name = "app"
+/// version = "0.1.0"
+///
+/// [modules]
+/// paths = ["src"]
+///
+/// [dependencies]
+/// json = "1.2"                                    # from the registry directory
+/// utils = { path = "../utils" }
+/// http = { git = "https://example.com/http.git", rev = "v2" }
+/// csv = { tarball = "https://example.com/csv-0.3.0.tar.gz" }
+///
+/// [plugins]
+/// sqlite = "native/libblink_sqlite.so"
+///
+/// [registry]
+/// path = "../registry"
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Manifest {
+    pub package: Option<PackageInfo>,
+    #[serde(default)]
+    pub modules: ModulesConfig,
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, Dependency>,
+    #[serde(default)]
+    pub plugins: BTreeMap<String, Plugin>,
+    pub registry: Option<RegistryConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PackageInfo {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ModulesConfig {
+    pub paths: Vec<String>,
+}
+
+impl Default for ModulesConfig {
+    fn default() -> Self {
+        ModulesConfig {
+            paths: DEFAULT_SOURCE_PATHS.iter().map(|path| path.to_string()).collect(),
+        }
+    }
+}
+
+/// `name = "1.2"` or `name = { path = ... }`, `{ git = ..., rev = ... }`,
+/// `{ tarball = ... }` or `{ version = ... }`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Dependency {
+    Version(String),
+    Detailed {
+        version: Option<String>,
+        path: Option<String>,
+        git: Option<String>,
+        rev: Option<String>,
+        tarball: Option<String>,
+    },
+}
+
+/// Where a dependency comes from
+#[derive(Clone, Debug, PartialEq)]
+pub enum DependencySource {
+    Path(String),
+    Registry(VersionReq),
+    Git { url: String, rev: Option<String> },
+    Tarball(String),
+}
+
+impl Dependency {
+    pub fn source(&self, name: &str) -> Result<DependencySource, String> {
+        let parse_req = |req: &str| {
+            VersionReq::parse(req).map_err(|e| format!("dependency {}: bad version requirement {}: {}", name, req, e))
+        };
+        match self {
+            Dependency::Version(req) => Ok(DependencySource::Registry(parse_req(req)?)),
+            Dependency::Detailed { version, path, git, rev, tarball } => {
+                match (path, git, tarball) {
+                    (Some(path), None, None) => Ok(DependencySource::Path(path.clone())),
+                    (None, Some(url), None) => Ok(DependencySource::Git { url: url.clone(), rev: rev.clone() }),
+                    (None, None, Some(url)) => Ok(DependencySource::Tarball(url.clone())),
+                    (None, None, None) => match version {
+                        Some(req) => Ok(DependencySource::Registry(parse_req(req)?)),
+                        None => Err(format!("dependency {}: needs a version, path, git or tarball", name)),
+                    },
+                    _ => Err(format!("dependency {}: use only one of path, git and tarball", name)),
+                }
+            }
+        }
+    }
+}
+
+/// `name = "lib.so"` or `name = { path = "lib.so" }`, relative to the package
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Plugin {
+    Path(String),
+    Detailed { path: String },
+}
+
+impl Plugin {
+    pub fn path(&self) -> &str {
+        match self {
+            Plugin::Path(path) | Plugin::Detailed { path } => path,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RegistryConfig {
+    pub path: String,
+}
+
+impl Manifest {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    /// Read `blink.toml` from a package root
+    pub fn load(root: &Path) -> Result<Self, String> {
+        let path = root.join(MANIFEST_FILE);
+        let text = fs::read_to_string(&path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// The manifest of a package root, or the defaults when it has none
+    pub fn load_or_default(root: &Path) -> Result<Self, String> {
+        if root.join(MANIFEST_FILE).is_file() {
+            Self::load(root)
+        } else {
+            Ok(Manifest::default())
+        }
+    }
+
+    /// The version in [package], if it declares one
+    pub fn version(&self) -> Result<Option<Version>, String> {
+        match self.package.as_ref().and_then(|package| package.version.as_deref()) {
+            Some(version) => Version::parse(version)
+                .map(Some)
+                .map_err(|e| format!("bad package version {}: {}", version, e)),
+            None => Ok(None),
+        }
+    }
+
+    /// Source directories of a package rooted at `root`
+    pub fn source_paths(&self, root: &Path) -> Vec<PathBuf> {
+        self.modules
+            .paths
+            .iter()
+            .map(|path| {
+                let dir = root.join(path);
+                fs::canonicalize(&dir).unwrap_or(dir)
+            })
+            .collect()
+    }
+}
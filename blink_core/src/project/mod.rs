@@ -0,0 +1,27 @@
+//! Blink projects: the `blink.toml` manifest, the dependency resolver and the
+//! `blink.lock` lockfile it writes. Resolution never touches the network;
+//! packages come from local paths, a registry directory and a checkout cache.
+
+use std::path::{Path, PathBuf};
+
+mod lockfile;
+mod manifest;
+mod resolver;
+
+pub use lockfile::*;
+pub use manifest::*;
+pub use resolver::*;
+
+/// Project configuration file, looked for in the working directory and its parents
+pub const MANIFEST_FILE: &str = "blink.toml";
+
+/// Resolved dependency versions, next to the manifest
+pub const LOCK_FILE: &str = "blink.lock";
+
+/// The nearest directory at or above `start` holding a `blink.toml`
+pub fn find_project_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| dir.join(MANIFEST_FILE).is_file())
+        .map(Path::to_path_buf)
+}
@@ -0,0 +1,500 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use semver::{Version, VersionReq};
+
+use crate::project::{DependencySource, LockedPackage, LockedPlugin, Lockfile, Manifest};
+
+/// Environment variable naming the registry directory
+pub const REGISTRY_VAR: &str = "BLINK_REGISTRY";
+
+/// Environment variable naming the checkout cache
+pub const CACHE_VAR: &str = "BLINK_CACHE";
+
+/// Where a resolved package was found
+#[derive(Clone, Debug, PartialEq)]
+pub enum PackageSource {
+    Root,
+    Path(String),
+    Registry,
+    Git { url: String, rev: Option<String> },
+    Tarball(String),
+}
+
+impl fmt::Display for PackageSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackageSource::Root => write!(f, "root"),
+            PackageSource::Path(path) => write!(f, "path+{}", path),
+            PackageSource::Registry => write!(f, "registry"),
+            PackageSource::Git { url, rev: Some(rev) } => write!(f, "git+{}#{}", url, rev),
+            PackageSource::Git { url, rev: None } => write!(f, "git+{}", url),
+            PackageSource::Tarball(url) => write!(f, "tarball+{}", url),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ResolvedPackage {
+    pub name: String,
+    pub version: Version,
+    pub source: PackageSource,
+    pub root: PathBuf,
+    pub source_paths: Vec<PathBuf>,
+    pub dependencies: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ResolvedPlugin {
+    pub name: String,
+    pub package: String,
+    pub path: PathBuf,
+}
+
+/// A project with its dependency graph resolved. The project itself is the
+/// first package, the rest follow in the order they were reached.
+#[derive(Clone, Debug)]
+pub struct ResolvedProject {
+    pub root: PathBuf,
+    pub packages: Vec<ResolvedPackage>,
+    pub plugins: Vec<ResolvedPlugin>,
+}
+
+impl ResolvedProject {
+    /// Directories to search for modules: the project's, then each dependency's
+    pub fn source_paths(&self) -> Vec<PathBuf> {
+        self.packages.iter().flat_map(|package| package.source_paths.iter().cloned()).collect()
+    }
+
+    pub fn lockfile(&self) -> Lockfile {
+        let packages = self
+            .packages
+            .iter()
+            .filter(|package| package.source != PackageSource::Root)
+            .map(|package| LockedPackage {
+                name: package.name.clone(),
+                version: package.version.to_string(),
+                source: package.source.to_string(),
+                dependencies: package.dependencies.clone(),
+            })
+            .collect();
+        let plugins = self
+            .plugins
+            .iter()
+            .map(|plugin| LockedPlugin {
+                name: plugin.name.clone(),
+                package: plugin.package.clone(),
+                path: plugin.path.display().to_string(),
+            })
+            .collect();
+        Lockfile { packages, plugins }
+    }
+}
+
+/// Resolves dependencies without the network: path dependencies from disk,
+/// registry dependencies from `<registry>/<name>/<version>/`, and git and
+/// tarball dependencies from checkouts already in the cache, at
+/// `<cache>/git/<name>/<rev>` and `<cache>/tarball/<file name without extension>`
+#[derive(Clone, Debug)]
+pub struct Resolver {
+    pub registry: PathBuf,
+    pub cache: PathBuf,
+    locked: Option<Lockfile>,
+}
+
+// A dependency waiting to be resolved
+struct Request {
+    name: String,
+    source: DependencySource,
+    base: PathBuf,
+    required_by: String,
+}
+
+// A registry version requirement and the package that made it
+#[derive(Clone, Debug)]
+struct Requirement {
+    req: VersionReq,
+    required_by: String,
+}
+
+// Requirements a registry package's version must meet besides the one being
+// resolved, learned from earlier attempts
+type Constraints = BTreeMap<String, Vec<Requirement>>;
+
+// The outcome of one pass over the dependency graph
+enum Attempt {
+    Resolved(ResolvedProject),
+    // A requirement the version already picked for a registry package misses
+    Constrain(String, Requirement),
+}
+
+impl Resolver {
+    pub fn new(registry: impl Into<PathBuf>, cache: impl Into<PathBuf>) -> Self {
+        Resolver { registry: registry.into(), cache: cache.into(), locked: None }
+    }
+
+    /// The registry from the manifest's [registry] table or $BLINK_REGISTRY,
+    /// and the cache from $BLINK_CACHE, defaulting to ~/.blink/registry and
+    /// ~/.blink/cache
+    pub fn from_environment(root: &Path, manifest: &Manifest) -> Self {
+        let home = std::env::var_os("HOME").map(PathBuf::from).unwrap_or_default().join(".blink");
+        let registry = match &manifest.registry {
+            Some(registry) => {
+                let dir = root.join(&registry.path);
+                fs::canonicalize(&dir).unwrap_or(dir)
+            }
+            None => std::env::var_os(REGISTRY_VAR).map(PathBuf::from).unwrap_or_else(|| home.join("registry")),
+        };
+        let cache = std::env::var_os(CACHE_VAR).map(PathBuf::from).unwrap_or_else(|| home.join("cache"));
+        Resolver::new(registry, cache)
+    }
+
+    /// Prefer the registry versions of an earlier resolution while they still
+    /// satisfy the manifest
+    pub fn with_lockfile(mut self, locked: Option<Lockfile>) -> Self {
+        self.locked = locked;
+        self
+    }
+
+    /// Resolve the dependency graph of the project at `root`. A registry package
+    /// required more than once gets the newest version meeting every requirement.
+    pub fn resolve(&self, root: &Path) -> Result<ResolvedProject, String> {
+        // The graph is walked breadth first, so a requirement found late can rule
+        // out the version picked for an earlier one. Walk it again with both until
+        // every pick holds; each pass adds a requirement, so this ends.
+        let mut constraints = Constraints::new();
+        loop {
+            match self.attempt(root, &constraints)? {
+                Attempt::Resolved(project) => return Ok(project),
+                Attempt::Constrain(name, requirement) => constraints.entry(name).or_default().push(requirement),
+            }
+        }
+    }
+
+    fn attempt(&self, root: &Path, constraints: &Constraints) -> Result<Attempt, String> {
+        let root = fs::canonicalize(root).map_err(|e| format!("{}: {}", root.display(), e))?;
+        let manifest = Manifest::load(&root)?;
+        let name = manifest
+            .package
+            .as_ref()
+            .map(|package| package.name.clone())
+            .or_else(|| root.file_name().map(|name| name.to_string_lossy().into_owned()))
+            .unwrap_or_default();
+
+        let mut project = ResolvedProject { root: root.clone(), packages: Vec::new(), plugins: Vec::new() };
+        let mut queue = VecDeque::new();
+        let version = manifest.version()?.unwrap_or(Version::new(0, 0, 0));
+        self.add_package(&mut project, &mut queue, name, version, PackageSource::Root, root, manifest)?;
+
+        while let Some(request) = queue.pop_front() {
+            let existing = project.packages.iter().find(|package| package.name == request.name);
+            if let (Some(existing), DependencySource::Registry(req)) = (existing, &request.source) {
+                if existing.source == PackageSource::Registry {
+                    if req.matches(&existing.version) {
+                        continue;
+                    }
+                    let requirement = Requirement { req: req.clone(), required_by: request.required_by };
+                    return Ok(Attempt::Constrain(request.name, requirement));
+                }
+            }
+
+            let (source, package_root, registry_version) = self.locate(&request, constraints)?;
+            if let Some(existing) = existing {
+                if existing.root != package_root {
+                    return Err(format!(
+                        "Conflicting sources for {}: {} {} and {} (required by {})",
+                        request.name,
+                        existing.source,
+                        existing.version,
+                        source,
+                        request.required_by
+                    ));
+                }
+                continue;
+            }
+
+            let manifest = Manifest::load_or_default(&package_root)?;
+            // A registry package is the version its directory is named for
+            let version = match registry_version {
+                Some(version) => version,
+                None => manifest.version()?.unwrap_or(Version::new(0, 0, 0)),
+            };
+            self.add_package(&mut project, &mut queue, request.name, version, source, package_root, manifest)?;
+        }
+
+        Ok(Attempt::Resolved(project))
+    }
+
+    // Record a package and queue its dependencies
+    #[allow(clippy::too_many_arguments)]
+    fn add_package(
+        &self,
+        project: &mut ResolvedProject,
+        queue: &mut VecDeque<Request>,
+        name: String,
+        version: Version,
+        source: PackageSource,
+        root: PathBuf,
+        manifest: Manifest,
+    ) -> Result<(), String> {
+        for (dependency, spec) in &manifest.dependencies {
+            queue.push_back(Request {
+                name: dependency.clone(),
+                source: spec.source(dependency)?,
+                base: root.clone(),
+                required_by: name.clone(),
+            });
+        }
+        for (plugin, spec) in &manifest.plugins {
+            project.plugins.push(ResolvedPlugin {
+                name: plugin.clone(),
+                package: name.clone(),
+                path: root.join(spec.path()),
+            });
+        }
+        project.packages.push(ResolvedPackage {
+            name,
+            version,
+            source,
+            source_paths: manifest.source_paths(&root),
+            root,
+            dependencies: manifest.dependencies.keys().cloned().collect(),
+        });
+        Ok(())
+    }
+
+    // The directory holding a dependency, and its version when the registry decides it
+    fn locate(&self, request: &Request, constraints: &Constraints) -> Result<(PackageSource, PathBuf, Option<Version>), String> {
+        let name = &request.name;
+        match &request.source {
+            DependencySource::Path(path) => {
+                let dir = request.base.join(path);
+                let dir = fs::canonicalize(&dir)
+                    .map_err(|_| format!("{}: no package at {} (required by {})", name, dir.display(), request.required_by))?;
+                Ok((PackageSource::Path(path.clone()), dir, None))
+            }
+            DependencySource::Registry(req) => {
+                let mut requirements = vec![Requirement { req: req.clone(), required_by: request.required_by.clone() }];
+                requirements.extend(constraints.get(name).into_iter().flatten().cloned());
+                let version = self.registry_version(name, &requirements)?;
+                let dir = self.registry.join(name).join(version.to_string());
+                Ok((PackageSource::Registry, dir, Some(version)))
+            }
+            DependencySource::Git { url, rev } => {
+                let dir = self.cache.join("git").join(name).join(rev.as_deref().unwrap_or("HEAD"));
+                if !dir.is_dir() {
+                    return Err(format!(
+                        "{}: no checkout of {} in the cache, expected {} (required by {})",
+                        name,
+                        url,
+                        dir.display(),
+                        request.required_by
+                    ));
+                }
+                Ok((PackageSource::Git { url: url.clone(), rev: rev.clone() }, dir, None))
+            }
+            DependencySource::Tarball(url) => {
+                let dir = self.cache.join("tarball").join(tarball_dir_name(url));
+                if !dir.is_dir() {
+                    return Err(format!(
+                        "{}: {} is not unpacked in the cache, expected {} (required by {})",
+                        name,
+                        url,
+                        dir.display(),
+                        request.required_by
+                    ));
+                }
+                Ok((PackageSource::Tarball(url.clone()), dir, None))
+            }
+        }
+    }
+
+    // The locked version if it still meets every requirement, otherwise the newest that does
+    fn registry_version(&self, name: &str, requirements: &[Requirement]) -> Result<Version, String> {
+        let dir = self.registry.join(name);
+        let mut available: Vec<Version> = fs::read_dir(&dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.path().is_dir())
+                    .filter_map(|entry| Version::parse(&entry.file_name().to_string_lossy()).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        available.sort();
+
+        let meets_all = |version: &Version| requirements.iter().all(|requirement| requirement.req.matches(version));
+        let locked = self.locked.as_ref().and_then(|lock| lock.registry_version(name));
+        if let Some(locked) = locked.filter(|version| meets_all(version) && available.contains(version)) {
+            return Ok(locked);
+        }
+        available.iter().rev().find(|version| meets_all(version)).cloned().ok_or_else(|| {
+            let found: Vec<String> = available.iter().map(Version::to_string).collect();
+            let found = if found.is_empty() { "none".to_string() } else { found.join(", ") };
+            match requirements {
+                [requirement] => format!(
+                    "{}: no version matching {} in the registry at {} (found {}; required by {})",
+                    name,
+                    requirement.req,
+                    self.registry.display(),
+                    found,
+                    requirement.required_by
+                ),
+                _ => {
+                    let required: Vec<String> = requirements
+                        .iter()
+                        .map(|requirement| format!("{} by {}", requirement.req, requirement.required_by))
+                        .collect();
+                    format!(
+                        "Conflicting versions for {}: no version in the registry at {} meets {} (found {})",
+                        name,
+                        self.registry.display(),
+                        required.join(" and "),
+                        found
+                    )
+                }
+            }
+        })
+    }
+}
+
+// csv-0.3.0.tar.gz -> csv-0.3.0
+fn tarball_dir_name(url: &str) -> String {
+    let file = url.rsplit('/').next().unwrap_or(url);
+    [".tar.gz", ".tgz", ".tar"]
+        .iter()
+        .find_map(|extension| file.strip_suffix(extension))
+        .unwrap_or(file)
+        .to_string()
+}
+
+/// Resolve the project at `root`, following its lockfile, and write the
+/// lockfile back if the resolution changed
+pub fn resolve_project(root: &Path) -> Result<ResolvedProject, String> {
+    let (project, _) = read_project(root)?;
+    project.lockfile().write(&project.root)?;
+    Ok(project)
+}
+
+/// Resolve the project at `root`, following its lockfile, without writing
+/// anything. Also says whether the lockfile already records this resolution.
+pub fn read_project(root: &Path) -> Result<(ResolvedProject, bool), String> {
+    let manifest = Manifest::load(root)?;
+    let locked = Lockfile::read(root)?;
+    let resolver = Resolver::from_environment(root, &manifest).with_lockfile(locked.clone());
+    let project = resolver.resolve(root)?;
+    let up_to_date = locked.as_ref() == Some(&project.lockfile());
+    Ok((project, up_to_date))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::LOCK_FILE;
+
+    // A fresh directory under the system temp dir
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("blink-resolver-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn package(dir: &Path, manifest: &str) {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join("blink.toml"), manifest).unwrap();
+    }
+
+    #[test]
+    fn resolves_path_registry_and_cached_dependencies() {
+        let base = scratch("graph");
+        package(
+            &base.join("app"),
+            "[package]\nname = \"app\"\n[dependencies]\nutils = { path = \"../utils\" }\nhttp = { git = \"https://example.com/http.git\", rev = \"v2\" }\n",
+        );
+        package(&base.join("utils"), "[package]\nname = \"utils\"\nversion = \"0.2.0\"\n[dependencies]\njson = \"1.1\"\n");
+        package(&base.join("registry/json/1.0.0"), "");
+        package(&base.join("registry/json/1.4.2"), "[plugins]\nfast = \"native/libfast.so\"\n");
+        package(&base.join("registry/json/2.0.0"), "");
+        package(&base.join("cache/git/http/v2"), "[modules]\npaths = [\"lib\"]\n");
+
+        let resolver = Resolver::new(base.join("registry"), base.join("cache"));
+        let project = resolver.resolve(&base.join("app")).unwrap();
+
+        let versions: Vec<(String, String)> =
+            project.packages.iter().map(|p| (p.name.clone(), p.version.to_string())).collect();
+        assert_eq!(
+            versions,
+            vec![
+                ("app".to_string(), "0.0.0".to_string()),
+                ("http".to_string(), "0.0.0".to_string()),
+                ("utils".to_string(), "0.2.0".to_string()),
+                ("json".to_string(), "1.4.2".to_string()),
+            ]
+        );
+        let http = &project.packages[1];
+        assert_eq!(http.source.to_string(), "git+https://example.com/http.git#v2");
+        assert!(project.source_paths().contains(&http.root.join("lib")));
+        assert_eq!(project.plugins[0].package, "json");
+    }
+
+    #[test]
+    fn lockfile_keeps_registry_versions() {
+        let base = scratch("lock");
+        package(&base.join("app"), "[dependencies]\njson = \"1\"\n");
+        package(&base.join("registry/json/1.0.0"), "");
+        let resolver = Resolver::new(base.join("registry"), base.join("cache"));
+        let first = resolver.resolve(&base.join("app")).unwrap();
+        first.lockfile().write(&base.join("app")).unwrap();
+        assert!(base.join("app").join(LOCK_FILE).is_file());
+
+        // A newer release does not move a locked project
+        package(&base.join("registry/json/1.3.0"), "");
+        let locked = Lockfile::read(&base.join("app")).unwrap();
+        let again = resolver.clone().with_lockfile(locked).resolve(&base.join("app")).unwrap();
+        assert_eq!(again.packages[1].version, Version::new(1, 0, 0));
+
+        // Without the lockfile the newest match wins
+        let fresh = resolver.resolve(&base.join("app")).unwrap();
+        assert_eq!(fresh.packages[1].version, Version::new(1, 3, 0));
+    }
+
+    #[test]
+    fn registry_requirements_on_one_package_are_combined() {
+        let base = scratch("combined");
+        package(&base.join("app"), "[dependencies]\njson = \"1\"\nutils = { path = \"../utils\" }\n");
+        package(&base.join("utils"), "[dependencies]\njson = \"~1.0\"\n");
+        package(&base.join("registry/json/1.0.3"), "");
+        package(&base.join("registry/json/1.4.2"), "");
+        let resolver = Resolver::new(base.join("registry"), base.join("cache"));
+
+        // 1.4.2 is the newest for the app alone, but utils needs 1.0.x
+        let project = resolver.resolve(&base.join("app")).unwrap();
+        let json: Vec<&ResolvedPackage> = project.packages.iter().filter(|package| package.name == "json").collect();
+        assert_eq!(json.len(), 1);
+        assert_eq!(json[0].version, Version::new(1, 0, 3));
+
+        package(&base.join("app"), "[dependencies]\njson = \"1.4\"\nutils = { path = \"../utils\" }\n");
+        let error = resolver.resolve(&base.join("app")).unwrap_err();
+        assert!(error.starts_with("Conflicting versions for json"), "{}", error);
+        assert!(error.contains("^1.4 by app and ~1.0 by utils"), "{}", error);
+    }
+
+    #[test]
+    fn reports_missing_and_conflicting_packages() {
+        let base = scratch("errors");
+        package(&base.join("app"), "[dependencies]\njson = \"3\"\n");
+        package(&base.join("registry/json/1.0.0"), "");
+        let resolver = Resolver::new(base.join("registry"), base.join("cache"));
+        let error = resolver.resolve(&base.join("app")).unwrap_err();
+        assert!(error.contains("no version matching ^3"), "{}", error);
+
+        package(&base.join("app"), "[dependencies]\njson = { path = \"../json\" }\nutils = { path = \"../utils\" }\n");
+        package(&base.join("json"), "");
+        package(&base.join("utils"), "[dependencies]\njson = \"1\"\n");
+        let error = resolver.resolve(&base.join("app")).unwrap_err();
+        assert!(error.starts_with("Conflicting sources for json"), "{}", error);
+    }
+}
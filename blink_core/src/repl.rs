@@ -2,12 +2,11 @@ use crate::error::{BlinkError, BlinkErrorType, ParseErrorType};
 use crate::module::{Module, SerializedModuleSource};
 use crate::parser::{parse, tokenize};
 use crate::runtime::{BlinkVM, BlinkRuntime, EvalResult, ExecutionContext, SymbolTable};
-use crate::value::{GcPtr, ParsedValue, ParsedValueWithPos, ValueRef};
+use crate::value::{GcPtr, NativeContext, ParsedValue, ParsedValueWithPos, ValueRef};
 
 use parking_lot::RwLock;
 use rustyline::history::FileHistory;
 use rustyline::{CompletionType, Config, EditMode, Editor};
-use std::collections::HashMap;
 use std::sync::Arc;
 use std::thread::Thread;
 use std::time::Duration;
@@ -28,7 +27,14 @@ pub fn get_global_output_sender() -> Option<&'static OutputSender> {
     GLOBAL_OUTPUT_SENDER.get()
 }
 
-pub async fn start_repl() {
+/// Command line options of the REPL
+#[derive(Clone, Debug, Default)]
+pub struct ReplOptions {
+    /// Reload loaded files that changed on disk before each evaluation
+    pub watch: bool,
+}
+
+pub async fn start_repl(options: ReplOptions) {
     // Create output manager - this is REPL's responsibility
     let output_manager = OutputManager::new();
     let output_sender = output_manager.get_sender();
@@ -46,6 +52,7 @@ pub async fn start_repl() {
     rl.load_history("history.txt").ok();
 
     let vm_arc = BlinkVM::new_arc();
+    vm_arc.use_project();
     vm_arc.symbol_table.read().print_all();
 
     let user_module_name = vm_arc.symbol_table.write().intern("user");
@@ -57,18 +64,25 @@ pub async fn start_repl() {
 
     let mut ctx = ExecutionContext::new(vm_arc.clone(), user_module_name);
 
-    let user_module = Module {
-        name: user_module_name,
-        exports: HashMap::new(),
-        source: SerializedModuleSource::Repl,
-        ready: true,
-        imports: HashMap::new(),
-    };
+    // Ctrl-C while an evaluation is running interrupts it instead of killing the process.
+    // At the prompt rustyline owns the terminal and reports Ctrl-C itself.
+    let cancel_token = ctx.cancel_token.clone();
+    tokio::spawn(async move {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            cancel_token.cancel();
+        }
+    });
+
+    let user_module = Module::new(user_module_name, SerializedModuleSource::Repl);
 
     vm_arc.module_registry.write().register_module(user_module);
 
     println!("🔮 Welcome to your blink REPL. Type 'exit' to quit.");
     println!("💡 Tip: End a line with \\ to continue on the next line");
+    if options.watch {
+        println!("👀 Watching loaded files; changes are reloaded before each evaluation");
+    }
+    print_warnings(&mut ctx);
 
     loop {
         // First, flush any pending output from goroutines
@@ -84,13 +98,23 @@ pub async fn start_repl() {
                                 break;
                             }
                         }
+                        if options.watch {
+                            reload_changed_files(&vm_arc, &mut ctx);
+                        }
                         let current_result = run_line(parsed, vm_arc.clone(), &mut ctx);
                         match current_result {
                             Ok(val) => println!("=> {}", val),
                             Err(err) => println!("=> {}", err),
                         }
                     },
+                    ParsedValue::List(ref items) if doc_target(&vm_arc, items).is_some() => {
+                        let symbol_id = doc_target(&vm_arc, items).unwrap();
+                        println!("{}", doc_text(&vm_arc, ctx.current_module, symbol_id));
+                    }
                     _ => {
+                        if options.watch {
+                            reload_changed_files(&vm_arc, &mut ctx);
+                        }
                         let current_result = run_line(parsed, vm_arc.clone(), &mut ctx);
                         match current_result {
                             Ok(val) => println!("=> {}", val),
@@ -98,6 +122,9 @@ pub async fn start_repl() {
                         }
                     }
                 }
+                print_warnings(&mut ctx);
+                // A Ctrl-C that came as the evaluation finished is not meant for the next one
+                ctx.cancel_token.reset();
 
                 // After processing the command, wait a bit for any goroutine output
                 // This handles cases like (complete future "value") triggering goroutines
@@ -174,11 +201,76 @@ fn read_multiline(
     }
 }
 
+// Re-evaluate the files changed since they were loaded, and the modules that
+// depend on them
+fn reload_changed_files(vm: &Arc<BlinkVM>, ctx: &mut ExecutionContext) {
+    let changed = ctx.changed_files();
+    if changed.is_empty() {
+        return;
+    }
+    match ctx.reload_files(&changed) {
+        Ok(modules) => {
+            let symbol_table = vm.symbol_table.read();
+            let names: Vec<String> = if modules.is_empty() {
+                changed.iter().filter_map(|file| symbol_table.get_symbol(*file)).collect()
+            } else {
+                modules.iter().filter_map(|module| symbol_table.get_symbol(*module)).collect()
+            };
+            println!("↻ Reloaded {}", names.join(", "));
+        }
+        Err(e) => println!("↻ Reload failed: {}", e),
+    }
+}
+
+// Warnings recorded while reloading and evaluating, such as definitions still
+// compiled with a macro that was redefined
+fn print_warnings(ctx: &mut ExecutionContext) {
+    for warning in ctx.take_warnings() {
+        println!("⚠ Warning: {}", warning);
+    }
+}
+
+// (doc name) is handled by the REPL itself, so the name is not evaluated
+fn doc_target(vm: &BlinkVM, items: &[ParsedValueWithPos]) -> Option<u32> {
+    match items {
+        [head, target] => match (&head.value, &target.value) {
+            (ParsedValue::Symbol(head), ParsedValue::Symbol(target))
+                if vm.get_symbol_name(*head).as_deref() == Some("doc") =>
+            {
+                Some(*target)
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn doc_text(vm: &BlinkVM, module_id: u32, symbol_id: u32) -> String {
+    vm.locate_global_symbol(module_id, symbol_id)
+        .and_then(|(module_id, name)| vm.definition_doc(module_id, name))
+        .map(|doc| doc.to_text())
+        .unwrap_or_else(|| format!("No definition named {}", vm.get_symbol_name(symbol_id).unwrap_or_default()))
+}
+
 fn run_line(
     parsed: ParsedValueWithPos,
     vm: Arc<BlinkVM>,
     ctx: &mut ExecutionContext,
 ) -> Result<ValueRef, BlinkError> {
     let ast = vm.alloc_parsed_value(parsed);
-    ctx.compile_and_execute(ast)
+    let value = ctx.compile_and_execute(ast)?;
+    realize_for_display(&vm, ctx, value);
+    Ok(value)
+}
+
+// A lazy seq prints only what has been realised, so realise the start of a lazy
+// result before it is shown. Errors raised while realising are left for the
+// code that uses the seq to see.
+const DISPLAY_REALIZE_LIMIT: usize = 100;
+
+fn realize_for_display(vm: &Arc<BlinkVM>, ctx: &mut ExecutionContext, value: ValueRef) {
+    let mut native_ctx = NativeContext::with_caller(vm, ctx);
+    if native_ctx.is_lazy_seq(value) {
+        let _ = native_ctx.seq_take(value, DISPLAY_REALIZE_LIMIT);
+    }
 }
\ No newline at end of file
@@ -33,7 +33,7 @@ pub struct BlinkRuntime<'a, S: GoroutineScheduler> {
 unsafe impl<S: GoroutineScheduler> Send for BlinkRuntime<'_, S> {}
 unsafe impl<S: GoroutineScheduler> Sync for BlinkRuntime<'_, S> {}
 
-impl BlinkRuntime<'_, SingleThreadedScheduler> {
+impl BlinkRuntime<'static, SingleThreadedScheduler> { // CHKFIX
     /// Initialize the global runtime singleton (single-threaded only)
     pub fn init_global(vm: Arc<BlinkVM>, current_module: u32) -> Result<Arc<Self>, String> {
         let runtime = Arc::new(Self::new(vm, current_module));
@@ -50,7 +50,7 @@ impl BlinkRuntime<'_, SingleThreadedScheduler> {
 }
 
 
-impl<S: GoroutineScheduler> BlinkRuntime<'_, S> {
+impl<S: GoroutineScheduler + 'static> BlinkRuntime<'static, S> { // CHKFIX
     pub fn new(vm: Arc<BlinkVM>, current_module: u32) -> Self {
         let execution_context = ExecutionContext::new(vm.clone(), current_module);
         let scheduler = Mutex::new(S::new(vm.clone()));
@@ -1,17 +1,17 @@
-use std::{collections::HashMap, future::Future, path::PathBuf, pin::Pin, sync::{Arc, OnceLock}};
+use std::{collections::HashMap, future::Future, path::{Path, PathBuf}, pin::Pin, sync::{Arc, OnceLock}};
 use std::collections::HashSet;
 use std::ops::DerefMut;
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicU64, Ordering};
 use dashmap::DashSet;
 use mmtk::{
     util::{options::PlanSelector, ObjectReference}, MMTKBuilder, MMTK
     
 };
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use tokio::runtime::Runtime;
-use crate::{env::Env, module::{Module, ModuleRegistry, SerializedModuleSource}, parser::ReaderContext, runtime::{
-    CompiledFunction, HandleRegistry, SuspendedContinuation, SymbolTable, ValueMetadataStore
-}, telemetry::TelemetryEvent, value::{ChannelEntry, ChannelHandle, FunctionHandle, SourceRange, ValueRef}, BlinkRuntime, FutureState, GLOBAL_RUNTIME};
+use crate::{env::Env, module::{Module, ModuleRegistry, SerializedModuleSource}, parser::ReaderContext, project::{find_project_root, read_project, LOCK_FILE}, runtime::{
+    CompiledFunction, HandleRegistry, ModulePath, Prng, Profiler, SuspendedContinuation, SymbolTable, ValueMetadataStore
+}, telemetry::TelemetryEvent, value::{ChannelEntry, ChannelHandle, FunctionHandle, Plugin, SourceRange, ValueRef}, BlinkRuntime, FutureState, GLOBAL_RUNTIME};
 use crate::value::FutureHandle;
 
 pub static GLOBAL_VM: OnceLock<Arc<BlinkVM>> = OnceLock::new();
@@ -110,11 +110,18 @@ pub struct BlinkVM {
     pub telemetry_sink: Option<Box<dyn Fn(TelemetryEvent) + Send + Sync + 'static>>,
     pub module_registry: RwLock<ModuleRegistry>,
     pub file_to_modules: RwLock<HashMap<PathBuf, Vec<String>>>,
+    pub module_path: RwLock<ModulePath>,
     pub reader_macros: RwLock<ReaderContext>,
     pub value_metadata: RwLock<ValueMetadataStore>,
+    pub profiler: Profiler,
+    pub rng: Mutex<Prng>,
     pub gc_roots: RwLock<Vec<ObjectReference>>,  // Track all roots
     pub handle_registry: RwLock<HandleRegistry>,
     pub core_module: Option<u32>,
+    pub gensym_counter: AtomicU64, // Suffix of the next gensym
+    // Warnings from outside any evaluation, such as setting up the project,
+    // handed to the first context that takes its warnings
+    pub(crate) warnings: Mutex<Vec<String>>,
 }
 
 impl std::fmt::Debug for BlinkVM {
@@ -156,12 +163,17 @@ impl BlinkVM {
             telemetry_sink: None,
             module_registry: RwLock::new(ModuleRegistry::new()),
             file_to_modules: RwLock::new(HashMap::new()),
+            module_path: RwLock::new(ModulePath::from_environment()),
 
             reader_macros: RwLock::new(ReaderContext::new()),
             value_metadata: RwLock::new(ValueMetadataStore::new()),
+            profiler: Profiler::new(),
+            rng: Mutex::new(Prng::from_time()),
             handle_registry: RwLock::new(HandleRegistry::new()),
             gc_roots: RwLock::new(Vec::new()),
             core_module: None,
+            gensym_counter: AtomicU64::new(1),
+            warnings: Mutex::new(Vec::new()),
         }
     }
 
@@ -179,13 +191,7 @@ impl BlinkVM {
 
         let core_module_id = vm.symbol_table.write().intern("core");
         vm.core_module = Some(core_module_id);
-        let core_module = Module {
-            name: core_module_id,
-            imports: HashMap::new(),
-            exports: HashMap::new(),
-            source: SerializedModuleSource::Repl,
-            ready: true,
-        };
+        let core_module = Module::new(core_module_id, SerializedModuleSource::Repl);
 
         vm.module_registry.write().register_module(core_module);
 
@@ -195,14 +201,96 @@ impl BlinkVM {
         vm.register_builtins(core_module_id);
         vm.register_builtin_macros(core_module_id);
         vm.register_complex_macros(core_module_id);
+
+        let string_module_id = vm.register_native_module("string");
+        vm.register_string_builtins(string_module_id);
+        let math_module_id = vm.register_native_module("math");
+        vm.register_math_builtins(math_module_id);
         
         vm
     }
 
+    // A module of natives that is ready without loading a file
+    fn register_native_module(&self, name: &str) -> u32 {
+        let module_id = self.symbol_table.write().intern(name);
+        self.module_registry
+            .write()
+            .register_module(Module::new(module_id, SerializedModuleSource::Repl));
+        module_id
+    }
+
+    /// Search the project of the working directory, from its blink.toml and
+    /// blink.lock, ahead of the module path the VM starts with, and load its
+    /// native plugins. Frontends call this at startup. The lockfile is only
+    /// read; a project that cannot be resolved leaves the module path as it
+    /// was, and it and a plugin that fails to load are reported as warnings.
+    pub fn use_project(&self) {
+        let Some(root) = std::env::current_dir().ok().and_then(|cwd| find_project_root(&cwd)) else {
+            return;
+        };
+        match read_project(&root) {
+            Ok((project, up_to_date)) => {
+                if !up_to_date {
+                    self.warn(format!(
+                        "{} is missing or out of date; run blink_repl --resolve to update it",
+                        root.join(LOCK_FILE).display()
+                    ));
+                }
+                *self.module_path.write() = ModulePath::for_project(&project);
+                for plugin in &project.plugins {
+                    if let Err(e) = self.load_plugin(&plugin.name, &plugin.path) {
+                        self.warn(e);
+                    }
+                }
+            }
+            Err(e) => self.warn(e),
+        }
+    }
+
+    /// Load a native plugin library and define the functions its `blink_register`
+    /// returns in a module called `name`, ready to import
+    pub fn load_plugin(&self, name: &str, path: &Path) -> Result<(), String> {
+        // SAFETY: a plugin is native code the project chose to run, built against
+        // this version of blink_core
+        let library = unsafe { libloading::Library::new(path) }
+            .map_err(|e| format!("plugin {}: could not load {}: {}", name, path.display(), e))?;
+        let register = unsafe { library.get::<extern "C" fn() -> Plugin>(b"blink_register") }
+            .map_err(|e| format!("plugin {}: {} has no blink_register: {}", name, path.display(), e))?;
+        let plugin = register();
+
+        let module_id = self.symbol_table.write().intern(name);
+        let path_id = self.symbol_table.write().intern(&path.to_string_lossy());
+        self.module_registry
+            .write()
+            .register_module(Module::new(module_id, SerializedModuleSource::NativeDylib(path_id)));
+        for (function, native_fn) in plugin.functions {
+            let symbol_id = self.symbol_table.write().intern(&function);
+            let value = ValueRef::native_function(native_fn);
+            if let Some(id) = value.get_or_create_id() {
+                self.value_metadata.write().set_debug_name(id, &function);
+            }
+            self.update_module(module_id, symbol_id, value);
+        }
+        // The functions point into the library, so it stays loaded with the VM
+        self.module_registry.write().store_native_library(module_id, library);
+        Ok(())
+    }
+
+    /// Record a warning for the frontends to show
+    pub fn warn(&self, message: impl Into<String>) {
+        self.warnings.lock().push(message.into());
+    }
+
+    /// Warnings recorded since the last call, oldest first
+    pub fn take_warnings(&self) -> Vec<String> {
+        std::mem::take(&mut *self.warnings.lock())
+    }
+
     pub fn new_arc() -> Arc<BlinkVM> {
         let vm = Self::new();
         let vm_arc = Arc::new(vm);
         GLOBAL_VM.set(vm_arc.clone()).expect("GLOBAL_VM already initialized");
+        vm_arc.load_core_prelude().expect("the core prelude failed to load");
         vm_arc.clone()
     }
     
@@ -347,7 +435,7 @@ impl BlinkVM {
         let mut roots = vec![];
         // TODO: Possible optimzation we can maintain roots in gc_roots and not have to scan the module registry
         for module in self.module_registry.read().modules.values() {
-            for (_, value) in module.exports.iter() {
+            for value in module.exports.values().chain(module.metadata.values()) {
                 if let ValueRef::Heap(gc_ptr) = value {
                     roots.push(gc_ptr.0);
                 }
@@ -423,11 +511,56 @@ impl BlinkVM {
         match value {
             Some(value) => Some(value),
             None => {
-                module_registry.resolve_symbol(core_module, symbol_id)
+                module_registry.resolve_symbol(core_module, symbol_id).or_else(|| {
+                    // module/name looks the name up in that module, or the one
+                    // the qualifier is an alias for. Other modules see only its
+                    // public names.
+                    let (qualifier, name) = self.symbol_table.read().get_qualified(symbol_id)?;
+                    match module_registry.resolve_alias(module_id, qualifier) {
+                        target if target == module_id => module_registry.resolve_symbol(target, name),
+                        target => module_registry.resolve_public(target, name),
+                    }
+                })
             }
         }
     }
 
+    /// Where a global resolves to from inside a module, as (module, name), by
+    /// the same rules as `resolve_global_symbol`
+    pub fn locate_global_symbol(&self, module_id: u32, symbol_id: u32) -> Option<(u32, u32)> {
+        let module_registry = self.module_registry.read();
+        let core_module = self.core_module?;
+        module_registry
+            .locate_symbol(module_id, symbol_id)
+            .or_else(|| module_registry.locate_symbol(core_module, symbol_id))
+            .or_else(|| {
+                let (qualifier, name) = self.symbol_table.read().get_qualified(symbol_id)?;
+                match module_registry.resolve_alias(module_id, qualifier) {
+                    target if target == module_id => module_registry.locate_symbol(target, name),
+                    target => module_registry.resolve_public(target, name).map(|_| (target, name)),
+                }
+            })
+    }
+
+    /// Why a global failed to resolve, for the error message
+    pub fn unresolved_symbol_message(&self, module_id: u32, symbol_id: u32) -> String {
+        let symbol_table = self.symbol_table.read();
+        let display = symbol_table.get_symbol(symbol_id).unwrap_or("Unknown symbol.".to_string());
+        if let Some((qualifier, name)) = symbol_table.get_qualified(symbol_id) {
+            let module_registry = self.module_registry.read();
+            let target = module_registry.resolve_alias(module_id, qualifier);
+            if module_registry.is_private(target, name) {
+                let module_name = symbol_table.get_symbol(target).unwrap_or_default();
+                return format!("Symbol {} is private to module {}", display, module_name);
+            }
+            if module_registry.get_module(target).is_none() {
+                let module_name = symbol_table.get_symbol(target).unwrap_or_default();
+                return format!("Global symbol {} not found: no module {}", display, module_name);
+            }
+        }
+        format!("Global symbol {} not found", display)
+    }
+
 
     fn build_simple_macro(&mut self,name: &str, module: u32) -> u32 {
         let symbol_id = self.symbol_table.write().intern(name);
@@ -446,6 +579,7 @@ impl BlinkVM {
         let unquote = self.build_simple_macro("unquote", module);
         let unquote_splicing = self.build_simple_macro("unquote-splicing", module);
         let deref = self.build_simple_macro("deref", module);
+        let var = self.build_simple_macro("var", module);
 
         let mut rm = self.reader_macros.write();
 
@@ -463,6 +597,9 @@ impl BlinkVM {
         rm.reader_macros
         .insert("@".into(), deref);
 
+        rm.reader_macros
+        .insert("#'".into(), var);
+
         
     }
 
@@ -134,6 +134,15 @@ impl ValueBoundary for ContextualBoundary {
                         HeapValue::Env(env) => {
                                                                     Err(format!("Env is not supported for boundary crossing"))
                                                                 }
+                        HeapValue::LazySeq(_) => {
+                                                                    Err(format!("Lazy seqs are not supported for boundary crossing"))
+                                                                }
+                        HeapValue::Transducer(_, _) => {
+                                                                    Err(format!("Transducers are not supported for boundary crossing"))
+                                                                }
+                        HeapValue::SortedMap(_, _) | HeapValue::SortedSet(_, _) => {
+                                                                    Err(format!("Sorted collections are not supported for boundary crossing"))
+                                                                }
                         HeapValue::Closure(closure_object) => {
                                                                     let handle = self.vm.handle_registry.write().register_function(value);
                                                                     Ok(IsolatedValue::Function(handle))
@@ -1,43 +1,323 @@
 use crate::{
-    env::Env, native_functions::{
-        native_add, native_concat, native_cons, native_div, native_eq, native_error, native_first, native_future, native_gc_stress, native_get, native_list, native_map_construct, native_mul, native_not, native_print, native_report_gc_stats, native_rest, native_run_scheduler, native_sub, native_type_of, native_vector
-    }, runtime::{BlinkVM, EvalResult, Macro}, value::{pack_number, Callable, GcPtr, NativeContext, NativeFn, ValueRef}
+    env::Env, math_functions::{
+        native_abs, native_acos, native_asin, native_atan, native_atan2, native_ceil, native_cos, native_dec, native_exp, native_floor, native_inc, native_log, native_log10, native_max, native_min, native_mod, native_pow, native_quot, native_rand, native_rand_int, native_rand_nth, native_rand_seed, native_rem, native_round, native_shuffle, native_sin, native_sqrt, native_tan
+    }, native_functions::{
+        native_add, native_concat, native_cons, native_count, native_div, native_empty_q, native_eq, native_error, native_gt, native_gte, native_lt, native_lte, native_first, native_future, native_gc_stress, native_get, native_hash, native_identical, native_list, native_map_construct, native_module_path, native_add_module_path, native_all_modules, native_module_exports, native_module_imports, native_resolve, native_meta, native_mul, native_not, native_print, native_profile_collapsed, native_profile_report, native_profile_start, native_profile_stop, native_report_gc_stats, native_rest, native_run_scheduler, native_sub, native_type_of, native_vector
+    }, sequence_functions::{
+        native_cycle, native_drop, native_filter, native_iterate, native_last, native_lazy_concat, native_line_seq, native_make_lazy_seq, native_map, native_nth, native_partition, native_range, native_reduce, native_repeat, native_reverse, native_seq, native_sort, native_take
+    }, collection_functions::{
+        native_assoc, native_assoc_in, native_assoc_mut, native_conj, native_conj_mut, native_contains, native_disj, native_dissoc, native_dissoc_mut, native_frequencies, native_get_in, native_group_by, native_keys, native_merge, native_merge_with, native_persistent, native_select_keys, native_transient, native_update, native_update_in, native_vals, native_zipmap
+    }, sorted_functions::{
+        native_compare, native_rsubseq, native_sorted_map, native_sorted_map_by, native_sorted_set, native_sorted_set_by, native_subseq
+    }, string_functions::{
+        native_ends_with, native_format, native_grapheme_subs, native_graphemes, native_includes, native_index_of, native_join, native_lower_case, native_replace, native_split, native_starts_with, native_str, native_subs, native_trim, native_triml, native_trimr, native_upper_case
+    }, type_functions::{
+        native_boolean_q, native_coll_q, native_false_q, native_fn_q, native_gensym, native_int_q, native_keyword, native_keyword_q, native_list_q, native_map_q, native_name, native_namespace, native_nil_q, native_number_q, native_seq_q, native_set, native_set_q, native_some_q, native_sorted_q, native_str_to_number, native_string_q, native_symbol, native_symbol_q, native_true_q, native_vec, native_vector_q
+    }, transducer_functions::{
+        native_comp, native_into, native_sequence, native_transduce
+    }, runtime::{BlinkVM, EvalResult, Macro, NoGcSection}, value::{pack_number, Callable, GcPtr, NativeContext, NativeFn, ValueRef}
 };
 
 impl BlinkVM {
+    /// Bind a native function to `name` in a module
+    pub fn register_native(&self, name: &str, f: fn(Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult, module: u32) -> ValueRef {
+        let sym = self.symbol_table.write().intern(name);
+        let boxed = Box::new(f);
+        let native_fn = NativeFn::Contextual(boxed);
+        let val = ValueRef::native_function(native_fn);
+        if let Some(id) = val.get_or_create_id() {
+            self.value_metadata.write().set_debug_name(id, name);
+        }
+
+        self.update_module(module, sym, val);
+
+        val
+    }
+
+    /// Give a native in a module the :doc and :arglists metadata a def records,
+    /// for `doc`, `meta` and editor hover. Each arglist is its parameter names,
+    /// as in "coll key & more".
+    pub fn document_native(&self, module: u32, name: &str, arglists: &[&str], doc: &str) {
+        let keyword = |key: &str| ValueRef::keyword(self.symbol_table.write().intern(&format!(":{}", key)));
+        let symbol_id = self.symbol_table.write().intern(name);
+        // Nothing roots the values until the map is stored in the module
+        let _no_gc = NoGcSection::enter();
+        let arglists = arglists
+            .iter()
+            .map(|params| {
+                let params = params
+                    .split_whitespace()
+                    .map(|param| ValueRef::symbol(self.symbol_table.write().intern(param)))
+                    .collect();
+                self.vector_value(params)
+            })
+            .collect();
+        let meta = self.map_value(vec![
+            (keyword("doc"), self.string_value(doc)),
+            (keyword("arglists"), self.list_value(arglists)),
+        ]);
+        self.module_registry.write().set_metadata(module, symbol_id, Some(meta), false);
+    }
+
     pub fn register_builtins(&mut self, module: u32) {
         
 
-        let mut reg =
-            |s: &str, f: fn(Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult, module: u32| -> ValueRef {
-                let sym = self.symbol_table.write().intern(s);
-                let boxed = Box::new(f);
-                let native_fn = NativeFn::Contextual(boxed);
-                let val = ValueRef::native_function(native_fn);
-                
-                self.update_module(module, sym, val);
-
-                val
-            };
+        let reg = |s: &str, f: fn(Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult, module: u32| -> ValueRef {
+            self.register_native(s, f, module)
+        };
+        let doc = |s: &str, arglists: &[&str], text: &str| self.document_native(module, s, arglists, text);
 
         reg("+", native_add, module);
+        doc("+", &["num1 num2 ..."], "Adds numbers together.");
         reg("-", native_sub, module);
+        doc("-", &["num1 num2 ..."], "Subtracts numbers from the first one.");
         reg("*", native_mul, module);
+        doc("*", &["num1 num2 ..."], "Multiplies numbers together.");
         reg("/", native_div, module);
+        doc("/", &["num1 num2 ..."], "Divides the first number by the rest.");
         reg("=", native_eq, module);
+        doc("=", &["val1 val2 ..."], "Checks if all values are equal. Equality is structural: a list equals a vector with equal items, a hash map equals a sorted map with the same entries, 0 equals -0 and NaN equals NaN.");
+        reg("identical?", native_identical, module);
+        doc("identical?", &["a b"], "True when both are the same heap object, or immediates with the same bits. NaN is identical to itself; 0 and -0 are not identical.");
+        reg("hash", native_hash, module);
+        doc("hash", &["x"], "Returns a whole number hash. Values that are `=` hash alike, so `(hash (list 1 2))` equals `(hash (vector 1 2))`.");
+        reg("<", native_lt, module);
+        reg(">", native_gt, module);
+        reg("<=", native_lte, module);
+        reg(">=", native_gte, module);
         reg("not", native_not, module);
+        doc("not", &["expr"], "Returns true if expr is falsy, false otherwise.");
+
+        // Numbers; the rest of the numeric library is in the math module
+        reg("inc", native_inc, module);
+        doc("inc", &["n"], "Add one.");
+        reg("dec", native_dec, module);
+        doc("dec", &["n"], "Subtract one.");
+        reg("mod", native_mod, module);
+        doc("mod", &["n d", "name", "name :export [a b]"], "With two numbers, floored modulo; the result has the sign of d, so `(mod -1 3)` is 2.\n\nWith a module name, makes that module current: later top-level forms are defined and run in it. With `:export` only the listed names are public; definitions are otherwise all public.");
+        reg("rem", native_rem, module);
+        doc("rem", &["n d"], "Truncated remainder; the result has the sign of n.");
+        reg("quot", native_quot, module);
+        doc("quot", &["n d"], "Divides and rounds toward zero.");
+        reg("abs", native_abs, module);
+        doc("abs", &["n"], "Absolute value.");
+        reg("min", native_min, module);
+        doc("min", &["n1 n2 ..."], "Smallest number.");
+        reg("max", native_max, module);
+        doc("max", &["n1 n2 ..."], "Largest number.");
+        reg("floor", native_floor, module);
+        doc("floor", &["n"], "Round down.");
+        reg("ceil", native_ceil, module);
+        doc("ceil", &["n"], "Round up.");
+        reg("round", native_round, module);
+        doc("round", &["n"], "Halves round away from zero.");
+        reg("rand", native_rand, module);
+        doc("rand", &["", "n"], "Returns a number in [0, 1), or in [0, n).");
+        reg("rand-int", native_rand_int, module);
+        doc("rand-int", &["n"], "Returns a whole number in [0, n).");
+        reg("rand-nth", native_rand_nth, module);
+        doc("rand-nth", &["coll"], "Returns a random item of a non-empty collection.");
+        reg("shuffle", native_shuffle, module);
+        doc("shuffle", &["coll"], "Returns a vector of the items in random order.");
 
         reg("list", native_list, module);
+        doc("list", &["item1 item2 ..."], "Creates a new list containing the given items.");
         reg("vector", native_vector, module);
+        doc("vector", &["item1 item2 ..."], "Creates a new vector containing the given items.");
         reg("hash-map", native_map_construct, module);
-        //reg("map", native_map); // TODO need to do a map fn
+        doc("hash-map", &["key1 val1 key2 val2 ..."], "Creates a new hash map with the given keys and values.");
         reg("print", native_print, module);
+        doc("print", &["val1 val2 ..."], "Prints values to standard output.");
         reg("type-of", native_type_of, module);
+        doc("type-of", &["value"], "Returns the type of the given value as a string.");
         reg("cons", native_cons, module);
+        doc("cons", &["item list"], "Prepends an item to the beginning of a list.");
         reg("concat", native_concat, module);
         reg("first", native_first, module);
+        doc("first", &["list"], "Returns the first item of a list.");
         reg("rest", native_rest, module);
+        doc("rest", &["list"], "Returns all items except the first one from a list.");
         reg("get", native_get, module);
+        doc("get", &["collection key [default]"], "Gets the value at the key/index in the collection, or returns default if not found.");
+
+        // Type predicates
+        reg("nil?", native_nil_q, module);
+        doc("nil?", &["value"], "Returns true if value is nil.");
+        reg("some?", native_some_q, module);
+        doc("some?", &["value"], "Returns true if value is anything but nil.");
+        reg("true?", native_true_q, module);
+        doc("true?", &["value"], "Returns true if value is true.");
+        reg("false?", native_false_q, module);
+        doc("false?", &["value"], "Returns true if value is false.");
+        reg("boolean?", native_boolean_q, module);
+        doc("boolean?", &["value"], "Returns true if value is true or false.");
+        reg("number?", native_number_q, module);
+        doc("number?", &["value"], "Returns true if value is a number.");
+        reg("int?", native_int_q, module);
+        doc("int?", &["value"], "Returns true if value is a finite whole number.");
+        reg("string?", native_string_q, module);
+        doc("string?", &["value"], "Returns true if value is a string.");
+        reg("keyword?", native_keyword_q, module);
+        doc("keyword?", &["value"], "Returns true if value is a keyword.");
+        reg("symbol?", native_symbol_q, module);
+        doc("symbol?", &["value"], "Returns true if value is a symbol.");
+        reg("list?", native_list_q, module);
+        doc("list?", &["value"], "Returns true if value is a list.");
+        reg("vector?", native_vector_q, module);
+        doc("vector?", &["value"], "Returns true if value is a vector.");
+        reg("map?", native_map_q, module);
+        doc("map?", &["value"], "Returns true if value is a hash map or sorted map.");
+        reg("set?", native_set_q, module);
+        doc("set?", &["value"], "Returns true if value is a hash set or sorted set.");
+        reg("sorted?", native_sorted_q, module);
+        doc("sorted?", &["value"], "Returns true if value is a sorted map or sorted set.");
+        reg("seq?", native_seq_q, module);
+        doc("seq?", &["value"], "Returns true if value is a list or lazy sequence.");
+        reg("coll?", native_coll_q, module);
+        doc("coll?", &["value"], "Returns true if value is any collection.");
+        reg("fn?", native_fn_q, module);
+        doc("fn?", &["value"], "Returns true if value is a function or closure.");
+
+        // Coercions
+        reg("keyword", native_keyword, module);
+        doc("keyword", &["name", "module name"], "Builds a keyword from strings, keywords or symbols.");
+        reg("symbol", native_symbol, module);
+        doc("symbol", &["name", "module name"], "Builds a symbol from strings, keywords or symbols.");
+        reg("gensym", native_gensym, module);
+        doc("gensym", &["", "prefix"], "Returns a symbol no other code uses, such as G__12 or tmp__13. In a quasiquoted template, `tmp#` does the same.");
+        reg("name", native_name, module);
+        doc("name", &["x"], "Returns the name without its module as a string. Strings are returned unchanged.");
+        reg("namespace", native_namespace, module);
+        doc("namespace", &["x"], "Returns the module part of a qualified keyword or symbol, or nil.");
+        reg("str->number", native_str_to_number, module);
+        doc("str->number", &["s"], "Returns the number in s, or an error if s is not a number.");
+        reg("vec", native_vec, module);
+        doc("vec", &["coll"], "Returns a vector of the items of any collection.");
+        reg("set", native_set, module);
+        doc("set", &["coll"], "Returns a hash set of the distinct items of any collection.");
+
+        // Sequence library
+        reg("map", native_map, module);
+        doc("map", &["fn coll & colls"], "Applies the function to each item in the collection. With several collections the function takes one item from each, stopping at the shortest.");
+        reg("filter", native_filter, module);
+        doc("filter", &["pred coll"], "Returns a list of the items for which the predicate is truthy.");
+        reg("reduce", native_reduce, module);
+        doc("reduce", &["fn coll", "fn init coll"], "Reduces the collection to a single value using the function. Without `init` the first item is the starting value.");
+        reg("nth", native_nth, module);
+        doc("nth", &["coll index [not-found]"], "Returns the item at the index. Out of range is an error unless `not-found` is given.");
+        reg("last", native_last, module);
+        doc("last", &["coll"], "Returns the last item of the collection, or nil if it is empty.");
+        reg("reverse", native_reverse, module);
+        doc("reverse", &["coll"], "Returns a list of the items in reverse order.");
+        reg("take", native_take, module);
+        doc("take", &["n coll"], "Returns a list of the first n items.");
+        reg("drop", native_drop, module);
+        doc("drop", &["n coll"], "Returns a list of the items after the first n.");
+        reg("sort", native_sort, module);
+        doc("sort", &["coll", "comparator coll"], "Returns a sorted list. The comparator returns a number like `compare`, or true when its first argument sorts first.");
+        reg("partition", native_partition, module);
+        doc("partition", &["n coll", "pred coll"], "Splits into lists of n items, dropping an incomplete tail, or into `[matching non-matching]` by a predicate.");
+        reg("count", native_count, module);
+        reg("empty?", native_empty_q, module);
+
+        // Lazy sequences
+        reg("seq", native_seq, module);
+        doc("seq", &["coll"], "Returns nil for an empty collection, otherwise a sequence of its items.");
+        reg("make-lazy-seq", native_make_lazy_seq, module);
+        reg("lazy-concat", native_lazy_concat, module);
+        reg("range", native_range, module);
+        doc("range", &["", "end", "start end", "start end step"], "Returns a lazy seq from start (default 0) up to but not including end, by step (default 1). Without an end the range is infinite.");
+        reg("iterate", native_iterate, module);
+        doc("iterate", &["f x"], "Returns the infinite lazy seq x, (f x), (f (f x)), ...");
+        reg("repeat", native_repeat, module);
+        doc("repeat", &["x", "n x"], "Returns a lazy seq of x, n times or forever.");
+        reg("cycle", native_cycle, module);
+        doc("cycle", &["coll"], "Returns an infinite lazy seq repeating the items of coll.");
+        reg("line-seq", native_line_seq, module);
+        doc("line-seq", &["path"], "Returns a lazy seq of the lines of a file. The file is read a chunk at a time as lines are used.");
+
+        // Transducers
+        reg("comp", native_comp, module);
+        doc("comp", &["xform ..."], "Returns a transducer running each stage in order, e.g. `(comp (map f) (filter pred) (take 10))`.");
+        reg("transduce", native_transduce, module);
+        doc("transduce", &["xform f coll", "xform f init coll"], "Reduces the items coming out of the pipeline with f, in a single pass. Without init, `(f)` supplies it.");
+        reg("into", native_into, module);
+        doc("into", &["to from", "to xform from"], "Adds the items of from, optionally run through a transducer, to a list, vector, map or set.");
+        reg("sequence", native_sequence, module);
+        doc("sequence", &["coll", "xform coll"], "Returns the items coming out of the pipeline. The result is lazy when coll is a lazy seq.");
+
+        // Persistent collection updates
+        reg("conj", native_conj, module);
+        doc("conj", &["coll item ..."], "Returns a new collection with the items added: at the front of a list, the end of a vector, as members of a set or as `[key value]` entries of a map.");
+        reg("assoc", native_assoc, module);
+        doc("assoc", &["coll key val ..."], "Returns a new map with the keys set, or a new vector with the indices replaced. The original is unchanged and shares structure with the result.");
+        reg("dissoc", native_dissoc, module);
+        doc("dissoc", &["map key ..."], "Returns a new map without the given keys.");
+        reg("disj", native_disj, module);
+        doc("disj", &["set item ..."], "Returns a copy of a set or sorted set without the items.");
+        reg("update", native_update, module);
+        doc("update", &["coll key f arg ..."], "Returns a new map or vector with the value at key replaced by `(f old-value arg ...)`.");
+        reg("transient", native_transient, module);
+        doc("transient", &["coll"], "Returns a mutable copy of a vector, map or set for batched updates with `conj!`, `assoc!` and `dissoc!`.");
+        reg("persistent!", native_persistent, module);
+        doc("persistent!", &["transient"], "Returns the transient as a persistent collection. The transient cannot be used afterwards.");
+        reg("conj!", native_conj_mut, module);
+        doc("conj!", &["transient item ..."], "Adds the items in place and returns the transient.");
+        reg("assoc!", native_assoc_mut, module);
+        doc("assoc!", &["transient key val ..."], "Sets the keys or indices in place and returns the transient.");
+        reg("dissoc!", native_dissoc_mut, module);
+        doc("dissoc!", &["transient key ..."], "Removes the keys in place and returns the transient.");
+
+        // Maps
+        reg("keys", native_keys, module);
+        doc("keys", &["map"], "Returns a list of the map's keys, or nil for an empty map.");
+        reg("vals", native_vals, module);
+        doc("vals", &["map"], "Returns a list of the map's values, or nil for an empty map.");
+        reg("contains?", native_contains, module);
+        doc("contains?", &["coll key"], "True if key is a key of a map, a member of a set or an index of a vector.");
+        reg("get-in", native_get_in, module);
+        doc("get-in", &["coll [k1 k2 ...]", "coll keys not-found"], "Looks each key up in the result of the previous one.");
+        reg("assoc-in", native_assoc_in, module);
+        doc("assoc-in", &["coll [k1 k2 ...] value"], "Returns a copy with the value set at the key path. Missing levels are created as maps.");
+        reg("update-in", native_update_in, module);
+        doc("update-in", &["coll [k1 k2 ...] f arg ..."], "Returns a copy with the value at the key path replaced by `(f old-value arg ...)`.");
+        reg("merge", native_merge, module);
+        doc("merge", &["map ..."], "Returns a map with the entries of every map. Later maps win.");
+        reg("merge-with", native_merge_with, module);
+        doc("merge-with", &["f map ..."], "Like merge, but the values of a key found in several maps are combined with `(f old new)`.");
+        reg("select-keys", native_select_keys, module);
+        doc("select-keys", &["map keys"], "Returns a map of only the given keys that are present.");
+        reg("zipmap", native_zipmap, module);
+        doc("zipmap", &["keys vals"], "Pairs keys with values until either runs out.");
+        reg("group-by", native_group_by, module);
+        doc("group-by", &["f coll"], "Returns a map from each `(f item)` to a vector of the items giving it.");
+        reg("frequencies", native_frequencies, module);
+        doc("frequencies", &["coll"], "Returns a map from each distinct item to the number of times it occurs.");
+
+        // Sorted collections
+        reg("compare", native_compare, module);
+        doc("compare", &["a b"], "Returns -1, 0 or 1. Orders nil, booleans, numbers, strings, keywords, symbols, sequences, sets and maps, in that order across kinds.");
+        reg("sorted-map", native_sorted_map, module);
+        doc("sorted-map", &["key1 val1 key2 val2 ..."], "Creates a map whose entries are kept in key order, using `compare`.");
+        reg("sorted-map-by", native_sorted_map_by, module);
+        doc("sorted-map-by", &["comparator key1 val1 ..."], "Like sorted-map, ordering keys with comparator. It returns a number or, like `<`, a boolean.");
+        reg("sorted-set", native_sorted_set, module);
+        doc("sorted-set", &["item1 item2 ..."], "Creates a set whose members are kept in order, using `compare`.");
+        reg("sorted-set-by", native_sorted_set_by, module);
+        doc("sorted-set-by", &["comparator item1 item2 ..."], "Like sorted-set, ordering members with comparator.");
+        reg("subseq", native_subseq, module);
+        doc("subseq", &["sc test key", "sc start-test start-key end-test end-key"], "Returns the entries whose keys pass the tests, e.g. `(subseq sc >= 2 < 5)`, in ascending order.");
+        reg("rsubseq", native_rsubseq, module);
+        doc("rsubseq", &["sc test key", "sc start-test start-key end-test end-key"], "Like subseq, in descending order.");
+
+        // Strings; the rest of the string library is in the string module
+        reg("str", native_str, module);
+        doc("str", &["val1 val2 ..."], "Joins the printed form of each value. Strings are added without quotes and nil adds nothing.");
+        reg("subs", native_subs, module);
+        doc("subs", &["s start", "s start end"], "Returns the characters from start up to end. Indices count characters, not bytes.");
+        reg("format", native_format, module);
+        doc("format", &["fmt arg ..."], "Formats the args into fmt, as in `(format \"%s has %d items, %.2f each\" name n price)`. Supports %s, %d and %f with an optional `-`, width and precision, and %% for a literal percent sign.");
+
         reg("report-gc-stats", native_report_gc_stats, module);
         reg("gc-stress", native_gc_stress, module);
         // TODO: Error module
@@ -49,9 +329,117 @@ impl BlinkVM {
         // Goroutine/scheduler module
         reg("run-scheduler", native_run_scheduler, module);
 
+        // Profiler
+        reg("profile-start", native_profile_start, module);
+        reg("profile-stop", native_profile_stop, module);
+        reg("profile-report", native_profile_report, module);
+        reg("profile-collapsed", native_profile_collapsed, module);
+
+        // Module loading
+        reg("module-path", native_module_path, module);
+        doc("module-path", &[""], "Returns the directories searched for module files, in order: the `[modules] paths` of `blink.toml`, then `BLINK_PATH`, then the working directory.");
+        reg("add-module-path!", native_add_module_path, module);
+        doc("add-module-path!", &["dir"], "Appends a directory to the module path and returns the new path.");
+
+        // Module introspection
+        reg("all-modules", native_all_modules, module);
+        doc("all-modules", &[""], "Returns the names of every loaded module, sorted.");
+        reg("module-exports", native_module_exports, module);
+        doc("module-exports", &["module"], "Returns a map from each public name of the module to its value.");
+        reg("module-imports", native_module_imports, module);
+        doc("module-imports", &["module"], "Returns a map from each module the module imported to the names it referred from it.");
+        reg("resolve", native_resolve, module);
+        doc("resolve", &["sym"], "Returns the qualified symbol `sym` refers to in the current module, or nil.");
+        reg("meta", native_meta, module);
+        doc("meta", &["name"], "Returns a map with the `:name`, `:module`, `:type` and `:private` flag of a definition, its `:file` when it was loaded from one, and its metadata: `:doc`, `:arglists` and any `^{...}` entries.");
+
         
     }
 
+    /// The `string` module, used as (string/split s ",")
+    pub fn register_string_builtins(&mut self, module: u32) {
+        let reg = |s: &str, f: fn(Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult| -> ValueRef {
+            self.register_native(s, f, module)
+        };
+        let doc = |s: &str, arglists: &[&str], text: &str| self.document_native(module, s, arglists, text);
+
+        reg("split", native_split);
+        doc("split", &["s separator", "s separator limit"], "Returns a vector of the parts. An empty separator splits into characters.");
+        reg("join", native_join);
+        doc("join", &["coll", "separator coll"], "Joins the items as `str` shows them, putting separator between them.");
+        reg("trim", native_trim);
+        doc("trim", &["s"], "Removes whitespace from both ends. `string/triml` and `string/trimr` trim one end.");
+        reg("triml", native_triml);
+        reg("trimr", native_trimr);
+        reg("replace", native_replace);
+        doc("replace", &["s match replacement"], "Replaces every occurrence of match.");
+        reg("starts-with?", native_starts_with);
+        doc("starts-with?", &["s prefix"], "`string/ends-with?` and `string/includes?` test a suffix and a substring.");
+        reg("ends-with?", native_ends_with);
+        reg("includes?", native_includes);
+        reg("index-of", native_index_of);
+        doc("index-of", &["s sub", "s sub from"], "Returns the character index of the first occurrence at or after from, or nil.");
+        reg("upper-case", native_upper_case);
+        doc("upper-case", &["s"], "Uppercase a string.");
+        reg("lower-case", native_lower_case);
+        doc("lower-case", &["s"], "Lowercase a string.");
+        reg("subs", native_subs);
+        reg("grapheme-subs", native_grapheme_subs);
+        reg("graphemes", native_graphemes);
+        doc("graphemes", &["s"], "Returns a list of the user-perceived characters of s. `string/grapheme-subs` takes a substring by grapheme index.");
+        reg("str", native_str);
+        reg("format", native_format);
+    }
+
+    /// The `math` module, used as (math/sqrt 2)
+    pub fn register_math_builtins(&mut self, module: u32) {
+        let reg = |s: &str, f: fn(Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult| -> ValueRef {
+            self.register_native(s, f, module)
+        };
+        let doc = |s: &str, arglists: &[&str], text: &str| self.document_native(module, s, arglists, text);
+
+        reg("inc", native_inc);
+        reg("dec", native_dec);
+        reg("mod", native_mod);
+        reg("rem", native_rem);
+        reg("quot", native_quot);
+        reg("abs", native_abs);
+        reg("min", native_min);
+        reg("max", native_max);
+        reg("floor", native_floor);
+        reg("ceil", native_ceil);
+        reg("round", native_round);
+        reg("sqrt", native_sqrt);
+        doc("sqrt", &["n"], "Square root.");
+        reg("pow", native_pow);
+        doc("pow", &["base exponent"], "Power.");
+        reg("exp", native_exp);
+        reg("log", native_log);
+        doc("log", &["n"], "`math/log10` is the base 10 logarithm and `math/exp` the inverse.");
+        reg("log10", native_log10);
+        reg("sin", native_sin);
+        doc("sin", &["radians"], "`math/cos`, `math/tan`, `math/asin`, `math/acos`, `math/atan` and `math/atan2` are also available, with `math/pi` and `math/e`.");
+        reg("cos", native_cos);
+        reg("tan", native_tan);
+        reg("asin", native_asin);
+        reg("acos", native_acos);
+        reg("atan", native_atan);
+        reg("atan2", native_atan2);
+
+        // Random numbers
+        reg("rand", native_rand);
+        reg("rand-int", native_rand_int);
+        reg("rand-nth", native_rand_nth);
+        reg("shuffle", native_shuffle);
+        reg("rand-seed!", native_rand_seed);
+        doc("rand-seed!", &["n"], "Restarts the random number generator from seed n, so later random results repeat.");
+
+        let pi = self.symbol_table.write().intern("pi");
+        self.update_module(module, pi, ValueRef::number(std::f64::consts::PI));
+        let e = self.symbol_table.write().intern("e");
+        self.update_module(module, e, ValueRef::number(std::f64::consts::E));
+    }
+
     pub fn register_builtin_macros(&mut self, module: u32) {
         
     //     let mut symbol_table = self.symbol_table.write();
@@ -234,8 +622,6 @@ impl BlinkVM {
         // let rest_sym_val = ValueRef::symbol(symbol_table.intern("rest"));
         // let empty_sym_val = ValueRef::symbol(symbol_table.intern("empty?"));
         // let nil_sym_val = ValueRef::symbol(symbol_table.intern("nil"));
-        let def_sym_val = ValueRef::symbol(symbol_table.intern("def"));
-        let fn_sym_val = ValueRef::symbol(symbol_table.intern("fn"));
         // let let_sym_val = ValueRef::symbol(symbol_table.intern("let"));
         // let list_check_sym_val = ValueRef::symbol(symbol_table.intern("list?"));
 
@@ -299,70 +685,17 @@ impl BlinkVM {
         // let value = ValueRef::Heap(GcPtr::new(cond_macro_val));
         // self.update_module(module, cond_sym, value);
 
-        // defn macro - complete implementation with quasiquote/unquote
-        let defn_sym_val = ValueRef::symbol(symbol_table.intern("defn"));
-        let name_sym_val = ValueRef::symbol(symbol_table.intern("name"));
-        let args_sym_val = ValueRef::symbol(symbol_table.intern("args"));
-        let body_sym_val = ValueRef::symbol(symbol_table.intern("body"));
-
+        // defn macro - (defn name [params] body...) is (def name (fn name [params] body...)).
+        // Its expander is a native, as core is set up before anything can be compiled
         let defn_sym = symbol_table.intern("defn");
         let name_sym = symbol_table.intern("name");
         let args_sym = symbol_table.intern("args");
         let body_sym = symbol_table.intern("body");
 
-        // Get the required symbols for quasiquote/unquote
-        let quasiquote_sym_val = ValueRef::symbol(symbol_table.intern("quasiquote"));
-        let unquote_sym_val = ValueRef::symbol(symbol_table.intern("unquote"));
-        let unquote_splicing_sym_val = ValueRef::symbol(symbol_table.intern("unquote-splicing"));
-
-        // Build: `(def ~name (fn ~name ~args ~@body))
-        //
-        // This creates a quasiquote expression that will be evaluated to produce:
-        // (def actual-name (fn actual-name actual-args body-form1 body-form2 ...))
-
-        // Build ~name (unquote name)
-        let unquote_name = ValueRef::Heap(GcPtr::new(
-            self.alloc_list_from_items(vec![unquote_sym_val.clone(), name_sym_val.clone()]),
-        ));
-
-        // Build ~args (unquote args)
-        let unquote_args = ValueRef::Heap(GcPtr::new(
-            self.alloc_list_from_items(vec![unquote_sym_val.clone(), args_sym_val.clone()]),
-        ));
-
-        // Build ~@body (unquote-splicing body)
-        let unquote_splicing_body = ValueRef::Heap(GcPtr::new(
-            self.alloc_list_from_items(vec![unquote_splicing_sym_val, body_sym_val.clone()]),
-        ));
-
-        // Build the fn expression: (fn ~name ~args ~@body)
-        let fn_expr = ValueRef::Heap(GcPtr::new(self.alloc_list_from_items(
-            vec![
-                fn_sym_val.clone(),
-                unquote_name.clone(),
-                unquote_args,
-                unquote_splicing_body,
-            ]
-        )));
-
-        // Build the complete def expression: (def ~name (fn ~name ~args ~@body))
-        let def_expr = ValueRef::Heap(GcPtr::new(
-            self.alloc_list_from_items(vec![def_sym_val.clone(), unquote_name, fn_expr]),
-        ));
-
-        // Wrap the entire thing in quasiquote: `(def ~name (fn ~name ~args ~@body))
-        let defn_body = ValueRef::Heap(GcPtr::new(
-            self.alloc_list_from_items(vec![quasiquote_sym_val, def_expr]),
-        ));
-
-        
-
-        // Create the macro
         let defn_macro = Macro {
             params: vec![name_sym, args_sym, body_sym],
+            expander: ValueRef::native_function(NativeFn::Contextual(Box::new(expand_defn))),
             is_variadic: true, // body can have multiple forms
-            body: vec![defn_body],
-            
             module: module,
         };
 
@@ -571,3 +904,15 @@ impl BlinkVM {
         // self.update_module(module, thread_last_sym, value);
     }
 }
+
+// The expander of defn: &form and &env, then the name, params and body forms.
+// A docstring in place of the params stays in the fn, where def finds it
+fn expand_defn(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    let [_form, _env, name, params, body] = args[..] else {
+        return EvalResult::Value(ctx.arity_error(5, args.len(), "defn"));
+    };
+    let mut function = vec![ctx.symbol("fn"), name, params];
+    function.extend(ctx.get_list(body).unwrap_or_default());
+    let function = ctx.list(function);
+    EvalResult::Value(ctx.list(vec![ctx.symbol("def"), name, function]))
+}
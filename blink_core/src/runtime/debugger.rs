@@ -0,0 +1,482 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::error::{BlinkError, StackFrame};
+use crate::module::SerializedModuleSource;
+use crate::runtime::{CallFrame, CompiledFunction, ExecutionContext, FunctionRef};
+use crate::value::{GcPtr, ValueRef};
+
+pub type BreakpointId = u32;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Breakpoint {
+    // `file: None` matches the line in any module
+    Line { file: Option<String>, line: usize },
+    Function { name: String },
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StepMode {
+    #[default]
+    Run,
+    StepIn,
+    StepOver,
+    StepOut,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PauseReason {
+    Breakpoint(BreakpointId),
+    Step,
+    Entry,
+    Interrupted,
+}
+
+#[derive(Debug)]
+pub enum DebugEvent {
+    Paused {
+        reason: PauseReason,
+        frames: Vec<StackFrame>, // Innermost first
+    },
+    Finished(ValueRef),
+    Failed(BlinkError),
+}
+
+// What the debugger remembers about each live frame
+#[derive(Clone, Debug)]
+struct FrameState {
+    function: Option<CompiledFunction>,
+    last_line: Option<usize>,
+}
+
+/// Breakpoints and stepping state for one debug session.
+/// Drives an `ExecutionContext` one instruction at a time through `debug_resume`.
+#[derive(Debug, Default)]
+pub struct Debugger {
+    breakpoints: HashMap<BreakpointId, Breakpoint>,
+    next_id: BreakpointId,
+    mode: StepMode,
+    step_depth: usize,
+    stop_on_entry: bool,
+    paused: bool,
+    frames: Vec<FrameState>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> BreakpointId {
+        self.next_id += 1;
+        self.breakpoints.insert(self.next_id, breakpoint);
+        self.next_id
+    }
+
+    pub fn remove_breakpoint(&mut self, id: BreakpointId) -> Option<Breakpoint> {
+        self.breakpoints.remove(&id)
+    }
+
+    /// Remove every breakpoint matching the predicate
+    pub fn clear_breakpoints(&mut self, mut predicate: impl FnMut(&Breakpoint) -> bool) {
+        self.breakpoints.retain(|_, breakpoint| !predicate(breakpoint));
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = (&BreakpointId, &Breakpoint)> {
+        self.breakpoints.iter()
+    }
+
+    /// Pause before the first instruction of the next evaluation
+    pub fn set_stop_on_entry(&mut self, stop: bool) {
+        self.stop_on_entry = stop;
+    }
+
+    /// Choose how the next `debug_resume` runs, relative to the current call depth
+    pub fn set_step_mode(&mut self, mode: StepMode, call_depth: usize) {
+        self.mode = mode;
+        self.step_depth = call_depth;
+    }
+
+    /// Bring the frame mirror in line with the call stack and decide whether to pause here
+    fn observe(&mut self, ctx: &ExecutionContext<'_>) -> Option<PauseReason> {
+        let depth = ctx.call_stack.len();
+        self.frames.truncate(depth);
+        while self.frames.len() < depth {
+            let frame = &ctx.call_stack[self.frames.len()];
+            self.frames.push(FrameState {
+                function: frame_function(frame),
+                last_line: None,
+            });
+        }
+
+        let frame = ctx.call_stack.last()?;
+        let state = self.frames.last_mut()?;
+        let function = state.function.as_ref()?;
+
+        if frame.pc == 0 {
+            for (id, breakpoint) in &self.breakpoints {
+                if let Breakpoint::Function { name } = breakpoint {
+                    let matches = function.name.map_or(false, |symbol| {
+                        ctx.vm.symbol_table.read().get_symbol(symbol).as_deref() == Some(name)
+                    });
+                    if matches {
+                        state.last_line = function.position_at(0).map(|pos| pos.start.line);
+                        return Some(PauseReason::Breakpoint(*id));
+                    }
+                }
+            }
+        }
+
+        // Only the first instruction on a new line of a frame can pause
+        let line = function.position_at(frame.pc)?.start.line;
+        if state.last_line == Some(line) {
+            return None;
+        }
+        state.last_line = Some(line);
+
+        if self.stop_on_entry {
+            self.stop_on_entry = false;
+            return Some(PauseReason::Entry);
+        }
+
+        for (id, breakpoint) in &self.breakpoints {
+            if let Breakpoint::Line { file, line: bp_line } = breakpoint {
+                if *bp_line == line && file_matches(ctx, frame, file.as_deref()) {
+                    return Some(PauseReason::Breakpoint(*id));
+                }
+            }
+        }
+
+        let step_done = match self.mode {
+            StepMode::StepIn => true,
+            StepMode::StepOver => depth <= self.step_depth,
+            StepMode::StepOut => depth < self.step_depth,
+            StepMode::Run => false,
+        };
+        if step_done {
+            Some(PauseReason::Step)
+        } else {
+            None
+        }
+    }
+}
+
+impl ExecutionContext<'_> {
+    /// Compile a top-level expression and leave it ready for `debug_resume`
+    pub fn debug_start(&mut self, expr: ValueRef) -> Result<(), BlinkError> {
+        self.prepare_toplevel(expr)
+    }
+
+    /// Run until a breakpoint or step completes, the evaluation finishes, or it fails
+    pub fn debug_resume(&mut self, debugger: &mut Debugger) -> DebugEvent {
+        // The location we are paused at has already been observed
+        let mut skip_check = std::mem::take(&mut debugger.paused);
+
+        loop {
+            if self.call_stack.is_empty() {
+                debugger.frames.clear();
+                debugger.mode = StepMode::Run;
+                return DebugEvent::Finished(self.last_return_value);
+            }
+
+            if self.cancel_token.is_cancelled() {
+                self.cancel_token.reset();
+                return self.pause(debugger, PauseReason::Interrupted);
+            }
+
+            if !skip_check {
+                if let Some(reason) = debugger.observe(self) {
+                    return self.pause(debugger, reason);
+                }
+            }
+            skip_check = false;
+
+            let step = match self.check_limits() {
+                Ok(()) => self.execute_single_step(),
+                Err(limit_error) => Err(self.abort_with(limit_error)),
+            };
+            if let Err(message) = step {
+                debugger.frames.clear();
+                debugger.mode = StepMode::Run;
+                return DebugEvent::Failed(self.fail_execution(message));
+            }
+        }
+    }
+
+    fn pause(&mut self, debugger: &mut Debugger, reason: PauseReason) -> DebugEvent {
+        debugger.paused = true;
+        debugger.mode = StepMode::Run;
+        DebugEvent::Paused {
+            reason,
+            frames: self.capture_stack_trace(),
+        }
+    }
+
+    /// Abandon a paused evaluation
+    pub fn debug_abort(&mut self, debugger: &mut Debugger) {
+        self.fail_execution("debug session aborted".to_string());
+        debugger.frames.clear();
+        debugger.mode = StepMode::Run;
+        debugger.paused = false;
+    }
+
+    /// Locals visible in a frame (0 is innermost), in binding order
+    pub fn frame_locals(&self, frame_index: usize) -> Vec<(String, ValueRef)> {
+        let Some(stack_index) = self.call_stack.len().checked_sub(frame_index + 1) else {
+            return Vec::new();
+        };
+        let frame = &self.call_stack[stack_index];
+        let Some(function) = frame_function(frame) else {
+            return Vec::new();
+        };
+
+        // Callers have already advanced past their Call instruction
+        let pc = if frame_index == 0 { frame.pc } else { frame.pc.saturating_sub(1) };
+
+        let symbol_table = self.vm.symbol_table.read();
+        let mut locals: Vec<(String, ValueRef)> = Vec::new();
+        for local in function.locals_at(pc) {
+            let Some(name) = symbol_table.get_symbol(local.symbol) else {
+                continue;
+            };
+            let value = self.register_stack[frame.reg_start + local.register as usize];
+            // Later entries shadow earlier bindings of the same name
+            match locals.iter_mut().find(|(existing, _)| *existing == name) {
+                Some(slot) => slot.1 = value,
+                None => locals.push((name, value)),
+            }
+        }
+        locals
+    }
+
+    /// Evaluate an expression with a paused frame's locals bound, in that frame's module
+    pub fn eval_in_frame(&mut self, frame_index: usize, expr: ValueRef) -> Result<ValueRef, BlinkError> {
+        let Some(stack_index) = self.call_stack.len().checked_sub(frame_index + 1) else {
+            return Err(BlinkError::eval(format!("No frame {}", frame_index)));
+        };
+        let frame_module = self.call_stack[stack_index].current_module;
+
+        // (let [name (quote value) ...] expr)
+        let mut bindings = Vec::new();
+        for (name, value) in self.frame_locals(frame_index) {
+            let symbol = ValueRef::symbol(self.vm.symbol_table.write().intern(&name));
+            let quote = ValueRef::symbol(self.vm.symbol_table.write().intern("quote"));
+            bindings.push(symbol);
+            bindings.push(ValueRef::Heap(GcPtr::new(self.vm.alloc_list_from_items(vec![quote, value]))));
+        }
+        let let_symbol = ValueRef::symbol(self.vm.symbol_table.write().intern("let"));
+        let binding_vector = ValueRef::Heap(GcPtr::new(self.vm.alloc_vec(bindings)));
+        let wrapped = ValueRef::Heap(GcPtr::new(
+            self.vm.alloc_list_from_items(vec![let_symbol, binding_vector, expr]),
+        ));
+
+        // Run on an empty call stack above the paused frames' registers
+        let saved_stack = std::mem::take(&mut self.call_stack);
+        let saved_module = std::mem::replace(&mut self.current_module, frame_module);
+        let saved_tracker = self.limit_tracker.take();
+
+        let result = self.compile_and_execute(wrapped);
+
+        self.call_stack = saved_stack;
+        self.current_module = saved_module;
+        self.limit_tracker = saved_tracker;
+        result
+    }
+}
+
+fn frame_function(frame: &CallFrame) -> Option<CompiledFunction> {
+    match &frame.func {
+        FunctionRef::CompiledFunction(compiled_fn, _) => Some(compiled_fn.clone()),
+        FunctionRef::Closure(closure_obj, _) => Some(GcPtr::new(closure_obj.template).read_callable()),
+        FunctionRef::Native(_) => None,
+    }
+}
+
+fn file_matches(ctx: &ExecutionContext<'_>, frame: &CallFrame, wanted: Option<&str>) -> bool {
+    let Some(wanted) = wanted else {
+        return true;
+    };
+    let module_registry = ctx.vm.module_registry.read();
+    let Some(module) = module_registry.get_module(frame.current_module) else {
+        return false;
+    };
+    match module.source {
+        SerializedModuleSource::BlinkFile(path) => {
+            ctx.vm.symbol_table.read().get_symbol(path).map_or(false, |path| {
+                // Editors send absolute paths, modules may be registered relative
+                Path::new(wanted).ends_with(&path) || Path::new(&path).ends_with(wanted)
+            })
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+    use crate::parser::parse_all;
+    use crate::test_support::context;
+
+    // A program being debugged form by form, like the DAP endpoint does
+    struct Program {
+        ctx: ExecutionContext<'static>,
+        debugger: Debugger,
+        forms: VecDeque<ValueRef>,
+    }
+
+    impl Program {
+        fn new(module: &str, code: &str) -> Self {
+            let ctx = context(module);
+            let vm = ctx.vm.clone();
+            let forms = {
+                let mut symbol_table = vm.symbol_table.write();
+                let mut reader_macros = vm.reader_macros.write();
+                parse_all(code, &mut reader_macros, &mut symbol_table).unwrap()
+            };
+            let forms = forms.into_iter().map(|form| vm.alloc_parsed_value(form)).collect();
+            Self { ctx, debugger: Debugger::new(), forms }
+        }
+
+        // Run until the next pause, as (reason, function, line); None once every form has run
+        fn resume(&mut self, mode: StepMode) -> Option<(PauseReason, String, usize)> {
+            self.debugger.set_step_mode(mode, self.ctx.call_stack.len());
+            loop {
+                if self.ctx.call_stack.is_empty() {
+                    let form = self.forms.pop_front()?;
+                    self.ctx.debug_start(form).unwrap();
+                    // A step that runs off the end of one form stops at the start of the next
+                    if mode != StepMode::Run {
+                        self.debugger.set_step_mode(StepMode::StepIn, 0);
+                    }
+                }
+                match self.ctx.debug_resume(&mut self.debugger) {
+                    DebugEvent::Paused { reason, frames } => {
+                        let frame = &frames[0];
+                        let function = frame.function.clone().unwrap_or_default();
+                        return Some((reason, function, frame.line.unwrap()));
+                    }
+                    DebugEvent::Finished(_) => continue,
+                    DebugEvent::Failed(e) => panic!("{}", e),
+                }
+            }
+        }
+
+        fn locals(&self, frame: usize) -> Vec<(String, String)> {
+            self.ctx
+                .frame_locals(frame)
+                .into_iter()
+                .map(|(name, value)| (name, value.to_string()))
+                .collect()
+        }
+    }
+
+    const PROGRAM: &str = "(def add2 (fn [x]
+  (+ x 2)))
+(def twice (fn [x]
+  (let [y (add2 x)]
+    (+ 0 (add2 y)))))
+(def result (twice 1))";
+
+    fn pause(reason: PauseReason, function: &str, line: usize) -> Option<(PauseReason, String, usize)> {
+        Some((reason, function.to_string(), line))
+    }
+
+    #[test]
+    fn stepping_in_visits_each_line() {
+        let mut program = Program::new("debug-step-in", PROGRAM);
+        program.debugger.set_stop_on_entry(true);
+        let mut steps = vec![program.resume(StepMode::Run).unwrap()];
+        while let Some(step) = program.resume(StepMode::StepIn) {
+            steps.push(step);
+        }
+
+        let lines: Vec<(&str, usize)> = steps.iter().map(|(_, function, line)| (function.as_str(), *line)).collect();
+        assert_eq!(lines, [("", 1), ("", 3), ("", 6), ("twice", 4), ("add2", 2), ("twice", 5), ("add2", 2)]);
+        assert_eq!(steps[0].0, PauseReason::Entry);
+        assert!(steps[1..].iter().all(|(reason, _, _)| *reason == PauseReason::Step));
+    }
+
+    #[test]
+    fn step_over_and_out() {
+        let mut program = Program::new("debug-step-over", PROGRAM);
+        let id = program.debugger.add_breakpoint(Breakpoint::Line { file: None, line: 4 });
+        assert_eq!(program.resume(StepMode::Run), pause(PauseReason::Breakpoint(id), "twice", 4));
+        assert_eq!(program.resume(StepMode::StepOver), pause(PauseReason::Step, "twice", 5));
+
+        let mut program = Program::new("debug-step-out", PROGRAM);
+        let id = program.debugger.add_breakpoint(Breakpoint::Line { file: None, line: 4 });
+        assert_eq!(program.resume(StepMode::Run), pause(PauseReason::Breakpoint(id), "twice", 4));
+        assert_eq!(program.resume(StepMode::StepIn), pause(PauseReason::Step, "add2", 2));
+        assert_eq!(program.resume(StepMode::StepOut), pause(PauseReason::Step, "twice", 5));
+        assert_eq!(program.resume(StepMode::StepOver), None);
+    }
+
+    #[test]
+    fn line_breakpoints_show_the_frame_locals() {
+        let mut program = Program::new("debug-locals", PROGRAM);
+        let id = program.debugger.add_breakpoint(Breakpoint::Line { file: None, line: 5 });
+        assert_eq!(program.resume(StepMode::Run), pause(PauseReason::Breakpoint(id), "twice", 5));
+
+        let locals = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+        };
+        assert_eq!(program.locals(0), locals(&[("x", "1"), ("y", "3")]));
+        assert_eq!(program.locals(1), locals(&[]));
+
+        let vm = program.ctx.vm.clone();
+        let expr = {
+            let mut symbol_table = vm.symbol_table.write();
+            let mut reader_macros = vm.reader_macros.write();
+            parse_all("(+ x y)", &mut reader_macros, &mut symbol_table).unwrap().remove(0)
+        };
+        let expr = vm.alloc_parsed_value(expr);
+        assert_eq!(program.ctx.eval_in_frame(0, expr).unwrap().to_string(), "4");
+
+        // Running on finishes the program, which is unaffected by the evaluation
+        assert_eq!(program.resume(StepMode::Run), None);
+        assert_eq!(program.ctx.last_return_value.to_string(), "5");
+    }
+
+    #[test]
+    fn locals_follow_their_scopes() {
+        let code = "(def scopes (fn [a]
+  (let [b (+ a 1)]
+    (do (let [a 10]
+          (+ a b))
+        (+ a b)))))
+(scopes 1)";
+        let mut program = Program::new("debug-scopes", code);
+        program.debugger.add_breakpoint(Breakpoint::Line { file: None, line: 4 });
+        program.debugger.add_breakpoint(Breakpoint::Line { file: None, line: 5 });
+
+        let names = |program: &Program| -> Vec<String> {
+            program.locals(0).into_iter().map(|(name, value)| format!("{}={}", name, value)).collect()
+        };
+        assert_eq!(program.resume(StepMode::Run).map(|(_, _, line)| line), Some(4));
+        assert_eq!(names(&program), ["a=10", "b=2"]);
+        assert_eq!(program.resume(StepMode::Run).map(|(_, _, line)| line), Some(5));
+        assert_eq!(names(&program), ["a=1", "b=2"]);
+    }
+
+    #[test]
+    fn function_breakpoints_pause_on_each_call() {
+        let mut program = Program::new("debug-function", PROGRAM);
+        let id = program.debugger.add_breakpoint(Breakpoint::Function { name: "add2".to_string() });
+        for _ in 0..2 {
+            let (reason, function, _) = program.resume(StepMode::Run).unwrap();
+            assert_eq!((reason, function.as_str()), (PauseReason::Breakpoint(id), "add2"));
+        }
+        assert_eq!(program.resume(StepMode::Run), None);
+    }
+
+    #[test]
+    fn removed_breakpoints_do_not_pause() {
+        let mut program = Program::new("debug-remove", PROGRAM);
+        let id = program.debugger.add_breakpoint(Breakpoint::Line { file: None, line: 2 });
+        program.debugger.add_breakpoint(Breakpoint::Line { file: Some("elsewhere.bl".to_string()), line: 5 });
+        assert!(program.debugger.remove_breakpoint(id).is_some());
+        assert_eq!(program.resume(StepMode::Run), None);
+    }
+}
@@ -0,0 +1,179 @@
+use crate::runtime::BlinkVM;
+use crate::value::ValueRef;
+
+/// What a definition's metadata says about it, for `doc` and editor hover
+#[derive(Clone, Debug)]
+pub struct DefinitionDoc {
+    pub module: String,
+    pub name: String,
+    pub doc: Option<String>,
+    pub arglists: Vec<String>,
+    /// `Some("")` for `^:deprecated`, or the message of `^{:deprecated "use g"}`
+    pub deprecated: Option<String>,
+    pub private: bool,
+}
+
+impl DefinitionDoc {
+    /// Plain text, as the REPL prints it:
+    ///
+    /// ```text
+    /// geo/area
+    /// ([r])
+    ///   Area of a circle of radius r.
+    /// ```
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}/{}", self.module, self.name);
+        if !self.arglists.is_empty() {
+            text.push_str(&format!("\n({})", self.arglists.join(" ")));
+        }
+        if let Some(message) = &self.deprecated {
+            text.push_str(&format!("\n  Deprecated{}", Self::suffix(message)));
+        }
+        match &self.doc {
+            Some(doc) => text.push_str(&format!("\n  {}", doc.replace('\n', "\n  "))),
+            None => text.push_str("\n  No documentation."),
+        }
+        text
+    }
+
+    /// Markdown, for LSP hover and completion items
+    pub fn to_markdown(&self) -> String {
+        let mut text = format!("**{}/{}**", self.module, self.name);
+        if self.private {
+            text.push_str(" (private)");
+        }
+        if !self.arglists.is_empty() {
+            let calls: Vec<String> = self
+                .arglists
+                .iter()
+                .map(|params| match params.trim_start_matches('[').trim_end_matches(']') {
+                    "" => format!("({})", self.name),
+                    params => format!("({} {})", self.name, params),
+                })
+                .collect();
+            text.push_str(&format!("\n\n```blink\n{}\n```", calls.join("\n")));
+        }
+        if let Some(message) = &self.deprecated {
+            text.push_str(&format!("\n\n*Deprecated{}*", Self::suffix(message)));
+        }
+        if let Some(doc) = &self.doc {
+            text.push_str(&format!("\n\n{}", doc));
+        }
+        text
+    }
+
+    fn suffix(message: &str) -> String {
+        if message.is_empty() {
+            String::new()
+        } else {
+            format!(": {}", message)
+        }
+    }
+}
+
+impl BlinkVM {
+    /// The documentation of a definition, by its module and name
+    pub fn definition_doc(&self, module_id: u32, symbol_id: u32) -> Option<DefinitionDoc> {
+        let (meta, private) = {
+            let registry = self.module_registry.read();
+            registry.resolve_symbol(module_id, symbol_id)?;
+            (registry.metadata(module_id, symbol_id), registry.is_private(module_id, symbol_id))
+        };
+        let entry = |key: &str| {
+            let key_id = self.symbol_table.read().lookup_symbol(&format!(":{}", key))?;
+            let map = meta?.get_map()?;
+            map.get(&ValueRef::keyword(key_id)).copied()
+        };
+
+        let arglists = entry("arglists")
+            .and_then(|arglists| arglists.get_list())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|params| params.get_vec())
+            .map(|params| {
+                let names: Vec<String> = params.iter().map(|param| param.to_string()).collect();
+                format!("[{}]", names.join(" "))
+            })
+            .collect();
+        let deprecated = entry("deprecated")
+            .filter(|value| value.is_truthy())
+            .map(|value| value.get_string().unwrap_or_default());
+
+        let symbol_table = self.symbol_table.read();
+        Some(DefinitionDoc {
+            module: symbol_table.get_symbol(module_id).unwrap_or_default(),
+            name: symbol_table.get_symbol(symbol_id).unwrap_or_default(),
+            doc: entry("doc").and_then(|doc| doc.get_string()),
+            arglists,
+            deprecated,
+            private,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::ExecutionContext;
+    use crate::test_support::{context, eval};
+
+    fn doc_of(ctx: &ExecutionContext, name: &str) -> DefinitionDoc {
+        let symbol_id = ctx.vm.symbol_table.write().intern(name);
+        ctx.vm.definition_doc(ctx.current_module, symbol_id).unwrap()
+    }
+
+    #[test]
+    fn definitions_keep_their_docstrings() {
+        let mut ctx = context("docs-def");
+        eval(&mut ctx, "(def answer \"The answer.\" 42)");
+        assert_eq!(eval(&mut ctx, "answer"), "42");
+        let doc = doc_of(&ctx, "answer");
+        assert_eq!(doc.doc.as_deref(), Some("The answer."));
+        assert_eq!(doc.to_text(), "docs-def/answer\n  The answer.");
+        assert_eq!(eval(&mut ctx, "(= (:doc (meta 'answer)) \"The answer.\")"), "true");
+
+        eval(&mut ctx, "(defn area \"Area of a square.\" [side] (* side side))");
+        let doc = doc_of(&ctx, "area");
+        assert_eq!(doc.doc.as_deref(), Some("Area of a square."));
+        assert_eq!(doc.arglists, vec!["[side]".to_string()]);
+        assert_eq!(eval(&mut ctx, "(area 3)"), "9");
+
+        eval(&mut ctx, "(def undocumented 1)");
+        assert_eq!(doc_of(&ctx, "undocumented").doc, None);
+        assert!(doc_of(&ctx, "undocumented").to_text().ends_with("No documentation."));
+    }
+
+    #[test]
+    fn metadata_maps_add_to_the_docs() {
+        let mut ctx = context("docs-meta");
+        eval(&mut ctx, "(def ^{:doc \"From the map.\" :deprecated \"use g\"} f \"From the def.\" 1)");
+        let doc = doc_of(&ctx, "f");
+        // The ^{...} entry wins over the docstring
+        assert_eq!(doc.doc.as_deref(), Some("From the map."));
+        assert_eq!(doc.deprecated.as_deref(), Some("use g"));
+
+        eval(&mut ctx, "(def ^:private hidden \"Internal.\" 2)");
+        let doc = doc_of(&ctx, "hidden");
+        assert!(doc.private);
+        assert!(doc.to_markdown().starts_with("**docs-meta/hidden** (private)"));
+    }
+
+    #[test]
+    fn natives_are_documented_when_registered() {
+        let mut ctx = context("docs-native");
+        let core_module = ctx.vm.core_module.unwrap();
+        let assoc = ctx.vm.symbol_table.write().intern("assoc");
+        let doc = ctx.vm.definition_doc(core_module, assoc).unwrap();
+        assert_eq!(doc.arglists, vec!["[coll key val ...]".to_string()]);
+        assert!(doc.doc.unwrap().starts_with("Returns a new map with the keys set"));
+
+        let (string_module, split) = {
+            let mut symbol_table = ctx.vm.symbol_table.write();
+            (symbol_table.intern("string"), symbol_table.intern("split"))
+        };
+        let doc = ctx.vm.definition_doc(string_module, split).unwrap();
+        assert_eq!(doc.arglists.len(), 2);
+        assert!(doc.to_markdown().starts_with("**string/split**\n\n```blink\n(split s separator)\n"));
+        assert_eq!(eval(&mut ctx, "(= (:arglists (meta 'inc)) '([n]))"), "true");
+    }
+}
@@ -1,16 +1,29 @@
 use crate::compiler::{BytecodeCompiler, MacroExpander};
-use crate::runtime::{BlinkRuntime, SuspendedContinuation};
-use crate::{error::BlinkError, runtime::{
+use crate::runtime::{BlinkRuntime, CancellationToken, ExecutionLimits, LimitTracker, PendingLoad, ProfileFrame, SuspendedContinuation, ValueId};
+use crate::module::{ImportError, ImportNames, SerializedModuleSource};
+use crate::{error::{BlinkError, BlinkErrorType, StackFrame}, runtime::{
     blink_runtime::GLOBAL_RUNTIME, BlinkVM, ClosureObject, CompiledFunction,
-    ContextualBoundary, EvalResult, Opcode, TypeTag, ValueBoundary,
+    ContextualBoundary, EvalResult, Macro, Opcode, TypeTag, ValueBoundary, IMPORT_REFER_ALL, NO_LIST, NO_SYMBOL,
 }, value::{
-    unpack_immediate, ContextualNativeFn, GcPtr, ImmediateValue, IsolatedNativeFn,
-    NativeContext, ValueRef,
+    unpack_immediate, FunctionCaller, GcPtr, ImmediateValue, NativeContext, NativeFn, SourcePos, SourceRange, ValueRef,
 }, SingleThreadedScheduler};
 use mmtk::util::ObjectReference;
-use std::sync::Arc;
+use std::collections::HashSet;
+use std::sync::{Arc, OnceLock};
 use parking_lot::Mutex;
 use crate::value::FutureHandle;
+use crate::math_functions::floored_mod;
+use crate::native_functions::native_get;
+
+// Native run when a keyword, map or set is called
+static LOOKUP_FN: OnceLock<usize> = OnceLock::new();
+
+fn lookup_native() -> usize {
+    *LOOKUP_FN.get_or_init(|| match ValueRef::native_function(NativeFn::Contextual(Box::new(native_get))) {
+        ValueRef::Handle(tagged_ptr) => tagged_ptr,
+        _ => unreachable!("native functions are handles"),
+    })
+}
 
 // Updated call frame for byte-sized bytecode
 #[derive(Clone, Debug)]
@@ -28,6 +41,7 @@ enum InstructionResult {
     Continue,
     Return,
     Call(CallFrame),
+    TailCall(CallFrame), // Replaces the calling frame
     SetupSelfReference(u8),
     CreateClosure {
         dest_register: u8,
@@ -67,26 +81,87 @@ pub struct ExecutionContext<'a> {
     pub call_stack: Vec<CallFrame>,
     pub scheduler: &'a Mutex<SingleThreadedScheduler>,
     pub current_goroutine_id: Option<u32>, // Track the current goroutine ID
+    pub limits: ExecutionLimits,
+    pub(crate) limit_tracker: Option<LimitTracker>,
+    pub cancel_token: CancellationToken,
+    safepoint_pending: bool,
+    // Structured error for an evaluation aborted by a limit or an interrupt
+    abort_error: Option<BlinkError>,
+    last_stack_trace: Vec<StackFrame>,
+    // Value of the last frame to return, for callers driving execute_single_step
+    pub last_return_value: ValueRef,
+    // Frames entered while the profiler was on, innermost last
+    pub(crate) profile_stack: Vec<ProfileFrame>,
+    pub(crate) profile_ticks: u64,
+    pub(crate) profile_updated: HashSet<ValueId>, // Functions whose profile changed since take_profile_updates
+    // Files being loaded, innermost last
+    pub(crate) load_stack: Vec<PendingLoad>,
+    // Warnings for the user, oldest first, until a frontend takes them to show
+    pub(crate) warnings: Vec<String>,
 }
 
-impl ExecutionContext {
+impl<'a> ExecutionContext<'a> { // CHKFIX
     pub fn new(vm: Arc<BlinkVM>, current_module: u32) -> Self {
         Self {
             vm: vm.clone(),
+            scheduler: Box::leak(Box::new(Mutex::new(<SingleThreadedScheduler as crate::GoroutineScheduler>::new(vm.clone())))), // CHKFIX
             current_module,
             
             register_stack: Vec::new(),
             call_stack: Vec::new(),
             current_goroutine_id: None, // Default to no goroutine (main thread execution)
+            limits: ExecutionLimits::unlimited(),
+            limit_tracker: None,
+            cancel_token: CancellationToken::new(),
+            safepoint_pending: false,
+            abort_error: None,
+            last_stack_trace: Vec::new(),
+            last_return_value: ValueRef::nil(),
+            profile_stack: Vec::new(),
+            profile_ticks: 0,
+            profile_updated: HashSet::new(),
+            load_stack: Vec::new(),
+            warnings: Vec::new(),
         }
     }
 
+    pub fn set_limits(&mut self, limits: ExecutionLimits) {
+        self.limits = limits;
+    }
+
+    /// Instructions executed and bytes allocated by the last top-level evaluation
+    pub fn last_run_usage(&self) -> Option<(u64, usize)> {
+        self.limit_tracker
+            .as_ref()
+            .map(|tracker| (tracker.instructions_executed(), tracker.bytes_allocated()))
+    }
+
+    /// Warnings recorded since the last call: the VM's, such as a project that
+    /// could not be resolved, then this context's, such as definitions still
+    /// compiled with a macro that was redefined or removed
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        let mut warnings = self.vm.take_warnings();
+        warnings.append(&mut self.warnings);
+        warnings
+    }
+
     pub fn compile_and_execute(&mut self, expr: ValueRef) -> Result<ValueRef, BlinkError> {
-        let mut macro_expander = MacroExpander::new(self.vm.clone(), self.current_module);
+        self.prepare_toplevel(expr)?;
+
+        // Execute frame loop
+        self.execute().map_err(|e| self.finish_error(e))
+    }
+
+    /// Compile a top-level expression and push its frame, ready to run
+    pub(crate) fn prepare_toplevel(&mut self, expr: ValueRef) -> Result<(), BlinkError> {
+        self.start_evaluation();
+        let mut macro_expander = MacroExpander::new(self.vm.clone(), self.current_module, self);
 
         let expanded = macro_expander
             .expand(*&expr)
             .map_err(|e| BlinkError::eval(e))?;
+        let expanded_macros = macro_expander.expanded_macros();
+        self.record_macro_uses(expanded, &expanded_macros);
         let mut compiler = BytecodeCompiler::new(self.vm.clone(), self.current_module);
         let compiled = compiler
             .compile_for_storage(expanded)
@@ -107,12 +182,126 @@ impl ExecutionContext {
             self.register_stack.push(ValueRef::nil());
         }
 
-        self.call_stack.push(initial_frame);
+        self.push_frame(initial_frame);
+        Ok(())
+    }
 
-        // Execute frame loop
-        let res = self.execute().map_err(|e| BlinkError::eval(e));
+    /// Start a top-level evaluation with no error from the last one and fresh
+    /// limits. Macro expansion runs code, so this comes before it. A pending
+    /// interrupt is kept: it was sent for this request, which may evaluate
+    /// several forms, so frontends reset the token between requests.
+    pub(crate) fn start_evaluation(&mut self) {
+        self.abort_error = None;
+        self.limit_tracker = Some(LimitTracker::start(&self.limits));
+    }
+
+    /// Turn an execution failure into a BlinkError carrying the recorded stack trace
+    fn finish_error(&mut self, message: String) -> BlinkError {
+        let error = self.abort_error.take().unwrap_or_else(|| BlinkError::eval(message));
+        let pos = error.pos.or_else(|| {
+            self.last_stack_trace.first().and_then(|frame| {
+                let (line, col) = (frame.line?, frame.col?);
+                let start = SourcePos { line, col };
+                Some(SourceRange::new(start, start))
+            })
+        });
+        error
+            .with_pos(pos)
+            .with_stack_trace(std::mem::take(&mut self.last_stack_trace))
+    }
+
+    /// Record the stack trace of a failed run, unwind it and build the error
+    pub(crate) fn fail_execution(&mut self, message: String) -> BlinkError {
+        self.last_stack_trace = self.capture_stack_trace();
+        self.unwind_call_stack();
+        self.finish_error(message)
+    }
+
+    /// Drop every frame and its registers
+    fn unwind_call_stack(&mut self) {
+        self.unwind_to(0);
+    }
+
+    /// Drop the frames above `depth` and their registers
+    pub(crate) fn unwind_to(&mut self, depth: usize) {
+        while self.call_stack.len() > depth {
+            let frame = self.call_stack.pop().unwrap();
+            self.register_stack.truncate(frame.reg_start);
+        }
+        self.profile_discard_from(depth);
+    }
+
+    /// The module of the running frame, where its globals resolve
+    fn frame_module(&self) -> u32 {
+        self.call_stack.last().map_or(self.current_module, |frame| frame.current_module)
+    }
 
-        res
+    pub(crate) fn push_frame(&mut self, frame: CallFrame) {
+        if self.vm.profiler.is_enabled() {
+            self.profile_enter(&frame);
+        }
+        self.call_stack.push(frame);
+    }
+
+    /// Swap the current frame for the callee of a tail call, which takes over its registers
+    fn replace_frame(&mut self, mut frame: CallFrame) {
+        let finished = self.pop_frame();
+        let callee_registers: Vec<ValueRef> = self.register_stack.drain(frame.reg_start..).collect();
+        self.register_stack.truncate(finished.reg_start);
+        frame.reg_start = finished.reg_start;
+        self.register_stack.extend(callee_registers);
+        self.push_frame(frame);
+    }
+
+    fn pop_frame(&mut self) -> CallFrame {
+        let frame = self.call_stack.pop().expect("pop_frame on an empty call stack");
+        if !self.profile_stack.is_empty() {
+            self.profile_exit();
+        }
+        frame
+    }
+
+    /// Charge one instruction against the active limits, if any
+    pub(crate) fn check_limits(&mut self) -> Result<(), BlinkError> {
+        let call_depth = self.call_stack.len();
+        match self.limit_tracker.as_mut() {
+            Some(tracker) => tracker.tick(&self.limits, call_depth),
+            None => Ok(()),
+        }
+    }
+
+    /// Poll the cancellation token; only called at safepoints
+    fn check_interrupt(&mut self) -> Result<(), BlinkError> {
+        if !self.cancel_token.is_cancelled() {
+            return Ok(());
+        }
+        self.cancel_token.reset();
+
+        let location = match self.call_stack.last() {
+            Some(frame) => {
+                let module_name = self
+                    .vm
+                    .symbol_table
+                    .read()
+                    .get_symbol(frame.current_module)
+                    .unwrap_or_else(|| "<unknown>".to_string());
+                format!(
+                    "in module {} at bytecode offset {} (call depth {})",
+                    module_name,
+                    frame.pc,
+                    self.call_stack.len()
+                )
+            }
+            None => "before execution started".to_string(),
+        };
+        Err(BlinkError::interrupted(format!("evaluation stopped {}", location)))
+    }
+
+    /// Abort the evaluation, keeping the structured error for the caller
+    pub(crate) fn abort_with(&mut self, error: BlinkError) -> String {
+        let message = error.to_string();
+        self.abort_error = Some(error);
+        message
     }
 
     /// Execute a single step (one instruction) and return whether to continue
@@ -183,7 +372,7 @@ impl ExecutionContext {
 
     /// Handle completion of a function (natural end or explicit return)
     fn handle_function_completion(&mut self) -> Result<(), String> {
-        let completed_frame = self.call_stack.pop().unwrap();
+        let completed_frame = self.pop_frame();
         let return_value = self.register_stack[completed_frame.reg_start];
 
         // Clean up registers
@@ -194,6 +383,8 @@ impl ExecutionContext {
             if let Some(caller_frame) = self.call_stack.last() {
                 self.register_stack[caller_frame.reg_start] = return_value;
             }
+        } else {
+            self.last_return_value = return_value;
         }
         Ok(())
     }
@@ -209,43 +400,7 @@ impl ExecutionContext {
             args.push(self.register_stack[current_frame.reg_start + 1 + i]);
         }
 
-        // Decode tagged pointer and call appropriate function type
-        let ptr = tagged_ptr & !1; // Clear the tag bit
-        let return_value = if tagged_ptr & 1 == 0 {
-            // Tag 0 = Isolated function
-            let boxed_fn_ptr = ptr as *const IsolatedNativeFn;
-            let boxed_fn = unsafe { &*boxed_fn_ptr };
-
-            // Convert args to isolated values and call
-            let mut boundary = ContextualBoundary::new(self.vm.clone());
-            let isolated_args: Result<Vec<_>, _> = args
-                .iter()
-                .map(|arg| boundary.extract_isolated(*arg))
-                .collect();
-
-            match isolated_args {
-                Ok(isolated_args) => match boxed_fn(isolated_args) {
-                    Ok(result) => boundary.alloc_from_isolated(result),
-                    Err(e) => self.vm.eval_error(&e.to_string()),
-                },
-                Err(e) => self.vm.eval_error(&e.to_string()),
-            }
-        } else {
-            // Tag 1 = Contextual function
-            let boxed_fn_ptr = ptr as *const ContextualNativeFn;
-            let boxed_fn = unsafe { &*boxed_fn_ptr };
-            let mut ctx = NativeContext::new(&self.vm);
-
-            // Call function and extract value (ignore suspension for now)
-            match boxed_fn(args, &mut ctx) {
-                EvalResult::Value(val) => val,
-                EvalResult::Suspended { .. } => {
-                    // Convert suspension to error for now
-                    self.vm
-                        .eval_error("Native function suspension not supported")
-                }
-            }
-        };
+        let return_value = self.call_native(tagged_ptr, args);
 
         // Native function completed - pop frame and handle return
         self.handle_function_completion()?;
@@ -260,6 +415,97 @@ impl ExecutionContext {
         Ok(())
     }
 
+    /// Call a function value and run it to completion on top of the current frames.
+    /// This is how natives call back into Blink code.
+    pub fn call_function(&mut self, func: ValueRef, args: Vec<ValueRef>) -> Result<ValueRef, BlinkError> {
+        let arg_count = u8::try_from(args.len())
+            .map_err(|_| BlinkError::eval(format!("Too many arguments in call: {}", args.len())))?;
+        let base_depth = self.call_stack.len();
+
+        // Lay the call out like a Call instruction would: function, then arguments
+        let scratch_start = self.register_stack.len();
+        self.register_stack.push(func);
+        self.register_stack.extend(args);
+
+        let frame = match Self::setup_function_call(
+            &mut self.register_stack,
+            self.current_module,
+            func,
+            0,
+            arg_count,
+            scratch_start,
+        ) {
+            Ok(frame) => frame,
+            Err(message) => {
+                self.register_stack.truncate(scratch_start);
+                return Err(BlinkError::eval(message));
+            }
+        };
+        self.push_frame(frame);
+
+        let result = self.run_frames_until(base_depth);
+        let result = match result {
+            Ok(value) => Ok(value),
+            Err(message) => {
+                self.last_stack_trace = self.capture_stack_trace();
+                self.unwind_to(base_depth);
+                let error = self.finish_error(message);
+                if matches!(error.error_type, BlinkErrorType::Interrupted) && base_depth > 0 {
+                    // Stop the caller too, at its next safepoint
+                    self.cancel_token.cancel();
+                    self.safepoint_pending = true;
+                }
+                Err(error)
+            }
+        };
+        self.register_stack.truncate(scratch_start);
+        result
+    }
+
+    /// Call a native function handle with evaluated arguments
+    fn call_native(&mut self, tagged_ptr: usize, args: Vec<ValueRef>) -> ValueRef {
+        let handle = ValueRef::Handle(tagged_ptr);
+        let Some(native_fn) = handle.get_native_fn() else {
+            return self.vm.eval_error("Called value is not a native function");
+        };
+
+        match native_fn {
+            NativeFn::Isolated(boxed_fn) => {
+                // Convert args to isolated values and call
+                let mut boundary = ContextualBoundary::new(self.vm.clone());
+                let isolated_args: Result<Vec<_>, _> = args
+                    .iter()
+                    .map(|arg| boundary.extract_isolated(*arg))
+                    .collect();
+
+                match isolated_args {
+                    Ok(isolated_args) => match boxed_fn(isolated_args) {
+                        Ok(result) => boundary.alloc_from_isolated(result),
+                        Err(e) => self.vm.eval_error(&e.to_string()),
+                    },
+                    Err(e) => self.vm.eval_error(&e.to_string()),
+                }
+            }
+            NativeFn::Contextual(boxed_fn) => {
+                let vm = self.vm.clone();
+                let roots_start = self.register_stack.len();
+                let mut ctx = NativeContext::with_caller(&vm, self);
+
+                // Call function and extract value (ignore suspension for now)
+                let result = match boxed_fn(args, &mut ctx) {
+                    EvalResult::Value(val) => val,
+                    EvalResult::Suspended { .. } => {
+                        // Convert suspension to error for now
+                        self.vm
+                            .eval_error("Native function suspension not supported")
+                    }
+                };
+                self.register_stack.truncate(roots_start);
+                result
+            }
+        }
+    }
+
     /// Handle the result of executing an instruction
     fn handle_instruction_result(
         &mut self,
@@ -281,7 +527,10 @@ impl ExecutionContext {
                 if let Some(frame) = self.call_stack.last_mut() {
                     frame.pc = current_frame.pc;
                 }
-                self.call_stack.push(new_frame);
+                self.push_frame(new_frame);
+            }
+            InstructionResult::TailCall(new_frame) => {
+                self.replace_frame(new_frame);
             }
             InstructionResult::SetupSelfReference(reg) => {
                 // Handle self-reference setup here where we have access to function context
@@ -399,10 +648,89 @@ impl ExecutionContext {
         Ok(())
     }
 
-    // Main execution loop - processes all frames until stack is empty
-    // Main execution loop - processes all frames until stack is empty
+    /// Run all frames until the stack is empty. On error the stack trace is
+    /// recorded and every frame is unwound, leaving the context reusable.
     pub fn execute(&mut self) -> Result<ValueRef, String> {
-        while !self.call_stack.is_empty() {
+        let result = self.run_frames();
+        if result.is_err() {
+            self.last_stack_trace = self.capture_stack_trace();
+            self.unwind_call_stack();
+        }
+        result
+    }
+
+    /// Build a stack trace from the live call stack, innermost frame first
+    pub fn capture_stack_trace(&self) -> Vec<StackFrame> {
+        let symbol_table = self.vm.symbol_table.read();
+        let module_registry = self.vm.module_registry.read();
+        let top = self.call_stack.len().saturating_sub(1);
+
+        self.call_stack
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(depth, frame)| {
+                let function = match &frame.func {
+                    FunctionRef::CompiledFunction(compiled_fn, _) => Some(compiled_fn.clone()),
+                    FunctionRef::Closure(closure_obj, _) => {
+                        Some(GcPtr::new(closure_obj.template).read_callable())
+                    }
+                    FunctionRef::Native(_) => None,
+                };
+
+                // Callers have already advanced past their Call instruction
+                let pc = if depth == top { frame.pc } else { frame.pc.saturating_sub(1) };
+                let pos = function.as_ref().and_then(|f| f.position_at(pc));
+
+                let function_name = match &function {
+                    Some(f) => f.name.and_then(|name| symbol_table.get_symbol(name)),
+                    None => Some("<native>".to_string()),
+                };
+
+                let file = module_registry
+                    .get_module(frame.current_module)
+                    .and_then(|module| match module.source {
+                        SerializedModuleSource::BlinkFile(path) => symbol_table.get_symbol(path),
+                        _ => None,
+                    });
+
+                StackFrame {
+                    function: function_name,
+                    module: symbol_table
+                        .get_symbol(frame.current_module)
+                        .unwrap_or_else(|| "<unknown>".to_string()),
+                    file,
+                    line: pos.map(|p| p.start.line),
+                    col: pos.map(|p| p.start.col),
+                }
+            })
+            .collect()
+    }
+
+    // Main execution loop - processes all frames until stack is empty
+    fn run_frames(&mut self) -> Result<ValueRef, String> {
+        self.run_frames_until(0)
+    }
+
+    // Run until the call stack is back down to `base_depth` frames, returning
+    // the value of the frame that brought it there
+    pub(crate) fn run_frames_until(&mut self, base_depth: usize) -> Result<ValueRef, String> {
+        while self.call_stack.len() > base_depth {
+            if let Err(limit_error) = self.check_limits() {
+                return Err(self.abort_with(limit_error));
+            }
+
+            if self.safepoint_pending {
+                self.safepoint_pending = false;
+                if let Err(interrupt) = self.check_interrupt() {
+                    return Err(self.abort_with(interrupt));
+                }
+            }
+
+            if self.vm.profiler.is_enabled() {
+                self.profile_tick();
+            }
+
             // Get current frame (don't pop yet)
             let mut current_frame = if let Some(frame) = self.call_stack.last().cloned() {
                 frame
@@ -414,13 +742,13 @@ impl ExecutionContext {
                 // Check for end of function
                 if current_frame.pc >= compiled_fn.bytecode.len() {
                     // Function completed naturally
-                    let completed_frame = self.call_stack.pop().unwrap();
+                    let completed_frame = self.pop_frame();
                     let return_value = self.register_stack[completed_frame.reg_start];
 
                     // Clean up registers
                     self.register_stack.truncate(completed_frame.reg_start);
 
-                    if self.call_stack.is_empty() {
+                    if self.call_stack.len() == base_depth {
                         return Ok(return_value);
                     }
 
@@ -445,11 +773,7 @@ impl ExecutionContext {
 
                 // In the main execution loop, when an error occurs:
                 if let Err(error) = instruction_result {
-                    // Clean up incomplete function calls
-                    while !self.call_stack.is_empty() {
-                        let incomplete_frame = self.call_stack.pop().unwrap();
-                        self.register_stack.truncate(incomplete_frame.reg_start);
-                    }
+                    // execute() records the stack trace and cleans up incomplete calls
                     return Err(error);
                 };
 
@@ -464,14 +788,14 @@ impl ExecutionContext {
                     }
                     InstructionResult::Return => {
                         // Get return value from register 0 of completed frame
-                        let completed_frame = self.call_stack.pop().unwrap();
+                        let completed_frame = self.pop_frame();
                         let return_value = self.register_stack[completed_frame.reg_start];
 
                         // Clean up registers used by completed frame
                         self.register_stack.truncate(completed_frame.reg_start);
 
                         // If no more frames, we're done
-                        if self.call_stack.is_empty() {
+                        if self.call_stack.len() == base_depth {
                             return Ok(return_value);
                         }
 
@@ -485,7 +809,10 @@ impl ExecutionContext {
                         if let Some(frame) = self.call_stack.last_mut() {
                             frame.pc = current_frame.pc;
                         }
-                        self.call_stack.push(new_frame);
+                        self.push_frame(new_frame);
+                    }
+                    InstructionResult::TailCall(new_frame) => {
+                        self.replace_frame(new_frame);
                     }
                     InstructionResult::SetupSelfReference(reg) => {
                         // Handle self-reference setup here where we have access to function context
@@ -603,51 +930,15 @@ impl ExecutionContext {
                     args.push(self.register_stack[current_frame.reg_start + 1 + i]);
                 }
 
-                // Decode tagged pointer and call appropriate function type
-                let ptr = tagged_ptr & !1; // Clear the tag bit
-                let return_value = if tagged_ptr & 1 == 0 {
-                    // Tag 0 = Isolated function
-                    let boxed_fn_ptr = ptr as *const IsolatedNativeFn;
-                    let boxed_fn = unsafe { &*boxed_fn_ptr };
-
-                    // Convert args to isolated values and call
-                    let mut boundary = ContextualBoundary::new(self.vm.clone());
-                    let isolated_args: Result<Vec<_>, _> = args
-                        .iter()
-                        .map(|arg| boundary.extract_isolated(*arg))
-                        .collect();
-
-                    match isolated_args {
-                        Ok(isolated_args) => match boxed_fn(isolated_args) {
-                            Ok(result) => boundary.alloc_from_isolated(result),
-                            Err(e) => self.vm.eval_error(&e.to_string()),
-                        },
-                        Err(e) => self.vm.eval_error(&e.to_string()),
-                    }
-                } else {
-                    // Tag 1 = Contextual function
-                    let boxed_fn_ptr = ptr as *const ContextualNativeFn;
-                    let boxed_fn = unsafe { &*boxed_fn_ptr };
-                    let mut ctx = NativeContext::new(&self.vm);
-
-                    // Call function and extract value (ignore suspension for now)
-                    match boxed_fn(args, &mut ctx) {
-                        EvalResult::Value(val) => val,
-                        EvalResult::Suspended { .. } => {
-                            // Convert suspension to error for now
-                            self.vm
-                                .eval_error("Native function suspension not supported")
-                        }
-                    }
-                };
+                let return_value = self.call_native(*tagged_ptr, args);
 
                 // Native function completed - pop frame and handle return
-                let completed_frame = self.call_stack.pop().unwrap();
+                let completed_frame = self.pop_frame();
 
                 // Clean up registers
                 self.register_stack.truncate(completed_frame.reg_start);
 
-                if self.call_stack.is_empty() {
+                if self.call_stack.len() == base_depth {
                     return Ok(return_value);
                 }
 
@@ -662,13 +953,13 @@ impl ExecutionContext {
                 // Check for end of function (same logic as CompiledFunction)
                 if current_frame.pc >= template_fn.bytecode.len() {
                     // Function completed naturally
-                    let completed_frame = self.call_stack.pop().unwrap();
+                    let completed_frame = self.pop_frame();
                     let return_value = self.register_stack[completed_frame.reg_start];
 
                     // Clean up registers
                     self.register_stack.truncate(completed_frame.reg_start);
 
-                    if self.call_stack.is_empty() {
+                    if self.call_stack.len() == base_depth {
                         return Ok(return_value);
                     }
 
@@ -699,12 +990,12 @@ impl ExecutionContext {
                         }
                     }
                     InstructionResult::Return => {
-                        let completed_frame = self.call_stack.pop().unwrap();
+                        let completed_frame = self.pop_frame();
                         let return_value = self.register_stack[completed_frame.reg_start];
 
                         self.register_stack.truncate(completed_frame.reg_start);
 
-                        if self.call_stack.is_empty() {
+                        if self.call_stack.len() == base_depth {
                             return Ok(return_value);
                         }
 
@@ -717,7 +1008,10 @@ impl ExecutionContext {
                         if let Some(frame) = self.call_stack.last_mut() {
                             frame.pc = current_frame.pc;
                         }
-                        self.call_stack.push(new_frame);
+                        self.push_frame(new_frame);
+                    }
+                    InstructionResult::TailCall(new_frame) => {
+                        self.replace_frame(new_frame);
                     }
                     InstructionResult::SetupSelfReference(reg) => {
                         // Handle self-reference setup for closures
@@ -881,17 +1175,12 @@ impl ExecutionContext {
                 let symbol_id = Self::read_u32(bytecode, pc)?; // Symbol ID to look up
 
                 // Look up the global symbol (not use it as register index!)
-                match self.vm.resolve_global_symbol(self.current_module, symbol_id) {
+                let module_id = self.frame_module();
+                match self.vm.resolve_global_symbol(module_id, symbol_id) {
                     Some(value) => {
                         self.register_stack[reg_base + dest_reg as usize] = value; // Use dest_reg, not symbol_id
                     }
-                    None => {
-                        let symbol = self.vm.symbol_table.read().get_symbol(symbol_id);
-                        return Err(format!(
-                            "Global symbol {} not found",
-                            symbol.unwrap_or("Unknown symbol.".to_string())
-                        ));
-                    }
+                    None => return Err(self.vm.unresolved_symbol_message(module_id, symbol_id)),
                 }
                 Ok(InstructionResult::Continue)
             }
@@ -899,10 +1188,28 @@ impl ExecutionContext {
                 let reg = Self::read_u8(bytecode, pc)?;
                 let symbol_id = Self::read_u32(bytecode, pc)?;
                 let value = self.register_stack[reg_base + reg as usize];
-                let module_id = self.current_module;
+                let module_id = self.frame_module();
+                self.check_macro_redefinition(module_id, symbol_id, value);
                 self.vm.update_module(module_id, symbol_id, value);
                 Ok(InstructionResult::Continue)
             }
+            Opcode::RemoveMacro => {
+                let symbol_id = Self::read_u32(bytecode, pc)?;
+                let module_id = self.frame_module();
+                self.remove_macro(module_id, symbol_id)?;
+                Ok(InstructionResult::Continue)
+            }
+            Opcode::StoreMeta => {
+                let meta_reg = Self::read_u8(bytecode, pc)?;
+                let symbol_id = Self::read_u32(bytecode, pc)?;
+                let private = Self::read_u8(bytecode, pc)? != 0;
+                let meta = self.register_stack[reg_base + meta_reg as usize];
+                // A map, or nil when the definition has no metadata
+                let meta = matches!(meta, ValueRef::Heap(_)).then_some(meta);
+                let module_id = self.frame_module();
+                self.vm.module_registry.write().set_metadata(module_id, symbol_id, meta, private);
+                Ok(InstructionResult::Continue)
+            }
             Opcode::Add => {
                 let result_reg = Self::read_u8(bytecode, pc)?;
                 let left_reg = Self::read_u8(bytecode, pc)?;
@@ -963,6 +1270,9 @@ impl ExecutionContext {
             }
             Opcode::Jump => {
                 let offset = Self::read_i16(bytecode, pc)?;
+                if offset < 0 {
+                    self.safepoint_pending = true;
+                }
                 // Use current PC (after reading offset) as base for jump
                 *pc = (*pc as i32 + offset as i32) as usize;
                 Ok(InstructionResult::Continue)
@@ -972,6 +1282,9 @@ impl ExecutionContext {
                 let offset = Self::read_i16(bytecode, pc)?;
                 let test_value = self.register_stack[reg_base + test_reg as usize];
                 if test_value.is_truthy() {
+                    if offset < 0 {
+                        self.safepoint_pending = true;
+                    }
                     *pc = (*pc as i32 + offset as i32) as usize; // Fixed: (*pc) not (pc*)
                 }
                 Ok(InstructionResult::Continue)
@@ -983,22 +1296,49 @@ impl ExecutionContext {
                 let test_value = self.register_stack[reg_base + test_reg as usize];
 
                 if !test_value.is_truthy() {
+                    if offset < 0 {
+                        self.safepoint_pending = true;
+                    }
                     let new_pc = (*pc as i32 + offset as i32) as usize;
                     *pc = new_pc;
                 }
 
                 Ok(InstructionResult::Continue)
             }
+            Opcode::JumpTable => {
+                let value_reg = Self::read_u8(bytecode, pc)?;
+                let table_index = Self::read_u8(bytecode, pc)?;
+                let count = Self::read_u8(bytecode, pc)? as usize;
+
+                // The table maps each key to the offset slot of its clause; a miss takes the default slot
+                let value = self.register_stack[reg_base + value_reg as usize];
+                let table = constants
+                    .get(table_index as usize)
+                    .and_then(|table| table.heap_object(TypeTag::Map))
+                    .ok_or("JumpTable: the table constant is not a map")?;
+                let slot = self
+                    .vm
+                    .hashmap_get(table, &value)
+                    .and_then(|index| index.get_number())
+                    .map_or(count, |index| (index as usize).min(count));
+
+                *pc += slot * 2;
+                let offset = Self::read_i16(bytecode, pc)?;
+                *pc = (*pc as i32 + offset as i32) as usize;
+                Ok(InstructionResult::Continue)
+            }
             Opcode::Call => {
                 let func_reg = Self::read_u8(bytecode, pc)?;
                 let arg_count = Self::read_u8(bytecode, pc)?;
                 let _result_reg = Self::read_u8(bytecode, pc)?; // Ignored - always use reg 0
+                self.safepoint_pending = true;
 
                 let func_value = self.register_stack[reg_base + func_reg as usize];
+                let module_id = self.frame_module();
 
                 let frame = Self::setup_function_call(
                     &mut self.register_stack,
-                    self.current_module,
+                    module_id,
                     func_value,
                     func_reg,
                     arg_count,
@@ -1067,8 +1407,19 @@ impl ExecutionContext {
 
                 let result = if left == right {
                     ValueRef::boolean(true)
-                } else {
+                } else if !matches!((left, right), (ValueRef::Heap(_), ValueRef::Heap(_))) {
                     ValueRef::boolean(false)
+                } else {
+                    // Lazy seqs are realised to be compared; roots taken meanwhile
+                    // sit above the frame's registers, as for a native call
+                    let vm = self.vm.clone();
+                    let roots_start = self.register_stack.len();
+                    let equal = NativeContext::with_caller(&vm, self).values_equal(left, right);
+                    self.register_stack.truncate(roots_start);
+                    match equal {
+                        Ok(equal) => ValueRef::boolean(equal),
+                        Err(error) => error,
+                    }
                 };
 
                 self.register_stack[reg_base + result_reg as usize] = result;
@@ -1096,6 +1447,26 @@ impl ExecutionContext {
                     captures,
                 })
             }
+            Opcode::MakeMacro => {
+                let dest_reg = Self::read_u8(bytecode, pc)?;
+                let expander_reg = Self::read_u8(bytecode, pc)?;
+                let is_variadic = Self::read_u8(bytecode, pc)? != 0;
+                let param_count = Self::read_u8(bytecode, pc)?;
+                let mut params = Vec::with_capacity(param_count as usize);
+                for _ in 0..param_count {
+                    params.push(Self::read_u32(bytecode, pc)?);
+                }
+
+                let macro_def = Macro {
+                    params,
+                    expander: self.register_stack[reg_base + expander_reg as usize],
+                    is_variadic,
+                    module: self.frame_module(),
+                };
+                let macro_obj = self.vm.alloc_macro(macro_def);
+                self.register_stack[reg_base + dest_reg as usize] = ValueRef::Heap(GcPtr::new(macro_obj));
+                Ok(InstructionResult::Continue)
+            }
             Opcode::LoadUpvalue => {
                 let dest_reg = Self::read_u8(bytecode, pc)?;
                 let upvalue_index = Self::read_u8(bytecode, pc)?;
@@ -1145,38 +1516,58 @@ impl ExecutionContext {
                 Ok(InstructionResult::Continue)
             }
             Opcode::StoreLocal => todo!(),
-            Opcode::AddImm8 => todo!(),
-            Opcode::SubImm8 => todo!(),
-            Opcode::MulImm8 => todo!(),
-            Opcode::DivImm8 => todo!(),
-            Opcode::TailCall => {
-                let func_reg = Self::read_u8(bytecode, pc)?;
-                let arg_count = Self::read_u8(bytecode, pc)?;
+            Opcode::AddImm8 | Opcode::SubImm8 | Opcode::MulImm8 | Opcode::DivImm8 => {
+                let result_reg = Self::read_u8(bytecode, pc)?;
+                let source_reg = Self::read_u8(bytecode, pc)?;
+                let immediate = Self::read_u8(bytecode, pc)? as f64;
+
+                let source = self.register_stack[reg_base + source_reg as usize];
+                let source_num = Self::extract_number(source)?;
+                let result = match opcode {
+                    Opcode::AddImm8 => source_num + immediate,
+                    Opcode::SubImm8 => source_num - immediate,
+                    Opcode::MulImm8 => source_num * immediate,
+                    _ if immediate == 0.0 => return Err("Division by zero".to_string()),
+                    _ => source_num / immediate,
+                };
+                self.register_stack[reg_base + result_reg as usize] = ValueRef::number(result);
+                Ok(InstructionResult::Continue)
+            }
+            Opcode::Mod => {
+                let result_reg = Self::read_u8(bytecode, pc)?;
+                let left_reg = Self::read_u8(bytecode, pc)?;
+                let right_reg = Self::read_u8(bytecode, pc)?;
 
-                let func = self.register_stack[reg_base + func_reg as usize];
+                let left = self.register_stack[reg_base + left_reg as usize];
+                let right = self.register_stack[reg_base + right_reg as usize];
+                let left_num = Self::extract_number(left)?;
+                let right_num = Self::extract_number(right)?;
 
-                // For tail calls, we reuse the current stack frame
-                // Move arguments to the beginning of the current register window
-                for i in 0..arg_count {
-                    let arg_reg = func_reg + 1 + i; // Args follow function register
-                    let arg_value = self.register_stack[reg_base + arg_reg as usize];
-                    self.register_stack[reg_base + i as usize] = arg_value;
+                if right_num == 0.0 {
+                    return Err("Division by zero".to_string());
                 }
 
-                // Set up for function call - this replaces the current frame
-                match func {
-                    ValueRef::Heap(gc_ptr) => {
-                        // Handle compiled functions, closures, etc.
-                        // This would integrate with existing function call logic
-                        // but without creating a new stack frame
+                let result = ValueRef::number(floored_mod(left_num, right_num));
+                self.register_stack[reg_base + result_reg as usize] = result;
+                Ok(InstructionResult::Continue)
+            }
+            Opcode::TailCall => {
+                let func_reg = Self::read_u8(bytecode, pc)?;
+                let arg_count = Self::read_u8(bytecode, pc)?;
+                self.safepoint_pending = true;
 
-                        // For now, let's handle the basic case
-                        todo!("Handle compiled function tail calls")
-                    }
-                    _ => {
-                        return Err("TailCall: not a function".to_string());
-                    }
-                }
+                let func_value = self.register_stack[reg_base + func_reg as usize];
+                let module_id = self.frame_module();
+
+                let frame = Self::setup_function_call(
+                    &mut self.register_stack,
+                    module_id,
+                    func_value,
+                    func_reg,
+                    arg_count,
+                    reg_base,
+                )?;
+                Ok(InstructionResult::TailCall(frame))
             }
             Opcode::CallDynamic => todo!(),
             Opcode::TailCallDynamic => todo!(),
@@ -1331,6 +1722,94 @@ impl ExecutionContext {
                     Err("Expected future".to_string())
                 }
             }
+            Opcode::EnterModule => {
+                let module_id = Self::read_u32(bytecode, pc)?;
+                let public = Self::read_symbol_list(bytecode, pc)?;
+                {
+                    let mut registry = self.vm.module_registry.write();
+                    match self.loading_file() {
+                        Some(file) => {
+                            registry.enter_module(module_id, public, SerializedModuleSource::BlinkFile(file));
+                            registry.record_module_file(module_id, file);
+                        }
+                        None => registry.enter_module(module_id, public, SerializedModuleSource::Repl),
+                    }
+                }
+
+                // Later top-level forms compile into the module, and the rest of
+                // this one runs in it
+                self.current_module = module_id;
+                if let Some(frame) = self.call_stack.last_mut() {
+                    frame.current_module = module_id;
+                }
+                Ok(InstructionResult::Continue)
+            }
+            Opcode::ImportModule => {
+                let from = Self::read_u32(bytecode, pc)?;
+                let alias = Self::read_u32(bytecode, pc)?;
+                let mode = Self::read_u8(bytecode, pc)?;
+                let names = Self::read_symbol_list(bytecode, pc)?;
+
+                let alias = (alias != NO_SYMBOL).then_some(alias);
+                let names = match (mode, names) {
+                    (IMPORT_REFER_ALL, _) => ImportNames::All,
+                    (_, Some(names)) => ImportNames::Only(names),
+                    (_, None) => ImportNames::None,
+                };
+                // A module not defined yet is loaded from its file on the module path
+                if self.is_loading_module(from) {
+                    return Err(self.module_cycle_error(from));
+                }
+                if self.vm.module_registry.read().get_module(from).is_none() {
+                    self.load_module(from)?;
+                }
+
+                let into = self.frame_module();
+                let imported = self.vm.module_registry.write().import(into, from, alias, names);
+                imported.map_err(|error| {
+                    let symbol_table = self.vm.symbol_table.read();
+                    let name = |id: u32| symbol_table.get_symbol(id).unwrap_or_default();
+                    match error {
+                        ImportError::NoModule(module) => format!("imp: no module named {}", name(module)),
+                        ImportError::Undefined(module, symbol) => {
+                            format!("imp: module {} does not define {}", name(module), name(symbol))
+                        }
+                        ImportError::Private(module, symbol) => {
+                            format!("imp: {} is private to module {}", name(symbol), name(module))
+                        }
+                    }
+                })?;
+                Ok(InstructionResult::Continue)
+            }
+            Opcode::LoadFile => {
+                let dest_reg = Self::read_u8(bytecode, pc)?;
+                let path_reg = Self::read_u8(bytecode, pc)?;
+
+                let path_value = self.register_stack[reg_base + path_reg as usize];
+                let path = path_value
+                    .get_string()
+                    .ok_or_else(|| format!("load expects a file path string, got {}", path_value))?;
+                let file = self.resolve_load_path(&path)?;
+                let result = self.load_file(&file, None)?;
+
+                self.register_stack[reg_base + dest_reg as usize] = result;
+                Ok(InstructionResult::Continue)
+            }
+            Opcode::ReloadModule => {
+                let dest_reg = Self::read_u8(bytecode, pc)?;
+                let module_reg = Self::read_u8(bytecode, pc)?;
+
+                let module_value = self.register_stack[reg_base + module_reg as usize];
+                let module_id = module_value
+                    .get_symbol()
+                    .ok_or_else(|| format!("reload expects a module name symbol, got {}", module_value))?;
+                let reloaded = self.reload_module(module_id)?;
+
+                let names = reloaded.into_iter().map(ValueRef::symbol).collect();
+                let result = ValueRef::Heap(GcPtr::new(self.vm.alloc_vec(names)));
+                self.register_stack[reg_base + dest_reg as usize] = result;
+                Ok(InstructionResult::Continue)
+            }
             Opcode::Suspend => {
                 // Suspend current execution - this will need coordination with scheduler
                 // For now, just continue execution
@@ -1354,12 +1833,54 @@ impl ExecutionContext {
         arg_count: u8,
         caller_reg_base: usize,
     ) -> Result<CallFrame, String> {
+        // Keywords, maps and sets look themselves up: (:k m) and (m :k) both run
+        // (get m :k), with an optional not-found value after the key
+        let is_keyword = func_value.get_keyword().is_some();
+        let is_lookup = is_keyword
+            || matches!(
+                func_value,
+                ValueRef::Heap(heap)
+                    if matches!(heap.type_tag(), TypeTag::Map | TypeTag::Set | TypeTag::SortedMap | TypeTag::SortedSet)
+            );
+        if is_lookup {
+            if arg_count == 0 || arg_count > 2 {
+                return Err(format!(
+                    "A {} called as a function expects 1 or 2 arguments, got {}",
+                    func_value.type_name(),
+                    arg_count
+                ));
+            }
+            let args_start = caller_reg_base + func_reg as usize + 1;
+            let (coll, key) = if is_keyword {
+                (register_stack[args_start], func_value)
+            } else {
+                (func_value, register_stack[args_start])
+            };
+            let not_found = (arg_count == 2).then(|| register_stack[args_start + 1]);
+
+            let reg_start = register_stack.len();
+            register_stack.push(ValueRef::nil());
+            register_stack.push(coll);
+            register_stack.push(key);
+            register_stack.extend(not_found);
+            return Ok(CallFrame {
+                func: FunctionRef::Native(lookup_native()),
+                pc: 0,
+                reg_start,
+                reg_count: (register_stack.len() - reg_start) as u8,
+                current_module,
+            });
+        }
+
         let (func_ref, module) = match func_value {
             ValueRef::Heap(heap) => {
                 let type_tag = heap.type_tag();
                 let obj_ref = heap.0;
                 match type_tag {
-                    TypeTag::UserDefinedFunction | TypeTag::Macro => {
+                    TypeTag::Macro => {
+                        return Err("A macro cannot be called as a function; it is expanded where it is used".to_string())
+                    }
+                    TypeTag::UserDefinedFunction => {
                         let compiled_func = heap.read_callable();
                         let module = compiled_func.module;
                         (
@@ -1489,6 +2010,15 @@ impl ExecutionContext {
         Ok(value)
     }
 
+    // A u16 count followed by that many symbol ids; NO_LIST for no list at all
+    fn read_symbol_list(bytecode: &[u8], pc: &mut usize) -> Result<Option<Vec<u32>>, String> {
+        let count = Self::read_u16(bytecode, pc)?;
+        if count == NO_LIST {
+            return Ok(None);
+        }
+        (0..count).map(|_| Self::read_u32(bytecode, pc)).collect::<Result<Vec<_>, _>>().map(Some)
+    }
+
     fn read_u16(bytecode: &[u8], pc: &mut usize) -> Result<u16, String> {
         if *pc + 1 >= bytecode.len() {
             return Err("Unexpected end of bytecode".to_string());
@@ -1535,6 +2065,28 @@ impl ExecutionContext {
     }
 }
 
+impl FunctionCaller for ExecutionContext<'_> {
+    fn call_function(&mut self, func: ValueRef, args: Vec<ValueRef>) -> Result<ValueRef, BlinkError> {
+        ExecutionContext::call_function(self, func, args)
+    }
+
+    fn root_values(&mut self, values: &[ValueRef]) -> usize {
+        // Above the calling frame's registers, where the GC scans them; call_native
+        // drops them once the native returns
+        let slot = self.register_stack.len();
+        self.register_stack.extend_from_slice(values);
+        slot
+    }
+
+    fn set_root(&mut self, slot: usize, value: ValueRef) {
+        self.register_stack[slot] = value;
+    }
+
+    fn current_module(&self) -> u32 {
+        self.frame_module()
+    }
+}
+
 // Bytecode disassembler for debugging
 pub fn disassemble_bytecode(bytecode: &[u8], constants: &[ValueRef]) -> String {
     let mut result = String::new();
@@ -1572,7 +2124,7 @@ pub fn disassemble_bytecode(bytecode: &[u8], constants: &[ValueRef]) -> String {
                     }
                 }
 
-                Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::Div => {
+                Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::Div | Opcode::Mod => {
                     if pc + 2 < bytecode.len() {
                         let result_reg = bytecode[pc];
                         let left_reg = bytecode[pc + 1];
@@ -1583,6 +2135,77 @@ pub fn disassemble_bytecode(bytecode: &[u8], constants: &[ValueRef]) -> String {
                     }
                 }
 
+                Opcode::AddImm8 | Opcode::SubImm8 | Opcode::MulImm8 | Opcode::DivImm8 => {
+                    if pc + 2 < bytecode.len() {
+                        let result_reg = bytecode[pc];
+                        let source_reg = bytecode[pc + 1];
+                        let immediate = bytecode[pc + 2];
+                        result.push_str(&format!(" r{}, r{}, {}", result_reg, source_reg, immediate));
+                        pc += 3;
+                    }
+                }
+
+                Opcode::EnterModule | Opcode::ImportModule => {
+                    // Module id; ImportModule adds an alias and a mode; then the symbol list
+                    let fixed = if opcode == Opcode::EnterModule { 4 } else { 9 };
+                    if pc + fixed + 1 < bytecode.len() {
+                        let module = u32::from_le_bytes([bytecode[pc], bytecode[pc + 1], bytecode[pc + 2], bytecode[pc + 3]]);
+                        result.push_str(&format!(" module {}", module));
+                        pc += fixed;
+                        let count = u16::from_le_bytes([bytecode[pc], bytecode[pc + 1]]);
+                        pc += 2;
+                        if count != NO_LIST {
+                            result.push_str(&format!(", {} names", count));
+                            pc += 4 * count as usize;
+                        }
+                    }
+                }
+
+                Opcode::StoreMeta => {
+                    if pc + 5 < bytecode.len() {
+                        let symbol_id = u32::from_le_bytes([bytecode[pc + 1], bytecode[pc + 2], bytecode[pc + 3], bytecode[pc + 4]]);
+                        result.push_str(&format!(" r{}, sym {}, private {}", bytecode[pc], symbol_id, bytecode[pc + 5]));
+                        pc += 6;
+                    }
+                }
+
+                Opcode::RemoveMacro => {
+                    if pc + 3 < bytecode.len() {
+                        let symbol_id = u32::from_le_bytes([bytecode[pc], bytecode[pc + 1], bytecode[pc + 2], bytecode[pc + 3]]);
+                        result.push_str(&format!(" sym {}", symbol_id));
+                        pc += 4;
+                    }
+                }
+
+                Opcode::MakeMacro => {
+                    if pc + 3 < bytecode.len() {
+                        let param_count = bytecode[pc + 3] as usize;
+                        result.push_str(&format!(
+                            " r{}, r{}, variadic {}, {} params",
+                            bytecode[pc], bytecode[pc + 1], bytecode[pc + 2], param_count
+                        ));
+                        pc += 4 + param_count * 4;
+                    }
+                }
+
+                Opcode::JumpTable => {
+                    if pc + 2 < bytecode.len() {
+                        let count = bytecode[pc + 2] as usize;
+                        result.push_str(&format!(
+                            " r{}, const[{}], {} clauses and a default",
+                            bytecode[pc], bytecode[pc + 1], count
+                        ));
+                        pc += 3 + (count + 1) * 2;
+                    }
+                }
+
+                Opcode::LoadFile | Opcode::ReloadModule => {
+                    if pc + 1 < bytecode.len() {
+                        result.push_str(&format!(" r{}, r{}", bytecode[pc], bytecode[pc + 1]));
+                        pc += 2;
+                    }
+                }
+
                 Opcode::Return => {
                     if pc < bytecode.len() {
                         let reg = bytecode[pc];
@@ -1637,3 +2260,43 @@ pub fn test_compact_bytecode() -> Result<(), String> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{context, eval, run};
+
+    #[test]
+    fn runtime_errors_carry_a_stack_trace() {
+        let mut ctx = context("stack-trace");
+        eval(
+            &mut ctx,
+            "(def inner (fn [x]
+               (+ 1 (x 1))))
+             (def outer (fn [y]
+               (+ 1 (inner y))))",
+        );
+        let error = run(&mut ctx, "(outer 5)").unwrap_err();
+
+        let frames: Vec<(Option<&str>, Option<usize>)> = error
+            .stack_trace
+            .iter()
+            .map(|frame| (frame.function.as_deref(), frame.line))
+            .collect();
+        assert_eq!(frames[..2], [(Some("inner"), Some(2)), (Some("outer"), Some(4))], "{}", error);
+        assert!(error.stack_trace.iter().all(|frame| frame.module == "stack-trace"));
+        assert_eq!(error.pos.map(|pos| pos.start.line), Some(2));
+    }
+
+    #[test]
+    fn tail_calls_keep_their_line() {
+        let mut ctx = context("stack-trace-tail");
+        eval(
+            &mut ctx,
+            "(def call-it (fn [x]
+               (x 1)))",
+        );
+        let error = run(&mut ctx, "(call-it 5)").unwrap_err();
+        let frame = &error.stack_trace[0];
+        assert_eq!((frame.function.as_deref(), frame.line), (Some("call-it"), Some(2)), "{}", error);
+    }
+}
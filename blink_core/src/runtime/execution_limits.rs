@@ -0,0 +1,190 @@
+use std::time::{Duration, Instant};
+
+use crate::error::{BlinkError, LimitKind};
+use crate::runtime::BlinkActivePlan;
+
+// How many instructions run between clock reads for the deadline check
+const DEADLINE_CHECK_INTERVAL: u64 = 1024;
+
+/// Resource limits applied to a single top-level evaluation.
+/// `None` means unlimited.
+#[derive(Clone, Debug, Default)]
+pub struct ExecutionLimits {
+    pub fuel: Option<u64>,
+    pub timeout: Option<Duration>,
+    pub max_call_depth: Option<usize>,
+    pub max_alloc_bytes: Option<usize>,
+}
+
+impl ExecutionLimits {
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    pub fn with_fuel(mut self, fuel: u64) -> Self {
+        self.fuel = Some(fuel);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_max_call_depth(mut self, depth: usize) -> Self {
+        self.max_call_depth = Some(depth);
+        self
+    }
+
+    pub fn with_max_alloc_bytes(mut self, bytes: usize) -> Self {
+        self.max_alloc_bytes = Some(bytes);
+        self
+    }
+}
+
+/// Bookkeeping for the evaluation currently running under a set of limits
+#[derive(Clone, Debug)]
+pub struct LimitTracker {
+    instructions: u64,
+    deadline: Option<Instant>,
+    alloc_baseline: usize,
+}
+
+impl LimitTracker {
+    pub fn start(limits: &ExecutionLimits) -> Self {
+        Self {
+            instructions: 0,
+            deadline: limits.timeout.map(|timeout| Instant::now() + timeout),
+            alloc_baseline: BlinkActivePlan::allocated_bytes(),
+        }
+    }
+
+    pub fn instructions_executed(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn bytes_allocated(&self) -> usize {
+        BlinkActivePlan::allocated_bytes().wrapping_sub(self.alloc_baseline)
+    }
+
+    /// Charge one instruction and check every configured limit
+    pub fn tick(&mut self, limits: &ExecutionLimits, call_depth: usize) -> Result<(), BlinkError> {
+        self.instructions += 1;
+
+        if let Some(fuel) = limits.fuel {
+            if self.instructions > fuel {
+                return Err(BlinkError::limit_exceeded(
+                    LimitKind::Fuel,
+                    format!("ran out of fuel after {} instructions", fuel),
+                ));
+            }
+        }
+
+        if let Some(max_depth) = limits.max_call_depth {
+            if call_depth > max_depth {
+                return Err(BlinkError::limit_exceeded(
+                    LimitKind::CallDepth,
+                    format!("call depth {} exceeds maximum of {}", call_depth, max_depth),
+                ));
+            }
+        }
+
+        if let Some(max_bytes) = limits.max_alloc_bytes {
+            let allocated = self.bytes_allocated();
+            if allocated > max_bytes {
+                return Err(BlinkError::limit_exceeded(
+                    LimitKind::Allocation,
+                    format!("allocated {} bytes, limit is {}", allocated, max_bytes),
+                ));
+            }
+        }
+
+        if let Some(deadline) = self.deadline {
+            if self.instructions % DEADLINE_CHECK_INTERVAL == 0 && Instant::now() >= deadline {
+                return Err(BlinkError::limit_exceeded(
+                    LimitKind::Deadline,
+                    format!("deadline passed after {} instructions", self.instructions),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::BlinkErrorType;
+    use crate::test_support::{context, eval, run};
+
+    // Run code under limits and return the limit it exceeded
+    fn exceeded(module: &str, limits: ExecutionLimits, code: &str) -> LimitKind {
+        let mut ctx = context(module);
+        eval(&mut ctx, "(def deep (fn [n] (if (= n 0) 0 (+ 1 (deep (- n 1))))))");
+        ctx.set_limits(limits);
+        let error = run(&mut ctx, code).unwrap_err();
+
+        // The context stays usable once the limits are lifted
+        ctx.set_limits(ExecutionLimits::unlimited());
+        assert_eq!(eval(&mut ctx, "(deep 3)"), "3");
+
+        match error.error_type {
+            BlinkErrorType::LimitExceeded { limit } => limit,
+            other => panic!("expected a limit error, got {}: {}", other, error.message),
+        }
+    }
+
+    #[test]
+    fn fuel_limits_instructions() {
+        let limits = ExecutionLimits::unlimited().with_fuel(10_000);
+        let limit = exceeded("limits-fuel", limits, "(loop [i 0] (recur (+ i 1)))");
+        assert_eq!(limit, LimitKind::Fuel);
+    }
+
+    #[test]
+    fn deadline_limits_wall_clock_time() {
+        let limits = ExecutionLimits::unlimited().with_timeout(Duration::from_millis(50));
+        let limit = exceeded("limits-deadline", limits, "(loop [i 0] (recur (+ i 1)))");
+        assert_eq!(limit, LimitKind::Deadline);
+    }
+
+    #[test]
+    fn call_depth_limits_recursion() {
+        let limits = ExecutionLimits::unlimited().with_max_call_depth(50);
+        let limit = exceeded("limits-depth", limits, "(deep 1000)");
+        assert_eq!(limit, LimitKind::CallDepth);
+    }
+
+    #[test]
+    fn allocation_limits_bytes_allocated() {
+        let limits = ExecutionLimits::unlimited().with_max_alloc_bytes(64 * 1024);
+        let limit = exceeded("limits-alloc", limits, "(loop [items (vector)] (recur (conj items items)))");
+        assert_eq!(limit, LimitKind::Allocation);
+    }
+
+    #[test]
+    fn evaluations_within_limits_succeed() {
+        let mut ctx = context("limits-within");
+        ctx.set_limits(ExecutionLimits::unlimited().with_fuel(10_000).with_max_call_depth(50));
+        assert_eq!(eval(&mut ctx, "(+ 1 2)"), "3");
+        assert_eq!(eval(&mut ctx, "(loop [i 0] (if (< i 10) (recur (+ i 1)) i))"), "10");
+    }
+
+    #[test]
+    fn macros_expand_after_an_evaluation_ran_out_of_fuel() {
+        let mut ctx = context("limits-macro");
+        eval(&mut ctx, "(def add-twice (macro [x] (list '+ x x)))");
+        ctx.set_limits(ExecutionLimits::unlimited().with_fuel(10_000));
+        let error = run(&mut ctx, "(loop [i 0] (recur (+ i 1)))").unwrap_err();
+        assert!(matches!(error.error_type, BlinkErrorType::LimitExceeded { limit: LimitKind::Fuel }));
+
+        // The expansion runs the macro body with fresh fuel, not what was left
+        assert_eq!(eval(&mut ctx, "(add-twice 4)"), "8");
+        // An interrupt sent before the evaluation stops it, expansion included, once
+        ctx.cancel_token.cancel();
+        let error = run(&mut ctx, "(add-twice 5)").unwrap_err();
+        assert!(error.message.contains("evaluation stopped"), "{}", error.message);
+        assert_eq!(eval(&mut ctx, "(add-twice 5)"), "10");
+    }
+}
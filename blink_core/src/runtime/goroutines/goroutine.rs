@@ -66,7 +66,10 @@ impl Goroutine {
                 let type_tag = heap.type_tag();
                 let obj_ref = heap.0;
                 match type_tag {
-                    TypeTag::UserDefinedFunction | TypeTag::Macro => {
+                    TypeTag::Macro => {
+                        Err("A macro cannot be called as a function; it is expanded where it is used".to_string())
+                    }
+                    TypeTag::UserDefinedFunction => {
                         let compiled_func = heap.read_callable();
                         let module = compiled_func.module;
                         Ok(CallFrame {
@@ -268,7 +268,7 @@ impl GoroutineScheduler for SingleThreadedScheduler {
         };
 
         self.channels.insert(id, entry);
-        ChannelHandle { id }
+        ChannelHandle { id, generation: 0 } // CHKFIX
     }
 
     fn channel_send(&mut self, handle: ChannelHandle, value: ValueRef) -> SchedulerAction {
@@ -0,0 +1,568 @@
+// blink_core/src/runtime/heap/hamt.rs
+
+use mmtk::util::ObjectReference;
+
+use crate::runtime::heap::trie_node::{new_edit, node_value, read_u32_field, read_value_field, write_u32_field, TrieNode};
+use crate::runtime::{BlinkActivePlan, BlinkObjectModel, BlinkVM, NoGcSection, TypeTag};
+use crate::value::{value_hash, ValueRef};
+
+// Hash array mapped trie in the CHAMP layout, backing both maps and sets (a set
+// maps every member to itself). Each node consumes 5 bits of a 32 bit hash:
+// `datamap` marks fragments holding an inline [key value] pair, `nodemap` marks
+// fragments holding a child node. Pairs come first in the slots, children after.
+// Keys whose full hashes are equal end up in a collision node: both bitmaps
+// zero and a flat list of pairs.
+//
+// Updates copy the path to the changed node. Removals keep the trie canonical by
+// pulling a child that is left with a single pair back up into its parent.
+//
+// Header: [count: u32][edit: u32][root: ValueRef], root is nil when empty.
+// A non-zero edit marks a transient; see trie_node.rs.
+//
+// The nodes an update allocates are only held in locals until the new root is
+// written to a header, so updates run in a NoGcSection.
+
+const BITS: u32 = 5;
+const MASK: u32 = (1 << BITS) - 1;
+const HASH_BITS: u32 = 32;
+
+const COUNT_OFFSET: usize = 0;
+const EDIT_OFFSET: usize = std::mem::size_of::<u32>();
+const ROOT_OFFSET: usize = 2 * std::mem::size_of::<u32>();
+const HEADER_SIZE: usize = ROOT_OFFSET + std::mem::size_of::<ValueRef>();
+
+#[derive(Clone, Copy)]
+struct HamtState {
+    count: u32,
+    edit: u32,
+    root: Option<TrieNode>,
+}
+
+pub(crate) fn hash_key(key: &ValueRef) -> u32 {
+    let hash = value_hash(key);
+    (hash ^ (hash >> 32)) as u32
+}
+
+fn bit_for(hash: u32, shift: u32) -> u32 {
+    1 << ((hash >> shift) & MASK)
+}
+
+// Position of `bit` among the bits set in `bitmap`
+fn index_in(bitmap: u32, bit: u32) -> usize {
+    (bitmap & (bit - 1)).count_ones() as usize
+}
+
+fn is_collision(node: TrieNode) -> bool {
+    node.datamap() == 0 && node.nodemap() == 0 && node.slot_count() > 0
+}
+
+fn child_slot(node: TrieNode, bit: u32) -> usize {
+    2 * node.datamap().count_ones() as usize + index_in(node.nodemap(), bit)
+}
+
+// Number of pairs held directly in the node
+fn pair_count(node: TrieNode) -> usize {
+    if is_collision(node) {
+        node.slot_count() / 2
+    } else {
+        node.datamap().count_ones() as usize
+    }
+}
+
+impl BlinkVM {
+    // === Header access ===
+
+    fn read_hamt(&self, object: ObjectReference) -> HamtState {
+        HamtState {
+            count: read_u32_field(object, COUNT_OFFSET),
+            edit: read_u32_field(object, EDIT_OFFSET),
+            root: match read_value_field(object, ROOT_OFFSET) {
+                ValueRef::Heap(gc_ptr) => Some(TrieNode(gc_ptr.0)),
+                _ => None,
+            },
+        }
+    }
+
+    fn write_hamt(&self, object: ObjectReference, state: &HamtState) {
+        write_u32_field(object, COUNT_OFFSET, state.count);
+        write_u32_field(object, EDIT_OFFSET, state.edit);
+        let root = state.root.map(node_value).unwrap_or_else(ValueRef::nil);
+        self.write_value_field(object, ROOT_OFFSET, root);
+    }
+
+    fn alloc_hamt_header(&self, type_tag: TypeTag, state: &HamtState) -> ObjectReference {
+        let object = self.with_mutator(|mutator| {
+            BlinkActivePlan::alloc_object(mutator, &type_tag, &HEADER_SIZE)
+        });
+        self.write_hamt(object, state);
+        object
+    }
+
+    // === Operations on map and set objects ===
+
+    /// Allocate a map or set object holding `pairs`, built through a transient
+    pub(crate) fn alloc_hamt(&self, type_tag: TypeTag, pairs: impl IntoIterator<Item = (ValueRef, ValueRef)>) -> ObjectReference {
+        let _no_gc = NoGcSection::enter();
+        let mut state = HamtState { count: 0, edit: new_edit(), root: None };
+        for (key, value) in pairs {
+            self.hamt_assoc_into(&mut state, key, value);
+        }
+        state.edit = 0;
+        self.alloc_hamt_header(type_tag, &state)
+    }
+
+    pub(crate) fn hamt_count(&self, object: ObjectReference) -> u32 {
+        read_u32_field(object, COUNT_OFFSET)
+    }
+
+    pub(crate) fn hamt_lookup(&self, object: ObjectReference, key: &ValueRef) -> Option<ValueRef> {
+        let root = self.read_hamt(object).root?;
+        self.hamt_find(root, 0, hash_key(key), key)
+    }
+
+    pub(crate) fn hamt_entries(&self, object: ObjectReference) -> Vec<(ValueRef, ValueRef)> {
+        let state = self.read_hamt(object);
+        let mut entries = Vec::with_capacity(state.count as usize);
+        if let Some(root) = state.root {
+            self.hamt_collect(root, &mut entries);
+        }
+        entries
+    }
+
+    pub(crate) fn hamt_with(&self, object: ObjectReference, key: ValueRef, value: ValueRef) -> ObjectReference {
+        let _no_gc = NoGcSection::enter();
+        let mut state = self.read_hamt(object);
+        debug_assert_eq!(state.edit, 0, "persistent update on a transient collection");
+        state.edit = 0;
+        self.hamt_assoc_into(&mut state, key, value);
+        self.alloc_hamt_header(BlinkObjectModel::get_type_tag(object), &state)
+    }
+
+    /// Remove `key`; returns the same object when the key is absent
+    pub(crate) fn hamt_without(&self, object: ObjectReference, key: &ValueRef) -> ObjectReference {
+        let _no_gc = NoGcSection::enter();
+        let mut state = self.read_hamt(object);
+        debug_assert_eq!(state.edit, 0, "persistent update on a transient collection");
+        state.edit = 0;
+        if self.hamt_dissoc_from(&mut state, key) {
+            self.alloc_hamt_header(BlinkObjectModel::get_type_tag(object), &state)
+        } else {
+            object
+        }
+    }
+
+    pub(crate) fn hamt_is_transient(&self, object: ObjectReference) -> bool {
+        read_u32_field(object, EDIT_OFFSET) != 0
+    }
+
+    pub(crate) fn hamt_transient(&self, object: ObjectReference) -> ObjectReference {
+        let mut state = self.read_hamt(object);
+        state.edit = new_edit();
+        self.alloc_hamt_header(BlinkObjectModel::get_type_tag(object), &state)
+    }
+
+    pub(crate) fn hamt_persistent(&self, object: ObjectReference) -> Result<ObjectReference, String> {
+        if !self.hamt_is_transient(object) {
+            return Err("Transient used after persistent! call".to_string());
+        }
+        write_u32_field(object, EDIT_OFFSET, 0);
+        Ok(object)
+    }
+
+    pub(crate) fn hamt_assoc_mut(&self, object: ObjectReference, key: ValueRef, value: ValueRef) -> Result<(), String> {
+        let mut state = self.read_hamt(object);
+        if state.edit == 0 {
+            return Err("Transient used after persistent! call".to_string());
+        }
+        let _no_gc = NoGcSection::enter();
+        self.hamt_assoc_into(&mut state, key, value);
+        self.write_hamt(object, &state);
+        Ok(())
+    }
+
+    pub(crate) fn hamt_dissoc_mut(&self, object: ObjectReference, key: &ValueRef) -> Result<(), String> {
+        let mut state = self.read_hamt(object);
+        if state.edit == 0 {
+            return Err("Transient used after persistent! call".to_string());
+        }
+        let _no_gc = NoGcSection::enter();
+        if self.hamt_dissoc_from(&mut state, key) {
+            self.write_hamt(object, &state);
+        }
+        Ok(())
+    }
+
+    // === Trie operations shared by both modes. Edit 0 copies every touched node. ===
+
+    fn hamt_assoc_into(&self, state: &mut HamtState, key: ValueRef, value: ValueRef) {
+        let hash = hash_key(&key);
+        let mut added = false;
+        let root = match state.root {
+            Some(root) => self.hamt_assoc(state.edit, root, 0, hash, key, value, &mut added),
+            None => {
+                added = true;
+                self.alloc_trie_node(state.edit, bit_for(hash, 0), 0, &[key, value], 2)
+            }
+        };
+        state.root = Some(root);
+        if added {
+            state.count += 1;
+        }
+    }
+
+    fn hamt_dissoc_from(&self, state: &mut HamtState, key: &ValueRef) -> bool {
+        let Some(root) = state.root else {
+            return false;
+        };
+        match self.hamt_dissoc(state.edit, root, 0, hash_key(key), key) {
+            Some(root) => {
+                state.root = if root.slot_count() == 0 { None } else { Some(root) };
+                state.count -= 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn hamt_find(&self, node: TrieNode, shift: u32, hash: u32, key: &ValueRef) -> Option<ValueRef> {
+        if is_collision(node) {
+            return (0..node.slot_count())
+                .step_by(2)
+                .find(|i| node.get(*i) == *key)
+                .map(|i| node.get(i + 1));
+        }
+
+        let bit = bit_for(hash, shift);
+        if node.datamap() & bit != 0 {
+            let index = index_in(node.datamap(), bit);
+            if node.get(2 * index) == *key {
+                return Some(node.get(2 * index + 1));
+            }
+            None
+        } else if node.nodemap() & bit != 0 {
+            let child = node.child(child_slot(node, bit)).expect("nodemap entry holds a node");
+            self.hamt_find(child, shift + BITS, hash, key)
+        } else {
+            None
+        }
+    }
+
+    fn hamt_collect(&self, node: TrieNode, entries: &mut Vec<(ValueRef, ValueRef)>) {
+        let pairs = pair_count(node);
+        for i in 0..pairs {
+            entries.push((node.get(2 * i), node.get(2 * i + 1)));
+        }
+        for slot in 2 * pairs..node.slot_count() {
+            if let Some(child) = node.child(slot) {
+                self.hamt_collect(child, entries);
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn hamt_assoc(&self, edit: u32, node: TrieNode, shift: u32, hash: u32, key: ValueRef, value: ValueRef, added: &mut bool) -> TrieNode {
+        if is_collision(node) {
+            let existing = (0..node.slot_count()).step_by(2).find(|i| node.get(*i) == key);
+            return match existing {
+                Some(index) => self.hamt_set_slot(edit, node, index + 1, value),
+                None => {
+                    *added = true;
+                    let mut slots = node.slots();
+                    slots.push(key);
+                    slots.push(value);
+                    self.alloc_trie_node(edit, 0, 0, &slots, slots.len())
+                }
+            };
+        }
+
+        let bit = bit_for(hash, shift);
+        let (datamap, nodemap) = (node.datamap(), node.nodemap());
+
+        if datamap & bit != 0 {
+            let index = index_in(datamap, bit);
+            let existing_key = node.get(2 * index);
+            if existing_key == key {
+                return self.hamt_set_slot(edit, node, 2 * index + 1, value);
+            }
+
+            // Two keys share this fragment: move both into a new child node
+            *added = true;
+            let existing_value = node.get(2 * index + 1);
+            let child = self.hamt_merge_pairs(
+                edit,
+                shift + BITS,
+                (existing_key, existing_value, hash_key(&existing_key)),
+                (key, value, hash),
+            );
+            let mut slots = node.slots();
+            slots.drain(2 * index..2 * index + 2);
+            let child_index = 2 * (datamap.count_ones() as usize - 1) + index_in(nodemap, bit);
+            slots.insert(child_index, node_value(child));
+            return self.alloc_trie_node(edit, datamap ^ bit, nodemap | bit, &slots, slots.len());
+        }
+
+        if nodemap & bit != 0 {
+            let slot = child_slot(node, bit);
+            let child = node.child(slot).expect("nodemap entry holds a node");
+            let new_child = self.hamt_assoc(edit, child, shift + BITS, hash, key, value, added);
+            if new_child == child {
+                return node;
+            }
+            return self.hamt_set_slot(edit, node, slot, node_value(new_child));
+        }
+
+        *added = true;
+        let index = index_in(datamap, bit);
+        let mut slots = node.slots();
+        slots.insert(2 * index, key);
+        slots.insert(2 * index + 1, value);
+        self.alloc_trie_node(edit, datamap | bit, nodemap, &slots, slots.len())
+    }
+
+    // Returns None when the key is absent. The returned node may be empty.
+    fn hamt_dissoc(&self, edit: u32, node: TrieNode, shift: u32, hash: u32, key: &ValueRef) -> Option<TrieNode> {
+        if is_collision(node) {
+            let index = (0..node.slot_count()).step_by(2).find(|i| node.get(*i) == *key)?;
+            let mut slots = node.slots();
+            slots.drain(index..index + 2);
+            return Some(self.alloc_trie_node(edit, 0, 0, &slots, slots.len()));
+        }
+
+        let bit = bit_for(hash, shift);
+        let (datamap, nodemap) = (node.datamap(), node.nodemap());
+
+        if datamap & bit != 0 {
+            let index = index_in(datamap, bit);
+            if node.get(2 * index) != *key {
+                return None;
+            }
+            let mut slots = node.slots();
+            slots.drain(2 * index..2 * index + 2);
+            return Some(self.alloc_trie_node(edit, datamap ^ bit, nodemap, &slots, slots.len()));
+        }
+
+        if nodemap & bit != 0 {
+            let slot = child_slot(node, bit);
+            let child = node.child(slot).expect("nodemap entry holds a node");
+            let new_child = self.hamt_dissoc(edit, child, shift + BITS, hash, key)?;
+
+            let mut slots = node.slots();
+            if new_child.slot_count() == 0 {
+                slots.remove(slot);
+                return Some(self.alloc_trie_node(edit, datamap, nodemap ^ bit, &slots, slots.len()));
+            }
+            if new_child.nodemap() == 0 && pair_count(new_child) == 1 {
+                // Inline the child's last pair here to keep the trie canonical
+                slots.remove(slot);
+                let index = index_in(datamap, bit);
+                slots.insert(2 * index, new_child.get(0));
+                slots.insert(2 * index + 1, new_child.get(1));
+                return Some(self.alloc_trie_node(edit, datamap | bit, nodemap ^ bit, &slots, slots.len()));
+            }
+            return Some(self.hamt_set_slot(edit, node, slot, node_value(new_child)));
+        }
+
+        None
+    }
+
+    // Build the smallest subtree holding two pairs whose hashes agree above `shift`
+    fn hamt_merge_pairs(&self, edit: u32, shift: u32, first: (ValueRef, ValueRef, u32), second: (ValueRef, ValueRef, u32)) -> TrieNode {
+        let (key1, value1, hash1) = first;
+        let (key2, value2, hash2) = second;
+
+        if shift >= HASH_BITS {
+            return self.alloc_trie_node(edit, 0, 0, &[key1, value1, key2, value2], 4);
+        }
+
+        let (bit1, bit2) = (bit_for(hash1, shift), bit_for(hash2, shift));
+        if bit1 == bit2 {
+            let child = self.hamt_merge_pairs(edit, shift + BITS, first, second);
+            return self.alloc_trie_node(edit, 0, bit1, &[node_value(child)], 1);
+        }
+
+        let slots = if bit1 < bit2 {
+            [key1, value1, key2, value2]
+        } else {
+            [key2, value2, key1, value1]
+        };
+        self.alloc_trie_node(edit, bit1 | bit2, 0, &slots, 4)
+    }
+
+    // Replace one slot: in place when the transient owns the node, otherwise on a copy
+    fn hamt_set_slot(&self, edit: u32, node: TrieNode, slot: usize, value: ValueRef) -> TrieNode {
+        if edit != 0 && node.edit() == edit {
+            self.trie_node_set(node, slot, value);
+            node
+        } else {
+            let mut slots = node.slots();
+            slots[slot] = value;
+            self.alloc_trie_node(edit, node.datamap(), node.nodemap(), &slots, slots.len())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use std::sync::OnceLock;
+
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::test_support::vm;
+
+    const PLAIN_KEYS: usize = 48;
+
+    // Keys for the maps under test: small integers, so updates often hit keys
+    // already present, then groups of numbers whose full hashes collide
+    fn key_pool() -> &'static [ValueRef] {
+        static POOL: OnceLock<Vec<ValueRef>> = OnceLock::new();
+        POOL.get_or_init(|| {
+            let mut keys: Vec<ValueRef> = (0..PLAIN_KEYS).map(|n| ValueRef::number(n as f64)).collect();
+            let mut by_hash: HashMap<u32, Vec<ValueRef>> = HashMap::new();
+            let mut collisions = 0;
+            for n in 0.. {
+                let key = ValueRef::number(n as f64 + 0.5);
+                let group = by_hash.entry(hash_key(&key)).or_default();
+                group.push(key);
+                if group.len() == 2 {
+                    keys.extend(group.iter().copied());
+                    collisions += 1;
+                    if collisions == 4 {
+                        break;
+                    }
+                }
+            }
+            keys
+        })
+    }
+
+    #[derive(Clone, Debug)]
+    enum Op {
+        Assoc(usize, u16),
+        Dissoc(usize),
+    }
+
+    fn ops(max: usize) -> impl Strategy<Value = Vec<Op>> {
+        let key = 0..key_pool().len();
+        let op = prop_oneof![
+            3 => (key.clone(), any::<u16>()).prop_map(|(key, value)| Op::Assoc(key, value)),
+            1 => key.prop_map(Op::Dissoc),
+        ];
+        prop::collection::vec(op, 0..max)
+    }
+
+    fn apply(model: &mut HashMap<usize, u16>, op: &Op) {
+        match *op {
+            Op::Assoc(key, value) => {
+                model.insert(key, value);
+            }
+            Op::Dissoc(key) => {
+                model.remove(&key);
+            }
+        }
+    }
+
+    // The map holds exactly the model's entries
+    fn check_map(map: ObjectReference, model: &HashMap<usize, u16>) -> Result<(), TestCaseError> {
+        let vm = vm();
+        prop_assert_eq!(vm.hashmap_get_length(map) as usize, model.len());
+        for (index, key) in key_pool().iter().enumerate() {
+            let expected = model.get(&index).map(|value| ValueRef::number(*value as f64));
+            prop_assert_eq!(vm.hashmap_get(map, key), expected);
+        }
+        let entries: HashSet<u64> = vm.hashmap_entries(map).iter().map(|(key, _)| key.get_number().unwrap().to_bits()).collect();
+        prop_assert_eq!(entries.len(), model.len());
+        Ok(())
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn persistent_updates_match_a_hash_map(ops in ops(300)) {
+            let vm = vm();
+            let mut map = vm.alloc_map(Vec::new());
+            let mut model = HashMap::new();
+            let mut versions = vec![(map, model.clone())];
+            for op in &ops {
+                map = match *op {
+                    Op::Assoc(key, value) => vm.hashmap_assoc(map, key_pool()[key], ValueRef::number(value as f64)),
+                    Op::Dissoc(key) => vm.hashmap_dissoc(map, &key_pool()[key]),
+                };
+                apply(&mut model, op);
+                check_map(map, &model)?;
+                versions.push((map, model.clone()));
+            }
+            // Every earlier version is unchanged
+            for (map, model) in &versions {
+                check_map(*map, model)?;
+            }
+        }
+
+        #[test]
+        fn transients_match_a_hash_map_and_leave_their_source_alone(before in ops(200), after in ops(200)) {
+            let vm = vm();
+            let mut model = HashMap::new();
+            let mut pairs = Vec::new();
+            for op in &before {
+                apply(&mut model, op);
+            }
+            for (key, value) in &model {
+                pairs.push((key_pool()[*key], ValueRef::number(*value as f64)));
+            }
+            let source = vm.alloc_map(pairs);
+            let source_model = model.clone();
+
+            let transient = vm.hashmap_transient(source);
+            for op in &after {
+                match *op {
+                    Op::Assoc(key, value) => vm.hashmap_assoc_mut(transient, key_pool()[key], ValueRef::number(value as f64)).unwrap(),
+                    Op::Dissoc(key) => vm.hashmap_dissoc_mut(transient, &key_pool()[key]).unwrap(),
+                }
+                apply(&mut model, op);
+            }
+            let result = vm.hashmap_persistent(transient).unwrap();
+            check_map(result, &model)?;
+            check_map(source, &source_model)?;
+            prop_assert!(vm.hashmap_assoc_mut(result, key_pool()[0], ValueRef::nil()).is_err());
+        }
+
+        #[test]
+        fn sets_match_a_hash_set(ops in ops(300)) {
+            let vm = vm();
+            let mut set = vm.alloc_set(Vec::new());
+            let mut model = HashSet::new();
+            for op in &ops {
+                match *op {
+                    Op::Assoc(key, _) => {
+                        set = vm.hashset_conj(set, key_pool()[key]);
+                        model.insert(key);
+                    }
+                    Op::Dissoc(key) => {
+                        set = vm.hashset_disj(set, &key_pool()[key]);
+                        model.remove(&key);
+                    }
+                }
+                prop_assert_eq!(vm.hashset_get_length(set) as usize, model.len());
+            }
+            for (index, key) in key_pool().iter().enumerate() {
+                prop_assert_eq!(vm.hashset_contains(set, key), model.contains(&index));
+            }
+        }
+    }
+
+    #[test]
+    fn colliding_keys_share_a_collision_node() {
+        let vm = vm();
+        let colliding = &key_pool()[PLAIN_KEYS..PLAIN_KEYS + 2];
+        assert_eq!(hash_key(&colliding[0]), hash_key(&colliding[1]));
+
+        let map = vm.alloc_map(vec![(colliding[0], ValueRef::number(1.0)), (colliding[1], ValueRef::number(2.0))]);
+        assert_eq!(vm.hashmap_get(map, &colliding[1]), Some(ValueRef::number(2.0)));
+        let map = vm.hashmap_dissoc(map, &colliding[0]);
+        assert_eq!(vm.hashmap_get_length(map), 1);
+        assert_eq!(vm.hashmap_get(map, &colliding[0]), None);
+        assert_eq!(vm.hashmap_get(map, &colliding[1]), Some(ValueRef::number(2.0)));
+    }
+}
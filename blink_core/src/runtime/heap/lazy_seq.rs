@@ -0,0 +1,158 @@
+// blink_core/src/runtime/heap/lazy_seq.rs
+
+use mmtk::util::ObjectReference;
+
+use crate::runtime::heap::trie_node::{read_u32_field, read_value_field, write_u32_field};
+use crate::runtime::{BlinkActivePlan, BlinkVM, TypeTag};
+use crate::value::{HeapValue, ValueRef};
+
+// A lazy sequence cell, realised at most once.
+// Layout: [state: u32][generator: u32][source: ValueRef][first: ValueRef][rest: ValueRef]
+// While pending, `generator` says how to produce the cell and `source` holds its
+// inputs: the thunk itself, or a vector of generator state. Realising stores
+// first/rest and clears source, so the thunk and its captures can be collected.
+
+const STATE_OFFSET: usize = 0;
+const GENERATOR_OFFSET: usize = std::mem::size_of::<u32>();
+const SOURCE_OFFSET: usize = 2 * std::mem::size_of::<u32>();
+const FIRST_OFFSET: usize = SOURCE_OFFSET + std::mem::size_of::<ValueRef>();
+const REST_OFFSET: usize = FIRST_OFFSET + std::mem::size_of::<ValueRef>();
+const LAZY_SEQ_SIZE: usize = REST_OFFSET + std::mem::size_of::<ValueRef>();
+
+const STATE_PENDING: u32 = 0;
+const STATE_CELL: u32 = 1;
+const STATE_EMPTY: u32 = 2;
+
+/// How a pending cell is produced. Apart from Thunk, the source is a vector
+/// of state, listed here, and the rest of the produced cell is another
+/// generator of the same kind.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeqGenerator {
+    /// source is a function of no arguments returning a seqable
+    Thunk = 0,
+    /// [start end step], end nil for no end
+    Range = 1,
+    /// [f previous]
+    Iterate = 2,
+    /// [x remaining], remaining nil for no end
+    Repeat = 3,
+    /// [items index]
+    Cycle = 4,
+    /// [seqables...], walked one after another
+    Concat = 5,
+    /// [path byte-offset]
+    Lines = 6,
+    /// [vector index], a seq over a realised collection
+    Items = 7,
+    /// [f seqables], f applied across the seqables
+    Map = 8,
+    /// [pred seqable]
+    Filter = 9,
+    /// [transducer seqable remaining], remaining is the take/drop state per stage
+    Transduce = 10,
+}
+
+impl SeqGenerator {
+    fn from_u32(value: u32) -> Self {
+        match value {
+            0 => SeqGenerator::Thunk,
+            1 => SeqGenerator::Range,
+            2 => SeqGenerator::Iterate,
+            3 => SeqGenerator::Repeat,
+            4 => SeqGenerator::Cycle,
+            5 => SeqGenerator::Concat,
+            6 => SeqGenerator::Lines,
+            7 => SeqGenerator::Items,
+            8 => SeqGenerator::Map,
+            9 => SeqGenerator::Filter,
+            10 => SeqGenerator::Transduce,
+            _ => panic!("Invalid lazy seq generator {}", value),
+        }
+    }
+}
+
+pub enum LazySeqState {
+    Pending(SeqGenerator, ValueRef),
+    Cell(ValueRef, ValueRef),
+    Empty,
+}
+
+/// What can be shown of a lazy seq without running any code
+#[derive(Debug, Clone, PartialEq)]
+pub struct LazySeqPrefix {
+    pub realized: Vec<ValueRef>,
+    pub pending: bool,
+}
+
+impl BlinkVM {
+    pub fn alloc_lazy_seq(&self, generator: SeqGenerator, source: ValueRef) -> ObjectReference {
+        let seq = self.with_mutator(|mutator| {
+            BlinkActivePlan::alloc_object(mutator, &TypeTag::LazySeq, &LAZY_SEQ_SIZE)
+        });
+        write_u32_field(seq, STATE_OFFSET, STATE_PENDING);
+        write_u32_field(seq, GENERATOR_OFFSET, generator as u32);
+        self.write_value_field(seq, SOURCE_OFFSET, source);
+        self.write_value_field(seq, FIRST_OFFSET, ValueRef::nil());
+        self.write_value_field(seq, REST_OFFSET, ValueRef::nil());
+        seq
+    }
+
+    /// A cell that is realised from the start, e.g. `first` consed onto a lazy `rest`
+    pub fn alloc_seq_cell(&self, first: ValueRef, rest: ValueRef) -> ObjectReference {
+        let seq = self.alloc_lazy_seq(SeqGenerator::Thunk, ValueRef::nil());
+        self.lazy_seq_realize_as(seq, Some((first, rest)));
+        seq
+    }
+
+    pub fn lazy_seq_state(&self, seq: ObjectReference) -> LazySeqState {
+        match read_u32_field(seq, STATE_OFFSET) {
+            STATE_CELL => LazySeqState::Cell(read_value_field(seq, FIRST_OFFSET), read_value_field(seq, REST_OFFSET)),
+            STATE_EMPTY => LazySeqState::Empty,
+            _ => LazySeqState::Pending(
+                SeqGenerator::from_u32(read_u32_field(seq, GENERATOR_OFFSET)),
+                read_value_field(seq, SOURCE_OFFSET),
+            ),
+        }
+    }
+
+    /// Cache the outcome of realising a pending cell
+    pub fn lazy_seq_realize_as(&self, seq: ObjectReference, cell: Option<(ValueRef, ValueRef)>) {
+        match cell {
+            Some((first, rest)) => {
+                self.write_value_field(seq, FIRST_OFFSET, first);
+                self.write_value_field(seq, REST_OFFSET, rest);
+                write_u32_field(seq, STATE_OFFSET, STATE_CELL);
+            }
+            None => write_u32_field(seq, STATE_OFFSET, STATE_EMPTY),
+        }
+        self.write_value_field(seq, SOURCE_OFFSET, ValueRef::nil());
+    }
+
+    /// Items realised so far, following the chain until it ends or reaches a pending cell
+    pub fn lazy_seq_prefix(&self, seq: ObjectReference) -> LazySeqPrefix {
+        let mut realized = Vec::new();
+        let mut current = seq;
+        loop {
+            match self.lazy_seq_state(current) {
+                LazySeqState::Empty => return LazySeqPrefix { realized, pending: false },
+                LazySeqState::Pending(_, _) => return LazySeqPrefix { realized, pending: true },
+                LazySeqState::Cell(first, rest) => {
+                    realized.push(first);
+                    match rest {
+                        ValueRef::Heap(gc_ptr) if gc_ptr.type_tag() == TypeTag::LazySeq => current = gc_ptr.0,
+                        ValueRef::Heap(gc_ptr) => {
+                            match gc_ptr.to_heap_value() {
+                                HeapValue::List(items) | HeapValue::Vector(items) => realized.extend(items),
+                                HeapValue::Set(set) => realized.extend(set.iter().copied()),
+                                _ => {}
+                            }
+                            return LazySeqPrefix { realized, pending: false };
+                        }
+                        _ => return LazySeqPrefix { realized, pending: false },
+                    }
+                }
+            }
+        }
+    }
+}
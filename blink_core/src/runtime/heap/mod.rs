@@ -2,24 +2,30 @@ mod vector;
 mod list;
 mod map;
 mod set;
+mod hamt;
+mod trie_node;
+mod lazy_seq;
+mod transducer;
+mod sorted;
 
 use mmtk::util::Address;
 pub use list::*;
 pub use vector::*;
+pub use lazy_seq::*;
+pub use transducer::*;
+pub use sorted::*;
 
-use std::hash::{DefaultHasher, Hash, Hasher};
 use std::sync::{Arc, OnceLock};
 use std::sync::atomic::AtomicUsize;
 use std::sync::Mutex;
 use std::sync::Condvar;
 use std::collections::HashMap;
 
-use crate::error::{BlinkError, BlinkErrorType, ParseErrorType};
+use crate::error::{BlinkError, BlinkErrorType, LimitKind, ParseErrorType};
 use crate::value::FutureHandle;
 use crate::module::SerializedModuleSource;
-use crate::runtime::{BlinkActivePlan, BlinkObjectModel, BlinkSlot, BlinkVM, ClosureObject, CompiledFunction, Macro, ObjectHeader, TypeTag, GLOBAL_MMTK};
+use crate::runtime::{BlinkActivePlan, BlinkObjectModel, BlinkSlot, BlinkVM, ClosureObject, CompiledFunction, LineTableEntry, LocalVarEntry, Macro, ObjectHeader, TypeTag, GLOBAL_MMTK};
 use crate::value::{ ParsedValue, ParsedValueWithPos, SourceRange};
-use crate::collections::{BlinkHashMap, BlinkHashSet};
 use crate::env::Env;
 use crate::{ value::ValueRef};
 
@@ -74,20 +80,19 @@ impl BlinkVM {
             /*
              *  pub struct Macro {
              *       params: Vec<u32>,           // Parameter symbol IDs
-             *       body: Vec<ValueRef>,        // Raw, unevaluated forms
+             *       expander: ValueRef,         // Function of (&form &env params...)
              *       is_variadic: bool,
              *       module: u32,
              *   }
              */
 
             let params_count = macro_fn.params.len();
-            let body_count = macro_fn.body.len();
             let is_variadic = macro_fn.is_variadic;
             let module = macro_fn.module;
 
+            // The expander comes first so the GC only scans the start of the object
             let total_size = 
-            std::mem::size_of::<u32>() +                              // body_count
-            body_count * std::mem::size_of::<ValueRef>() +            // body
+            std::mem::size_of::<ValueRef>() +                         // expander
             std::mem::size_of::<u32>() +                              // params_count
             params_count * std::mem::size_of::<u32>() +               // params
             std::mem::size_of::<u8>() +                               // is_variadic
@@ -100,18 +105,12 @@ impl BlinkVM {
                 let data_ptr = data_start.to_raw_address().as_usize() as *mut u8;
                 let mut offset = 0;
 
-                std::ptr::write_unaligned(data_ptr.add(offset) as *mut u32, body_count as u32);
-                offset += std::mem::size_of::<u32>();
+                std::ptr::write_unaligned(data_ptr.add(offset) as *mut ValueRef, macro_fn.expander);
+                offset += std::mem::size_of::<ValueRef>();
 
                 std::ptr::write_unaligned(data_ptr.add(offset) as *mut u32, params_count as u32);
                 offset += std::mem::size_of::<u32>();
 
-                for item in &macro_fn.body {
-                    std::ptr::write_unaligned(data_ptr.add(offset) as *mut ValueRef, *item);
-                    offset += std::mem::size_of::<ValueRef>();
-                }
-                
-
                 for param in &macro_fn.params {
                     std::ptr::write_unaligned(data_ptr.add(offset) as *mut u32, *param);
                     offset += std::mem::size_of::<u32>();
@@ -121,7 +120,6 @@ impl BlinkVM {
                 offset += std::mem::size_of::<u8>();
 
                 std::ptr::write_unaligned(data_ptr.add(offset) as *mut u32, module);
-                offset += std::mem::size_of::<u32>();
                 
             }
             data_start
@@ -145,11 +143,16 @@ impl BlinkVM {
                 pub register_count: u8,
                 pub module: u32,
                 pub register_start: u8,
-                pub has_self_reference: bool
+                pub has_self_reference: bool,
+                pub name: Option<u32>,
+                pub line_table: Vec<LineTableEntry>,
+                pub locals: Vec<LocalVarEntry>,
             }
              */
             let constants_count = function.constants.len();
             let bytecode_len = function.bytecode.len();
+            let line_table_len = function.line_table.len();
+            let locals_len = function.locals.len();
             
             // GC-FRIENDLY LAYOUT: All ObjectReferences first!
             // [parameter_count: u8]
@@ -159,6 +162,12 @@ impl BlinkVM {
             // [constants: ValueRef...]
             // [bytecode_count: u32]
             // [bytecode: u8...]
+            // [has_name: u8]
+            // [name: u32]
+            // [line_table_count: u32]
+            // [line_table: LineTableEntry...]
+            // [locals_count: u32]
+            // [locals: LocalVarEntry...]
             
             let total_size = 
             std::mem::size_of::<u32>() +                              // constants_count
@@ -169,7 +178,13 @@ impl BlinkVM {
             std::mem::size_of::<u8>() +                               // register_start
             std::mem::size_of::<u8>() +                               // has_self_reference
             std::mem::size_of::<u32>() +                              // bytecode_len
-            bytecode_len;                                             // bytecode data
+            bytecode_len +                                            // bytecode data
+            std::mem::size_of::<u8>() +                               // has_name
+            std::mem::size_of::<u32>() +                              // name
+            std::mem::size_of::<u32>() +                              // line_table_len
+            line_table_len * std::mem::size_of::<LineTableEntry>() +  // line table
+            std::mem::size_of::<u32>() +                              // locals_len
+            locals_len * std::mem::size_of::<LocalVarEntry>();        // locals
             
             
             let type_tag = if is_macro { TypeTag::Macro } else { TypeTag::UserDefinedFunction };
@@ -216,6 +231,30 @@ impl BlinkVM {
                     data_ptr.add(offset),
                     bytecode_len
                 );
+                offset += bytecode_len;
+
+                // Debug info: name and line table
+                std::ptr::write_unaligned(data_ptr.add(offset) as *mut u8, function.name.is_some() as u8);
+                offset += std::mem::size_of::<u8>();
+
+                std::ptr::write_unaligned(data_ptr.add(offset) as *mut u32, function.name.unwrap_or(0));
+                offset += std::mem::size_of::<u32>();
+
+                std::ptr::write_unaligned(data_ptr.add(offset) as *mut u32, line_table_len as u32);
+                offset += std::mem::size_of::<u32>();
+
+                for entry in &function.line_table {
+                    std::ptr::write_unaligned(data_ptr.add(offset) as *mut LineTableEntry, *entry);
+                    offset += std::mem::size_of::<LineTableEntry>();
+                }
+
+                std::ptr::write_unaligned(data_ptr.add(offset) as *mut u32, locals_len as u32);
+                offset += std::mem::size_of::<u32>();
+
+                for local in &function.locals {
+                    std::ptr::write_unaligned(data_ptr.add(offset) as *mut LocalVarEntry, *local);
+                    offset += std::mem::size_of::<LocalVarEntry>();
+                }
             }
             
             data_start
@@ -452,132 +491,6 @@ pub fn alloc_env(&self, env: Env) -> ObjectReference {
         
     }
 
-    pub fn alloc_blink_hash_map(&self, map: BlinkHashMap) -> ObjectReference {
-        let pairs: Vec<(&ValueRef, &ValueRef)> = map.iter().collect();
-        self.alloc_map(pairs)
-    }
-
-    
-
-    pub fn alloc_map(&self, pairs: Vec<(&ValueRef, &ValueRef)>) -> ObjectReference {
-        self.with_mutator(|mutator| {
-            let bucket_count = Self::calculate_bucket_count(pairs.len());
-            let item_count = pairs.len();
-            
-            // Calculate sizes
-            let metadata_size = 2 * std::mem::size_of::<usize>(); // bucket_count + item_count
-            let buckets_size = bucket_count * std::mem::size_of::<u32>(); // bucket offsets
-            let pairs_size = item_count * 2 * std::mem::size_of::<ValueRef>(); // key-value pairs
-            let total_data_size = metadata_size + buckets_size + pairs_size;
-            
-            let data_start = BlinkActivePlan::alloc_object(mutator, &TypeTag::Map, &total_data_size);
-            
-            unsafe {
-                let data_ptr = data_start.to_raw_address().as_usize() as *mut u8;
-                let mut offset = 0;
-                
-                // Write metadata
-                std::ptr::write_unaligned(data_ptr.add(offset) as *mut usize, bucket_count);
-                offset += std::mem::size_of::<usize>();
-                std::ptr::write_unaligned(data_ptr.add(offset) as *mut usize, item_count);
-                offset += std::mem::size_of::<usize>();
-                
-                // Organize into buckets
-                let mut buckets: Vec<Vec<(ValueRef, ValueRef)>> = vec![Vec::new(); bucket_count];
-                for (key, val) in pairs {
-                    let mut hasher = DefaultHasher::new();
-                    key.hash(&mut hasher);
-                    let hash_value = hasher.finish();
-                    let bucket = (hash_value as usize) % bucket_count;
-                    buckets[bucket].push((*key, *val));
-                }
-                
-                // Write bucket offsets (using correct offset)
-                let bucket_offsets_ptr = data_ptr.add(offset) as *mut u32;  // ← Fixed!
-                let mut current_offset = 0u32;
-                for (i, bucket) in buckets.iter().enumerate() {
-                    std::ptr::write(bucket_offsets_ptr.add(i), current_offset);
-                    current_offset += bucket.len() as u32;
-                }
-                offset += buckets_size;
-                
-                // Write pairs (using correct offset)
-                let pairs_ptr = data_ptr.add(offset) as *mut ValueRef;  // ← Fixed!
-                let mut pair_index = 0;
-                for bucket in buckets {
-                    for (key, val) in bucket {
-                        std::ptr::write(pairs_ptr.add(pair_index * 2), key);
-                        std::ptr::write(pairs_ptr.add(pair_index * 2 + 1), val);
-                        pair_index += 1;
-                    }
-                }
-            }
-            data_start
-        })
-    }
-
-    pub fn alloc_blink_hash_set(&self, set: BlinkHashSet) -> ObjectReference {
-        let items: Vec<&ValueRef> = set.iter().collect();
-        self.alloc_set(items)
-    }
-    
-    pub fn alloc_set(&self, items: Vec<&ValueRef>) -> ObjectReference {
-        self.with_mutator(|mutator| {
-            let bucket_count = Self::calculate_bucket_count(items.len());
-            let item_count = items.len();
-            
-            let header_size = std::mem::size_of::<usize>() * 2;
-            let buckets_size = bucket_count * std::mem::size_of::<u32>();
-            let items_size = item_count * std::mem::size_of::<ValueRef>();
-            let total_data_size = header_size + buckets_size + items_size;
-            
-            // Use consistent allocation method like other types
-            let data_start = BlinkActivePlan::alloc_object(mutator, &TypeTag::Set, &total_data_size);
-            
-            unsafe {
-                let data_ptr = data_start.to_raw_address().as_usize() as *mut u8;
-                let mut offset = 0;
-                
-                // Write metadata
-                std::ptr::write_unaligned(data_ptr.add(offset) as *mut usize, bucket_count);
-                offset += std::mem::size_of::<usize>();
-                std::ptr::write_unaligned(data_ptr.add(offset) as *mut usize, item_count);
-                offset += std::mem::size_of::<usize>();
-                
-                // Organize into buckets
-                let mut buckets: Vec<Vec<ValueRef>> = vec![Vec::new(); bucket_count];
-                for item in items {
-                    let mut hasher = DefaultHasher::new();
-                    item.hash(&mut hasher);
-                    let hash_value = hasher.finish();
-                    let bucket = (hash_value as usize) % bucket_count;
-                    buckets[bucket].push(*item);
-                }
-                
-                // Write bucket offsets
-                let bucket_offsets_ptr = data_ptr.add(offset) as *mut u32;
-                let mut current_offset = 0u32;
-                for (i, bucket) in buckets.iter().enumerate() {
-                    std::ptr::write(bucket_offsets_ptr.add(i), current_offset);
-                    current_offset += bucket.len() as u32;
-                }
-                offset += buckets_size;
-                
-                // Write items
-                let items_ptr = data_ptr.add(offset) as *mut ValueRef;
-                let mut item_index = 0;
-                for bucket in buckets {
-                    for item in bucket {
-                        std::ptr::write(items_ptr.add(item_index), item);
-                        item_index += 1;
-                    }
-                }
-            }
-            
-            data_start  // Return the ObjectReference directly
-        })
-    }
-
     pub fn alloc_error(&self, error: BlinkError) -> ObjectReference {
         self.with_mutator(|mutator| {
             let message_bytes = error.message.as_bytes();
@@ -652,6 +565,11 @@ pub fn alloc_env(&self, env: Env) -> ObjectReference {
                 std::mem::size_of::<u8>() + 
                 if data.is_some() { std::mem::size_of::<ObjectReference>() } else { 0 }
             },
+            BlinkErrorType::LimitExceeded { .. } => {
+                // Store LimitKind discriminant
+                std::mem::size_of::<u8>()
+            },
+            BlinkErrorType::Interrupted => 0,
         };
         
         discriminant_size + variant_size
@@ -670,6 +588,8 @@ pub fn alloc_env(&self, env: Env) -> ObjectReference {
             BlinkErrorType::ArityMismatch { .. } => 4u8,
             BlinkErrorType::UnexpectedToken { .. } => 5u8,
             BlinkErrorType::UserDefined { .. } => 6u8,
+            BlinkErrorType::LimitExceeded { .. } => 7u8,
+            BlinkErrorType::Interrupted => 8u8,
         };
         
         std::ptr::write_unaligned(ptr.add(offset) as *mut u8, discriminant);
@@ -677,7 +597,7 @@ pub fn alloc_env(&self, env: Env) -> ObjectReference {
         
         // Write variant data
         match error_type {
-            BlinkErrorType::Tokenizer | BlinkErrorType::Eval => {
+            BlinkErrorType::Tokenizer | BlinkErrorType::Eval | BlinkErrorType::Interrupted => {
                 // No additional data
             },
             BlinkErrorType::Parse(parse_type) => {
@@ -735,6 +655,15 @@ pub fn alloc_env(&self, env: Env) -> ObjectReference {
                     }
                 }
             },
+            BlinkErrorType::LimitExceeded { limit } => {
+                let limit_discriminant = match limit {
+                    LimitKind::Fuel => 0u8,
+                    LimitKind::Deadline => 1u8,
+                    LimitKind::CallDepth => 2u8,
+                    LimitKind::Allocation => 3u8,
+                };
+                std::ptr::write_unaligned(ptr.add(offset) as *mut u8, limit_discriminant);
+            },
         }
     }
 
@@ -850,61 +779,21 @@ pub fn alloc_env(&self, env: Env) -> ObjectReference {
             HeapValue::List(list) => self.alloc_list_from_items(list), // true = list
             HeapValue::Str(str) => self.alloc_str(&str),
             HeapValue::Map(map) => self.alloc_blink_hash_map(map),
-            HeapValue::Vector(value_refs) => self.alloc_vec(value_refs, None), // false = vector
+            HeapValue::Vector(value_refs) => self.alloc_vec(value_refs),
             HeapValue::Set(blink_hash_set) => self.alloc_blink_hash_set(blink_hash_set),
             HeapValue::Error(blink_error) => self.alloc_error(blink_error),
             HeapValue::Function(callable) => self.alloc_user_defined_fn(callable),
             HeapValue::Macro(macro_fn) => self.alloc_macro(macro_fn),
             HeapValue::Env(env) => self.alloc_env(env),
             HeapValue::Closure(closure_object) => self.alloc_closure(closure_object),
-        }
-    }
-    
-    
-    fn calculate_bucket_count(item_count: usize) -> usize {
-        if item_count == 0 {
-            1
-        } else {
-            (item_count * 2).next_power_of_two().max(8)
-        }
-    }
-    
-    /// Convert map to BlinkHashMap for compatibility with existing code
-    pub fn map_to_blink_hash_map(&self, map: ObjectReference) -> BlinkHashMap {
-        // Check if it's using the new hashmap implementation in map.rs
-        let type_tag = crate::runtime::BlinkObjectModel::get_type_tag(map);
-        if type_tag == crate::runtime::TypeTag::Map {
-            // Try using the new hashmap API if available
-            // For now we'll use the old layout conversion method
-            unsafe {
-                let header_ptr = map.to_raw_address().as_usize() as *const u8;
-                let mut offset = 0;
-                
-                let bucket_count = std::ptr::read_unaligned(header_ptr.add(offset) as *const usize);
-                offset += std::mem::size_of::<usize>();
-                
-                let item_count = std::ptr::read_unaligned(header_ptr.add(offset) as *const usize);
-                offset += std::mem::size_of::<usize>();
-                
-                if item_count == 0 {
-                    return BlinkHashMap::new();
-                }
-                
-                offset += bucket_count * std::mem::size_of::<u32>(); // Skip bucket offsets
-                
-                let mut map = BlinkHashMap::new();
-                let pairs_ptr = header_ptr.add(offset) as *const ValueRef;
-                
-                for i in 0..item_count {
-                    let key = std::ptr::read_unaligned(pairs_ptr.add(i * 2));
-                    let value = std::ptr::read_unaligned(pairs_ptr.add(i * 2 + 1));
-                    map.insert(key, value);
-                }
-                
-                map
+            // Only the realised items of a lazy seq can be copied
+            HeapValue::LazySeq(prefix) => self.alloc_list_from_items(prefix.realized),
+            HeapValue::Transducer(kind, arg) => self.alloc_transducer(kind, arg),
+            HeapValue::SortedMap(comparator, entries) => self.alloc_sorted_from_entries(TypeTag::SortedMap, comparator, &entries),
+            HeapValue::SortedSet(comparator, items) => {
+                let entries: Vec<_> = items.into_iter().map(|item| (item, ValueRef::nil())).collect();
+                self.alloc_sorted_from_entries(TypeTag::SortedSet, comparator, &entries)
             }
-        } else {
-            BlinkHashMap::new()
         }
     }
 
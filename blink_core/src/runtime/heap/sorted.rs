@@ -0,0 +1,261 @@
+// blink_core/src/runtime/heap/sorted.rs
+
+use std::cmp::Ordering;
+
+use mmtk::util::ObjectReference;
+
+use crate::runtime::heap::trie_node::{read_u32_field, read_value_field, write_u32_field};
+use crate::runtime::{BlinkActivePlan, BlinkVM, TypeTag};
+use crate::value::{GcPtr, ValueRef};
+
+// Sorted maps and sets are persistent AVL trees. Updates copy the path from the
+// root to the changed node and share every other node with the original.
+//
+// Header (SortedMap and SortedSet): [count: u32][padding: u32][comparator: ValueRef][root: ValueRef]
+// The comparator is nil for the natural order of `compare`.
+// Node: [height: u32][padding: u32][key: ValueRef][value: ValueRef][left: ValueRef][right: ValueRef]
+// Empty subtrees are nil. Set nodes leave the value nil.
+
+const COUNT_OFFSET: usize = 0;
+const COMPARATOR_OFFSET: usize = 2 * std::mem::size_of::<u32>();
+const ROOT_OFFSET: usize = COMPARATOR_OFFSET + std::mem::size_of::<ValueRef>();
+const SORTED_HEADER_SIZE: usize = ROOT_OFFSET + std::mem::size_of::<ValueRef>();
+
+const HEIGHT_OFFSET: usize = 0;
+const KEY_OFFSET: usize = 2 * std::mem::size_of::<u32>();
+const VALUE_OFFSET: usize = KEY_OFFSET + std::mem::size_of::<ValueRef>();
+const LEFT_OFFSET: usize = VALUE_OFFSET + std::mem::size_of::<ValueRef>();
+const RIGHT_OFFSET: usize = LEFT_OFFSET + std::mem::size_of::<ValueRef>();
+const TREE_NODE_SIZE: usize = RIGHT_OFFSET + std::mem::size_of::<ValueRef>();
+
+/// Orders two keys, or fails with the error value a comparator returned
+pub type KeyComparator<'a> = dyn FnMut(ValueRef, ValueRef) -> Result<Ordering, ValueRef> + 'a;
+
+#[derive(Clone, Copy)]
+struct Node {
+    key: ValueRef,
+    value: ValueRef,
+    left: ValueRef,
+    right: ValueRef,
+}
+
+fn node_object(node: ValueRef) -> Option<ObjectReference> {
+    match node {
+        ValueRef::Heap(gc_ptr) => Some(gc_ptr.0),
+        _ => None,
+    }
+}
+
+fn read_node(node: ValueRef) -> Option<Node> {
+    let object = node_object(node)?;
+    Some(Node {
+        key: read_value_field(object, KEY_OFFSET),
+        value: read_value_field(object, VALUE_OFFSET),
+        left: read_value_field(object, LEFT_OFFSET),
+        right: read_value_field(object, RIGHT_OFFSET),
+    })
+}
+
+fn height(node: ValueRef) -> u32 {
+    node_object(node).map_or(0, |object| read_u32_field(object, HEIGHT_OFFSET))
+}
+
+impl BlinkVM {
+    pub fn alloc_sorted(&self, tag: TypeTag, comparator: ValueRef, root: ValueRef, count: u32) -> ObjectReference {
+        debug_assert!(matches!(tag, TypeTag::SortedMap | TypeTag::SortedSet));
+        let sorted = self.with_mutator(|mutator| {
+            BlinkActivePlan::alloc_object(mutator, &tag, &SORTED_HEADER_SIZE)
+        });
+        write_u32_field(sorted, COUNT_OFFSET, count);
+        write_u32_field(sorted, COUNT_OFFSET + std::mem::size_of::<u32>(), 0);
+        self.write_value_field(sorted, COMPARATOR_OFFSET, comparator);
+        self.write_value_field(sorted, ROOT_OFFSET, root);
+        sorted
+    }
+
+    pub fn sorted_count(&self, sorted: ObjectReference) -> u32 {
+        read_u32_field(sorted, COUNT_OFFSET)
+    }
+
+    pub fn sorted_comparator(&self, sorted: ObjectReference) -> ValueRef {
+        read_value_field(sorted, COMPARATOR_OFFSET)
+    }
+
+    pub fn sorted_root(&self, sorted: ObjectReference) -> ValueRef {
+        read_value_field(sorted, ROOT_OFFSET)
+    }
+
+    /// A sorted map or set holding `entries`, which must already be in order
+    pub fn alloc_sorted_from_entries(&self, tag: TypeTag, comparator: ValueRef, entries: &[(ValueRef, ValueRef)]) -> ObjectReference {
+        let root = self.tree_from_entries(entries);
+        self.alloc_sorted(tag, comparator, root, entries.len() as u32)
+    }
+
+    // Split at the middle entry at every level, giving a balanced tree without comparing keys
+    fn tree_from_entries(&self, entries: &[(ValueRef, ValueRef)]) -> ValueRef {
+        if entries.is_empty() {
+            return ValueRef::nil();
+        }
+        let middle = entries.len() / 2;
+        let left = self.tree_from_entries(&entries[..middle]);
+        let right = self.tree_from_entries(&entries[middle + 1..]);
+        let (key, value) = entries[middle];
+        self.alloc_tree_node(key, value, left, right)
+    }
+
+    fn alloc_tree_node(&self, key: ValueRef, value: ValueRef, left: ValueRef, right: ValueRef) -> ValueRef {
+        let node = self.with_mutator(|mutator| {
+            BlinkActivePlan::alloc_object(mutator, &TypeTag::TreeNode, &TREE_NODE_SIZE)
+        });
+        write_u32_field(node, HEIGHT_OFFSET, 1 + height(left).max(height(right)));
+        write_u32_field(node, HEIGHT_OFFSET + std::mem::size_of::<u32>(), 0);
+        self.write_value_field(node, KEY_OFFSET, key);
+        self.write_value_field(node, VALUE_OFFSET, value);
+        self.write_value_field(node, LEFT_OFFSET, left);
+        self.write_value_field(node, RIGHT_OFFSET, right);
+        ValueRef::Heap(GcPtr::new(node))
+    }
+
+    // Build a node from subtrees whose heights differ by at most two, rotating
+    // so they differ by at most one
+    fn balanced_node(&self, key: ValueRef, value: ValueRef, left: ValueRef, right: ValueRef) -> ValueRef {
+        let (left_height, right_height) = (height(left), height(right));
+        if left_height > right_height + 1 {
+            let l = read_node(left).expect("taller subtree is not empty");
+            if height(l.left) >= height(l.right) {
+                let new_right = self.alloc_tree_node(key, value, l.right, right);
+                return self.alloc_tree_node(l.key, l.value, l.left, new_right);
+            }
+            let lr = read_node(l.right).expect("taller subtree is not empty");
+            let new_left = self.alloc_tree_node(l.key, l.value, l.left, lr.left);
+            let new_right = self.alloc_tree_node(key, value, lr.right, right);
+            return self.alloc_tree_node(lr.key, lr.value, new_left, new_right);
+        }
+        if right_height > left_height + 1 {
+            let r = read_node(right).expect("taller subtree is not empty");
+            if height(r.right) >= height(r.left) {
+                let new_left = self.alloc_tree_node(key, value, left, r.left);
+                return self.alloc_tree_node(r.key, r.value, new_left, r.right);
+            }
+            let rl = read_node(r.left).expect("taller subtree is not empty");
+            let new_left = self.alloc_tree_node(key, value, left, rl.left);
+            let new_right = self.alloc_tree_node(r.key, r.value, rl.right, r.right);
+            return self.alloc_tree_node(rl.key, rl.value, new_left, new_right);
+        }
+        self.alloc_tree_node(key, value, left, right)
+    }
+
+    /// Insert or replace a key. Returns the new root and whether the key is new.
+    pub fn sorted_insert(&self, root: ValueRef, key: ValueRef, value: ValueRef, cmp: &mut KeyComparator) -> Result<(ValueRef, bool), ValueRef> {
+        let Some(node) = read_node(root) else {
+            return Ok((self.alloc_tree_node(key, value, ValueRef::nil(), ValueRef::nil()), true));
+        };
+        match cmp(key, node.key)? {
+            Ordering::Less => {
+                let (left, added) = self.sorted_insert(node.left, key, value, cmp)?;
+                Ok((self.balanced_node(node.key, node.value, left, node.right), added))
+            }
+            Ordering::Greater => {
+                let (right, added) = self.sorted_insert(node.right, key, value, cmp)?;
+                Ok((self.balanced_node(node.key, node.value, node.left, right), added))
+            }
+            Ordering::Equal => Ok((self.alloc_tree_node(node.key, value, node.left, node.right), false)),
+        }
+    }
+
+    // Remove the smallest entry, returning it and the new subtree
+    fn sorted_remove_min(&self, root: ValueRef) -> ((ValueRef, ValueRef), ValueRef) {
+        let node = read_node(root).expect("remove_min on an empty tree");
+        if ValueRef::nil() == node.left {
+            return ((node.key, node.value), node.right);
+        }
+        let (min, left) = self.sorted_remove_min(node.left);
+        (min, self.balanced_node(node.key, node.value, left, node.right))
+    }
+
+    /// Remove a key. Returns the new root and whether the key was present.
+    pub fn sorted_remove(&self, root: ValueRef, key: ValueRef, cmp: &mut KeyComparator) -> Result<(ValueRef, bool), ValueRef> {
+        let Some(node) = read_node(root) else {
+            return Ok((root, false));
+        };
+        match cmp(key, node.key)? {
+            Ordering::Less => {
+                let (left, removed) = self.sorted_remove(node.left, key, cmp)?;
+                if !removed {
+                    return Ok((root, false));
+                }
+                Ok((self.balanced_node(node.key, node.value, left, node.right), true))
+            }
+            Ordering::Greater => {
+                let (right, removed) = self.sorted_remove(node.right, key, cmp)?;
+                if !removed {
+                    return Ok((root, false));
+                }
+                Ok((self.balanced_node(node.key, node.value, node.left, right), true))
+            }
+            Ordering::Equal => {
+                if ValueRef::nil() == node.left {
+                    return Ok((node.right, true));
+                }
+                if ValueRef::nil() == node.right {
+                    return Ok((node.left, true));
+                }
+                let ((min_key, min_value), right) = self.sorted_remove_min(node.right);
+                Ok((self.balanced_node(min_key, min_value, node.left, right), true))
+            }
+        }
+    }
+
+    /// The stored key and value equal to `key`, if any
+    pub fn sorted_find(&self, root: ValueRef, key: ValueRef, cmp: &mut KeyComparator) -> Result<Option<(ValueRef, ValueRef)>, ValueRef> {
+        let mut current = root;
+        while let Some(node) = read_node(current) {
+            current = match cmp(key, node.key)? {
+                Ordering::Less => node.left,
+                Ordering::Greater => node.right,
+                Ordering::Equal => return Ok(Some((node.key, node.value))),
+            };
+        }
+        Ok(None)
+    }
+
+    /// All entries in order
+    pub fn sorted_entries(&self, root: ValueRef) -> Vec<(ValueRef, ValueRef)> {
+        let mut entries = Vec::new();
+        let mut cursor = self.sorted_seek(root, true, &mut |_| Ok(true)).unwrap_or_default();
+        while let Some(entry) = self.sorted_cursor_next(&mut cursor, true) {
+            entries.push(entry);
+        }
+        entries
+    }
+
+    /// Position a cursor at the first key, walking in the given direction, for
+    /// which `pred` holds. `pred` must be false for a run of keys and then true
+    /// for the rest, so the position is found in O(log n) calls.
+    pub fn sorted_seek(&self, root: ValueRef, ascending: bool, pred: &mut dyn FnMut(ValueRef) -> Result<bool, ValueRef>) -> Result<Vec<ValueRef>, ValueRef> {
+        let mut stack = Vec::new();
+        let mut current = root;
+        while let Some(node) = read_node(current) {
+            let (near, far) = if ascending { (node.left, node.right) } else { (node.right, node.left) };
+            if pred(node.key)? {
+                stack.push(current);
+                current = near;
+            } else {
+                current = far;
+            }
+        }
+        Ok(stack)
+    }
+
+    /// The entry under a cursor from `sorted_seek`, advancing the cursor
+    pub fn sorted_cursor_next(&self, cursor: &mut Vec<ValueRef>, ascending: bool) -> Option<(ValueRef, ValueRef)> {
+        let node = read_node(cursor.pop()?)?;
+        // The rest of the walk continues into the far subtree, nearest key first
+        let mut current = if ascending { node.right } else { node.left };
+        while let Some(next) = read_node(current) {
+            cursor.push(current);
+            current = if ascending { next.left } else { next.right };
+        }
+        Some((node.key, node.value))
+    }
+}
@@ -0,0 +1,64 @@
+// blink_core/src/runtime/heap/transducer.rs
+
+use mmtk::util::ObjectReference;
+
+use crate::runtime::heap::trie_node::{read_u32_field, read_value_field, write_u32_field};
+use crate::runtime::{BlinkActivePlan, BlinkVM, TypeTag};
+use crate::value::ValueRef;
+
+// A transducer: one stage of a collection pipeline, or a composition of stages.
+// Layout: [kind: u32][padding: u32][arg: ValueRef]
+// `arg` is the stage's function or count. For Comp it is a vector of the
+// composed stages in order, already flattened, so no Comp nests another.
+
+const KIND_OFFSET: usize = 0;
+const ARG_OFFSET: usize = 2 * std::mem::size_of::<u32>();
+const TRANSDUCER_SIZE: usize = ARG_OFFSET + std::mem::size_of::<ValueRef>();
+
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransducerKind {
+    /// arg is f
+    Map = 0,
+    /// arg is the predicate
+    Filter = 1,
+    /// arg is the number of items to keep
+    Take = 2,
+    /// arg is the number of items to skip
+    Drop = 3,
+    /// arg is a vector of stages
+    Comp = 4,
+}
+
+impl TransducerKind {
+    fn from_u32(value: u32) -> Self {
+        match value {
+            0 => TransducerKind::Map,
+            1 => TransducerKind::Filter,
+            2 => TransducerKind::Take,
+            3 => TransducerKind::Drop,
+            4 => TransducerKind::Comp,
+            _ => panic!("Invalid transducer kind {}", value),
+        }
+    }
+}
+
+impl BlinkVM {
+    pub fn alloc_transducer(&self, kind: TransducerKind, arg: ValueRef) -> ObjectReference {
+        let transducer = self.with_mutator(|mutator| {
+            BlinkActivePlan::alloc_object(mutator, &TypeTag::Transducer, &TRANSDUCER_SIZE)
+        });
+        write_u32_field(transducer, KIND_OFFSET, kind as u32);
+        write_u32_field(transducer, KIND_OFFSET + std::mem::size_of::<u32>(), 0);
+        self.write_value_field(transducer, ARG_OFFSET, arg);
+        transducer
+    }
+
+    pub fn transducer_kind(&self, transducer: ObjectReference) -> TransducerKind {
+        TransducerKind::from_u32(read_u32_field(transducer, KIND_OFFSET))
+    }
+
+    pub fn transducer_arg(&self, transducer: ObjectReference) -> ValueRef {
+        read_value_field(transducer, ARG_OFFSET)
+    }
+}
@@ -0,0 +1,150 @@
+// blink_core/src/runtime/heap/trie_node.rs
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use mmtk::util::{Address, ObjectReference};
+use mmtk::MutatorContext;
+
+use crate::runtime::{BlinkActivePlan, BlinkSlot, BlinkVM, TypeTag};
+use crate::value::{GcPtr, ValueRef};
+
+// Interior node shared by the persistent vector trie and the CHAMP hash trie.
+// Layout: [slot_count: u32][edit: u32][datamap: u32][nodemap: u32][slots: ValueRef; slot_count]
+// Child nodes are stored as ValueRef::Heap so every slot is scanned the same way.
+pub(crate) const NODE_HEADER_SIZE: usize = 4 * std::mem::size_of::<u32>();
+
+// Edit ids mark the nodes owned by a transient. A node whose edit matches the
+// transient's edit may be mutated in place; edit 0 is never handed out.
+static NEXT_EDIT: AtomicU32 = AtomicU32::new(1);
+
+pub(crate) fn new_edit() -> u32 {
+    loop {
+        let edit = NEXT_EDIT.fetch_add(1, Ordering::Relaxed);
+        if edit != 0 {
+            return edit;
+        }
+    }
+}
+
+pub(crate) fn node_value(node: TrieNode) -> ValueRef {
+    ValueRef::Heap(GcPtr::new(node.0))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TrieNode(pub(crate) ObjectReference);
+
+impl TrieNode {
+    fn base_ptr(&self) -> *mut u8 {
+        self.0.to_raw_address().as_usize() as *mut u8
+    }
+
+    fn read_u32(&self, index: usize) -> u32 {
+        unsafe { std::ptr::read_unaligned(self.base_ptr().add(index * std::mem::size_of::<u32>()) as *const u32) }
+    }
+
+    fn slot_ptr(&self, index: usize) -> *mut ValueRef {
+        unsafe { self.base_ptr().add(NODE_HEADER_SIZE + index * std::mem::size_of::<ValueRef>()) as *mut ValueRef }
+    }
+
+    pub(crate) fn slot_count(&self) -> usize {
+        self.read_u32(0) as usize
+    }
+
+    pub(crate) fn edit(&self) -> u32 {
+        self.read_u32(1)
+    }
+
+    pub(crate) fn datamap(&self) -> u32 {
+        self.read_u32(2)
+    }
+
+    pub(crate) fn nodemap(&self) -> u32 {
+        self.read_u32(3)
+    }
+
+    pub(crate) fn get(&self, index: usize) -> ValueRef {
+        debug_assert!(index < self.slot_count());
+        unsafe { std::ptr::read_unaligned(self.slot_ptr(index)) }
+    }
+
+    /// Read a slot holding a child node; nil slots have no child
+    pub(crate) fn child(&self, index: usize) -> Option<TrieNode> {
+        match self.get(index) {
+            ValueRef::Heap(gc_ptr) => Some(TrieNode(gc_ptr.0)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn slots(&self) -> Vec<ValueRef> {
+        (0..self.slot_count()).map(|i| self.get(i)).collect()
+    }
+}
+
+pub(crate) fn read_u32_field(object: ObjectReference, offset: usize) -> u32 {
+    unsafe { std::ptr::read_unaligned((object.to_raw_address().as_usize() + offset) as *const u32) }
+}
+
+pub(crate) fn write_u32_field(object: ObjectReference, offset: usize, value: u32) {
+    unsafe { std::ptr::write_unaligned((object.to_raw_address().as_usize() + offset) as *mut u32, value) }
+}
+
+pub(crate) fn read_value_field(object: ObjectReference, offset: usize) -> ValueRef {
+    unsafe { std::ptr::read_unaligned((object.to_raw_address().as_usize() + offset) as *const ValueRef) }
+}
+
+impl BlinkVM {
+    /// Store a ValueRef field of a collection header, with the write barrier
+    pub(crate) fn write_value_field(&self, object: ObjectReference, offset: usize, value: ValueRef) {
+        self.with_mutator(|mutator| unsafe {
+            let slot_ptr = (object.to_raw_address().as_usize() + offset) as *mut ValueRef;
+            std::ptr::write_unaligned(slot_ptr, value);
+
+            if let ValueRef::Heap(gc_ptr) = value {
+                mutator.barrier().object_reference_write(
+                    object,
+                    BlinkSlot::ValueRef(Address::from_mut_ptr(slot_ptr)),
+                    gc_ptr.0,
+                );
+            }
+        })
+    }
+
+    /// Allocate a trie node with `slot_count` slots, filled from `slots` and padded with nil
+    pub(crate) fn alloc_trie_node(&self, edit: u32, datamap: u32, nodemap: u32, slots: &[ValueRef], slot_count: usize) -> TrieNode {
+        debug_assert!(slots.len() <= slot_count);
+        self.with_mutator(|mutator| {
+            let total_size = NODE_HEADER_SIZE + slot_count * std::mem::size_of::<ValueRef>();
+            let node_ref = BlinkActivePlan::alloc_object(mutator, &TypeTag::TrieNode, &total_size);
+
+            unsafe {
+                let node_ptr = node_ref.to_raw_address().as_usize() as *mut u8;
+                let header = [slot_count as u32, edit, datamap, nodemap];
+                for (i, field) in header.iter().enumerate() {
+                    std::ptr::write_unaligned(node_ptr.add(i * std::mem::size_of::<u32>()) as *mut u32, *field);
+                }
+
+                let slots_ptr = node_ptr.add(NODE_HEADER_SIZE) as *mut ValueRef;
+                for i in 0..slot_count {
+                    let value = slots.get(i).copied().unwrap_or_else(ValueRef::nil);
+                    std::ptr::write_unaligned(slots_ptr.add(i), value);
+
+                    if let ValueRef::Heap(gc_ptr) = value {
+                        mutator.barrier().object_reference_write(
+                            node_ref,
+                            BlinkSlot::ValueRef(Address::from_mut_ptr(slots_ptr.add(i))),
+                            gc_ptr.0,
+                        );
+                    }
+                }
+            }
+
+            TrieNode(node_ref)
+        })
+    }
+
+    /// Overwrite one slot in place. Only valid on nodes owned by the caller's transient.
+    pub(crate) fn trie_node_set(&self, node: TrieNode, index: usize, value: ValueRef) {
+        debug_assert!(index < node.slot_count());
+        self.write_value_field(node.0, NODE_HEADER_SIZE + index * std::mem::size_of::<ValueRef>(), value);
+    }
+}
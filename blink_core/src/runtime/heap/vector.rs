@@ -1,400 +1,454 @@
 // blink_core/src/runtime/heap/vector.rs
 
-use crate::runtime::{is_object_shared, obj_lock, obj_unlock, BlinkActivePlan, BlinkObjectModel, BlinkSlot, ObjectLockGuard, TypeTag };
+use mmtk::util::ObjectReference;
+
+use crate::runtime::heap::trie_node::{new_edit, node_value, read_u32_field, read_value_field, write_u32_field, TrieNode};
+use crate::runtime::{BlinkActivePlan, NoGcSection, TypeTag};
 use crate::{runtime::BlinkVM, value::ValueRef};
 
+// Persistent vector: a 32-way bit-partitioned trie plus a tail buffer holding
+// the last (up to) 32 items, as in Clojure's PersistentVector. Updates copy the
+// path from the root to the changed leaf, so every version shares the rest.
+//
+// Header: [count: u32][shift: u32][edit: u32][padding: u32][root: ValueRef][tail: ValueRef]
+// root is nil until the first leaf is pushed into the trie, tail is nil when empty.
+// A non-zero edit marks a transient; see trie_node.rs.
+//
+// The nodes an update allocates are only held in locals until the new root and
+// tail are written to a header, so updates run in a NoGcSection.
+
+const BITS: u32 = 5;
+const WIDTH: usize = 1 << BITS;
+const MASK: u32 = (WIDTH - 1) as u32;
+
+const COUNT_OFFSET: usize = 0;
+const SHIFT_OFFSET: usize = std::mem::size_of::<u32>();
+const EDIT_OFFSET: usize = 2 * std::mem::size_of::<u32>();
+const ROOT_OFFSET: usize = 4 * std::mem::size_of::<u32>();
+const TAIL_OFFSET: usize = ROOT_OFFSET + std::mem::size_of::<ValueRef>();
+const HEADER_SIZE: usize = TAIL_OFFSET + std::mem::size_of::<ValueRef>();
+
+#[derive(Clone, Copy)]
+struct VectorState {
+    count: u32,
+    shift: u32,
+    edit: u32,
+    root: Option<TrieNode>,
+    tail: Option<TrieNode>,
+}
 
-use mmtk::util::{ Address};
-use mmtk::MutatorContext;
-use mmtk::{util::ObjectReference, Mutator};
+impl VectorState {
+    fn empty(edit: u32) -> Self {
+        VectorState { count: 0, shift: BITS, edit, root: None, tail: None }
+    }
 
-use std::ptr;
+    // Index of the first item held in the tail
+    fn tail_offset(&self) -> u32 {
+        if (self.count as usize) < WIDTH {
+            0
+        } else {
+            ((self.count - 1) >> BITS) << BITS
+        }
+    }
+
+    fn tail_len(&self) -> usize {
+        (self.count - self.tail_offset()) as usize
+    }
+}
+
+fn optional_node(value: ValueRef) -> Option<TrieNode> {
+    match value {
+        ValueRef::Heap(gc_ptr) => Some(TrieNode(gc_ptr.0)),
+        _ => None,
+    }
+}
+
+fn optional_value(node: Option<TrieNode>) -> ValueRef {
+    node.map(node_value).unwrap_or_else(ValueRef::nil)
+}
+
+fn tail_items(state: &VectorState) -> Vec<ValueRef> {
+    let len = state.tail_len();
+    state.tail.map(|tail| (0..len).map(|i| tail.get(i)).collect()).unwrap_or_default()
+}
 
 impl BlinkVM {
-    // === Read Operations (No barriers needed) ===
-    
-    /// Get vector length - fast, no locking needed for metadata reads
-    pub fn vector_get_length(&self, vector: ObjectReference) -> u32 {
-        unsafe {
-            let header_ptr = vector.to_raw_address().as_usize() as *mut u8;
-            ptr::read_unaligned(header_ptr as *const u32)
+    // === Header access ===
+
+    fn read_vector(&self, vector: ObjectReference) -> VectorState {
+        VectorState {
+            count: read_u32_field(vector, COUNT_OFFSET),
+            shift: read_u32_field(vector, SHIFT_OFFSET),
+            edit: read_u32_field(vector, EDIT_OFFSET),
+            root: optional_node(read_value_field(vector, ROOT_OFFSET)),
+            tail: optional_node(read_value_field(vector, TAIL_OFFSET)),
         }
     }
-    
-    /// Get vector capacity
-    pub fn vector_get_capacity(&self, vector: ObjectReference) -> u32 {
-        unsafe {
-            let header_ptr = vector.to_raw_address().as_usize() as *mut u8;
-            let offset = std::mem::size_of::<u32>(); // Skip length
-            std::ptr::read_unaligned(header_ptr.add(offset) as *const u32)
+
+    fn write_vector(&self, vector: ObjectReference, state: &VectorState) {
+        write_u32_field(vector, COUNT_OFFSET, state.count);
+        write_u32_field(vector, SHIFT_OFFSET, state.shift);
+        write_u32_field(vector, EDIT_OFFSET, state.edit);
+        self.write_value_field(vector, ROOT_OFFSET, optional_value(state.root));
+        self.write_value_field(vector, TAIL_OFFSET, optional_value(state.tail));
+    }
+
+    fn alloc_vector_header(&self, state: &VectorState) -> ObjectReference {
+        let vector = self.with_mutator(|mutator| {
+            BlinkActivePlan::alloc_object(mutator, &TypeTag::Vector, &HEADER_SIZE)
+        });
+        self.write_vector(vector, state);
+        vector
+    }
+
+    // === Read Operations ===
+
+    /// Allocate a vector holding `items`, built through a transient
+    pub fn alloc_vec(&self, items: Vec<ValueRef>) -> ObjectReference {
+        let _no_gc = NoGcSection::enter();
+        let mut state = VectorState::empty(new_edit());
+        for item in items {
+            self.vector_conj_into(&mut state, item);
         }
+        state.edit = 0;
+        self.alloc_vector_header(&state)
     }
-    
-    /// Get element at index - conditional locking
+
+    pub fn vector_get_length(&self, vector: ObjectReference) -> u32 {
+        read_u32_field(vector, COUNT_OFFSET)
+    }
+
     pub fn vector_get_at(&self, vector: ObjectReference, index: u32) -> Result<ValueRef, String> {
-        // Conditional locking based on sharing
-        let _guard = if is_object_shared(vector) {
-            Some(ObjectLockGuard::new(vector))
-        } else {
-            None
-        };
-        
-        unsafe {
-            let header_ptr = vector.to_raw_address().as_usize() as *mut u8;
-            let mut offset = 0;
-            
-            let length = std::ptr::read_unaligned(header_ptr.add(offset) as *const u32);
-            offset += std::mem::size_of::<u32>() * 2; // Skip length and capacity
-            
-            if index >= length {
-                return Err(format!("Index {} out of bounds for vector of length {}", index, length));
-            }
-            
-            let data_ref = std::ptr::read_unaligned(header_ptr.add(offset) as *const ObjectReference);
-            let data_ptr = data_ref.to_raw_address().as_usize() as *mut u8;
-            let item_ptr = data_ptr.add(index as usize * std::mem::size_of::<ValueRef>());
-            
-            Ok(std::ptr::read_unaligned(item_ptr as *const ValueRef))
+        let state = self.read_vector(vector);
+        if index >= state.count {
+            return Err(format!("Index {} out of bounds for vector of length {}", index, state.count));
         }
+        Ok(self.vector_leaf_for(&state, index).get((index & MASK) as usize))
     }
-    
-    // === Write Operations (Need barriers and locking) ===
-    
-    /// Update element at index - proper barriers and conditional locking
-    pub fn vector_update_at(&self, vector: ObjectReference, index: u32, element: ValueRef) -> Result<(), String> {
-        self.with_mutator(|mutator| {
-            // Conditional locking
-            let _guard = if is_object_shared(vector) {
-                Some(ObjectLockGuard::new(vector))
+
+    /// Copy out every item, one leaf at a time
+    pub fn vector_to_vec(&self, vector: ObjectReference) -> Vec<ValueRef> {
+        let state = self.read_vector(vector);
+        let mut items = Vec::with_capacity(state.count as usize);
+        let mut start = 0;
+        while start < state.count {
+            let leaf = self.vector_leaf_for(&state, start);
+            let len = (state.count - start).min(WIDTH as u32) as usize;
+            items.extend((0..len).map(|i| leaf.get(i)));
+            start += WIDTH as u32;
+        }
+        items
+    }
+
+    fn vector_leaf_for(&self, state: &VectorState, index: u32) -> TrieNode {
+        if index >= state.tail_offset() {
+            return state.tail.expect("non-empty vector has a tail");
+        }
+        let mut node = state.root.expect("vector trie has a root");
+        let mut level = state.shift;
+        while level > 0 {
+            node = node.child(((index >> level) & MASK) as usize).expect("vector trie path is complete");
+            level -= BITS;
+        }
+        node
+    }
+
+    // === Persistent updates (return a new vector, the old one is unchanged) ===
+
+    pub fn vector_conj(&self, vector: ObjectReference, item: ValueRef) -> ObjectReference {
+        let _no_gc = NoGcSection::enter();
+        let mut state = self.read_vector(vector);
+        debug_assert_eq!(state.edit, 0, "persistent update on a transient vector");
+        state.edit = 0;
+        self.vector_conj_into(&mut state, item);
+        self.alloc_vector_header(&state)
+    }
+
+    /// Replace the item at `index`; an index equal to the length appends
+    pub fn vector_assoc(&self, vector: ObjectReference, index: u32, item: ValueRef) -> Result<ObjectReference, String> {
+        let _no_gc = NoGcSection::enter();
+        let mut state = self.read_vector(vector);
+        debug_assert_eq!(state.edit, 0, "persistent update on a transient vector");
+        state.edit = 0;
+        self.vector_assoc_into(&mut state, index, item)?;
+        Ok(self.alloc_vector_header(&state))
+    }
+
+    // === Transients (batch updates in place, nodes copied at most once) ===
+
+    pub fn vector_is_transient(&self, vector: ObjectReference) -> bool {
+        read_u32_field(vector, EDIT_OFFSET) != 0
+    }
+
+    /// Make a transient copy sharing all nodes with `vector`
+    pub fn vector_transient(&self, vector: ObjectReference) -> ObjectReference {
+        let mut state = self.read_vector(vector);
+        state.edit = new_edit();
+        self.alloc_vector_header(&state)
+    }
+
+    /// Freeze a transient in place and return it as a persistent vector
+    pub fn vector_persistent(&self, vector: ObjectReference) -> Result<ObjectReference, String> {
+        if !self.vector_is_transient(vector) {
+            return Err("Transient used after persistent! call".to_string());
+        }
+        write_u32_field(vector, EDIT_OFFSET, 0);
+        Ok(vector)
+    }
+
+    pub fn vector_conj_mut(&self, vector: ObjectReference, item: ValueRef) -> Result<(), String> {
+        let mut state = self.read_vector(vector);
+        if state.edit == 0 {
+            return Err("Transient used after persistent! call".to_string());
+        }
+        let _no_gc = NoGcSection::enter();
+        self.vector_conj_into(&mut state, item);
+        self.write_vector(vector, &state);
+        Ok(())
+    }
+
+    pub fn vector_assoc_mut(&self, vector: ObjectReference, index: u32, item: ValueRef) -> Result<(), String> {
+        let mut state = self.read_vector(vector);
+        if state.edit == 0 {
+            return Err("Transient used after persistent! call".to_string());
+        }
+        let _no_gc = NoGcSection::enter();
+        self.vector_assoc_into(&mut state, index, item)?;
+        self.write_vector(vector, &state);
+        Ok(())
+    }
+
+    // === Trie operations shared by both modes. Edit 0 copies every touched node. ===
+
+    fn vector_conj_into(&self, state: &mut VectorState, item: ValueRef) {
+        let tail_len = state.tail_len();
+        if tail_len < WIDTH {
+            if state.edit == 0 {
+                let mut items = tail_items(state);
+                items.push(item);
+                state.tail = Some(self.alloc_trie_node(0, 0, 0, &items, items.len()));
             } else {
-                None
-            };
-            
-            unsafe {
-                let header_ptr = vector.to_raw_address().as_usize() as *mut u8;
-                let mut offset = 0;
-                
-                let length = std::ptr::read_unaligned(header_ptr.add(offset) as *const u32);
-                offset += std::mem::size_of::<u32>() * 2; // Skip length and capacity
-                
-                if index >= length {
-                    return Err(format!("Index {} out of bounds for vector of length {}", index, length));
-                }
-                
-                let data_ref = std::ptr::read_unaligned(header_ptr.add(offset) as *const ObjectReference);
-                let data_ptr = data_ref.to_raw_address().as_usize() as *mut u8;
-                let item_ptr = data_ptr.add(index as usize * std::mem::size_of::<ValueRef>());
-                
-                // Write the element
-                std::ptr::write_unaligned(item_ptr as *mut ValueRef, element);
-                
-                // Barrier only if storing a heap reference
-                if let ValueRef::Heap(gc_ptr) = element {
-                    let slot_addr = Address::from_mut_ptr(item_ptr);
-                    mutator.barrier().object_reference_write(
-                        data_ref,  // Source object (data array)
-                        BlinkSlot::ValueRef(slot_addr), 
-                        gc_ptr.0   // Target reference being stored
-                    );
-                }
-                
-                Ok(())
+                let tail = self.editable_tail(state);
+                self.trie_node_set(tail, tail_len, item);
+                state.tail = Some(tail);
             }
-        })
-    }
-    
-    /// Push element to end of vector
-    pub fn vector_push(&self, vector: ObjectReference, element: ValueRef) -> Result<(), String> {
-        self.with_mutator(|mutator| {
-            let _guard = if is_object_shared(vector) {
-                Some(ObjectLockGuard::new(vector))
+        } else {
+            self.push_tail_into_trie(state);
+            let capacity = if state.edit == 0 { 1 } else { WIDTH };
+            state.tail = Some(self.alloc_trie_node(state.edit, 0, 0, &[item], capacity));
+        }
+        state.count += 1;
+    }
+
+    fn vector_assoc_into(&self, state: &mut VectorState, index: u32, item: ValueRef) -> Result<(), String> {
+        if index == state.count {
+            self.vector_conj_into(state, item);
+            return Ok(());
+        }
+        if index > state.count {
+            return Err(format!("Index {} out of bounds for vector of length {}", index, state.count));
+        }
+
+        let slot = (index & MASK) as usize;
+        if index >= state.tail_offset() {
+            if state.edit == 0 {
+                let mut items = tail_items(state);
+                items[slot] = item;
+                state.tail = Some(self.alloc_trie_node(0, 0, 0, &items, items.len()));
             } else {
-                None
-            };
-            
-            unsafe {
-                let header_ptr = vector.to_raw_address().as_usize() as *mut u8;
-                let mut offset = 0;
-                
-                let current_length = std::ptr::read_unaligned(header_ptr.add(offset) as *const u32);
-                offset += std::mem::size_of::<u32>();
-                
-                let current_capacity = std::ptr::read_unaligned(header_ptr.add(offset) as *const u32);
-                offset += std::mem::size_of::<u32>();
-                
-                // Check if we need to resize
-                if current_length >= current_capacity {
-                    let new_capacity = (current_capacity * 2).max(8);
-                    self.vector_ensure_capacity_internal(vector, new_capacity, mutator)?;
-                }
-                
-                // Get (possibly new) data reference
-                let data_ref = std::ptr::read_unaligned(header_ptr.add(offset) as *const ObjectReference);
-                let data_ptr = data_ref.to_raw_address().as_usize() as *mut u8;
-                let item_ptr = data_ptr.add(current_length as usize * std::mem::size_of::<ValueRef>());
-                
-                // Write the element
-                std::ptr::write_unaligned(item_ptr as *mut ValueRef, element);
-                
-                // Update length
-                std::ptr::write_unaligned(header_ptr as *mut u32, current_length + 1);
-                
-                // Barrier for the new element
-                if let ValueRef::Heap(gc_ptr) = element {
-                    let slot_addr = Address::from_mut_ptr(item_ptr);
-                    mutator.barrier().object_reference_write(
-                        data_ref, 
-                        BlinkSlot::ValueRef(slot_addr), 
-                        gc_ptr.0
-                    );
-                }
-                
-                Ok(())
+                let tail = self.editable_tail(state);
+                self.trie_node_set(tail, slot, item);
+                state.tail = Some(tail);
             }
-        })
+        } else {
+            let root = state.root.expect("vector trie has a root");
+            state.root = Some(self.assoc_in_trie(state.edit, state.shift, root, index, item));
+        }
+        Ok(())
     }
-    
-    /// Pop element from end of vector
-    pub fn vector_pop(&self, vector: ObjectReference) -> Result<Option<ValueRef>, String> {
-        let _guard = if is_object_shared(vector) {
-            Some(ObjectLockGuard::new(vector))
+
+    // Move the full tail into the trie as a new leaf, growing the trie by a level when the root is full
+    fn push_tail_into_trie(&self, state: &mut VectorState) {
+        let leaf = state.tail.expect("full tail");
+        if (state.count >> BITS) > (1 << state.shift) {
+            let old_root = state.root.expect("vector trie has a root");
+            let path = self.new_trie_path(state.edit, state.shift, leaf);
+            let new_root = self.alloc_trie_node(state.edit, 0, 0, &[node_value(old_root), node_value(path)], WIDTH);
+            state.root = Some(new_root);
+            state.shift += BITS;
         } else {
-            None
+            state.root = Some(self.push_tail(state.edit, state.count, state.shift, state.root, leaf));
+        }
+    }
+
+    fn push_tail(&self, edit: u32, count: u32, level: u32, parent: Option<TrieNode>, leaf: TrieNode) -> TrieNode {
+        let parent = match parent {
+            Some(parent) => self.editable_trie_node(parent, edit),
+            None => self.alloc_trie_node(edit, 0, 0, &[], WIDTH),
         };
-        
-        unsafe {
-            let header_ptr = vector.to_raw_address().as_usize() as *mut u8;
-            let current_length = std::ptr::read_unaligned(header_ptr as *const u32);
-            
-            if current_length == 0 {
-                return Ok(None);
+        let index = (((count - 1) >> level) & MASK) as usize;
+        let insert = if level == BITS {
+            leaf
+        } else {
+            match parent.child(index) {
+                Some(child) => self.push_tail(edit, count, level - BITS, Some(child), leaf),
+                None => self.new_trie_path(edit, level - BITS, leaf),
             }
-            
-            let new_length = current_length - 1;
-            
-            // Get the element before removing it
-            let element = self.vector_get_at(vector, new_length)?;
-            
-            // Update length
-            std::ptr::write_unaligned(header_ptr as *mut u32, new_length);
-            
-            Ok(Some(element))
+        };
+        self.trie_node_set(parent, index, node_value(insert));
+        parent
+    }
+
+    fn new_trie_path(&self, edit: u32, level: u32, node: TrieNode) -> TrieNode {
+        if level == 0 {
+            return node;
         }
+        let child = self.new_trie_path(edit, level - BITS, node);
+        self.alloc_trie_node(edit, 0, 0, &[node_value(child)], WIDTH)
     }
-    
-    /// Resize vector - complex operation with proper data copying
-    pub fn vector_resize(&self, vector: ObjectReference, new_size: u32, fill_value: ValueRef) -> Result<(), String> {
-        self.with_mutator(|mutator| {
-            let _guard = if is_object_shared(vector) {
-                Some(ObjectLockGuard::new(vector))
-            } else {
-                None
-            };
-            
-            unsafe {
-                let header_ptr = vector.to_raw_address().as_usize() as *mut u8;
-                let mut offset = 0;
-                
-                let current_length = std::ptr::read_unaligned(header_ptr.add(offset) as *const u32);
-                offset += std::mem::size_of::<u32>();
-                
-                let current_capacity = std::ptr::read_unaligned(header_ptr.add(offset) as *const u32);
-                offset += std::mem::size_of::<u32>();
-                
-                // If shrinking, just update length
-                if new_size <= current_length {
-                    std::ptr::write_unaligned(header_ptr as *mut u32, new_size);
-                    return Ok(());
-                }
-                
-                // If growing beyond capacity, reallocate
-                if new_size > current_capacity {
-                    self.vector_ensure_capacity_internal(vector, new_size, mutator)?;
-                }
-                
-                // Fill new elements
-                let data_ref = std::ptr::read_unaligned(header_ptr.add(offset) as *const ObjectReference);
-                let data_ptr = data_ref.to_raw_address().as_usize() as *mut u8;
-                
-                for i in current_length..new_size {
-                    let item_ptr = data_ptr.add(i as usize * std::mem::size_of::<ValueRef>());
-                    std::ptr::write_unaligned(item_ptr as *mut ValueRef, fill_value);
-                    
-                    // Barrier for each new element
-                    if let ValueRef::Heap(gc_ptr) = fill_value {
-                        let slot_addr = Address::from_mut_ptr(item_ptr);
-                        mutator.barrier().object_reference_write(
-                            data_ref, 
-                            BlinkSlot::ValueRef(slot_addr), 
-                            gc_ptr.0
-                        );
-                    }
-                }
-                
-                // Update length
-                std::ptr::write_unaligned(header_ptr as *mut u32, new_size);
-                
-                Ok(())
-            }
-        })
-    }
-    
-    // === Internal Helper Methods ===
-    
-    /// Ensure vector has at least the specified capacity
-    fn vector_ensure_capacity_internal(&self, vector: ObjectReference, new_capacity: u32, mutator: &mut Mutator<BlinkVM>) -> Result<(), String> {
-        unsafe {
-            let header_ptr = vector.to_raw_address().as_usize() as *mut u8;
-            let mut offset = 0;
-            
-            let current_length = std::ptr::read_unaligned(header_ptr.add(offset) as *const u32);
-            offset += std::mem::size_of::<u32>();
-            
-            let current_capacity = std::ptr::read_unaligned(header_ptr.add(offset) as *const u32);
-            offset += std::mem::size_of::<u32>();
-            
-            if new_capacity <= current_capacity {
-                return Ok(()); // Already sufficient capacity
+
+    fn assoc_in_trie(&self, edit: u32, level: u32, node: TrieNode, index: u32, item: ValueRef) -> TrieNode {
+        let node = self.editable_trie_node(node, edit);
+        if level == 0 {
+            self.trie_node_set(node, (index & MASK) as usize, item);
+        } else {
+            let slot = ((index >> level) & MASK) as usize;
+            let child = node.child(slot).expect("vector trie path is complete");
+            let child = self.assoc_in_trie(edit, level - BITS, child, index, item);
+            self.trie_node_set(node, slot, node_value(child));
+        }
+        node
+    }
+
+    // A node the current edit may write to: itself if the transient owns it, otherwise a copy
+    fn editable_trie_node(&self, node: TrieNode, edit: u32) -> TrieNode {
+        if edit != 0 && node.edit() == edit {
+            node
+        } else {
+            self.alloc_trie_node(edit, 0, 0, &node.slots(), node.slot_count())
+        }
+    }
+
+    // Transient tails always have room for a full leaf
+    fn editable_tail(&self, state: &VectorState) -> TrieNode {
+        match state.tail {
+            Some(tail) if tail.edit() == state.edit && tail.slot_count() == WIDTH => tail,
+            _ => self.alloc_trie_node(state.edit, 0, 0, &tail_items(state), WIDTH),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::test_support::vm;
+
+    #[derive(Clone, Debug)]
+    enum Op {
+        Conj(u16),
+        // Index taken modulo the length plus one, so the end appends
+        Assoc(usize, u16),
+    }
+
+    fn ops(max: usize) -> impl Strategy<Value = Vec<Op>> {
+        let op = prop_oneof![
+            3 => any::<u16>().prop_map(Op::Conj),
+            1 => (any::<usize>(), any::<u16>()).prop_map(|(index, value)| Op::Assoc(index, value)),
+        ];
+        prop::collection::vec(op, 0..max)
+    }
+
+    fn item(value: u16) -> ValueRef {
+        ValueRef::number(value as f64)
+    }
+
+    // Apply an op to the model, returning the index it wrote
+    fn apply(model: &mut Vec<ValueRef>, op: &Op) -> u32 {
+        match *op {
+            Op::Conj(value) => {
+                model.push(item(value));
+                model.len() as u32 - 1
             }
-            
-            // Allocate new data array
-            let new_data_size = new_capacity as usize * std::mem::size_of::<ValueRef>();
-            let new_data_ref = BlinkActivePlan::alloc(mutator, &new_data_size);
-            let new_data_ptr = new_data_ref.to_raw_address().as_usize() as *mut u8;
-            
-            // Copy existing elements
-            let old_data_ref = std::ptr::read_unaligned(header_ptr.add(offset) as *const ObjectReference);
-            let old_data_ptr = old_data_ref.to_raw_address().as_usize() as *const u8;
-            
-            for i in 0..current_length {
-                let old_item_ptr = old_data_ptr.add(i as usize * std::mem::size_of::<ValueRef>());
-                let new_item_ptr = new_data_ptr.add(i as usize * std::mem::size_of::<ValueRef>());
-                let element = std::ptr::read_unaligned(old_item_ptr as *const ValueRef);
-                std::ptr::write_unaligned(new_item_ptr as *mut ValueRef, element);
-                
-                // Barrier for copied elements
-                if let ValueRef::Heap(gc_ptr) = element {
-                    let slot_addr = Address::from_mut_ptr(new_item_ptr);
-                    mutator.barrier().object_reference_write(
-                        new_data_ref, 
-                        BlinkSlot::ValueRef(slot_addr), 
-                        gc_ptr.0
-                    );
+            Op::Assoc(index, value) => {
+                let index = index % (model.len() + 1);
+                if index == model.len() {
+                    model.push(item(value));
+                } else {
+                    model[index] = item(value);
                 }
+                index as u32
             }
-            
-            // Update header with new data reference and capacity
-            std::ptr::write_unaligned(header_ptr.add(offset) as *mut u32, new_capacity);
-            offset += std::mem::size_of::<u32>();
-            std::ptr::write_unaligned(header_ptr.add(offset) as *mut ObjectReference, new_data_ref);
-            
-            // Barrier for data reference update
-            let data_ref_slot_addr = vector.to_raw_address() + (2 * std::mem::size_of::<u32>());
-            mutator.barrier().object_reference_write(
-                vector, 
-                BlinkSlot::ObjectRef(data_ref_slot_addr), 
-                new_data_ref
-            );
-            
-            Ok(())
         }
     }
-    
-    /// Mark object as shared when passed to another goroutine
-    pub fn mark_object_as_shared(&self, obj_ref: ObjectReference) {
-        // This will transition the object to thin-lock state
-        obj_lock(obj_ref);
-        obj_unlock(obj_ref);
-        // Object is now marked as having been accessed by multiple goroutines
-    }
-    
-    // === Convenience Methods ===
-    
-    /// Check if vector is empty
-    pub fn vector_is_empty(&self, vector: ObjectReference) -> bool {
-        self.vector_get_length(vector) == 0
-    }
-    
-    /// Clear all elements from vector
-    pub fn vector_clear(&self, vector: ObjectReference) {
-        let _guard = if is_object_shared(vector) {
-            Some(ObjectLockGuard::new(vector))
-        } else {
-            None
-        };
-        
-        unsafe {
-            let header_ptr = vector.to_raw_address().as_usize() as *mut u8;
-            std::ptr::write_unaligned(header_ptr as *mut u32, 0); // Set length to 0
+
+    fn check_vector(vector: ObjectReference, model: &[ValueRef]) -> Result<(), TestCaseError> {
+        let vm = vm();
+        prop_assert_eq!(vm.vector_get_length(vector) as usize, model.len());
+        prop_assert_eq!(&vm.vector_to_vec(vector), model);
+        for (index, expected) in model.iter().enumerate() {
+            prop_assert_eq!(vm.vector_get_at(vector, index as u32).unwrap(), *expected);
         }
+        prop_assert!(vm.vector_get_at(vector, model.len() as u32).is_err());
+        Ok(())
     }
 
-    pub fn alloc_vec(&self, items: Vec<ValueRef>, capacity: Option<usize>) -> ObjectReference {
-        
-        self.with_mutator(|mutator| {
-            let capacity = capacity.unwrap_or(items.len().max(8));
-            
-
-            let header_size =
-                std::mem::size_of::<u32>() +      // length
-                std::mem::size_of::<u32>() +      // capacity  
-                std::mem::size_of::<ObjectReference>(); // data_ptr
-
-            let data_size = capacity * std::mem::size_of::<ValueRef>();
-            
-            
-            let type_tag = TypeTag::Vector;
-            
-            // Allocate header object
-            
-            let object_start = BlinkActivePlan::alloc_object(mutator, &type_tag, &header_size);
-
-            // Allocate separate data array
-            let data_start = BlinkActivePlan::alloc(mutator, &data_size);
-            
-            // Initialize metadata for both allocations
-            
-            
-            unsafe {
-                let header_ptr = object_start.to_raw_address().as_usize() as *mut u8;
-                let mut offset = 0;
-
-                // Write length
-                std::ptr::write_unaligned(header_ptr.add(offset) as *mut u32, items.len() as u32);
-                offset += std::mem::size_of::<u32>();
-
-                // Write capacity
-                std::ptr::write_unaligned(header_ptr.add(offset) as *mut u32, capacity as u32);
-                offset += std::mem::size_of::<u32>();
-
-                // Write data pointer
-                std::ptr::write_unaligned(header_ptr.add(offset) as *mut ObjectReference, data_start);
-
-                // Write data array
-                let data_ptr = data_start.to_raw_address().as_usize() as *mut ValueRef;
-                for (i, item) in items.iter().enumerate() {
-                    std::ptr::write(data_ptr.add(i), *item);
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        // Long enough to grow the trie past two levels (1056 items)
+        #[test]
+        fn persistent_updates_match_a_vec(ops in ops(1500)) {
+            let vm = vm();
+            let mut vector = vm.alloc_vec(Vec::new());
+            let mut model = Vec::new();
+            let mut versions = vec![(vector, model.clone())];
+            for (step, op) in ops.iter().enumerate() {
+                let index = apply(&mut model, op);
+                vector = match *op {
+                    Op::Conj(value) => vm.vector_conj(vector, item(value)),
+                    Op::Assoc(_, value) => vm.vector_assoc(vector, index, item(value)).unwrap(),
+                };
+                if step % 100 == 0 {
+                    versions.push((vector, model.clone()));
                 }
-                
-                // Zero out unused capacity
-                for i in items.len()..capacity {
-                    std::ptr::write(data_ptr.add(i), ValueRef::nil());
+            }
+            check_vector(vector, &model)?;
+            // Earlier versions are unchanged
+            for (vector, model) in &versions {
+                check_vector(*vector, model)?;
+            }
+        }
+
+        #[test]
+        fn transients_match_a_vec_and_leave_their_source_alone(initial in 0usize..1200, ops in ops(1200)) {
+            let vm = vm();
+            let source_model: Vec<ValueRef> = (0..initial).map(|n| item(n as u16)).collect();
+            let source = vm.alloc_vec(source_model.clone());
+
+            let mut model = source_model.clone();
+            let transient = vm.vector_transient(source);
+            for op in &ops {
+                let index = apply(&mut model, op);
+                match *op {
+                    Op::Conj(value) => vm.vector_conj_mut(transient, item(value)).unwrap(),
+                    Op::Assoc(_, value) => vm.vector_assoc_mut(transient, index, item(value)).unwrap(),
                 }
             }
-            
-            object_start
-        })
-    }
-    
-    // Debug version of vector access
-    pub fn vector_get_length_debug(&self, vector: ObjectReference) -> u32 {
-        
-        unsafe {
-            let header_ptr = vector.to_raw_address().as_usize() as *const u8;
-            
-            let length = std::ptr::read_unaligned(header_ptr as *const u32);
-            
-            length
+            let result = vm.vector_persistent(transient).unwrap();
+            check_vector(result, &model)?;
+            check_vector(source, &source_model)?;
+            prop_assert!(vm.vector_conj_mut(result, item(0)).is_err());
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn assoc_past_the_end_is_an_error() {
+        let vm = vm();
+        let vector = vm.alloc_vec(vec![item(1)]);
+        assert!(vm.vector_assoc(vector, 2, item(2)).is_err());
+        let appended = vm.vector_assoc(vector, 1, item(2)).unwrap();
+        assert_eq!(vm.vector_to_vec(appended), vec![item(1), item(2)]);
+    }
+}
@@ -1,3 +1,4 @@
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use parking_lot::RwLock;
@@ -32,6 +33,12 @@ impl BlinkVM {
         self.symbol_table.read().get_symbol(id)
     }
 
+    /// A new symbol, `prefix__N`, for names a macro introduces
+    pub fn gensym(&self, prefix: &str) -> u32 {
+        let n = self.gensym_counter.fetch_add(1, Ordering::Relaxed);
+        self.symbol_table.write().intern(&format!("{}__{}", prefix, n))
+    }
+
     pub fn get_bool(&self, val: ValueRef) -> Option<bool> {
         if let ValueRef::Immediate(packed) = val {
             let unpacked = unpack_immediate(packed);
@@ -75,7 +82,7 @@ impl BlinkVM {
     }
 
     pub fn vector_value( &self, values: Vec<ValueRef>) -> ValueRef {
-        let object_ref = self.alloc_vec(values, None);
+        let object_ref = self.alloc_vec(values);
         ValueRef::Heap(GcPtr::new(object_ref))
     }
 
@@ -86,7 +93,7 @@ impl BlinkVM {
     }   
 
     pub fn set_value( &self, set: Vec<ValueRef>) -> ValueRef {
-        let object_ref = self.alloc_hashset(set, None);
+        let object_ref = self.alloc_set(set);
         ValueRef::Heap(GcPtr::new(object_ref))
     }
 
@@ -127,7 +134,7 @@ impl BlinkVM {
 
     pub fn empty_vector_value( &self) -> ValueRef {
         let vector = Vec::new();
-        let object_ref = self.alloc_vec(vector, None);
+        let object_ref = self.alloc_vec(vector);
         ValueRef::Heap(GcPtr::new(object_ref))
     }
     pub fn error_value( &self, error: BlinkError) -> ValueRef {
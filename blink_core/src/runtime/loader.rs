@@ -0,0 +1,392 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::compiler::{BytecodeCompiler, MacroExpander};
+use crate::module::{FileLoad, SerializedModuleSource};
+use crate::parser::parse_all;
+use crate::project::ResolvedProject;
+use crate::runtime::{CallFrame, ExecutionContext, FunctionRef};
+use crate::value::ValueRef;
+
+/// Extension of Blink source files
+pub const SOURCE_EXTENSION: &str = "blink";
+
+/// Environment variable with extra module directories, separated like PATH
+pub const MODULE_PATH_VAR: &str = "BLINK_PATH";
+
+// Marks a load error with the file it happened in
+const LOADING_NOTE: &str = "while loading";
+
+/// Directories searched, in order, for the file of a module that is
+/// imported before it is defined
+#[derive(Clone, Debug, Default)]
+pub struct ModulePath {
+    dirs: Vec<PathBuf>,
+}
+
+impl ModulePath {
+    /// $BLINK_PATH, then the working directory
+    pub fn from_environment() -> Self {
+        let mut path = ModulePath::default();
+        if let Some(dirs) = std::env::var_os(MODULE_PATH_VAR) {
+            std::env::split_paths(&dirs).for_each(|dir| path.add(dir));
+        }
+        path.add(std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")));
+        path
+    }
+
+    /// The source paths of a resolved project and of its dependencies, then
+    /// `from_environment`
+    pub fn for_project(project: &ResolvedProject) -> Self {
+        let mut path = ModulePath::default();
+        project.source_paths().into_iter().for_each(|dir| path.add(dir));
+        ModulePath::from_environment().dirs.into_iter().for_each(|dir| path.add(dir));
+        path
+    }
+
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    /// Append a directory, ignoring one already on the path
+    pub fn add(&mut self, dir: impl Into<PathBuf>) {
+        let dir = dir.into();
+        if !self.dirs.contains(&dir) {
+            self.dirs.push(dir);
+        }
+    }
+
+    /// The file for module `a.b.c`: the first `a/b/c.blink` on the path
+    pub fn find_module(&self, name: &str) -> Option<PathBuf> {
+        let relative = module_file_name(name);
+        self.find_file(&relative)
+    }
+
+    /// The first directory on the path holding `relative`
+    pub fn find_file(&self, relative: &Path) -> Option<PathBuf> {
+        self.dirs.iter().map(|dir| dir.join(relative)).find(|path| path.is_file())
+    }
+}
+
+/// `a.b.c` -> `a/b/c.blink`
+pub fn module_file_name(name: &str) -> PathBuf {
+    let mut path: PathBuf = name.split('.').collect();
+    path.set_extension(SOURCE_EXTENSION);
+    path
+}
+
+/// A file being evaluated, for cycle detection and relative loads
+#[derive(Clone, Debug)]
+pub struct PendingLoad {
+    pub file: PathBuf,
+    pub file_id: u32,
+    /// The module an import is loading the file for
+    pub module: Option<u32>,
+}
+
+impl ExecutionContext<'_> {
+    /// The file of the innermost load in progress
+    pub fn loading_file(&self) -> Option<u32> {
+        self.load_stack.last().map(|load| load.file_id)
+    }
+
+    /// Resolve the path given to (load ...): relative to the file doing the
+    /// load, or the working directory at the top level, with or without the
+    /// .blink extension, and then on the module path
+    pub fn resolve_load_path(&self, path: &str) -> Result<PathBuf, String> {
+        let requested = Path::new(path);
+        let base = self
+            .load_stack
+            .last()
+            .and_then(|load| load.file.parent().map(Path::to_path_buf))
+            .unwrap_or_default();
+
+        let mut candidates = vec![base.join(requested)];
+        if requested.extension().is_none() {
+            candidates.push(base.join(requested).with_extension(SOURCE_EXTENSION));
+        }
+        if let Some(found) = candidates.into_iter().find(|candidate| candidate.is_file()) {
+            return Ok(found);
+        }
+
+        let module_path = self.vm.module_path.read();
+        module_path
+            .find_file(requested)
+            .or_else(|| module_path.find_file(&requested.with_extension(SOURCE_EXTENSION)))
+            .ok_or_else(|| format!("load: no such file: {}", path))
+    }
+
+    /// Find and evaluate the file that defines `module_id`
+    pub fn load_module(&mut self, module_id: u32) -> Result<(), String> {
+        let name = self.vm.symbol_table.read().get_symbol(module_id).unwrap_or_default();
+        let Some(path) = self.vm.module_path.read().find_module(&name) else {
+            return Err(format!(
+                "imp: no module named {} (looked for {} on the module path)",
+                name,
+                module_file_name(&name).display()
+            ));
+        };
+        self.load_file(&path, Some(module_id))?;
+
+        if self.vm.module_registry.read().get_module(module_id).is_none() {
+            return Err(format!("imp: {} did not define module {}", path.display(), name));
+        }
+        Ok(())
+    }
+
+    /// Evaluate every form in a file, once. Loading for an import starts the
+    /// file in that module; a plain load starts it in the current module.
+    /// Returns the value of the last form, or nil if the file was already loaded.
+    pub fn load_file(&mut self, path: &Path, module: Option<u32>) -> Result<ValueRef, String> {
+        let file = fs::canonicalize(path).map_err(|e| format!("load: {}: {}", path.display(), e))?;
+        let file_id = self.vm.symbol_table.write().intern_simple(&file.display().to_string());
+        if self.vm.module_registry.read().is_file_evaluated(file_id) {
+            return Ok(ValueRef::nil());
+        }
+
+        let mut created_module = false;
+        if let Some(module_id) = module {
+            let mut registry = self.vm.module_registry.write();
+            created_module = registry.get_module(module_id).is_none();
+            registry.enter_module(module_id, None, SerializedModuleSource::BlinkFile(file_id));
+            registry.record_module_file(module_id, file_id);
+        }
+
+        let start_module = module.unwrap_or(self.current_module);
+        let result = self.evaluate_file(file, file_id, module, start_module);
+        if let (Err(_), Some(module_id), true) = (&result, module, created_module) {
+            // Drop the half-loaded module so a fixed file can be imported again
+            self.vm.module_registry.write().remove_module(module_id);
+        }
+        result
+    }
+
+    /// Run a file's forms starting in `start_module`, and record the load
+    pub(crate) fn evaluate_file(
+        &mut self,
+        file: PathBuf,
+        file_id: u32,
+        module: Option<u32>,
+        start_module: u32,
+    ) -> Result<ValueRef, String> {
+        let display = file.display().to_string();
+        if let Some(start) = self.load_stack.iter().position(|load| load.file_id == file_id) {
+            return Err(self.cycle_error(start, file_id));
+        }
+
+        let modified = fs::metadata(&file).and_then(|metadata| metadata.modified()).ok();
+        let source = fs::read_to_string(&file).map_err(|e| format!("load: {}: {}", display, e))?;
+        let forms = {
+            let mut reader_macros = self.vm.reader_macros.write();
+            let mut symbol_table = self.vm.symbol_table.write();
+            parse_all(&source, &mut reader_macros, &mut symbol_table)
+                .map_err(|e| format!("load: {}: {}", display, e))?
+        };
+
+        let saved_module = self.current_module;
+        self.current_module = start_module;
+        self.load_stack.push(PendingLoad { file: file.clone(), file_id, module });
+
+        let mut result = Ok(ValueRef::nil());
+        for form in forms {
+            let expr = self.vm.alloc_parsed_value(form);
+            result = self.eval_nested(expr);
+            if result.is_err() {
+                break;
+            }
+        }
+
+        self.load_stack.pop();
+        self.current_module = saved_module;
+        match result {
+            Ok(value) => {
+                let mut registry = self.vm.module_registry.write();
+                registry.mark_file_evaluated(file_id);
+                registry.record_file_load(file_id, FileLoad { path: file, module: start_module, modified });
+                Ok(value)
+            }
+            // Name only the innermost file, where the error happened
+            Err(e) if e.contains(LOADING_NOTE) => Err(e),
+            Err(e) => Err(format!("{} ({} {})", e, LOADING_NOTE, display)),
+        }
+    }
+
+    /// Whether `module_id` is the module of a load still in progress
+    pub fn is_loading_module(&self, module_id: u32) -> bool {
+        self.load_stack.iter().any(|load| load.module == Some(module_id))
+    }
+
+    /// The cycle error for importing a module whose file is still loading
+    pub fn module_cycle_error(&self, module_id: u32) -> String {
+        let start = self
+            .load_stack
+            .iter()
+            .position(|load| load.module == Some(module_id))
+            .unwrap_or(0);
+        let file_id = self.load_stack[start].file_id;
+        self.cycle_error(start, file_id)
+    }
+
+    // "Cyclic import: a -> b -> a", naming modules where known and files otherwise
+    fn cycle_error(&self, start: usize, file_id: u32) -> String {
+        let symbol_table = self.vm.symbol_table.read();
+        let name = |load: &PendingLoad| {
+            load.module
+                .and_then(|module| symbol_table.get_symbol(module))
+                .unwrap_or_else(|| load.file.display().to_string())
+        };
+        let mut cycle: Vec<String> = self.load_stack[start..].iter().map(name).collect();
+        if let Some(first) = self.load_stack.iter().find(|load| load.file_id == file_id) {
+            cycle.push(name(first));
+        }
+        format!("Cyclic import: {}", cycle.join(" -> "))
+    }
+
+    /// Expand, compile and run one top-level form on top of the running frames
+    fn eval_nested(&mut self, expr: ValueRef) -> Result<ValueRef, String> {
+        // A load inside a running evaluation stays under its limits; a reload
+        // started with nothing running, as the REPL's watch mode does, starts afresh
+        if self.call_stack.is_empty() {
+            self.start_evaluation();
+        }
+        let mut macro_expander = MacroExpander::new(self.vm.clone(), self.current_module, self);
+        let expanded = macro_expander.expand(expr)?;
+        let expanded_macros = macro_expander.expanded_macros();
+        self.record_macro_uses(expanded, &expanded_macros);
+        let mut compiler = BytecodeCompiler::new(self.vm.clone(), self.current_module);
+        let compiled = compiler.compile_for_storage(expanded)?;
+
+        let base_depth = self.call_stack.len();
+        let reg_start = self.register_stack.len();
+        let reg_count = compiled.register_count;
+        self.register_stack.extend((0..reg_count).map(|_| ValueRef::nil()));
+        self.push_frame(CallFrame {
+            func: FunctionRef::CompiledFunction(compiled, None),
+            pc: 0,
+            reg_start,
+            reg_count,
+            current_module: self.current_module,
+        });
+
+        let result = self.run_frames_until(base_depth);
+        if result.is_err() {
+            self.unwind_to(base_depth);
+        }
+        self.register_stack.truncate(reg_start);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::{read_project, resolve_project, LOCK_FILE};
+    use crate::test_support::{context, eval, try_eval};
+
+    // A fresh directory under the system temp dir
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("blink-loader-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(dir: &Path, relative: &str, source: &str) -> PathBuf {
+        let path = dir.join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, source).unwrap();
+        path
+    }
+
+    #[test]
+    fn load_paths_resolve_from_the_loading_file_then_the_module_path() {
+        let dir = scratch("paths");
+        let main = write(&dir, "nested/main.blink", "(load \"helper\") (+ loader-helper 1)");
+        write(&dir, "nested/helper.blink", "(def loader-helper 41)");
+        let on_path = write(&dir, "loader-on-path.blink", "(def loader-on-path 3)");
+        let mut ctx = context("loader-paths");
+        ctx.vm.module_path.write().add(&dir);
+
+        let main_name = main.display().to_string();
+        assert_eq!(ctx.resolve_load_path(&main_name), Ok(main.clone()));
+        assert_eq!(ctx.resolve_load_path("loader-on-path"), Ok(on_path));
+        let message = ctx.resolve_load_path("loader-missing").unwrap_err();
+        assert!(message.contains("no such file: loader-missing"), "{}", message);
+
+        assert_eq!(eval(&mut ctx, &format!("(load \"{}\")", main_name)), "42");
+        // Each file is evaluated once
+        eval(&mut ctx, "(def loader-helper 0)");
+        assert_eq!(eval(&mut ctx, &format!("(load \"{}\")", main_name)), "nil");
+        assert_eq!(eval(&mut ctx, "loader-helper"), "0");
+        assert_eq!(eval(&mut ctx, "(load \"loader-on-path\") loader-on-path"), "3");
+    }
+
+    #[test]
+    fn imports_load_module_files_from_the_module_path() {
+        let dir = scratch("modules");
+        write(&dir, "loader/shapes.blink", "(def sides 4)");
+        let mut ctx = context("loader-modules");
+        ctx.vm.module_path.write().add(&dir);
+
+        eval(&mut ctx, "(imp loader.shapes :as shapes)");
+        assert_eq!(eval(&mut ctx, "shapes/sides"), "4");
+
+        let missing = ctx.vm.symbol_table.write().intern("loader.missing");
+        let message = ctx.load_module(missing).unwrap_err();
+        assert!(message.contains("no module named loader.missing"), "{}", message);
+        assert!(message.contains(&module_file_name("loader.missing").display().to_string()), "{}", message);
+    }
+
+    #[test]
+    fn cyclic_imports_name_the_cycle() {
+        let dir = scratch("cycle");
+        write(&dir, "cycle/ping.blink", "(imp cycle.pong) (def ping 1)");
+        write(&dir, "cycle/pong.blink", "(imp cycle.ping) (def pong 2)");
+        let mut ctx = context("loader-cycle");
+        ctx.vm.module_path.write().add(&dir);
+
+        let message = try_eval(&mut ctx, "(imp cycle.ping)").unwrap_err();
+        assert!(message.contains("Cyclic import: cycle.ping -> cycle.pong -> cycle.ping"), "{}", message);
+
+        // The half-loaded modules are dropped, so the fixed files can be imported
+        write(&dir, "cycle/pong.blink", "(def pong 2)");
+        eval(&mut ctx, "(imp cycle.ping)");
+        assert_eq!(eval(&mut ctx, "(+ cycle.ping/ping cycle.pong/pong)"), "3");
+    }
+
+    #[test]
+    fn project_module_paths_come_first_and_report_a_malformed_manifest() {
+        let dir = scratch("project");
+        write(&dir, "blink.toml", "[modules]\npaths = [\"lib\"]\n");
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        let (project, up_to_date) = read_project(&dir).unwrap();
+        let path = ModulePath::for_project(&project);
+        assert_eq!(path.dirs().first(), Some(&dir.join("lib")));
+
+        // Reading a project leaves its lockfile to an explicit resolve
+        assert!(!up_to_date);
+        assert!(!dir.join(LOCK_FILE).exists());
+        resolve_project(&dir).unwrap();
+        assert!(read_project(&dir).unwrap().1);
+
+        write(&dir, "blink.toml", "[modules\n");
+        let message = read_project(&dir).unwrap_err();
+        assert!(message.contains("blink.toml"), "{}", message);
+    }
+
+    #[test]
+    fn project_plugins_are_resolved_and_a_missing_library_is_reported() {
+        let dir = scratch("plugins");
+        write(&dir, "blink.toml", "[plugins]\nsqlite = \"native/libblink_sqlite.so\"\n");
+        let (project, _) = read_project(&dir).unwrap();
+        assert_eq!(project.plugins.len(), 1);
+        let plugin = &project.plugins[0];
+        assert_eq!(plugin.path, fs::canonicalize(&dir).unwrap().join("native/libblink_sqlite.so"));
+
+        let ctx = context("loader-plugins");
+        let message = ctx.vm.load_plugin(&plugin.name, &plugin.path).unwrap_err();
+        assert!(message.starts_with("plugin sqlite: could not load"), "{}", message);
+        let module_id = ctx.vm.symbol_table.write().intern("sqlite");
+        assert!(ctx.vm.module_registry.read().get_module(module_id).is_none());
+    }
+}
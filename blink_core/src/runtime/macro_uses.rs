@@ -0,0 +1,99 @@
+use crate::runtime::{ExecutionContext, TypeTag};
+use crate::value::ValueRef;
+
+impl ExecutionContext<'_> {
+    /// Record the macros a top-level form expanded when the form is a
+    /// definition, so changing one of them can name the definitions built with it
+    pub(crate) fn record_macro_uses(&self, expanded: ValueRef, macros: &[(u32, u32)]) {
+        if let Some(name) = self.defined_name(expanded) {
+            self.vm
+                .module_registry
+                .write()
+                .record_macro_uses((self.current_module, name), macros);
+        }
+    }
+
+    // The name of (def name ...) or (defonce name ...), also when the reader
+    // wrapped it as (with-meta name {...})
+    fn defined_name(&self, form: ValueRef) -> Option<u32> {
+        let items = form.get_list()?;
+        let is_form = |item: Option<&ValueRef>, names: &[&str]| {
+            item.and_then(ValueRef::get_symbol)
+                .and_then(|symbol_id| self.vm.get_symbol_name(symbol_id))
+                .is_some_and(|name| names.contains(&name.as_str()))
+        };
+        if !is_form(items.first(), &["def", "defonce"]) {
+            return None;
+        }
+        let name = *items.get(1)?;
+        match name.get_list() {
+            Some(wrapped) if is_form(wrapped.first(), &["with-meta"]) => wrapped.get(1)?.get_symbol(),
+            _ => name.get_symbol(),
+        }
+    }
+
+    /// Called before a definition is stored: when it replaces a macro, warn
+    /// about the definitions still compiled with the old expansion
+    pub(crate) fn check_macro_redefinition(&mut self, module_id: u32, symbol_id: u32, value: ValueRef) {
+        let old_value = self.defined_macro(module_id, symbol_id);
+        if old_value.is_some_and(|old_value| old_value != value) {
+            if let Some(dependents) = self.macro_dependent_names((module_id, symbol_id)) {
+                let warning = format!(
+                    "macro {} was redefined; {} were compiled with its old expansion. Evaluate them again to use the new one.",
+                    self.qualified_name((module_id, symbol_id)),
+                    dependents
+                );
+                self.warnings.push(warning);
+            }
+        }
+    }
+
+    /// (rmac name): remove a macro defined in the module
+    pub(crate) fn remove_macro(&mut self, module_id: u32, symbol_id: u32) -> Result<(), String> {
+        if self.defined_macro(module_id, symbol_id).is_none() {
+            return Err(format!(
+                "rmac: {} is not a macro defined in {}",
+                self.vm.get_symbol_name(symbol_id).unwrap_or_default(),
+                self.vm.get_symbol_name(module_id).unwrap_or_default()
+            ));
+        }
+
+        self.vm.module_registry.write().remove_definition(module_id, symbol_id);
+        if let Some(dependents) = self.macro_dependent_names((module_id, symbol_id)) {
+            let warning = format!(
+                "macro {} was removed; {} were compiled with its expansion and keep it.",
+                self.qualified_name((module_id, symbol_id)),
+                dependents
+            );
+            self.warnings.push(warning);
+        }
+        self.vm.module_registry.write().forget_macro((module_id, symbol_id));
+        Ok(())
+    }
+
+    // The macro a module itself defines under a name
+    fn defined_macro(&self, module_id: u32, symbol_id: u32) -> Option<ValueRef> {
+        let registry = self.vm.module_registry.read();
+        let value = *registry.get_module(module_id)?.exports.get(&symbol_id)?;
+        value.heap_object(TypeTag::Macro).map(|_| value)
+    }
+
+    // "a/f, b/g" for the definitions compiled with a macro, if there are any
+    fn macro_dependent_names(&self, macro_name: (u32, u32)) -> Option<String> {
+        let dependents = self.vm.module_registry.read().macro_dependents(macro_name);
+        if dependents.is_empty() {
+            return None;
+        }
+        let names: Vec<String> = dependents.into_iter().map(|name| self.qualified_name(name)).collect();
+        Some(names.join(", "))
+    }
+
+    fn qualified_name(&self, (module_id, symbol_id): (u32, u32)) -> String {
+        let symbol_table = self.vm.symbol_table.read();
+        format!(
+            "{}/{}",
+            symbol_table.get_symbol(module_id).unwrap_or_default(),
+            symbol_table.get_symbol(symbol_id).unwrap_or_default()
+        )
+    }
+}
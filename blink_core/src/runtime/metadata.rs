@@ -1,4 +1,4 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use crate::{value::SourceRange, value::{GcPtr, ValueRef}};
 
@@ -9,9 +9,22 @@ pub struct TypeInfo {
     kind: String,
 }
 
+#[derive(Clone, Debug, Default)]
 pub struct ProfileData {
-    hits: u64,
-    time: u64,
+    pub calls: u64,
+    pub self_time: Duration,
+    pub total_time: Duration, // Recursive calls are only counted once
+    pub max_time: Duration,
+    pub alloc_bytes: usize,   // Allocated by the function itself, excluding callees
+}
+
+// One completed call, as measured by the execution context
+#[derive(Clone, Copy, Debug)]
+pub struct ProfileSample {
+    pub elapsed: Duration,
+    pub self_time: Duration,
+    pub self_alloc: usize,
+    pub recursive: bool,
 }
 
 
@@ -39,6 +52,53 @@ impl ValueMetadataStore {
     pub fn get_position(&self, id: ValueId) -> Option<SourceRange> {
         self.positions.get(&id).cloned()
     }
+
+    pub fn set_debug_name(&mut self, id: ValueId, name: impl Into<String>) {
+        self.debug_names.insert(id, name.into());
+    }
+
+    pub fn get_debug_name(&self, id: ValueId) -> Option<&str> {
+        self.debug_names.get(&id).map(String::as_str)
+    }
+
+    pub fn record_profile(&mut self, id: ValueId, sample: ProfileSample) {
+        let data = self.profiling.entry(id).or_default();
+        data.calls += 1;
+        data.self_time += sample.self_time;
+        if !sample.recursive {
+            data.total_time += sample.elapsed;
+        }
+        data.max_time = data.max_time.max(sample.elapsed);
+        data.alloc_bytes += sample.self_alloc;
+    }
+
+    pub fn clear_profile(&mut self) {
+        self.profiling.clear();
+    }
+
+    /// Per-function profile entries, slowest self time first
+    pub fn profile_report(&self) -> Vec<(String, ProfileData)> {
+        let mut entries: Vec<(String, ProfileData)> = self
+            .profiling
+            .iter()
+            .map(|(id, data)| (self.profile_name(*id), data.clone()))
+            .collect();
+        entries.sort_by(|a, b| b.1.self_time.cmp(&a.1.self_time));
+        entries
+    }
+
+    /// The profile entry of one function, if it was called while profiling
+    pub fn profile_entry(&self, id: ValueId) -> Option<(String, ProfileData)> {
+        let data = self.profiling.get(&id)?.clone();
+        Some((self.profile_name(id), data))
+    }
+
+    fn profile_name(&self, id: ValueId) -> String {
+        self.debug_names
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| format!("<fn@{:x}>", id))
+    }
 }
 
 impl GcPtr {
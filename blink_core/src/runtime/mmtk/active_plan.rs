@@ -3,7 +3,7 @@
 
 use mmtk::util::Address;
 use mmtk::{util::ObjectReference, vm::ActivePlan, Mutator, ObjectQueue};
-use std::cell::{RefCell, UnsafeCell};
+use std::cell::{Cell, RefCell, UnsafeCell};
 use std::collections::HashMap;
 use parking_lot::Mutex;
 use std::sync::{OnceLock, Arc};
@@ -35,6 +35,8 @@ unsafe impl Sync for ThreadSafeMutator {}
 thread_local! {
     static MUTATOR: RefCell<Option<Arc<ThreadSafeMutator>>> = RefCell::new(None);
     static THREAD_TLS: RefCell<Option<mmtk::util::VMMutatorThread>> = RefCell::new(None);
+    // Running total of bytes this thread has allocated through its mutator
+    static ALLOCATED_BYTES: Cell<usize> = Cell::new(0);
 }
 
 // Global mutator tracking - only for MMTk iteration, not coordination
@@ -85,6 +87,15 @@ impl BlinkActivePlan {
         })
     }
 
+    /// Total bytes allocated by the current thread's mutator so far
+    pub fn allocated_bytes() -> usize {
+        ALLOCATED_BYTES.with(|bytes| bytes.get())
+    }
+
+    fn record_allocation(size: usize) {
+        ALLOCATED_BYTES.with(|bytes| bytes.set(bytes.get().wrapping_add(size)));
+    }
+
     pub fn alloc(mutator: &mut Mutator<BlinkVM>, size: &usize) -> ObjectReference {
         let total_size = (size + 7) & !7; // Add alignment here too
         
@@ -109,6 +120,8 @@ impl BlinkActivePlan {
         if start.is_zero() {
             panic!("Failed to allocate raw memory of size {}", total_size);
         }
+
+        Self::record_allocation(total_size);
         
         // For raw allocation, return the start address directly as ObjectReference
         ObjectReference::from_raw_address(start).unwrap()
@@ -117,24 +130,25 @@ impl BlinkActivePlan {
     pub fn alloc_object(mutator: &mut Mutator<BlinkVM>, type_tag: &TypeTag, data_size: &usize) -> ObjectReference {
         let total_size = (ObjectHeader::SIZE + data_size + 7) & !7;
         
+        // Objects too big for the plan's default space go to the large object space
+        let max_default_size = crate::runtime::GLOBAL_MMTK
+            .get()
+            .expect("MMTK not initialized")
+            .get_plan()
+            .constraints()
+            .max_non_los_default_alloc_bytes;
+        let semantics = if total_size > max_default_size {
+            mmtk::AllocationSemantics::Los
+        } else {
+            mmtk::AllocationSemantics::Default
+        };
+
         // Try fast path
-        let mut start = mmtk::memory_manager::alloc(
-            mutator,
-            total_size,
-            8,
-            0,
-            mmtk::AllocationSemantics::Default,
-        );
+        let mut start = mmtk::memory_manager::alloc(mutator, total_size, 8, 0, semantics);
 
         // If it fails, fall back to slow path
         if start.is_zero() {
-            start = mmtk::memory_manager::alloc_slow(
-                mutator,
-                total_size,
-                8,
-                0,
-                mmtk::AllocationSemantics::Default,
-            );
+            start = mmtk::memory_manager::alloc_slow(mutator, total_size, 8, 0, semantics);
         }
 
         // Now continue as before
@@ -164,7 +178,8 @@ impl BlinkActivePlan {
         
         let obj_ref = ObjectReference::from_raw_address(start + ObjectHeader::SIZE).unwrap();
         
-        mmtk::memory_manager::post_alloc(mutator, obj_ref, total_size, mmtk::AllocationSemantics::Default);
+        mmtk::memory_manager::post_alloc(mutator, obj_ref, total_size, semantics);
+        Self::record_allocation(total_size);
         
         
         obj_ref
@@ -1,7 +1,7 @@
 // blink_core/src/runtime/mmtk/collection.rs
 // PROPER MMTk Collection implementation - let MMTk handle coordination
 
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use mmtk::{
     util::{VMThread, VMWorkerThread, VMMutatorThread}, 
     vm::Collection, 
@@ -15,6 +15,29 @@ pub struct BlinkCollection;
 // Simple global flag - MMTk will coordinate the timing
 static GC_IN_PROGRESS: AtomicBool = AtomicBool::new(false);
 
+// Open NoGcSections; collections only start when there are none
+static NO_GC_SECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// While alive, allocation never starts a collection. For building a structure
+/// whose new nodes are only held in Rust locals until the finished structure is
+/// linked into a rooted object, like the path copied by a persistent update.
+/// Keep sections short and free of calls into Blink code: the heap grows past
+/// its limit instead of being collected.
+pub(crate) struct NoGcSection(());
+
+impl NoGcSection {
+    pub(crate) fn enter() -> Self {
+        NO_GC_SECTIONS.fetch_add(1, Ordering::SeqCst);
+        NoGcSection(())
+    }
+}
+
+impl Drop for NoGcSection {
+    fn drop(&mut self) {
+        NO_GC_SECTIONS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
 impl Collection<BlinkVM> for BlinkCollection {
     fn stop_all_mutators<F>(_tls: VMWorkerThread, mut mutator_visitor: F)
     where
@@ -62,6 +85,10 @@ impl Collection<BlinkVM> for BlinkCollection {
         println!("🚦 MMTk: Mutator {:?} resumed after GC", std::thread::current().id());
     }
 
+    fn is_collection_enabled() -> bool {
+        NO_GC_SECTIONS.load(Ordering::SeqCst) == 0
+    }
+
     fn spawn_gc_thread(_tls: VMThread, ctx: mmtk::vm::GCThreadContext<BlinkVM>) {
         let mmtk = crate::runtime::GLOBAL_MMTK.get()
             .expect("MMTK not initialized");
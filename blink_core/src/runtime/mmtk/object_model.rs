@@ -23,6 +23,12 @@ pub enum TypeTag {
     Closure = 8,
     Env = 9,
     ListNode = 10,
+    TrieNode = 11,
+    LazySeq = 12,
+    Transducer = 13,
+    SortedMap = 14,
+    SortedSet = 15,
+    TreeNode = 16,
 }
 
 impl TypeTag {
@@ -39,6 +45,12 @@ impl TypeTag {
             TypeTag::Env => "env",
             TypeTag::Closure => "closure",
             TypeTag::ListNode => "list-node",
+            TypeTag::TrieNode => "trie-node",
+            TypeTag::LazySeq => "lazy-seq",
+            TypeTag::Transducer => "transducer",
+            TypeTag::SortedMap => "sorted-map",
+            TypeTag::SortedSet => "sorted-set",
+            TypeTag::TreeNode => "tree-node",
         }
     }
 }
@@ -162,6 +174,13 @@ impl ObjectModel<BlinkVM> for BlinkObjectModel {
             7 => &[7], // Macro
             8 => &[8], // Future
             9 => &[9], // Env
+            10 => &[10], // ListNode
+            11 => &[11], // TrieNode
+            12 => &[12], // LazySeq
+            13 => &[13], // Transducer
+            14 => &[14], // SortedMap
+            15 => &[15], // SortedSet
+            16 => &[16], // TreeNode
             _ => &[127], // Unknown/invalid type
         }
     }
@@ -33,7 +33,12 @@ impl Scanning<BlinkVM> for BlinkScanning {
             TypeTag::Set => Self::scan_set_object(slot_visitor, object),
             TypeTag::Error => Self::scan_error_object(slot_visitor, object),
             TypeTag::Closure => todo!(),
-            TypeTag::Macro => Self::scan_callable(slot_visitor, object),
+            TypeTag::Macro => Self::scan_macro(slot_visitor, object),
+            TypeTag::TrieNode => Self::scan_trie_node(slot_visitor, object),
+            TypeTag::LazySeq => Self::scan_lazy_seq(slot_visitor, object),
+            TypeTag::Transducer => Self::scan_transducer(slot_visitor, object),
+            TypeTag::SortedMap | TypeTag::SortedSet => Self::scan_sorted(slot_visitor, object),
+            TypeTag::TreeNode => Self::scan_tree_node(slot_visitor, object),
         }
     }
 
@@ -100,7 +105,7 @@ impl Scanning<BlinkVM> for BlinkScanning {
         {
             let modules = runtime.vm.module_registry.read();
             for module in modules.modules.values() {
-                for value in module.exports.values() {
+                for value in module.exports.values().chain(module.metadata.values()) {
                     if let ValueRef::Heap(_) = value {
                         let cell_addr = Address::from_ptr(value as *const ValueRef);
                         batch.push(BlinkSlot::ValueRef(cell_addr));
@@ -236,63 +241,24 @@ impl BlinkScanning {
         }
     }
     
-    /// Scan a vector object: [length: u32][capacity: u32][data_ptr: ObjectReference]
+    /// Scan a vector header: [count: u32][shift: u32][edit: u32][padding: u32][root: ValueRef][tail: ValueRef]
     fn scan_vector_object<SV: mmtk::vm::SlotVisitor<<BlinkVM as VMBinding>::VMSlot>>(
         slot_visitor: &mut SV,
         object: ObjectReference
     ) {
-        unsafe {
-            let header_ptr = object.to_raw_address().as_usize() as *const u8;
-            let mut offset = 0;
-            
-            // Skip length
-            offset += std::mem::size_of::<u32>();
-            
-            // Skip capacity 
-            offset += std::mem::size_of::<u32>();
-            
-            // Scan data pointer
-            let data_ptr_ptr = header_ptr.add(offset) as *const ObjectReference;
-            let data_ptr = std::ptr::read_unaligned(data_ptr_ptr);
-            // Note: data_ptr should never be None/null for vectors, but let's be safe
-            let slot = BlinkSlot::ObjectRef(Address::from_ptr(data_ptr_ptr));
-            slot_visitor.visit_slot(slot);
-            
-            // Also scan the data array contents
-            Self::scan_vector_data(slot_visitor, data_ptr, object);
-        }
+        let data_ptr = object.to_raw_address().as_usize() as *const u8;
+        Self::scan_value_ref_seq(slot_visitor, data_ptr, 2, 4 * std::mem::size_of::<u32>());
     }
-    
-    /// Scan the data array of a vector
-    fn scan_vector_data<SV: mmtk::vm::SlotVisitor<<BlinkVM as VMBinding>::VMSlot>>(
+
+    /// Scan a vector or hash trie node: [slot_count: u32][edit: u32][datamap: u32][nodemap: u32][slots: ValueRef; slot_count]
+    fn scan_trie_node<SV: mmtk::vm::SlotVisitor<<BlinkVM as VMBinding>::VMSlot>>(
         slot_visitor: &mut SV,
-        data_array: ObjectReference,
-        vector_header: ObjectReference
+        object: ObjectReference
     ) {
         unsafe {
-            // Get the length from the vector header
-            let header_ptr = vector_header.to_raw_address().as_usize() as *const u8;
-            let length = std::ptr::read_unaligned(header_ptr as *const u32) as usize;
-            
-            // Scan each ValueRef in the data array
-            let data_ptr = data_array.to_raw_address().as_usize() as *const u8;
-            
-            for i in 0..length {
-                let value_ptr = data_ptr.add(i * std::mem::size_of::<ValueRef>()) as *const ValueRef;
-                let value = std::ptr::read_unaligned(value_ptr);
-                
-                match value {
-                    ValueRef::Heap(_) => {
-                        let slot = BlinkSlot::ValueRef(Address::from_ptr(value_ptr));
-                        slot_visitor.visit_slot(slot);
-                    }
-                    ValueRef::Handle(handle) => {
-                        let vm = GLOBAL_VM.get().expect("BlinkVM not initialized");
-                        vm.mark_future_handle_reachable(value)
-                    }
-                    _ => {} // Skip immediate/native values
-                }
-            }
+            let data_ptr = object.to_raw_address().as_usize() as *const u8;
+            let slot_count = std::ptr::read_unaligned(data_ptr as *const u32) as usize;
+            Self::scan_value_ref_seq(slot_visitor, data_ptr, slot_count, 4 * std::mem::size_of::<u32>());
         }
     }
 
@@ -350,52 +316,66 @@ impl BlinkScanning {
         Self::scan_list_node(slot_visitor, object);
     }
 
+    /// Scan a lazy seq cell: [state: u32][generator: u32][source: ValueRef][first: ValueRef][rest: ValueRef]
+    fn scan_lazy_seq<SV: mmtk::vm::SlotVisitor<<BlinkVM as VMBinding>::VMSlot>>(
+        slot_visitor: &mut SV,
+        object: ObjectReference
+    ) {
+        let data_ptr = object.to_raw_address().as_usize() as *const u8;
+        Self::scan_value_ref_seq(slot_visitor, data_ptr, 3, 2 * std::mem::size_of::<u32>());
+    }
+
+    /// Scan a macro: [expander: ValueRef][params_count: u32][params...]
+    fn scan_macro<SV: mmtk::vm::SlotVisitor<<BlinkVM as VMBinding>::VMSlot>>(
+        slot_visitor: &mut SV,
+        object: ObjectReference
+    ) {
+        let data_ptr = object.to_raw_address().as_usize() as *const u8;
+        Self::scan_value_ref_seq(slot_visitor, data_ptr, 1, 0);
+    }
+
+    /// Scan a transducer: [kind: u32][padding: u32][arg: ValueRef]
+    fn scan_transducer<SV: mmtk::vm::SlotVisitor<<BlinkVM as VMBinding>::VMSlot>>(
+        slot_visitor: &mut SV,
+        object: ObjectReference
+    ) {
+        let data_ptr = object.to_raw_address().as_usize() as *const u8;
+        Self::scan_value_ref_seq(slot_visitor, data_ptr, 1, 2 * std::mem::size_of::<u32>());
+    }
+
+    /// Scan a sorted map or set header: [count: u32][padding: u32][comparator: ValueRef][root: ValueRef]
+    fn scan_sorted<SV: mmtk::vm::SlotVisitor<<BlinkVM as VMBinding>::VMSlot>>(
+        slot_visitor: &mut SV,
+        object: ObjectReference
+    ) {
+        let data_ptr = object.to_raw_address().as_usize() as *const u8;
+        Self::scan_value_ref_seq(slot_visitor, data_ptr, 2, 2 * std::mem::size_of::<u32>());
+    }
+
+    /// Scan a sorted tree node: [height: u32][padding: u32][key][value][left][right]
+    fn scan_tree_node<SV: mmtk::vm::SlotVisitor<<BlinkVM as VMBinding>::VMSlot>>(
+        slot_visitor: &mut SV,
+        object: ObjectReference
+    ) {
+        let data_ptr = object.to_raw_address().as_usize() as *const u8;
+        Self::scan_value_ref_seq(slot_visitor, data_ptr, 4, 2 * std::mem::size_of::<u32>());
+    }
+
+    /// Scan a map header: [count: u32][edit: u32][root: ValueRef]
     fn scan_map_object<SV: mmtk::vm::SlotVisitor<<BlinkVM as VMBinding>::VMSlot>>(
         slot_visitor: &mut SV,
         object: ObjectReference
     ) {
-        unsafe {
-            let data_ptr = object.to_raw_address().as_usize() as *const u8;
-            let mut offset = 0;
-            
-            // Read bucket_count
-            let bucket_count = std::ptr::read_unaligned(data_ptr.add(offset) as *const usize);
-            offset += std::mem::size_of::<usize>();
-            
-            // Read item_count
-            let item_count = std::ptr::read_unaligned(data_ptr.add(offset) as *const usize);
-            offset += std::mem::size_of::<usize>();
-            
-            // Skip bucket offsets (non-reference data)
-            offset += bucket_count * std::mem::size_of::<u32>();
-            
-            // Scan key-value pairs (each pair is 2 ValueRefs)
-            Self::scan_value_ref_seq(slot_visitor, data_ptr, item_count * 2, offset);
-        }
+        let data_ptr = object.to_raw_address().as_usize() as *const u8;
+        Self::scan_value_ref_seq(slot_visitor, data_ptr, 1, 2 * std::mem::size_of::<u32>());
     }
 
+    /// Sets share the map header layout
     fn scan_set_object<SV: mmtk::vm::SlotVisitor<<BlinkVM as VMBinding>::VMSlot>>(
         slot_visitor: &mut SV,
         object: ObjectReference
     ) {
-        unsafe {
-            let data_ptr = object.to_raw_address().as_usize() as *const u8;
-            let mut offset = 0;
-            
-            // Read bucket_count
-            let bucket_count = std::ptr::read_unaligned(data_ptr.add(offset) as *const usize);
-            offset += std::mem::size_of::<usize>();
-            
-            // Read item_count
-            let item_count = std::ptr::read_unaligned(data_ptr.add(offset) as *const usize);
-            offset += std::mem::size_of::<usize>();
-            
-            // Skip bucket offsets (non-reference data)
-            offset += bucket_count * std::mem::size_of::<u32>();
-            
-            // Scan all values
-            Self::scan_value_ref_seq(slot_visitor, data_ptr, item_count, offset);
-        }
+        Self::scan_map_object(slot_visitor, object);
     }
 
     fn scan_error_object<SV: mmtk::vm::SlotVisitor<<BlinkVM as VMBinding>::VMSlot>>(
@@ -11,6 +11,16 @@ mod mmtk;
 mod blink_runtime;
 mod builtins;
 mod execution_context;
+mod execution_limits;
+mod cancellation;
+mod debugger;
+mod profiler;
+mod loader;
+mod macro_uses;
+mod prelude;
+mod reload;
+mod docs;
+mod prng;
 mod helpers;
 mod opcode;
 mod eval_result;
@@ -27,6 +37,13 @@ pub use blink_vm::*;
 pub use mmtk::*;
 pub use blink_runtime::*;
 pub use execution_context::*;
+pub use execution_limits::*;
+pub use cancellation::*;
+pub use debugger::*;
+pub use profiler::*;
+pub use loader::*;
+pub use docs::*;
+pub use prng::*;
 pub use opcode::*;
 pub use helpers::*;
 pub use eval_result::*;
@@ -1,6 +1,6 @@
 use mmtk::util::ObjectReference;
 
-use crate::value::{ParsedValue, ParsedValueWithPos, ValueRef};
+use crate::value::{ParsedValue, ParsedValueWithPos, SourceRange, ValueRef};
 
 // Opcodes - each fits in a single byte
 #[repr(u8)]
@@ -28,6 +28,7 @@ pub enum Opcode {
     SubImm8 = 0x25,         // Subtract 8-bit immediate from register
     MulImm8 = 0x26,         // Multiply 8-bit immediate by register
     DivImm8 = 0x27,         // Divide register by 8-bit immediate
+    Mod = 0x28,             // Floored modulo of two registers
     
     // Comparison operations
     Eq = 0x30,              // Equal comparison
@@ -38,6 +39,7 @@ pub enum Opcode {
     Jump = 0x40,            // Unconditional jump
     JumpIfTrue = 0x41,      // Jump if register is truthy
     JumpIfFalse = 0x42,     // Jump if register is falsy
+    JumpTable = 0x43,       // value, table constant, count (u8), count + 1 offsets (i16 each, the last is the default)
     
     // Function operations
     Call = 0x50,            // Call function
@@ -50,6 +52,7 @@ pub enum Opcode {
     SetupSelfReference = 0x57, // Setup self reference for function
     
     CreateClosure = 0x58, // Create closure with upvalues
+    MakeMacro = 0x59,     // dest, expander, variadic, param count (u8), param symbol_ids (u32 each)
     
     
     // Scope operations
@@ -80,11 +83,25 @@ pub enum Opcode {
     Resume = 0xA4,          // Resume suspended goroutine
     Spawn = 0xA5,           // Spawn new goroutine (go)
 
+    // Module operations
+    EnterModule = 0xB0,     // Make a module current, with an optional export list
+    ImportModule = 0xB1,    // Import a module into the current one
+    LoadFile = 0xB2,        // dest, path: evaluate a source file once
+    ReloadModule = 0xB3,    // dest, module: evaluate a module's file and its dependents again
+    StoreMeta = 0xB4,       // meta, symbol_id (u32), private: set a definition's metadata map
+    RemoveMacro = 0xB5,     // symbol_id (u32): remove a macro from the module
+
 
 
     
 }
 
+// Operands of EnterModule and ImportModule. Each ends with a symbol list: a u16
+// count, then that many u32 symbol ids.
+pub const NO_SYMBOL: u32 = u32::MAX; // ImportModule without an alias
+pub const NO_LIST: u16 = u16::MAX; // In place of the count when there is no list
+pub const IMPORT_REFER_ALL: u8 = 1; // ImportModule mode; 0 refers only the listed names
+
 impl Opcode {
     pub fn from_u8(byte: u8) -> Result<Self, String> {
         match byte {
@@ -106,12 +123,14 @@ impl Opcode {
             0x25 => Ok(Opcode::SubImm8),
             0x26 => Ok(Opcode::MulImm8),
             0x27 => Ok(Opcode::DivImm8),
+            0x28 => Ok(Opcode::Mod),
             0x30 => Ok(Opcode::Eq),
             0x31 => Ok(Opcode::Lt),
             0x32 => Ok(Opcode::Gt),
             0x40 => Ok(Opcode::Jump),
             0x41 => Ok(Opcode::JumpIfTrue),
             0x42 => Ok(Opcode::JumpIfFalse),
+            0x43 => Ok(Opcode::JumpTable),
             0x50 => Ok(Opcode::Call),
             0x51 => Ok(Opcode::TailCall),
             0x52 => Ok(Opcode::Return),
@@ -121,6 +140,7 @@ impl Opcode {
             0x56 => Ok(Opcode::PrepareArgs),
             0x57 => Ok(Opcode::SetupSelfReference),
             0x58 => Ok(Opcode::CreateClosure),
+            0x59 => Ok(Opcode::MakeMacro),
             0x60 => Ok(Opcode::BeginScope),
             0x61 => Ok(Opcode::EndScope),
             0x62 => Ok(Opcode::Bind),
@@ -140,6 +160,12 @@ impl Opcode {
     0xA3 => Ok(Opcode::Suspend),
     0xA4 => Ok(Opcode::Resume),
     0xA5 => Ok(Opcode::Spawn),
+            0xB0 => Ok(Opcode::EnterModule),
+            0xB1 => Ok(Opcode::ImportModule),
+            0xB2 => Ok(Opcode::LoadFile),
+            0xB3 => Ok(Opcode::ReloadModule),
+            0xB4 => Ok(Opcode::StoreMeta),
+            0xB5 => Ok(Opcode::RemoveMacro),
             _ => Err(format!("Invalid opcode: 0x{:02x}", byte)),
         }
     }
@@ -157,13 +183,53 @@ pub struct CompiledFunction {
     pub register_count: u8,
     pub module: u32,
     pub register_start: u8,
-    pub has_self_reference: bool
+    pub has_self_reference: bool,
+    pub name: Option<u32>,              // Symbol ID of the function name, if known
+    pub line_table: Vec<LineTableEntry>, // Sorted by bytecode offset
+    pub locals: Vec<LocalVarEntry>,      // Named registers, for debuggers
+}
+
+// A local binding that lives in `register` for bytecode offsets start..end
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LocalVarEntry {
+    pub symbol: u32,
+    pub register: u8,
+    pub start: u32,
+    pub end: u32,
+}
+
+// Source range of the form whose code starts at a bytecode offset
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineTableEntry {
+    pub offset: u32,
+    pub pos: SourceRange,
+}
+
+impl CompiledFunction {
+    /// Source range of the instruction at byte offset `pc`
+    pub fn position_at(&self, pc: usize) -> Option<SourceRange> {
+        let index = self
+            .line_table
+            .partition_point(|entry| entry.offset as usize <= pc);
+        if index == 0 {
+            None
+        } else {
+            Some(self.line_table[index - 1].pos)
+        }
+    }
+
+    /// Locals in scope at byte offset `pc`; inner bindings come after the ones they shadow
+    pub fn locals_at(&self, pc: usize) -> impl Iterator<Item = &LocalVarEntry> {
+        self.locals
+            .iter()
+            .filter(move |local| local.start as usize <= pc && pc < local.end as usize)
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct Macro {
     pub params: Vec<u32>,           // Parameter symbol IDs
-    pub body: Vec<ValueRef>,        // Raw AST body (not compiled)
+    pub expander: ValueRef,         // Function of (&form &env params...) returning the expansion
     pub is_variadic: bool,          // Whether last param is &rest
     pub module: u32,   
 }
@@ -0,0 +1,28 @@
+use std::sync::Arc;
+
+use crate::{parser::parse_all, runtime::{BlinkVM, ExecutionContext}};
+
+/// The macros of core that are written in Blink
+const CORE_PRELUDE: &str = include_str!("core.blink");
+
+impl BlinkVM {
+    /// Evaluate the core prelude in the core module. Runs once, after the
+    /// natives are registered, since its macros are compiled code.
+    pub fn load_core_prelude(self: &Arc<Self>) -> Result<(), String> {
+        let core_module = self.core_module.ok_or("core prelude: no core module")?;
+        let forms = {
+            let mut reader_macros = self.reader_macros.write();
+            let mut symbol_table = self.symbol_table.write();
+            parse_all(CORE_PRELUDE, &mut reader_macros, &mut symbol_table)
+                .map_err(|e| format!("core prelude: {}", e))?
+        };
+
+        let mut ctx = ExecutionContext::new(self.clone(), core_module);
+        for form in forms {
+            let expr = self.alloc_parsed_value(form);
+            ctx.compile_and_execute(expr)
+                .map_err(|e| format!("core prelude: {}", e))?;
+        }
+        Ok(())
+    }
+}
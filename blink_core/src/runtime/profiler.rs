@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::runtime::{
+    BlinkActivePlan, CallFrame, ExecutionContext, FunctionRef, ProfileData, ProfileSample, ValueId,
+};
+use crate::value::GcPtr;
+
+// Instructions between stack samples while profiling
+pub const PROFILE_SAMPLE_INTERVAL: u64 = 1000;
+
+// Top-level expressions have no function object; they share one entry
+const TOPLEVEL_PROFILE_ID: ValueId = 0;
+
+/// Switch and stack samples for the profiler.
+/// Per-function call statistics are kept in `ValueMetadataStore`.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    enabled: AtomicBool,
+    started_at: Mutex<Option<Instant>>,
+    samples: Mutex<HashMap<String, u64>>, // Collapsed stack -> sample count
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Start a fresh profile, discarding earlier samples
+    pub fn start(&self) {
+        self.samples.lock().clear();
+        *self.started_at.lock() = Some(Instant::now());
+        self.enabled.store(true, Ordering::Relaxed);
+    }
+
+    /// Stop profiling and return how long the profile ran
+    pub fn stop(&self) -> Option<Duration> {
+        self.enabled.store(false, Ordering::Relaxed);
+        self.started_at.lock().take().map(|started| started.elapsed())
+    }
+
+    /// Record one sample of a call stack, outermost frame first
+    pub fn record_sample(&self, stack: &[String]) {
+        *self.samples.lock().entry(stack.join(";")).or_insert(0) += 1;
+    }
+
+    /// Samples in the collapsed-stack format read by flamegraph tools
+    pub fn collapsed_stacks(&self) -> String {
+        let samples = self.samples.lock();
+        let mut stacks: Vec<(&String, &u64)> = samples.iter().collect();
+        stacks.sort();
+
+        let mut out = String::new();
+        for (stack, count) in stacks {
+            let _ = writeln!(out, "{} {}", stack, count);
+        }
+        out
+    }
+}
+
+/// Render per-function statistics as a table
+pub fn format_profile_report(entries: &[(String, ProfileData)]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{:<32} {:>10} {:>12} {:>12} {:>12} {:>12}",
+        "function", "calls", "self (us)", "total (us)", "max (us)", "alloc (B)"
+    );
+    for (name, data) in entries {
+        let _ = writeln!(
+            out,
+            "{:<32} {:>10} {:>12} {:>12} {:>12} {:>12}",
+            name,
+            data.calls,
+            data.self_time.as_micros(),
+            data.total_time.as_micros(),
+            data.max_time.as_micros(),
+            data.alloc_bytes
+        );
+    }
+    out
+}
+
+// A call being timed by the profiler
+#[derive(Clone, Debug)]
+pub(crate) struct ProfileFrame {
+    id: ValueId,
+    depth: usize, // Index of the frame in the call stack
+    entered: Instant,
+    child_time: Duration,
+    alloc_start: usize,
+    child_alloc: usize,
+}
+
+impl ExecutionContext<'_> {
+    pub(crate) fn profile_enter(&mut self, frame: &CallFrame) {
+        let id = self.profile_id(frame);
+        self.profile_stack.push(ProfileFrame {
+            id,
+            depth: self.call_stack.len(),
+            entered: Instant::now(),
+            child_time: Duration::ZERO,
+            alloc_start: BlinkActivePlan::allocated_bytes(),
+            child_alloc: 0,
+        });
+    }
+
+    /// Called after a frame was popped; records it if it was entered while profiling
+    pub(crate) fn profile_exit(&mut self) {
+        if !self.vm.profiler.is_enabled() {
+            // Stopped mid-call: frames still open are not reported
+            self.profile_stack.clear();
+            return;
+        }
+        if self.profile_stack.last().map(|top| top.depth) != Some(self.call_stack.len()) {
+            return;
+        }
+
+        let frame = self.profile_stack.pop().unwrap();
+        let elapsed = frame.entered.elapsed();
+        let allocated = BlinkActivePlan::allocated_bytes().wrapping_sub(frame.alloc_start);
+        if let Some(parent) = self.profile_stack.last_mut() {
+            parent.child_time += elapsed;
+            parent.child_alloc += allocated;
+        }
+
+        let sample = ProfileSample {
+            elapsed,
+            self_time: elapsed.saturating_sub(frame.child_time),
+            self_alloc: allocated.saturating_sub(frame.child_alloc),
+            recursive: self.profile_stack.iter().any(|outer| outer.id == frame.id),
+        };
+        self.vm.value_metadata.write().record_profile(frame.id, sample);
+        self.profile_updated.insert(frame.id);
+    }
+
+    /// Profile entries of the functions this context ran since the previous call,
+    /// for streaming to clients. Calls that finished before the profiler was
+    /// stopped are still reported.
+    pub fn take_profile_updates(&mut self) -> Vec<(String, ProfileData)> {
+        let updated = std::mem::take(&mut self.profile_updated);
+        let metadata = self.vm.value_metadata.read();
+        updated.into_iter().filter_map(|id| metadata.profile_entry(id)).collect()
+    }
+
+    /// Forget timings of frames at `depth` and above, which were unwound without returning
+    pub(crate) fn profile_discard_from(&mut self, depth: usize) {
+        self.profile_stack.retain(|frame| frame.depth < depth);
+    }
+
+    /// Count an instruction and sample the call stack every PROFILE_SAMPLE_INTERVAL
+    pub(crate) fn profile_tick(&mut self) {
+        self.profile_ticks += 1;
+        if self.profile_ticks % PROFILE_SAMPLE_INTERVAL != 0 {
+            return;
+        }
+
+        let stack: Vec<String> = self
+            .call_stack
+            .iter()
+            .map(|frame| {
+                let id = self.profile_id(frame);
+                self.vm
+                    .value_metadata
+                    .read()
+                    .get_debug_name(id)
+                    .unwrap_or("<anonymous>")
+                    .to_string()
+            })
+            .collect();
+        self.vm.profiler.record_sample(&stack);
+    }
+
+    /// Identity of a frame's function, naming it in the metadata store on first sight
+    fn profile_id(&self, frame: &CallFrame) -> ValueId {
+        let (id, name) = match &frame.func {
+            FunctionRef::Native(tagged_ptr) => return *tagged_ptr as ValueId,
+            FunctionRef::CompiledFunction(_, None) => {
+                (TOPLEVEL_PROFILE_ID, Some("<toplevel>".to_string()))
+            }
+            FunctionRef::CompiledFunction(function, Some(obj_ref)) => {
+                let id = GcPtr::new(*obj_ref).object_id().unwrap_or(TOPLEVEL_PROFILE_ID);
+                (id, function.name.and_then(|name| self.vm.symbol_table.read().get_symbol(name)))
+            }
+            FunctionRef::Closure(closure_obj, obj_ref) => {
+                let id = GcPtr::new(obj_ref.unwrap_or(closure_obj.template))
+                    .object_id()
+                    .unwrap_or(TOPLEVEL_PROFILE_ID);
+                if self.vm.value_metadata.read().get_debug_name(id).is_some() {
+                    return id;
+                }
+                let template = GcPtr::new(closure_obj.template).read_callable();
+                (id, template.name.and_then(|name| self.vm.symbol_table.read().get_symbol(name)))
+            }
+        };
+
+        let mut metadata = self.vm.value_metadata.write();
+        if metadata.get_debug_name(id).is_none() {
+            metadata.set_debug_name(id, name.unwrap_or_else(|| "<anonymous>".to_string()));
+        }
+        id
+    }
+}
@@ -0,0 +1,137 @@
+use std::fs;
+
+use crate::runtime::ExecutionContext;
+
+impl ExecutionContext<'_> {
+    /// Evaluate a module's file again, then the files of the modules that
+    /// depend on it. Definitions are replaced in the existing module, so code
+    /// that calls through its globals sees the new ones. Returns the reloaded
+    /// modules in the order they ran.
+    pub fn reload_module(&mut self, module_id: u32) -> Result<Vec<u32>, String> {
+        let file = {
+            let registry = self.vm.module_registry.read();
+            let name = || self.vm.symbol_table.read().get_symbol(module_id).unwrap_or_default();
+            if registry.get_module(module_id).is_none() {
+                return Err(format!("reload: no module named {}", name()));
+            }
+            registry
+                .find_module_file(module_id)
+                .ok_or_else(|| format!("reload: module {} was not loaded from a file", name()))?
+        };
+        self.reload_files(&[file])
+    }
+
+    /// Evaluate files again, followed by every module that imports what they
+    /// define, each after the modules it imports
+    pub fn reload_files(&mut self, files: &[u32]) -> Result<Vec<u32>, String> {
+        let (order, to_reload) = {
+            let registry = self.vm.module_registry.read();
+            let symbol_table = self.vm.symbol_table.read();
+            let roots: Vec<u32> = files.iter().flat_map(|file| registry.modules_in_file(*file)).collect();
+            let order = registry.dependents_in_order(&roots, |module| symbol_table.get_symbol(module).unwrap_or_default());
+
+            // Files defining no module were loaded into another; they go first
+            let mut to_reload: Vec<u32> = files
+                .iter()
+                .copied()
+                .filter(|file| registry.modules_in_file(*file).is_empty())
+                .collect();
+            for module in &order {
+                if let Some(file) = registry.find_module_file(*module) {
+                    if !to_reload.contains(&file) {
+                        to_reload.push(file);
+                    }
+                }
+            }
+            (order, to_reload)
+        };
+
+        for file_id in to_reload {
+            let Some(load) = self.vm.module_registry.read().file_load(file_id).cloned() else {
+                continue;
+            };
+            self.vm.module_registry.write().unmark_file_evaluated(file_id);
+            self.evaluate_file(load.path, file_id, None, load.module)?;
+        }
+
+        let registry = self.vm.module_registry.read();
+        Ok(order
+            .into_iter()
+            .filter(|module| registry.find_module_file(*module).is_some())
+            .collect())
+    }
+
+    /// Evaluated files that have been modified since they were loaded
+    pub fn changed_files(&self) -> Vec<u32> {
+        let registry = self.vm.module_registry.read();
+        let mut changed: Vec<u32> = registry
+            .file_loads()
+            .filter(|(_, load)| {
+                let modified = fs::metadata(&load.path).and_then(|metadata| metadata.modified()).ok();
+                modified.is_some() && modified != load.modified
+            })
+            .map(|(file, _)| file)
+            .collect();
+        changed.sort_unstable();
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+    use crate::test_support::{context, eval};
+
+    // A fresh directory under the system temp dir
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("blink-reload-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(dir: &Path, relative: &str, source: &str) {
+        let path = dir.join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, source).unwrap();
+    }
+
+    #[test]
+    fn reload_replaces_definitions_and_keeps_defonce_values() {
+        let dir = scratch("defonce");
+        write(&dir, "keep/state.blink", "(defonce started 1) (def version 1) (def report (fn [] (+ started version)))");
+        let mut ctx = context("reload-defonce");
+        ctx.vm.module_path.write().add(&dir);
+        eval(&mut ctx, "(imp keep.state :as state) (def call-report (fn [] (state/report)))");
+        assert_eq!(eval(&mut ctx, "(call-report)"), "2");
+
+        write(&dir, "keep/state.blink", "(defonce started 10) (def version 20) (def report (fn [] (* started version)))");
+        eval(&mut ctx, "(reload 'keep.state)");
+        assert_eq!(eval(&mut ctx, "state/started"), "1");
+        assert_eq!(eval(&mut ctx, "state/version"), "20");
+        // Code compiled before the reload calls the new definition
+        assert_eq!(eval(&mut ctx, "(call-report)"), "20");
+    }
+
+    #[test]
+    fn reload_runs_dependents_after_what_they_import() {
+        let dir = scratch("order");
+        // Named so that alphabetical order would be the wrong one
+        write(&dir, "order/zbase.blink", "(def version 1)");
+        write(&dir, "order/ymid.blink", "(imp order.zbase) (def version (+ order.zbase/version 10))");
+        write(&dir, "order/atop.blink", "(imp order.ymid) (imp order.zbase) (def seen (vector order.zbase/version order.ymid/version))");
+        write(&dir, "order/other.blink", "(def version 0)");
+        let mut ctx = context("reload-order");
+        ctx.vm.module_path.write().add(&dir);
+        eval(&mut ctx, "(imp order.atop) (imp order.other)");
+        assert_eq!(eval(&mut ctx, "(= order.atop/seen [1 11])"), "true");
+
+        write(&dir, "order/zbase.blink", "(def version 2)");
+        assert_eq!(eval(&mut ctx, "(= (reload 'order.zbase) '(order.zbase order.ymid order.atop))"), "true");
+        assert_eq!(eval(&mut ctx, "(= order.atop/seen [2 12])"), "true");
+        // Reloading the top module leaves the modules it imports alone
+        assert_eq!(eval(&mut ctx, "(= (reload 'order.atop) '(order.atop))"), "true");
+    }
+}
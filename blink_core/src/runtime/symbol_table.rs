@@ -77,7 +77,8 @@ impl SymbolTable {
         }
     }
     
-    fn intern_simple(&mut self, name: &str) -> u32 {
+    /// Intern a name verbatim, without splitting on '/'. Used for file paths.
+    pub fn intern_simple(&mut self, name: &str) -> u32 {
         if let Some(&id) = self.lookup.get(name) {
             id
         } else {
@@ -0,0 +1,551 @@
+use std::cmp::Ordering;
+
+use std::path::Path;
+
+use crate::runtime::{EvalResult, SeqGenerator, TransducerKind};
+use crate::value::{NativeContext, ValueRef};
+
+// Core sequence library. Every function takes any collection (list, vector,
+// set, map or string, with nil as the empty sequence) or lazy seq through the
+// seq protocol and returns a list, like `rest` does. `map`, `filter`, `take` and
+// `drop` stay lazy when given a lazy seq, so they work on infinite ones, and
+// return a transducer when called without a collection.
+
+macro_rules! try_value {
+    ($expr:expr) => {
+        match $expr {
+            Ok(value) => value,
+            Err(error) => return EvalResult::Value(error),
+        }
+    };
+}
+
+// The items are rooted: map entries and characters are allocated here, and
+// callers hold them while calling back into Blink
+fn seq_arg(ctx: &mut NativeContext, value: ValueRef, function_name: &str) -> Result<Vec<ValueRef>, ValueRef> {
+    let items = if ctx.is_lazy_seq(value) {
+        ctx.seq_to_vec(value)?
+    } else {
+        ctx.seq_items(value)
+            .ok_or_else(|| ctx.type_error("a collection", ctx.type_name(value), function_name))?
+    };
+    ctx.root(&items);
+    Ok(items)
+}
+
+fn fn_arg(ctx: &NativeContext, value: ValueRef, function_name: &str) -> Result<ValueRef, ValueRef> {
+    if ctx.is_callable(value) {
+        Ok(value)
+    } else {
+        Err(ctx.type_error("a function", ctx.type_name(value), function_name))
+    }
+}
+
+fn count_arg(ctx: &NativeContext, value: ValueRef, function_name: &str) -> Result<usize, ValueRef> {
+    match ctx.get_number(value) {
+        Some(n) if n.is_finite() && n.fract() == 0.0 => Ok(n.max(0.0) as usize),
+        _ => Err(ctx.type_error("an integer", ctx.type_name(value), function_name)),
+    }
+}
+
+/// Natural ordering used by `sort`: numbers, strings, keywords and symbols
+/// each compare among themselves. Values of different kinds are unordered.
+pub fn natural_order(ctx: &NativeContext, a: ValueRef, b: ValueRef) -> Option<Ordering> {
+    if let (Some(x), Some(y)) = (ctx.get_number(a), ctx.get_number(b)) {
+        return x.partial_cmp(&y);
+    }
+    if let (Some(x), Some(y)) = (a.get_string(), b.get_string()) {
+        return Some(x.cmp(&y));
+    }
+    if let (Some(x), Some(y)) = (a.get_keyword(), b.get_keyword()) {
+        return Some(ctx.symbol_name(x).cmp(&ctx.symbol_name(y)));
+    }
+    if let (Some(x), Some(y)) = (a.get_symbol(), b.get_symbol()) {
+        return Some(ctx.symbol_name(x).cmp(&ctx.symbol_name(y)));
+    }
+    None
+}
+
+pub fn native_map(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    try_value!(ctx.require_min_arity(&args, 1, "map"));
+    let f = try_value!(fn_arg(ctx, args[0], "map"));
+    if args.len() == 1 {
+        return EvalResult::Value(ctx.transducer(TransducerKind::Map, f));
+    }
+
+    if args[1..].iter().any(|coll| ctx.is_lazy_seq(*coll)) {
+        let seqables = ctx.vector(args[1..].to_vec());
+        let state = ctx.vector(vec![f, seqables]);
+        return EvalResult::Value(ctx.lazy_seq(SeqGenerator::Map, state));
+    }
+
+    let mut colls = Vec::with_capacity(args.len() - 1);
+    for coll in &args[1..] {
+        colls.push(try_value!(seq_arg(ctx, *coll, "map")));
+    }
+
+    // With several collections, f takes one item from each and stops at the shortest
+    let len = colls.iter().map(Vec::len).min().unwrap_or(0);
+    let mut results = Vec::with_capacity(len);
+    for i in 0..len {
+        let call_args = colls.iter().map(|coll| coll[i]).collect();
+        let result = try_value!(ctx.call(f, call_args));
+        ctx.root(&[result]);
+        results.push(result);
+    }
+    EvalResult::Value(ctx.list(results))
+}
+
+pub fn native_filter(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    if args.len() == 1 {
+        let pred = try_value!(fn_arg(ctx, args[0], "filter"));
+        return EvalResult::Value(ctx.transducer(TransducerKind::Filter, pred));
+    }
+    try_value!(ctx.require_arity(&args, 2, "filter"));
+    let pred = try_value!(fn_arg(ctx, args[0], "filter"));
+    if ctx.is_lazy_seq(args[1]) {
+        let state = ctx.vector(vec![pred, args[1]]);
+        return EvalResult::Value(ctx.lazy_seq(SeqGenerator::Filter, state));
+    }
+    let items = try_value!(seq_arg(ctx, args[1], "filter"));
+
+    let mut results = Vec::new();
+    for item in items {
+        if try_value!(ctx.call(pred, vec![item])).is_truthy() {
+            results.push(item);
+        }
+    }
+    EvalResult::Value(ctx.list(results))
+}
+
+pub fn native_reduce(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (reduce f coll) or (reduce f init coll)
+    let (f, init, coll) = match args.len() {
+        2 => (args[0], None, args[1]),
+        3 => (args[0], Some(args[1]), args[2]),
+        n => return EvalResult::Value(ctx.arity_error(2, n, "reduce (or 3)")),
+    };
+    let f = try_value!(fn_arg(ctx, f, "reduce"));
+    let mut items = try_value!(seq_arg(ctx, coll, "reduce")).into_iter();
+
+    let mut acc = match init {
+        Some(init) => init,
+        None => match items.next() {
+            Some(first) => first,
+            // Like Clojure, reducing nothing calls f with no arguments
+            None => return EvalResult::Value(try_value!(ctx.call(f, vec![]))),
+        },
+    };
+    for item in items {
+        acc = try_value!(ctx.call(f, vec![acc, item]));
+    }
+    EvalResult::Value(acc)
+}
+
+pub fn native_nth(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (nth coll index) or (nth coll index not-found)
+    if args.len() != 2 && args.len() != 3 {
+        return EvalResult::Value(ctx.arity_error(2, args.len(), "nth (or 3)"));
+    }
+    let index = try_value!(count_arg(ctx, args[1], "nth"));
+    // Only the items up to the index are realised
+    let items = if ctx.is_lazy_seq(args[0]) {
+        try_value!(ctx.seq_take(args[0], index.saturating_add(1)))
+    } else {
+        try_value!(seq_arg(ctx, args[0], "nth"))
+    };
+
+    match items.get(index) {
+        Some(item) => EvalResult::Value(*item),
+        None if args.len() == 3 => EvalResult::Value(args[2]),
+        None => EvalResult::Value(ctx.eval_error(&format!(
+            "nth: index {} out of bounds for collection of {} items",
+            index,
+            items.len()
+        ))),
+    }
+}
+
+pub fn native_last(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    try_value!(ctx.require_arity(&args, 1, "last"));
+    let items = try_value!(seq_arg(ctx, args[0], "last"));
+    EvalResult::Value(items.last().copied().unwrap_or(ctx.nil()))
+}
+
+pub fn native_reverse(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    try_value!(ctx.require_arity(&args, 1, "reverse"));
+    let mut items = try_value!(seq_arg(ctx, args[0], "reverse"));
+    items.reverse();
+    EvalResult::Value(ctx.list(items))
+}
+
+pub fn native_take(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    if args.len() == 1 {
+        let n = try_value!(count_arg(ctx, args[0], "take"));
+        return EvalResult::Value(ctx.transducer(TransducerKind::Take, ctx.number(n as f64)));
+    }
+    try_value!(ctx.require_arity(&args, 2, "take"));
+    let n = try_value!(count_arg(ctx, args[0], "take"));
+    if ctx.is_lazy_seq(args[1]) {
+        let items = try_value!(ctx.seq_take(args[1], n));
+        return EvalResult::Value(ctx.list(items));
+    }
+    let mut items = try_value!(seq_arg(ctx, args[1], "take"));
+    items.truncate(n);
+    EvalResult::Value(ctx.list(items))
+}
+
+pub fn native_drop(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    if args.len() == 1 {
+        let n = try_value!(count_arg(ctx, args[0], "drop"));
+        return EvalResult::Value(ctx.transducer(TransducerKind::Drop, ctx.number(n as f64)));
+    }
+    try_value!(ctx.require_arity(&args, 2, "drop"));
+    let n = try_value!(count_arg(ctx, args[0], "drop"));
+    if ctx.is_lazy_seq(args[1]) {
+        let rest = try_value!(ctx.seq_drop(args[1], n));
+        return EvalResult::Value(if ctx.is_nil(rest) { ctx.list(vec![]) } else { rest });
+    }
+    let items = try_value!(seq_arg(ctx, args[1], "drop"));
+    let rest = items.get(n..).unwrap_or(&[]).to_vec();
+    EvalResult::Value(ctx.list(rest))
+}
+
+pub fn native_sort(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (sort coll) or (sort comparator coll)
+    let (comparator, coll) = match args.len() {
+        1 => (None, args[0]),
+        2 => (Some(try_value!(fn_arg(ctx, args[0], "sort"))), args[1]),
+        n => return EvalResult::Value(ctx.arity_error(1, n, "sort (or 2)")),
+    };
+    let mut items = try_value!(seq_arg(ctx, coll, "sort"));
+
+    // sort_by cannot fail, so the first error is kept and the rest of the sort is moot
+    let mut failure: Option<ValueRef> = None;
+    items.sort_by(|a, b| {
+        if failure.is_some() {
+            return Ordering::Equal;
+        }
+        let ordering = match comparator {
+            None => natural_order(ctx, *a, *b).ok_or_else(|| {
+                ctx.eval_error(&format!(
+                    "sort: cannot compare {} with {}",
+                    ctx.type_name(*a),
+                    ctx.type_name(*b)
+                ))
+            }),
+            Some(comparator) => compare_with(ctx, comparator, *a, *b),
+        };
+        ordering.unwrap_or_else(|error| {
+            failure = Some(error);
+            Ordering::Equal
+        })
+    });
+
+    match failure {
+        Some(error) => EvalResult::Value(error),
+        None => EvalResult::Value(ctx.list(items)),
+    }
+}
+
+// A comparator returns a number (negative, zero, positive) or, as a
+// less-than predicate, a boolean
+pub(crate) fn compare_with(ctx: &mut NativeContext, comparator: ValueRef, a: ValueRef, b: ValueRef) -> Result<Ordering, ValueRef> {
+    let result = ctx.call(comparator, vec![a, b])?;
+    if let Some(n) = ctx.get_number(result) {
+        return Ok(n.partial_cmp(&0.0).unwrap_or(Ordering::Equal));
+    }
+    if result.is_truthy() {
+        return Ok(Ordering::Less);
+    }
+    if ctx.call(comparator, vec![b, a])?.is_truthy() {
+        Ok(Ordering::Greater)
+    } else {
+        Ok(Ordering::Equal)
+    }
+}
+
+pub fn native_partition(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    try_value!(ctx.require_arity(&args, 2, "partition"));
+    let items = try_value!(seq_arg(ctx, args[1], "partition"));
+
+    // (partition n coll) splits into lists of n, dropping an incomplete tail
+    if ctx.get_number(args[0]).is_some() {
+        let n = try_value!(count_arg(ctx, args[0], "partition"));
+        if n == 0 {
+            return EvalResult::Value(ctx.eval_error("partition: size must be positive"));
+        }
+        let chunks = items
+            .chunks_exact(n)
+            .map(|chunk| ctx.list(chunk.to_vec()))
+            .collect();
+        return EvalResult::Value(ctx.list(chunks));
+    }
+
+    // (partition pred coll) splits into [matching non-matching]
+    let pred = try_value!(fn_arg(ctx, args[0], "partition"));
+    let mut matching = Vec::new();
+    let mut rest = Vec::new();
+    for item in items {
+        if try_value!(ctx.call(pred, vec![item])).is_truthy() {
+            matching.push(item);
+        } else {
+            rest.push(item);
+        }
+    }
+    let matching = ctx.list(matching);
+    let rest = ctx.list(rest);
+    EvalResult::Value(ctx.vector(vec![matching, rest]))
+}
+
+// === Lazy sequences ===
+
+pub fn native_seq(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (seq coll) is nil for an empty collection, otherwise a sequence of its items
+    try_value!(ctx.require_arity(&args, 1, "seq"));
+    let coll = args[0];
+    if try_value!(ctx.seq_step(coll)).is_none() {
+        return EvalResult::Value(ctx.nil());
+    }
+    if ctx.is_lazy_seq(coll) || coll.get_list().is_some() {
+        return EvalResult::Value(coll);
+    }
+    let items = try_value!(seq_arg(ctx, coll, "seq"));
+    EvalResult::Value(ctx.list(items))
+}
+
+pub fn native_make_lazy_seq(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // Target of the lazy-seq special form: the thunk runs once, on first use
+    try_value!(ctx.require_arity(&args, 1, "make-lazy-seq"));
+    let thunk = try_value!(fn_arg(ctx, args[0], "make-lazy-seq"));
+    EvalResult::Value(ctx.lazy_seq(SeqGenerator::Thunk, thunk))
+}
+
+pub fn native_range(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (range), (range end), (range start end) or (range start end step)
+    if args.len() > 3 {
+        return EvalResult::Value(ctx.arity_error(3, args.len(), "range (at most)"));
+    }
+    let numbers = try_value!(ctx.extract_numbers(&args, "range"));
+    let (start, end, step) = match numbers.as_slice() {
+        [] => (0.0, None, 1.0),
+        [end] => (0.0, Some(*end), 1.0),
+        [start, end] => (*start, Some(*end), 1.0),
+        [start, end, step] => (*start, Some(*end), *step),
+        _ => unreachable!(),
+    };
+    let end = end.map_or(ctx.nil(), |end| ctx.number(end));
+    let state = ctx.vector(vec![ctx.number(start), end, ctx.number(step)]);
+    EvalResult::Value(ctx.lazy_seq(SeqGenerator::Range, state))
+}
+
+pub fn native_iterate(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (iterate f x) is x, (f x), (f (f x)), ...
+    try_value!(ctx.require_arity(&args, 2, "iterate"));
+    let f = try_value!(fn_arg(ctx, args[0], "iterate"));
+    let state = ctx.vector(vec![f, args[1]]);
+    let rest = ctx.lazy_seq(SeqGenerator::Iterate, state);
+    EvalResult::Value(ctx.seq_cell(args[1], rest))
+}
+
+pub fn native_repeat(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (repeat x) forever or (repeat n x) n times
+    let (remaining, x) = match args.len() {
+        1 => (ctx.nil(), args[0]),
+        2 => (ctx.number(try_value!(count_arg(ctx, args[0], "repeat")) as f64), args[1]),
+        n => return EvalResult::Value(ctx.arity_error(1, n, "repeat (or 2)")),
+    };
+    let state = ctx.vector(vec![x, remaining]);
+    EvalResult::Value(ctx.lazy_seq(SeqGenerator::Repeat, state))
+}
+
+pub fn native_cycle(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    try_value!(ctx.require_arity(&args, 1, "cycle"));
+    let items = try_value!(seq_arg(ctx, args[0], "cycle"));
+    let items = ctx.vector(items);
+    let state = ctx.vector(vec![items, ctx.number(0.0)]);
+    EvalResult::Value(ctx.lazy_seq(SeqGenerator::Cycle, state))
+}
+
+pub fn native_lazy_concat(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // Target of the lazy-cat special form, which wraps each argument in lazy-seq
+    let state = ctx.vector(args);
+    EvalResult::Value(ctx.lazy_seq(SeqGenerator::Concat, state))
+}
+
+pub fn native_line_seq(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (line-seq path) reads the file a chunk at a time as its lines are used
+    try_value!(ctx.require_arity(&args, 1, "line-seq"));
+    let Some(path) = ctx.get_string(args[0]) else {
+        return EvalResult::Value(ctx.type_error("a file path", ctx.type_name(args[0]), "line-seq"));
+    };
+    if !Path::new(&path).is_file() {
+        return EvalResult::Value(ctx.eval_error(&format!("line-seq: no such file: {}", path)));
+    }
+    let state = ctx.vector(vec![args[0], ctx.number(0.0)]);
+    EvalResult::Value(ctx.lazy_seq(SeqGenerator::Lines, state))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::runtime::ExecutionContext;
+    use crate::test_support::{context, eval, try_eval};
+
+    // Whether the code evaluates to a value equal to `expected`
+    fn returns(ctx: &mut ExecutionContext, code: &str, expected: &str) -> bool {
+        eval(ctx, &format!("(= {} {})", code, expected)) == "true"
+    }
+
+    #[test]
+    fn map_calls_closures_over_each_collection() {
+        let mut ctx = context("seq-map");
+        eval(&mut ctx, "(def offset 10)");
+        assert!(returns(&mut ctx, "(let [n 5] (map (fn [x] (+ x n offset)) [1 2 3]))", "'(16 17 18)"));
+        assert!(returns(&mut ctx, "(map (fn [a b] (* a b)) [1 2 3] '(4 5))", "'(4 10)"));
+        assert!(returns(&mut ctx, "(map (fn [entry] (first entry)) {:a 1})", "'(:a)"));
+        assert!(returns(&mut ctx, "(map (fn [c] (str c c)) \"ab\")", "'(\"aa\" \"bb\")"));
+        assert!(returns(&mut ctx, "(map (fn [x] x) nil)", "'()"));
+    }
+
+    #[test]
+    fn filter_and_reduce_call_closures() {
+        let mut ctx = context("seq-filter-reduce");
+        assert!(returns(&mut ctx, "(let [limit 2] (filter (fn [x] (> x limit)) [1 2 3 4]))", "'(3 4)"));
+        assert_eq!(eval(&mut ctx, "(let [k 2] (reduce (fn [acc x] (+ acc (* k x))) 0 [1 2 3]))"), "12");
+        assert_eq!(eval(&mut ctx, "(reduce (fn [acc x] (+ acc x)) [1 2 3])"), "6");
+        assert_eq!(eval(&mut ctx, "(reduce (fn [] 42) [])"), "42");
+
+        // Callbacks that themselves call natives taking callbacks
+        let nested = "(reduce (fn [acc xs] (+ acc (reduce + 0 (map (fn [x] (* x x)) xs)))) 0 [[1 2] [3]])";
+        assert_eq!(eval(&mut ctx, nested), "14");
+    }
+
+    #[test]
+    fn errors_in_callbacks_reach_the_caller() {
+        let mut ctx = context("seq-errors");
+        let result = try_eval(&mut ctx, "(map (fn [x] (err \"bad item\")) [1 2])");
+        let message = result.unwrap_or_else(|error| error);
+        assert!(message.contains("bad item"), "{}", message);
+        assert!(returns(&mut ctx, "(filter (fn [x] (= x 2)) [1 2 3])", "'(2)"));
+    }
+
+    #[test]
+    fn roots_are_released_when_the_native_returns() {
+        let mut ctx = context("seq-roots");
+        let before = ctx.register_stack.len();
+        eval(&mut ctx, "(map (fn [entry] entry) {:a 1 :b 2})");
+        eval(&mut ctx, "(transduce (map (fn [x] (+ x 1))) + 0 (range 100))");
+        assert_eq!(ctx.register_stack.len(), before);
+    }
+
+    // A file in the temp directory, removed when the test ends
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, contents: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("blink-seq-{}-{}.txt", name, std::process::id()));
+            std::fs::write(&path, contents).unwrap();
+            TempFile(path)
+        }
+
+        fn blink_path(&self) -> String {
+            format!("{:?}", self.0.to_str().unwrap())
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn lazy_seqs_realize_only_what_is_used() {
+        let mut ctx = context("seq-laziness");
+        eval(&mut ctx, "(def xs (map (fn [x] (* x 10)) (range)))");
+        assert_eq!(eval(&mut ctx, "xs"), "(...)");
+        assert_eq!(eval(&mut ctx, "(nth xs 2)"), "20");
+        assert_eq!(eval(&mut ctx, "xs"), "(0 10 20 ...)");
+
+        // Items past the ones taken are never computed
+        let guarded = "(into [] (take 2 (map (fn [x] (if (= x 2) (err \"realized too far\") x)) (range))))";
+        assert!(returns(&mut ctx, guarded, "[0 1]"));
+        eval(&mut ctx, "(def deferred (lazy-seq (err \"body ran\")))");
+        assert_eq!(eval(&mut ctx, "deferred"), "(...)");
+    }
+
+    #[test]
+    fn lazy_seqs_realize_their_items_once() {
+        let mut ctx = context("seq-realization");
+        eval(&mut ctx, "(def squares (map (fn [x] (* x x)) [1 2 3]))");
+        assert_eq!(eval(&mut ctx, "(reduce + 0 squares)"), "14");
+        assert_eq!(eval(&mut ctx, "squares"), "(1 4 9 )");
+        assert_eq!(eval(&mut ctx, "(reduce + 0 squares)"), "14");
+
+        assert!(returns(&mut ctx, "(into [] (take 4 (iterate (fn [x] (* x 2)) 1)))", "[1 2 4 8]"));
+        assert!(returns(&mut ctx, "(into [] (take 3 (repeat :a)))", "[:a :a :a]"));
+        assert!(returns(&mut ctx, "(into [] (repeat 2 :b))", "[:b :b]"));
+        assert!(returns(&mut ctx, "(into [] (take 5 (cycle [1 2])))", "[1 2 1 2 1]"));
+        assert!(returns(&mut ctx, "(into [] (range 1 10 3))", "[1 4 7]"));
+        assert!(returns(&mut ctx, "(into [] (lazy-cat [1 2] nil '(3)))", "[1 2 3]"));
+        assert!(returns(&mut ctx, "(into [] (drop 2 (range 5)))", "[2 3 4]"));
+        assert_eq!(eval(&mut ctx, "(seq [])"), "nil");
+        assert!(returns(&mut ctx, "(first (seq {:a 1}))", "[:a 1]"));
+
+        // A self-referencing lazy seq sees its own realized cells
+        eval(&mut ctx, "(defn fib-from [a b] (lazy-seq (cons a (fib-from b (+ a b)))))");
+        assert!(returns(&mut ctx, "(into [] (take 6 (fib-from 0 1)))", "[0 1 1 2 3 5]"));
+    }
+
+    #[test]
+    fn errors_while_realizing_reach_the_caller() {
+        let mut ctx = context("seq-realization-errors");
+        eval(&mut ctx, "(def failing (map (fn [x] (err \"bad item\")) (range)))");
+        let message = try_eval(&mut ctx, "(first failing)").unwrap_or_else(|error| error);
+        assert!(message.contains("bad item"), "{}", message);
+        let message = try_eval(&mut ctx, "(range 1 2 3 4)").unwrap_or_else(|error| error);
+        assert!(message.contains("range"), "{}", message);
+    }
+
+    #[test]
+    fn line_seq_reads_lines_across_chunks() {
+        let mut ctx = context("seq-line-seq");
+        let file = TempFile::new("lines", "first\r\nsecond\n\nlast");
+        let lines = format!("(into [] (line-seq {}))", file.blink_path());
+        assert!(returns(&mut ctx, &lines, "[\"first\" \"second\" \"\" \"last\"]"));
+
+        // Lines longer than a chunk, and lines that straddle chunk boundaries
+        let long_line = "x".repeat(100_000);
+        let contents: String = (0..3000).map(|n| format!("line {}\n", n)).collect();
+        let file = TempFile::new("chunks", &format!("{}\n{}", long_line, contents));
+        eval(&mut ctx, &format!("(def lines (line-seq {}))", file.blink_path()));
+        assert_eq!(eval(&mut ctx, "(count (first lines))"), "100000");
+        assert!(eval(&mut ctx, "lines").ends_with("...)"));
+        assert_eq!(eval(&mut ctx, "(count (into [] lines))"), "3001");
+        assert_eq!(eval(&mut ctx, "(nth lines 3000)"), "line 2999");
+
+        let message = try_eval(&mut ctx, "(line-seq \"/no/such/file.txt\")").unwrap_or_else(|error| error);
+        assert!(message.contains("no such file"), "{}", message);
+    }
+
+    #[test]
+    fn lazy_seqs_equal_sequences_with_the_same_items() {
+        let mut ctx = context("seq-equality");
+        assert_eq!(eval(&mut ctx, "(= (range 3) [0 1 2])"), "true");
+        assert_eq!(eval(&mut ctx, "(= '(2 3) (map inc (range 1 3)))"), "true");
+        assert_eq!(eval(&mut ctx, "(= (range 3) (map dec (range 1 4)) (vector 0 1 2))"), "true");
+        assert_eq!(eval(&mut ctx, "(= (vector (range 2) 5) (vector [0 1] 5))"), "true");
+        assert_eq!(eval(&mut ctx, "(= (range 0) [])"), "true");
+        assert_eq!(eval(&mut ctx, "(= (range 0) nil)"), "false");
+        assert_eq!(eval(&mut ctx, "(= (range 3) [0 1])"), "false");
+        // Only as much is realised as it takes to tell them apart
+        assert_eq!(eval(&mut ctx, "(= (range) [0 1 2])"), "false");
+        // Passed as a value, = compares the same way, and hash agrees with it
+        assert_eq!(eval(&mut ctx, "(let [eq =] (eq (range 3) '(0 1 2)))"), "true");
+        assert_eq!(eval(&mut ctx, "(= (hash (range 3)) (hash [0 1 2]))"), "true");
+        let message = try_eval(&mut ctx, "(= (map (fn [x] (err \"bad item\")) (range 1 2)) [1])").unwrap_or_else(|error| error);
+        assert!(message.contains("bad item"), "{}", message);
+    }
+}
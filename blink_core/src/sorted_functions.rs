@@ -0,0 +1,419 @@
+use std::cmp::Ordering;
+
+use mmtk::util::ObjectReference;
+
+use crate::runtime::{EvalResult, TypeTag};
+use crate::sequence_functions::compare_with;
+use crate::value::{GcPtr, NativeContext, ValueRef};
+
+// Sorted maps and sets. Keys are kept in comparator order in a persistent
+// balanced tree, so lookups and updates are O(log n), iteration is in order and
+// `subseq`/`rsubseq` read a key range without walking the keys before it.
+// Without a comparator keys are ordered by `compare`.
+
+macro_rules! try_value {
+    ($expr:expr) => {
+        match $expr {
+            Ok(value) => value,
+            Err(error) => return EvalResult::Value(error),
+        }
+    };
+}
+
+// Values of different kinds order by kind, in this order
+fn kind_rank(ctx: &NativeContext, value: ValueRef) -> Option<u8> {
+    if ctx.is_nil(value) {
+        return Some(0);
+    }
+    if ctx.get_boolean(value).is_some() {
+        return Some(1);
+    }
+    if ctx.get_number(value).is_some() {
+        return Some(2);
+    }
+    if value.get_keyword().is_some() {
+        return Some(4);
+    }
+    if value.get_symbol().is_some() {
+        return Some(5);
+    }
+    match value {
+        ValueRef::Heap(gc_ptr) => match gc_ptr.type_tag() {
+            TypeTag::Str => Some(3),
+            TypeTag::List | TypeTag::Vector => Some(6),
+            TypeTag::Set | TypeTag::SortedSet => Some(7),
+            TypeTag::Map | TypeTag::SortedMap => Some(8),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn compare_all(ctx: &NativeContext, a: &[ValueRef], b: &[ValueRef]) -> Result<Ordering, ValueRef> {
+    for (x, y) in a.iter().zip(b) {
+        let ordering = compare_values(ctx, *x, *y)?;
+        if ordering != Ordering::Equal {
+            return Ok(ordering);
+        }
+    }
+    Ok(a.len().cmp(&b.len()))
+}
+
+// Members of an unordered collection in `compare` order, so two collections with
+// the same members line up
+fn ordered_members(ctx: &NativeContext, coll: ValueRef) -> Result<Vec<ValueRef>, ValueRef> {
+    let mut members = ctx.seq_items(coll).unwrap_or_default();
+    let mut failure = None;
+    members.sort_by(|a, b| {
+        compare_values(ctx, *a, *b).unwrap_or_else(|error| {
+            failure.get_or_insert(error);
+            Ordering::Equal
+        })
+    });
+    match failure {
+        Some(error) => Err(error),
+        None => Ok(members),
+    }
+}
+
+/// The total order behind `compare`. nil sorts first, then booleans, numbers,
+/// strings, keywords, symbols, lists and vectors, sets and maps. Within a kind,
+/// numbers compare by value (NaN first), strings, keywords and symbols by name,
+/// lists and vectors item by item with the shorter first on a tie, and sets and
+/// maps by size and then by their sorted members. Functions and other values
+/// cannot be compared.
+pub fn compare_values(ctx: &NativeContext, a: ValueRef, b: ValueRef) -> Result<Ordering, ValueRef> {
+    let (Some(rank_a), Some(rank_b)) = (kind_rank(ctx, a), kind_rank(ctx, b)) else {
+        return Err(ctx.eval_error(&format!(
+            "compare: cannot compare {} with {}",
+            ctx.type_name(a),
+            ctx.type_name(b)
+        )));
+    };
+    if rank_a != rank_b {
+        return Ok(rank_a.cmp(&rank_b));
+    }
+    match rank_a {
+        0 => Ok(Ordering::Equal),
+        1 => Ok(ctx.get_boolean(a).cmp(&ctx.get_boolean(b))),
+        2 => {
+            let (x, y) = (ctx.get_number(a).unwrap_or(0.0), ctx.get_number(b).unwrap_or(0.0));
+            Ok(x.partial_cmp(&y).unwrap_or_else(|| y.is_nan().cmp(&x.is_nan())))
+        }
+        3 => Ok(a.get_string().cmp(&b.get_string())),
+        4 => Ok(ctx.symbol_name(a.get_keyword().unwrap_or_default()).cmp(&ctx.symbol_name(b.get_keyword().unwrap_or_default()))),
+        5 => Ok(ctx.symbol_name(a.get_symbol().unwrap_or_default()).cmp(&ctx.symbol_name(b.get_symbol().unwrap_or_default()))),
+        6 => {
+            let items_a = ctx.seq_items(a).unwrap_or_default();
+            let items_b = ctx.seq_items(b).unwrap_or_default();
+            compare_all(ctx, &items_a, &items_b)
+        }
+        _ => {
+            let (count_a, count_b) = (ctx.count(a).unwrap_or(0), ctx.count(b).unwrap_or(0));
+            if count_a != count_b {
+                return Ok(count_a.cmp(&count_b));
+            }
+            compare_all(ctx, &ordered_members(ctx, a)?, &ordered_members(ctx, b)?)
+        }
+    }
+}
+
+/// A sorted map or set
+pub fn sorted_object(value: ValueRef) -> Option<(ObjectReference, TypeTag)> {
+    match value {
+        ValueRef::Heap(gc_ptr) => match gc_ptr.type_tag() {
+            tag @ (TypeTag::SortedMap | TypeTag::SortedSet) => Some((gc_ptr.0, tag)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Order two keys of a sorted collection with its comparator, or `compare` when it has none
+fn key_order(ctx: &mut NativeContext, comparator: ValueRef, a: ValueRef, b: ValueRef) -> Result<Ordering, ValueRef> {
+    if ctx.is_nil(comparator) {
+        compare_values(ctx, a, b)
+    } else {
+        compare_with(ctx, comparator, a, b)
+    }
+}
+
+/// The stored key and value for `key`, if present
+pub fn sorted_get(ctx: &mut NativeContext, sorted: ObjectReference, key: ValueRef) -> Result<Option<(ValueRef, ValueRef)>, ValueRef> {
+    let vm = ctx.vm().clone();
+    let comparator = vm.sorted_comparator(sorted);
+    vm.sorted_find(vm.sorted_root(sorted), key, &mut |a, b| key_order(ctx, comparator, a, b))
+}
+
+/// A copy with `key` set to `value`. Sets store nil values.
+pub fn sorted_assoc(ctx: &mut NativeContext, sorted: ObjectReference, tag: TypeTag, key: ValueRef, value: ValueRef) -> Result<ObjectReference, ValueRef> {
+    let vm = ctx.vm().clone();
+    let comparator = vm.sorted_comparator(sorted);
+    let (root, added) = vm.sorted_insert(vm.sorted_root(sorted), key, value, &mut |a, b| key_order(ctx, comparator, a, b))?;
+    Ok(vm.alloc_sorted(tag, comparator, root, vm.sorted_count(sorted) + added as u32))
+}
+
+/// A copy without `key`, or the original when it is absent
+pub fn sorted_dissoc(ctx: &mut NativeContext, sorted: ObjectReference, tag: TypeTag, key: ValueRef) -> Result<ObjectReference, ValueRef> {
+    let vm = ctx.vm().clone();
+    let comparator = vm.sorted_comparator(sorted);
+    let (root, removed) = vm.sorted_remove(vm.sorted_root(sorted), key, &mut |a, b| key_order(ctx, comparator, a, b))?;
+    if !removed {
+        return Ok(sorted);
+    }
+    Ok(vm.alloc_sorted(tag, comparator, root, vm.sorted_count(sorted) - 1))
+}
+
+fn build_sorted(ctx: &mut NativeContext, tag: TypeTag, comparator: ValueRef, entries: Vec<(ValueRef, ValueRef)>) -> Result<ValueRef, ValueRef> {
+    let mut sorted = ctx.vm().alloc_sorted(tag, comparator, ValueRef::nil(), 0);
+    // Held while the comparator runs for the next entry
+    let sorted_root = ctx.root_slot(ValueRef::Heap(GcPtr::new(sorted)));
+    for (key, value) in entries {
+        sorted = sorted_assoc(ctx, sorted, tag, key, value)?;
+        ctx.set_root_slot(sorted_root, ValueRef::Heap(GcPtr::new(sorted)));
+    }
+    Ok(ValueRef::Heap(GcPtr::new(sorted)))
+}
+
+fn comparator_arg(ctx: &NativeContext, args: &[ValueRef], function_name: &str) -> Result<ValueRef, ValueRef> {
+    match args.first() {
+        Some(comparator) if ctx.is_callable(*comparator) => Ok(*comparator),
+        Some(other) => Err(ctx.type_error("a comparator function", ctx.type_name(*other), function_name)),
+        None => Err(ctx.arity_error(1, 0, function_name)),
+    }
+}
+
+fn map_pairs(ctx: &NativeContext, args: &[ValueRef], function_name: &str) -> Result<Vec<(ValueRef, ValueRef)>, ValueRef> {
+    if args.len() % 2 != 0 {
+        return Err(ctx.eval_error(&format!("{} expects keys and values in pairs", function_name)));
+    }
+    Ok(args.chunks_exact(2).map(|pair| (pair[0], pair[1])).collect())
+}
+
+fn set_members(args: &[ValueRef]) -> Vec<(ValueRef, ValueRef)> {
+    args.iter().map(|item| (*item, ValueRef::nil())).collect()
+}
+
+pub fn native_compare(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (compare a b) is -1, 0 or 1
+    try_value!(ctx.require_arity(&args, 2, "compare"));
+    let ordering = try_value!(compare_values(ctx, args[0], args[1]));
+    EvalResult::Value(ctx.number(ordering as i8 as f64))
+}
+
+pub fn native_sorted_map(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (sorted-map k1 v1 k2 v2 ...)
+    let pairs = try_value!(map_pairs(ctx, &args, "sorted-map"));
+    EvalResult::Value(try_value!(build_sorted(ctx, TypeTag::SortedMap, ValueRef::nil(), pairs)))
+}
+
+pub fn native_sorted_map_by(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (sorted-map-by comparator k1 v1 ...)
+    let comparator = try_value!(comparator_arg(ctx, &args, "sorted-map-by"));
+    let pairs = try_value!(map_pairs(ctx, &args[1..], "sorted-map-by"));
+    EvalResult::Value(try_value!(build_sorted(ctx, TypeTag::SortedMap, comparator, pairs)))
+}
+
+pub fn native_sorted_set(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (sorted-set x1 x2 ...)
+    EvalResult::Value(try_value!(build_sorted(ctx, TypeTag::SortedSet, ValueRef::nil(), set_members(&args))))
+}
+
+pub fn native_sorted_set_by(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (sorted-set-by comparator x1 x2 ...)
+    let comparator = try_value!(comparator_arg(ctx, &args, "sorted-set-by"));
+    EvalResult::Value(try_value!(build_sorted(ctx, TypeTag::SortedSet, comparator, set_members(&args[1..]))))
+}
+
+// A bound of a range query: keep keys k for which (test (compare k key) 0) holds,
+// comparing with the collection's comparator
+#[derive(Clone, Copy)]
+struct Bound {
+    test: ValueRef,
+    key: ValueRef,
+}
+
+fn bound_holds(ctx: &mut NativeContext, comparator: ValueRef, bound: Bound, key: ValueRef) -> Result<bool, ValueRef> {
+    let ordering = key_order(ctx, comparator, key, bound.key)?;
+    let ordering = ctx.number(ordering as i8 as f64);
+    let zero = ctx.number(0.0);
+    Ok(ctx.call(bound.test, vec![ordering, zero])?.is_truthy())
+}
+
+// Walk the collection from its start (ascending) or end, skip keys until `start`
+// holds, then collect keys while `end` holds. The skip is a tree descent, so
+// only the keys in range are visited one by one.
+fn range_query(ctx: &mut NativeContext, args: &[ValueRef], ascending: bool, function_name: &str) -> Result<ValueRef, ValueRef> {
+    if args.len() != 3 && args.len() != 5 {
+        return Err(ctx.arity_error(3, args.len(), &format!("{} (or 5)", function_name)));
+    }
+    let Some((sorted, tag)) = sorted_object(args[0]) else {
+        return Err(ctx.type_error("a sorted map or set", ctx.type_name(args[0]), function_name));
+    };
+    for test in args[1..].iter().step_by(2) {
+        if !ctx.is_callable(*test) {
+            return Err(ctx.type_error("a test function such as <", ctx.type_name(*test), function_name));
+        }
+    }
+    let first = Bound { test: args[1], key: args[2] };
+    // (subseq sc start-test start-key end-test end-key) and, reading from the
+    // end, (rsubseq sc start-test start-key end-test end-key)
+    let (start, end) = match args.len() {
+        3 => (first, first),
+        _ => {
+            let second = Bound { test: args[3], key: args[4] };
+            if ascending { (first, second) } else { (second, first) }
+        }
+    };
+
+    let vm = ctx.vm().clone();
+    let comparator = vm.sorted_comparator(sorted);
+    let root = vm.sorted_root(sorted);
+
+    // A single test may already hold at the first key walked, e.g. (subseq sc < 5),
+    // in which case the range runs from there rather than from where it first holds
+    let mut cursor = vm.sorted_seek(root, ascending, &mut |_| Ok(true))?;
+    let holds_at_first = match vm.sorted_cursor_next(&mut cursor.clone(), ascending) {
+        Some((key, _)) => bound_holds(ctx, comparator, start, key)?,
+        None => true,
+    };
+    if !holds_at_first {
+        cursor = vm.sorted_seek(root, ascending, &mut |key| bound_holds(ctx, comparator, start, key))?;
+    }
+
+    let mut items = Vec::new();
+    while let Some((key, value)) = vm.sorted_cursor_next(&mut cursor, ascending) {
+        if !bound_holds(ctx, comparator, end, key)? {
+            break;
+        }
+        let item = match tag {
+            TypeTag::SortedMap => ctx.vector(vec![key, value]),
+            _ => key,
+        };
+        // Held while the end test runs for the next key
+        ctx.root(&[item]);
+        items.push(item);
+    }
+    Ok(ctx.list(items))
+}
+
+pub fn native_subseq(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (subseq sc test key) or (subseq sc start-test start-key end-test end-key), ascending
+    EvalResult::Value(try_value!(range_query(ctx, &args, true, "subseq")))
+}
+
+pub fn native_rsubseq(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // Like subseq but descending
+    EvalResult::Value(try_value!(range_query(ctx, &args, false, "rsubseq")))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::ExecutionContext;
+    use crate::test_support::{context, eval, try_eval};
+
+    // Whether the code evaluates to a value equal to `expected`
+    fn returns(ctx: &mut ExecutionContext, code: &str, expected: &str) -> bool {
+        eval(ctx, &format!("(= {} {})", code, expected)) == "true"
+    }
+
+    // The message of an error, whether it was raised or returned as a value
+    fn error_message(ctx: &mut ExecutionContext, code: &str) -> String {
+        try_eval(ctx, code).unwrap_or_else(|error| error)
+    }
+
+    #[test]
+    fn sorted_collections_keep_their_keys_in_order() {
+        let mut ctx = context("sorted-order");
+        // 0..100 in a scrambled order
+        eval(&mut ctx, "(def scrambled (map (fn [x] (mod (* x 37) 101)) (range 101)))");
+        assert!(returns(&mut ctx, "(into [] (into (sorted-set) scrambled))", "(into [] (range 101))"));
+        assert!(returns(&mut ctx, "(into [] (keys (into (sorted-map) (map (fn [x] (vector x (* x x)))) scrambled)))", "(into [] (range 101))"));
+        assert!(returns(&mut ctx, "(into [] (sorted-set :b :c :a))", "[:a :b :c]"));
+        assert!(returns(&mut ctx, "(into [] (sorted-set \"b\" \"a\" \"c\" \"a\"))", "[\"a\" \"b\" \"c\"]"));
+        assert!(returns(&mut ctx, "(seq (sorted-map 2 :two 1 :one))", "'([1 :one] [2 :two])"));
+        assert_eq!(eval(&mut ctx, "(count (sorted-set 3 1 3 2 1))"), "3");
+    }
+
+    #[test]
+    fn sorted_collections_update_persistently() {
+        let mut ctx = context("sorted-update");
+        eval(&mut ctx, "(def m (sorted-map :b 2 :a 1))");
+        assert_eq!(eval(&mut ctx, "(get m :a)"), "1");
+        assert_eq!(eval(&mut ctx, "(get m :z)"), "nil");
+        assert_eq!(eval(&mut ctx, "(m :b)"), "2");
+        assert!(returns(&mut ctx, "(seq (assoc m :c 3 :a 0))", "'([:a 0] [:b 2] [:c 3])"));
+        assert!(returns(&mut ctx, "(seq (dissoc m :a))", "'([:b 2])"));
+        assert!(returns(&mut ctx, "(seq m)", "'([:a 1] [:b 2])"));
+        assert_eq!(eval(&mut ctx, "(contains? m :b)"), "true");
+
+        eval(&mut ctx, "(def s (sorted-set 1 2 3))");
+        assert!(returns(&mut ctx, "(into [] (conj s 0 5))", "[0 1 2 3 5]"));
+        assert!(returns(&mut ctx, "(into [] (disj s 2 7))", "[1 3]"));
+        assert_eq!(eval(&mut ctx, "(contains? s 4)"), "false");
+        assert_eq!(eval(&mut ctx, "(count (reduce (fn [acc x] (disj acc x)) (into (sorted-set) (range 50)) (range 0 50 2)))"), "25");
+    }
+
+    #[test]
+    fn comparators_decide_the_order() {
+        let mut ctx = context("sorted-comparators");
+        assert!(returns(&mut ctx, "(into [] (sorted-set-by > 1 3 2))", "[3 2 1]"));
+        // A comparator may also return a number like compare does
+        eval(&mut ctx, "(def by-length (fn [a b] (compare (count a) (count b))))");
+        assert!(returns(&mut ctx, "(into [] (sorted-set-by by-length \"ccc\" \"a\" \"bb\"))", "[\"a\" \"bb\" \"ccc\"]"));
+        // Keys the comparator calls equal are the same key
+        assert_eq!(eval(&mut ctx, "(count (sorted-set-by by-length \"ab\" \"cd\"))"), "1");
+        assert!(returns(&mut ctx, "(keys (sorted-map-by > 1 :a 2 :b))", "'(2 1)"));
+        assert!(error_message(&mut ctx, "(sorted-set-by 5 1 2)").contains("comparator"));
+        assert!(error_message(&mut ctx, "(sorted-map :a)").contains("pairs"));
+    }
+
+    #[test]
+    fn subseq_and_rsubseq_read_key_ranges() {
+        let mut ctx = context("sorted-subseq");
+        eval(&mut ctx, "(def s (into (sorted-set) (range 10)))");
+        assert!(returns(&mut ctx, "(subseq s > 6)", "'(7 8 9)"));
+        assert!(returns(&mut ctx, "(subseq s >= 6)", "'(6 7 8 9)"));
+        assert!(returns(&mut ctx, "(subseq s < 3)", "'(0 1 2)"));
+        assert!(returns(&mut ctx, "(subseq s >= 2 < 5)", "'(2 3 4)"));
+        assert!(returns(&mut ctx, "(subseq s > 20)", "'()"));
+        assert!(returns(&mut ctx, "(rsubseq s < 3)", "'(2 1 0)"));
+        assert!(returns(&mut ctx, "(rsubseq s > 6)", "'(9 8 7)"));
+        assert!(returns(&mut ctx, "(rsubseq s > 2 <= 5)", "'(5 4 3)"));
+        // Bounds between keys
+        assert!(returns(&mut ctx, "(subseq (sorted-set 1 5 9) >= 4 <= 9.5)", "'(5 9)"));
+
+        eval(&mut ctx, "(def m (sorted-map :a 1 :b 2 :c 3 :d 4))");
+        assert!(returns(&mut ctx, "(subseq m > :a <= :c)", "'([:b 2] [:c 3])"));
+        assert!(returns(&mut ctx, "(rsubseq m >= :c)", "'([:d 4] [:c 3])"));
+        // With a comparator, ranges follow its order
+        assert!(returns(&mut ctx, "(subseq (sorted-set-by > 1 2 3 4) > 2)", "'(1)"));
+        assert!(error_message(&mut ctx, "(subseq [1 2] > 1)").contains("sorted"));
+        assert!(error_message(&mut ctx, "(subseq s > 1 <)").contains("subseq"));
+    }
+
+    #[test]
+    fn compare_orders_values_of_any_kind() {
+        let mut ctx = context("sorted-compare");
+        assert_eq!(eval(&mut ctx, "(compare 1 2)"), "-1");
+        assert_eq!(eval(&mut ctx, "(compare \"b\" \"a\")"), "1");
+        assert_eq!(eval(&mut ctx, "(compare :a :a)"), "0");
+        assert_eq!(eval(&mut ctx, "(compare [1 2] [1 3])"), "-1");
+        assert_eq!(eval(&mut ctx, "(compare [1 2] [1 2 0])"), "-1");
+        // Different kinds order by kind
+        assert_eq!(eval(&mut ctx, "(compare nil false)"), "-1");
+        assert_eq!(eval(&mut ctx, "(compare 100 \"a\")"), "-1");
+        assert!(returns(&mut ctx, "(into [] (sorted-set :k 1 \"s\" nil))", "[nil 1 \"s\" :k]"));
+        assert!(error_message(&mut ctx, "(compare (fn [] 1) 1)").contains("cannot compare"));
+    }
+
+    #[test]
+    fn sorted_collections_equal_by_entries_whatever_the_order() {
+        let mut ctx = context("sorted-equality");
+        assert_eq!(eval(&mut ctx, "(= (sorted-set-by > 1 2 3) (sorted-set 3 1 2))"), "true");
+        assert_eq!(eval(&mut ctx, "(= (sorted-set-by > 1 2 3) (set [2 3 1]))"), "true");
+        assert_eq!(eval(&mut ctx, "(= (sorted-map-by > 1 :a 2 :b) (sorted-map 2 :b 1 :a))"), "true");
+        assert_eq!(eval(&mut ctx, "(= (sorted-map-by > 1 :a 2 :b) (sorted-map 1 :a 2 :c))"), "false");
+        assert_eq!(eval(&mut ctx, "(= (hash (sorted-set-by > 1 2)) (hash (sorted-set 1 2)))"), "true");
+    }
+}
@@ -0,0 +1,429 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::runtime::EvalResult;
+use crate::value::{NativeContext, ValueRef};
+
+// The string library. `str`, `subs` and `format` are in core; the rest live in
+// the `string` module and are called qualified, e.g. (string/split s ","). Every
+// index counts characters (Unicode scalar values), never bytes, and the
+// grapheme functions count user-perceived characters, so "é" written as e plus
+// a combining accent is one grapheme.
+
+macro_rules! try_value {
+    ($expr:expr) => {
+        match $expr {
+            Ok(value) => value,
+            Err(error) => return EvalResult::Value(error),
+        }
+    };
+}
+
+/// How `str` and `format`'s %s show a value: strings without quotes, keywords
+/// and symbols by name, anything else as the printer shows it
+pub fn display_string(ctx: &NativeContext, value: ValueRef) -> String {
+    if let Some(s) = value.get_string() {
+        return s;
+    }
+    // Keywords are interned with their leading colon
+    if let Some(id) = value.get_keyword().or_else(|| value.get_symbol()) {
+        return ctx.symbol_name(id).unwrap_or_default();
+    }
+    value.to_string()
+}
+
+fn string_arg(ctx: &NativeContext, value: ValueRef, function_name: &str) -> Result<String, ValueRef> {
+    value
+        .get_string()
+        .ok_or_else(|| ctx.type_error("a string", ctx.type_name(value), function_name))
+}
+
+fn index_arg(ctx: &NativeContext, value: ValueRef, function_name: &str) -> Result<usize, ValueRef> {
+    match ctx.get_number(value) {
+        Some(n) if n >= 0.0 && n.fract() == 0.0 => Ok(n as usize),
+        _ => Err(ctx.type_error("a non-negative integer index", ctx.type_name(value), function_name)),
+    }
+}
+
+// Character index of a byte offset in s
+fn char_index(s: &str, byte_offset: usize) -> usize {
+    s[..byte_offset].chars().count()
+}
+
+// Byte offset of a character index in s, or None past the end
+fn byte_offset(s: &str, char_index: usize) -> Option<usize> {
+    s.char_indices()
+        .map(|(offset, _)| offset)
+        .chain(std::iter::once(s.len()))
+        .nth(char_index)
+}
+
+// The slice between two unit indices, where the units are the pieces of s in order
+fn slice_units(ctx: &NativeContext, units: &[&str], args: &[ValueRef], function_name: &str) -> Result<String, ValueRef> {
+    let start = index_arg(ctx, args[1], function_name)?;
+    let end = match args.get(2) {
+        Some(end) => index_arg(ctx, *end, function_name)?,
+        None => units.len(),
+    };
+    if start > end || end > units.len() {
+        return Err(ctx.eval_error(&format!(
+            "{}: range {}..{} is out of bounds for length {}",
+            function_name,
+            start,
+            end,
+            units.len()
+        )));
+    }
+    Ok(units[start..end].concat())
+}
+
+pub fn native_str(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (str x ...) concatenates the display form of each value; nil adds nothing
+    let mut result = String::new();
+    for arg in args {
+        if !ctx.is_nil(arg) {
+            result.push_str(&display_string(ctx, arg));
+        }
+    }
+    EvalResult::Value(ctx.string(&result))
+}
+
+pub fn native_subs(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (subs s start) or (subs s start end), by character index
+    if args.len() != 2 && args.len() != 3 {
+        return EvalResult::Value(ctx.arity_error(2, args.len(), "subs (or 3)"));
+    }
+    let s = try_value!(string_arg(ctx, args[0], "subs"));
+    let chars: Vec<&str> = s.char_indices().map(|(offset, c)| &s[offset..offset + c.len_utf8()]).collect();
+    let sub = try_value!(slice_units(ctx, &chars, &args, "subs"));
+    EvalResult::Value(ctx.string(&sub))
+}
+
+pub fn native_grapheme_subs(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (string/grapheme-subs s start end?), by grapheme index
+    if args.len() != 2 && args.len() != 3 {
+        return EvalResult::Value(ctx.arity_error(2, args.len(), "grapheme-subs (or 3)"));
+    }
+    let s = try_value!(string_arg(ctx, args[0], "grapheme-subs"));
+    let graphemes: Vec<&str> = s.graphemes(true).collect();
+    let sub = try_value!(slice_units(ctx, &graphemes, &args, "grapheme-subs"));
+    EvalResult::Value(ctx.string(&sub))
+}
+
+pub fn native_graphemes(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (string/graphemes s) is a list of the grapheme clusters of s
+    try_value!(ctx.require_arity(&args, 1, "graphemes"));
+    let s = try_value!(string_arg(ctx, args[0], "graphemes"));
+    let graphemes = s.graphemes(true).map(|grapheme| ctx.string(grapheme)).collect();
+    EvalResult::Value(ctx.list(graphemes))
+}
+
+// A parsed %[-][width][.precision]conversion directive
+struct Directive {
+    left_align: bool,
+    width: Option<usize>,
+    precision: Option<usize>,
+    conversion: char,
+}
+
+fn parse_directive(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<Directive> {
+    let left_align = chars.next_if_eq(&'-').is_some();
+    let digits = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+        let mut number = String::new();
+        while let Some(digit) = chars.next_if(|c| c.is_ascii_digit()) {
+            number.push(digit);
+        }
+        number.parse::<usize>().ok()
+    };
+    let width = digits(chars);
+    let precision = match chars.next_if_eq(&'.') {
+        Some(_) => Some(digits(chars).unwrap_or(0)),
+        None => None,
+    };
+    let conversion = chars.next()?;
+    Some(Directive { left_align, width, precision, conversion })
+}
+
+fn format_directive(ctx: &NativeContext, directive: &Directive, value: ValueRef) -> Result<String, ValueRef> {
+    let number = || {
+        ctx.get_number(value).ok_or_else(|| {
+            ctx.eval_error(&format!(
+                "format: %{} expects a number, got {}",
+                directive.conversion,
+                ctx.type_name(value)
+            ))
+        })
+    };
+    let text = match directive.conversion {
+        's' if ctx.is_nil(value) => "nil".to_string(),
+        's' => display_string(ctx, value),
+        'd' => format!("{}", number()?.trunc() as i64),
+        'f' => format!("{:.*}", directive.precision.unwrap_or(6), number()?),
+        other => return Err(ctx.eval_error(&format!("format: unknown directive %{}", other))),
+    };
+    let text = match (directive.conversion, directive.precision) {
+        // A precision on %s truncates it
+        ('s', Some(precision)) => text.chars().take(precision).collect(),
+        _ => text,
+    };
+    let padding = directive.width.unwrap_or(0).saturating_sub(text.chars().count());
+    Ok(if directive.left_align {
+        format!("{}{}", text, " ".repeat(padding))
+    } else {
+        format!("{}{}", " ".repeat(padding), text)
+    })
+}
+
+pub fn native_format(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (format "%s is %d, %.2f" a b c). Directives are %s, %d and %f with an
+    // optional - (left align), width and .precision, and %% for a literal %.
+    try_value!(ctx.require_min_arity(&args, 1, "format"));
+    let template = try_value!(string_arg(ctx, args[0], "format"));
+    let mut values = args[1..].iter();
+    let mut result = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+        if chars.next_if_eq(&'%').is_some() {
+            result.push('%');
+            continue;
+        }
+        let Some(directive) = parse_directive(&mut chars) else {
+            return EvalResult::Value(ctx.eval_error("format: the template ends in the middle of a directive"));
+        };
+        let Some(value) = values.next() else {
+            return EvalResult::Value(ctx.eval_error("format: more directives than arguments"));
+        };
+        result.push_str(&try_value!(format_directive(ctx, &directive, *value)));
+    }
+    if values.next().is_some() {
+        return EvalResult::Value(ctx.eval_error("format: more arguments than directives"));
+    }
+    EvalResult::Value(ctx.string(&result))
+}
+
+pub fn native_split(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (string/split s separator) or (string/split s separator limit). An empty
+    // separator splits into characters; a limit caps the number of parts.
+    if args.len() != 2 && args.len() != 3 {
+        return EvalResult::Value(ctx.arity_error(2, args.len(), "split (or 3)"));
+    }
+    let s = try_value!(string_arg(ctx, args[0], "split"));
+    let separator = try_value!(string_arg(ctx, args[1], "split"));
+    let limit = match args.get(2) {
+        Some(limit) => match try_value!(index_arg(ctx, *limit, "split")) {
+            0 => return EvalResult::Value(ctx.eval_error("split: limit must be positive")),
+            limit => limit,
+        },
+        None => usize::MAX,
+    };
+    let parts: Vec<String> = if separator.is_empty() {
+        let chars: Vec<String> = s.chars().map(String::from).collect();
+        match chars.len() > limit {
+            true => {
+                let mut parts = chars[..limit - 1].to_vec();
+                parts.push(chars[limit - 1..].concat());
+                parts
+            }
+            false => chars,
+        }
+    } else {
+        s.splitn(limit, separator.as_str()).map(String::from).collect()
+    };
+    let parts = parts.iter().map(|part| ctx.string(part)).collect();
+    EvalResult::Value(ctx.vector(parts))
+}
+
+pub fn native_join(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (string/join coll) or (string/join separator coll)
+    let (separator, coll) = match args.len() {
+        1 => (String::new(), args[0]),
+        2 => (try_value!(string_arg(ctx, args[0], "join")), args[1]),
+        n => return EvalResult::Value(ctx.arity_error(1, n, "join (or 2)")),
+    };
+    let items = match ctx.seq_to_vec(coll) {
+        Ok(items) => items,
+        Err(_) => return EvalResult::Value(ctx.type_error("a collection", ctx.type_name(coll), "join")),
+    };
+    let parts: Vec<String> = items
+        .into_iter()
+        .filter(|item| !ctx.is_nil(*item))
+        .map(|item| display_string(ctx, item))
+        .collect();
+    EvalResult::Value(ctx.string(&parts.join(&separator)))
+}
+
+fn map_string(args: Vec<ValueRef>, ctx: &mut NativeContext, function_name: &str, f: fn(&str) -> String) -> EvalResult {
+    try_value!(ctx.require_arity(&args, 1, function_name));
+    let s = try_value!(string_arg(ctx, args[0], function_name));
+    EvalResult::Value(ctx.string(&f(&s)))
+}
+
+pub fn native_trim(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    map_string(args, ctx, "trim", |s| s.trim().to_string())
+}
+
+pub fn native_triml(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    map_string(args, ctx, "triml", |s| s.trim_start().to_string())
+}
+
+pub fn native_trimr(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    map_string(args, ctx, "trimr", |s| s.trim_end().to_string())
+}
+
+pub fn native_upper_case(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    map_string(args, ctx, "upper-case", str::to_uppercase)
+}
+
+pub fn native_lower_case(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    map_string(args, ctx, "lower-case", str::to_lowercase)
+}
+
+pub fn native_replace(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (string/replace s match replacement) replaces every occurrence
+    try_value!(ctx.require_arity(&args, 3, "replace"));
+    let s = try_value!(string_arg(ctx, args[0], "replace"));
+    let pattern = try_value!(string_arg(ctx, args[1], "replace"));
+    let replacement = try_value!(string_arg(ctx, args[2], "replace"));
+    if pattern.is_empty() {
+        return EvalResult::Value(ctx.eval_error("replace: the string to replace is empty"));
+    }
+    EvalResult::Value(ctx.string(&s.replace(&pattern, &replacement)))
+}
+
+fn test_strings(args: Vec<ValueRef>, ctx: &mut NativeContext, function_name: &str, test: fn(&str, &str) -> bool) -> EvalResult {
+    try_value!(ctx.require_arity(&args, 2, function_name));
+    let s = try_value!(string_arg(ctx, args[0], function_name));
+    let other = try_value!(string_arg(ctx, args[1], function_name));
+    EvalResult::Value(ctx.bool(test(&s, &other)))
+}
+
+pub fn native_starts_with(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    test_strings(args, ctx, "starts-with?", |s, prefix| s.starts_with(prefix))
+}
+
+pub fn native_ends_with(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    test_strings(args, ctx, "ends-with?", |s, suffix| s.ends_with(suffix))
+}
+
+pub fn native_includes(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    test_strings(args, ctx, "includes?", |s, sub| s.contains(sub))
+}
+
+pub fn native_index_of(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (string/index-of s sub) or (string/index-of s sub from): the character
+    // index of the first occurrence at or after from, or nil
+    if args.len() != 2 && args.len() != 3 {
+        return EvalResult::Value(ctx.arity_error(2, args.len(), "index-of (or 3)"));
+    }
+    let s = try_value!(string_arg(ctx, args[0], "index-of"));
+    let sub = try_value!(string_arg(ctx, args[1], "index-of"));
+    let from = match args.get(2) {
+        Some(from) => try_value!(index_arg(ctx, *from, "index-of")),
+        None => 0,
+    };
+    let Some(start) = byte_offset(&s, from) else {
+        return EvalResult::Value(ctx.nil());
+    };
+    match s[start..].find(&sub) {
+        Some(found) => EvalResult::Value(ctx.number(char_index(&s, start + found) as f64)),
+        None => EvalResult::Value(ctx.nil()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::ExecutionContext;
+    use crate::test_support::{context, eval, try_eval};
+
+    // Whether the code evaluates to a value equal to `expected`
+    fn returns(ctx: &mut ExecutionContext, code: &str, expected: &str) -> bool {
+        eval(ctx, &format!("(= {} {})", code, expected)) == "true"
+    }
+
+    // The message of an error, whether it was raised or returned as a value
+    fn error_message(ctx: &mut ExecutionContext, code: &str) -> String {
+        try_eval(ctx, code).unwrap_or_else(|error| error)
+    }
+
+    #[test]
+    fn str_joins_display_forms() {
+        let mut ctx = context("string-str");
+        assert!(returns(&mut ctx, "(str \"a\" 1 :k 'sym nil true)", "\"a1:ksymtrue\""));
+        assert!(returns(&mut ctx, "(str)", "\"\""));
+        assert!(returns(&mut ctx, "(str 1.5 \"\" -2)", "\"1.5-2\""));
+    }
+
+    #[test]
+    fn subs_counts_characters_and_graphemes() {
+        let mut ctx = context("string-subs");
+        assert!(returns(&mut ctx, "(subs \"hello\" 1 3)", "\"el\""));
+        assert!(returns(&mut ctx, "(subs \"hello\" 2)", "\"llo\""));
+        assert!(returns(&mut ctx, "(subs \"hello\" 5)", "\"\""));
+        // Indices are characters, not bytes
+        assert!(returns(&mut ctx, "(subs \"äöü\" 1 2)", "\"ö\""));
+        assert!(error_message(&mut ctx, "(subs \"abc\" 2 5)").contains("out of bounds"));
+        assert!(error_message(&mut ctx, "(subs \"abc\" 2 1)").contains("out of bounds"));
+        assert!(error_message(&mut ctx, "(subs \"abc\" -1)").contains("index"));
+
+        // e followed by a combining accent is two characters but one grapheme
+        eval(&mut ctx, "(def word \"cafe\u{301}!\")");
+        assert_eq!(eval(&mut ctx, "(count (string/graphemes word))"), "5");
+        assert!(returns(&mut ctx, "(string/grapheme-subs word 3 4)", "\"e\u{301}\""));
+        assert!(returns(&mut ctx, "(subs word 3 4)", "\"e\""));
+    }
+
+    #[test]
+    fn format_fills_directives() {
+        let mut ctx = context("string-format");
+        assert!(returns(&mut ctx, "(format \"%s is %d\" :x 42.9)", "\":x is 42\""));
+        assert!(returns(&mut ctx, "(format \"%.2f%%\" 12.345)", "\"12.35%\""));
+        assert!(returns(&mut ctx, "(format \"[%5s|%-5s]\" \"ab\" \"cd\")", "\"[   ab|cd   ]\""));
+        assert!(returns(&mut ctx, "(format \"%.3s\" \"truncated\")", "\"tru\""));
+        assert!(returns(&mut ctx, "(format \"%s\" nil)", "\"nil\""));
+        assert!(error_message(&mut ctx, "(format \"%d\" \"x\")").contains("expects a number"));
+        assert!(error_message(&mut ctx, "(format \"%s %s\" 1)").contains("more directives"));
+        assert!(error_message(&mut ctx, "(format \"%s\" 1 2)").contains("more arguments"));
+        assert!(error_message(&mut ctx, "(format \"%q\" 1)").contains("unknown directive"));
+    }
+
+    #[test]
+    fn split_and_join_are_inverses() {
+        let mut ctx = context("string-split");
+        assert!(returns(&mut ctx, "(string/split \"a,b,,c\" \",\")", "[\"a\" \"b\" \"\" \"c\"]"));
+        assert!(returns(&mut ctx, "(string/split \"a,b,c\" \",\" 2)", "[\"a\" \"b,c\"]"));
+        assert!(returns(&mut ctx, "(string/split \"abc\" \"\")", "[\"a\" \"b\" \"c\"]"));
+        assert!(returns(&mut ctx, "(string/split \"abc\" \"\" 2)", "[\"a\" \"bc\"]"));
+        assert!(error_message(&mut ctx, "(string/split \"abc\" \",\" 0)").contains("positive"));
+
+        assert!(returns(&mut ctx, "(string/join \", \" [1 :b \"c\" nil])", "\"1, :b, c\""));
+        assert!(returns(&mut ctx, "(string/join [\"a\" \"b\"])", "\"ab\""));
+        assert!(returns(&mut ctx, "(string/join \"-\" (map (fn [x] (* x 2)) (range 3)))", "\"0-2-4\""));
+        assert!(returns(&mut ctx, "(string/join \",\" (string/split \"x,y,z\" \",\"))", "\"x,y,z\""));
+    }
+
+    #[test]
+    fn strings_are_trimmed_cased_and_searched() {
+        let mut ctx = context("string-search");
+        assert!(returns(&mut ctx, "(string/trim \"  pad \n\t\")", "\"pad\""));
+        assert!(returns(&mut ctx, "(string/triml \"  pad \")", "\"pad \""));
+        assert!(returns(&mut ctx, "(string/trimr \"  pad \")", "\"  pad\""));
+        assert!(returns(&mut ctx, "(string/upper-case \"straße\")", "\"STRASSE\""));
+        assert!(returns(&mut ctx, "(string/lower-case \"ÄB\")", "\"äb\""));
+        assert!(returns(&mut ctx, "(string/replace \"a-b-c\" \"-\" \"+\")", "\"a+b+c\""));
+        assert!(error_message(&mut ctx, "(string/replace \"abc\" \"\" \"x\")").contains("empty"));
+
+        assert_eq!(eval(&mut ctx, "(string/starts-with? \"prefix\" \"pre\")"), "true");
+        assert_eq!(eval(&mut ctx, "(string/ends-with? \"suffix\" \"fix\")"), "true");
+        assert_eq!(eval(&mut ctx, "(string/includes? \"haystack\" \"needle\")"), "false");
+        assert_eq!(eval(&mut ctx, "(string/index-of \"ab-ab\" \"ab\")"), "0");
+        assert_eq!(eval(&mut ctx, "(string/index-of \"ab-ab\" \"ab\" 1)"), "3");
+        // Character indices also after multi-byte characters
+        assert_eq!(eval(&mut ctx, "(string/index-of \"ääx\" \"x\")"), "2");
+        assert_eq!(eval(&mut ctx, "(string/index-of \"abc\" \"z\")"), "nil");
+        assert_eq!(eval(&mut ctx, "(string/index-of \"abc\" \"a\" 10)"), "nil");
+        assert!(error_message(&mut ctx, "(string/trim 5)").contains("string"));
+    }
+}
@@ -0,0 +1,54 @@
+use std::sync::{Arc, Once};
+
+use crate::{
+    error::BlinkError,
+    module::{Module, SerializedModuleSource},
+    parser::parse_all,
+    runtime::{BlinkVM, ExecutionContext, GLOBAL_VM},
+    value::ValueRef,
+};
+
+static PRELUDE: Once = Once::new();
+
+// One VM per test process, with the core prelude loaded
+pub(crate) fn vm() -> Arc<BlinkVM> {
+    let vm = GLOBAL_VM.get_or_init(|| Arc::new(BlinkVM::new())).clone();
+    PRELUDE.call_once(|| vm.load_core_prelude().unwrap());
+    vm
+}
+
+// Each test evaluates in a module of its own
+pub(crate) fn context(module: &str) -> ExecutionContext<'static> {
+    let vm = vm();
+    let module_id = vm.symbol_table.write().intern(module);
+    vm.module_registry
+        .write()
+        .register_module(Module::new(module_id, SerializedModuleSource::Repl));
+    ExecutionContext::new(vm, module_id)
+}
+
+// The value of the last form, or the first error
+pub(crate) fn run(ctx: &mut ExecutionContext, code: &str) -> Result<ValueRef, BlinkError> {
+    let vm = ctx.vm.clone();
+    let forms = {
+        let mut symbol_table = vm.symbol_table.write();
+        let mut reader_macros = vm.reader_macros.write();
+        parse_all(code, &mut reader_macros, &mut symbol_table)?
+    };
+    let mut result = ValueRef::nil();
+    for form in forms {
+        let ast = vm.alloc_parsed_value(form);
+        result = ctx.compile_and_execute(ast)?;
+    }
+    Ok(result)
+}
+
+// The printed value of the last form
+pub(crate) fn eval(ctx: &mut ExecutionContext, code: &str) -> String {
+    try_eval(ctx, code).unwrap()
+}
+
+// The printed value of the last form, or the first error
+pub(crate) fn try_eval(ctx: &mut ExecutionContext, code: &str) -> Result<String, String> {
+    run(ctx, code).map(|value| value.to_string()).map_err(|e| e.to_string())
+}
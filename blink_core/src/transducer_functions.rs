@@ -0,0 +1,281 @@
+use crate::runtime::{EvalResult, SeqGenerator, TransducerKind, TypeTag};
+use crate::sorted_functions::{sorted_assoc, sorted_object};
+use crate::value::{GcPtr, NativeContext, ValueRef};
+
+// Transducers. `(map f)`, `(filter pred)`, `(take n)` and `(drop n)` called
+// without a collection return a pipeline stage, `comp` chains stages, and
+// `transduce`, `into` and `sequence` run a pipeline over a collection in one
+// pass. Items flow straight from the input to the result, so no intermediate
+// collection is allocated per stage.
+
+macro_rules! try_value {
+    ($expr:expr) => {
+        match $expr {
+            Ok(value) => value,
+            Err(error) => return EvalResult::Value(error),
+        }
+    };
+}
+
+pub fn native_comp(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (comp xf1 xf2 ...) runs xf1 first, then xf2, ...; (comp) passes items through
+    let mut stages = Vec::with_capacity(args.len());
+    for xform in &args {
+        let Some(transducer) = xform.heap_object(TypeTag::Transducer) else {
+            return EvalResult::Value(ctx.type_error("transducers", ctx.type_name(*xform), "comp"));
+        };
+        // Nested compositions are flattened so running a pipeline never recurses
+        if ctx.vm().transducer_kind(transducer) == TransducerKind::Comp {
+            stages.extend(ctx.vm().transducer_arg(transducer).get_vec().unwrap_or_default());
+        } else {
+            stages.push(*xform);
+        }
+    }
+    let stages = ctx.vector(stages);
+    EvalResult::Value(ctx.transducer(TransducerKind::Comp, stages))
+}
+
+pub fn native_transduce(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (transduce xform f coll) or (transduce xform f init coll)
+    let (xform, f, init, coll) = match args.len() {
+        3 => (args[0], args[1], None, args[2]),
+        4 => (args[0], args[1], Some(args[2]), args[3]),
+        n => return EvalResult::Value(ctx.arity_error(3, n, "transduce (or 4)")),
+    };
+    let mut stages = try_value!(ctx.transducer_stages(xform, "transduce"));
+    if !ctx.is_callable(f) {
+        return EvalResult::Value(ctx.type_error("a function", ctx.type_name(f), "transduce"));
+    }
+
+    // Without an init, like reduce over nothing, f called with no arguments supplies it
+    let mut acc = match init {
+        Some(init) => init,
+        None => try_value!(ctx.call(f, vec![])),
+    };
+    // The accumulator is held while the stages call back into Blink
+    let acc_root = ctx.root_slot(acc);
+    try_value!(ctx.transduce_each(&mut stages, coll, "transduce", |ctx, item| {
+        acc = ctx.call(f, vec![acc, item])?;
+        ctx.set_root_slot(acc_root, acc);
+        Ok(())
+    }));
+    EvalResult::Value(acc)
+}
+
+pub fn native_into(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (into to from) or (into to xform from)
+    let (to, xform, from) = match args.len() {
+        2 => (args[0], None, args[1]),
+        3 => (args[0], Some(args[1]), args[2]),
+        n => return EvalResult::Value(ctx.arity_error(2, n, "into (or 3)")),
+    };
+    let mut stages = match xform {
+        Some(xform) => try_value!(ctx.transducer_stages(xform, "into")),
+        None => Vec::new(),
+    };
+
+    // Lists and nil grow at the front, like conj
+    if ctx.is_nil(to) || to.get_list().is_some() {
+        let mut items = to.get_list().unwrap_or_default();
+        try_value!(ctx.transduce_each(&mut stages, from, "into", |ctx, item| {
+            ctx.root(&[item]);
+            items.insert(0, item);
+            Ok(())
+        }));
+        return EvalResult::Value(ctx.list(items));
+    }
+
+    // Sorted collections have no transients and take each item as an update
+    if let Some((mut sorted, tag)) = sorted_object(to) {
+        let sorted_root = ctx.root_slot(to);
+        try_value!(ctx.transduce_each(&mut stages, from, "into", |ctx, item| {
+            let (key, value) = match tag {
+                TypeTag::SortedSet => (item, ValueRef::nil()),
+                _ => match item.get_vec() {
+                    Some(entry) if entry.len() == 2 => (entry[0], entry[1]),
+                    _ => return Err(ctx.eval_error("into: map entries must be [key value] vectors")),
+                },
+            };
+            sorted = sorted_assoc(ctx, sorted, tag, key, value)?;
+            ctx.set_root_slot(sorted_root, ValueRef::Heap(GcPtr::new(sorted)));
+            Ok(())
+        }));
+        return EvalResult::Value(ValueRef::Heap(GcPtr::new(sorted)));
+    }
+
+    let (object, tag) = match to {
+        ValueRef::Heap(gc_ptr) if matches!(gc_ptr.type_tag(), TypeTag::Vector | TypeTag::Map | TypeTag::Set) => {
+            (gc_ptr.0, gc_ptr.type_tag())
+        }
+        _ => return EvalResult::Value(ctx.type_error("a list, vector, map or set", ctx.type_name(to), "into")),
+    };
+
+    // Items are added to a transient copy, which is frozen once the input is used up
+    let vm = ctx.vm().clone();
+    let transient = match tag {
+        TypeTag::Vector if !vm.vector_is_transient(object) => vm.vector_transient(object),
+        TypeTag::Map if !vm.hashmap_is_transient(object) => vm.hashmap_transient(object),
+        TypeTag::Set if !vm.hashset_is_transient(object) => vm.hashset_transient(object),
+        _ => return EvalResult::Value(ctx.eval_error("into: cannot add to a transient, call persistent! first")),
+    };
+    ctx.root(&[ValueRef::Heap(GcPtr::new(transient))]);
+    try_value!(ctx.transduce_each(&mut stages, from, "into", |ctx, item| {
+        let added = match tag {
+            TypeTag::Vector => vm.vector_conj_mut(transient, item),
+            TypeTag::Set => vm.hashset_conj_mut(transient, item),
+            _ => match item.get_vec() {
+                Some(entry) if entry.len() == 2 => vm.hashmap_assoc_mut(transient, entry[0], entry[1]),
+                _ => Err("into: map entries must be [key value] vectors".to_string()),
+            },
+        };
+        added.map_err(|message| ctx.eval_error(&message))
+    }));
+
+    let frozen = match tag {
+        TypeTag::Vector => vm.vector_persistent(transient),
+        TypeTag::Map => vm.hashmap_persistent(transient),
+        _ => vm.hashset_persistent(transient),
+    };
+    match frozen {
+        Ok(object) => EvalResult::Value(ValueRef::Heap(GcPtr::new(object))),
+        Err(message) => EvalResult::Value(ctx.eval_error(&message)),
+    }
+}
+
+pub fn native_sequence(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (sequence coll) or (sequence xform coll). Over a lazy seq the result is lazy
+    // too, so a pipeline can run over an infinite input.
+    let (xform, coll) = match args.len() {
+        1 => (None, args[0]),
+        2 => (Some(args[0]), args[1]),
+        n => return EvalResult::Value(ctx.arity_error(1, n, "sequence (or 2)")),
+    };
+    let mut stages = match xform {
+        Some(xform) => try_value!(ctx.transducer_stages(xform, "sequence")),
+        None => Vec::new(),
+    };
+
+    if let (Some(xform), true) = (xform, ctx.is_lazy_seq(coll)) {
+        let counts = stages.iter().map(|stage| ctx.number(stage.remaining as f64)).collect();
+        let counts = ctx.vector(counts);
+        let state = ctx.vector(vec![xform, coll, counts]);
+        return EvalResult::Value(ctx.lazy_seq(SeqGenerator::Transduce, state));
+    }
+    if ctx.is_lazy_seq(coll) {
+        return EvalResult::Value(coll);
+    }
+
+    let mut items = Vec::new();
+    try_value!(ctx.transduce_each(&mut stages, coll, "sequence", |ctx, item| {
+        ctx.root(&[item]);
+        items.push(item);
+        Ok(())
+    }));
+    EvalResult::Value(ctx.list(items))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::ExecutionContext;
+    use crate::test_support::{context, eval, try_eval};
+
+    // Whether the code evaluates to a value equal to `expected`
+    fn returns(ctx: &mut ExecutionContext, code: &str, expected: &str) -> bool {
+        eval(ctx, &format!("(= {} {})", code, expected)) == "true"
+    }
+
+    // The message of an error, whether it was raised or returned as a value
+    fn error_message(ctx: &mut ExecutionContext, code: &str) -> String {
+        try_eval(ctx, code).unwrap_or_else(|error| error)
+    }
+
+    #[test]
+    fn composed_stages_run_first_to_last() {
+        let mut ctx = context("xf-comp");
+        eval(&mut ctx, "(def xf (comp (map (fn [x] (* x 10))) (filter (fn [x] (> x 15))) (take 2)))");
+        assert!(returns(&mut ctx, "(into [] xf [1 2 3 4])", "[20 30]"));
+        // Filtering before mapping sees the original items
+        assert!(returns(&mut ctx, "(into [] (comp (filter (fn [x] (> x 2))) (map (fn [x] (* x 10)))) [1 2 3 4])", "[30 40]"));
+        assert!(returns(&mut ctx, "(into [] (comp) [1 2])", "[1 2]"));
+        // Nested compositions behave like the flat one
+        assert!(returns(&mut ctx, "(into [] (comp (comp (drop 1) (take 2)) (map (fn [x] (+ x 1)))) [1 2 3 4])", "[3 4]"));
+    }
+
+    #[test]
+    fn transduce_folds_what_comes_out_of_the_pipeline() {
+        let mut ctx = context("xf-transduce");
+        assert_eq!(eval(&mut ctx, "(transduce (map (fn [x] (* x x))) + 0 [1 2 3])"), "14");
+        // Without an init, f called with no arguments supplies it
+        assert_eq!(eval(&mut ctx, "(transduce (filter (fn [x] (> x 1))) + [1 2 3])"), "5");
+        assert_eq!(eval(&mut ctx, "(transduce (map (fn [x] x)) + 7 [])"), "7");
+        assert_eq!(eval(&mut ctx, "(let [k 3] (transduce (map (fn [x] (* k x))) + 0 '(1 2)))"), "9");
+    }
+
+    #[test]
+    fn take_stops_reading_the_input() {
+        let mut ctx = context("xf-take");
+        assert_eq!(eval(&mut ctx, "(transduce (comp (map (fn [x] (+ x 1))) (take 3)) + 0 (range))"), "6");
+        // Items after the last one taken are never mapped
+        let guarded = "(into [] (comp (map (fn [x] (if (= x 2) (err \"read too far\") x))) (take 2)) (range))";
+        assert!(returns(&mut ctx, guarded, "[0 1]"));
+        assert!(returns(&mut ctx, "(into [] (take 0) (map (fn [x] (err \"never\")) (range)))", "[]"));
+    }
+
+    #[test]
+    fn stateful_stages_start_over_on_each_run() {
+        let mut ctx = context("xf-state");
+        eval(&mut ctx, "(def firsts (comp (drop 1) (take 2)))");
+        assert!(returns(&mut ctx, "(into [] firsts [1 2 3 4])", "[2 3]"));
+        assert!(returns(&mut ctx, "(into [] firsts [5 6 7 8])", "[6 7]"));
+        eval(&mut ctx, "(def lazy (sequence firsts (range)))");
+        assert!(returns(&mut ctx, "(into [] lazy)", "[1 2]"));
+        assert!(returns(&mut ctx, "(into [] (sequence firsts (range)))", "[1 2]"));
+    }
+
+    #[test]
+    fn into_adds_to_every_kind_of_collection() {
+        let mut ctx = context("xf-into");
+        assert!(returns(&mut ctx, "(into [0] (map (fn [x] (* x 2))) '(1 2))", "[0 2 4]"));
+        // Lists grow at the front, like conj
+        assert!(returns(&mut ctx, "(into '(0) [1 2])", "'(2 1 0)"));
+        assert!(returns(&mut ctx, "(into nil [1 2])", "'(2 1)"));
+        assert!(returns(&mut ctx, "(into {:a 1} (map (fn [k] (vector k 2))) [:b :c])", "{:a 1 :b 2 :c 2}"));
+        assert!(returns(&mut ctx, "(into (set []) (map (fn [x] (mod x 3))) [1 2 3 4])", "(set [0 1 2])"));
+        assert!(returns(&mut ctx, "(into (sorted-set) [3 1 2])", "(sorted-set 1 2 3)"));
+        assert!(returns(&mut ctx, "(into (sorted-map) [[:b 2] [:a 1]])", "(sorted-map :a 1 :b 2)"));
+        // The collection into started from is unchanged
+        eval(&mut ctx, "(def base [1])");
+        eval(&mut ctx, "(into base [2 3])");
+        assert!(returns(&mut ctx, "base", "[1]"));
+    }
+
+    #[test]
+    fn sequence_is_lazy_over_lazy_input() {
+        let mut ctx = context("xf-sequence");
+        eval(&mut ctx, "(def evens (sequence (filter (fn [x] (= 0 (mod x 2)))) (range)))");
+        assert_eq!(eval(&mut ctx, "evens"), "(...)");
+        assert!(returns(&mut ctx, "(into [] (take 3 evens))", "[0 2 4]"));
+        assert!(returns(&mut ctx, "(sequence (map (fn [x] (+ x 1))) [1 2])", "'(2 3)"));
+        assert!(returns(&mut ctx, "(sequence [1 2])", "'(1 2)"));
+    }
+
+    #[test]
+    fn bad_pipelines_are_errors() {
+        let mut ctx = context("xf-errors");
+        assert!(error_message(&mut ctx, "(comp (take 1) 5)").contains("comp"));
+        assert!(error_message(&mut ctx, "(transduce (map (fn [x] x)) 5 0 [1])").contains("transduce"));
+        assert!(error_message(&mut ctx, "(into {} [1 2])").contains("[key value]"));
+        assert!(error_message(&mut ctx, "(into (transient []) [1])").contains("transient"));
+        let message = error_message(&mut ctx, "(into [] (map (fn [x] (err \"bad stage\"))) [1])");
+        assert!(message.contains("bad stage"), "{}", message);
+    }
+
+    #[test]
+    fn transducers_equal_the_same_stages() {
+        let mut ctx = context("xf-equality");
+        assert_eq!(eval(&mut ctx, "(= (map inc) (map inc))"), "true");
+        assert_eq!(eval(&mut ctx, "(= (comp (take 2) (filter number?)) (comp (take 2) (filter number?)))"), "true");
+        assert_eq!(eval(&mut ctx, "(= (map inc) (map dec))"), "false");
+        assert_eq!(eval(&mut ctx, "(= (take 2) (drop 2))"), "false");
+    }
+}
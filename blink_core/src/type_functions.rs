@@ -0,0 +1,389 @@
+use crate::runtime::{EvalResult, TypeTag};
+use crate::value::{unpack_immediate, ImmediateValue, NativeContext, ValueRef};
+
+// Type predicates and coercions. The predicates read the immediate tag or the
+// heap TypeTag directly, so they agree with `type-of`. Sorted maps and sets
+// count as maps and sets, and lazy seqs as seqs.
+
+macro_rules! try_value {
+    ($expr:expr) => {
+        match $expr {
+            Ok(value) => value,
+            Err(error) => return EvalResult::Value(error),
+        }
+    };
+}
+
+fn immediate(value: ValueRef) -> Option<ImmediateValue> {
+    match value {
+        ValueRef::Immediate(packed) => Some(unpack_immediate(packed)),
+        _ => None,
+    }
+}
+
+fn heap_tag(value: ValueRef) -> Option<TypeTag> {
+    match value {
+        ValueRef::Heap(gc_ptr) => Some(gc_ptr.type_tag()),
+        _ => None,
+    }
+}
+
+fn type_predicate(args: Vec<ValueRef>, ctx: &mut NativeContext, function_name: &str, test: fn(ValueRef) -> bool) -> EvalResult {
+    try_value!(ctx.require_arity(&args, 1, function_name));
+    EvalResult::Value(ctx.bool(test(args[0])))
+}
+
+pub fn native_nil_q(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    type_predicate(args, ctx, "nil?", |v| matches!(immediate(v), Some(ImmediateValue::Nil)))
+}
+
+pub fn native_some_q(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    type_predicate(args, ctx, "some?", |v| !matches!(immediate(v), Some(ImmediateValue::Nil)))
+}
+
+pub fn native_true_q(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    type_predicate(args, ctx, "true?", |v| matches!(immediate(v), Some(ImmediateValue::Bool(true))))
+}
+
+pub fn native_false_q(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    type_predicate(args, ctx, "false?", |v| matches!(immediate(v), Some(ImmediateValue::Bool(false))))
+}
+
+pub fn native_boolean_q(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    type_predicate(args, ctx, "boolean?", |v| matches!(immediate(v), Some(ImmediateValue::Bool(_))))
+}
+
+pub fn native_number_q(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    type_predicate(args, ctx, "number?", |v| matches!(immediate(v), Some(ImmediateValue::Number(_))))
+}
+
+pub fn native_int_q(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // Numbers are all floats; int? holds for finite whole ones
+    type_predicate(args, ctx, "int?", |v| {
+        matches!(immediate(v), Some(ImmediateValue::Number(n)) if n.is_finite() && n.fract() == 0.0)
+    })
+}
+
+pub fn native_string_q(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    type_predicate(args, ctx, "string?", |v| heap_tag(v) == Some(TypeTag::Str))
+}
+
+pub fn native_keyword_q(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    type_predicate(args, ctx, "keyword?", |v| matches!(immediate(v), Some(ImmediateValue::Keyword(_))))
+}
+
+pub fn native_symbol_q(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    type_predicate(args, ctx, "symbol?", |v| matches!(immediate(v), Some(ImmediateValue::Symbol(_))))
+}
+
+pub fn native_list_q(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    type_predicate(args, ctx, "list?", |v| heap_tag(v) == Some(TypeTag::List))
+}
+
+pub fn native_vector_q(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    type_predicate(args, ctx, "vector?", |v| heap_tag(v) == Some(TypeTag::Vector))
+}
+
+pub fn native_map_q(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    type_predicate(args, ctx, "map?", |v| matches!(heap_tag(v), Some(TypeTag::Map | TypeTag::SortedMap)))
+}
+
+pub fn native_set_q(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    type_predicate(args, ctx, "set?", |v| matches!(heap_tag(v), Some(TypeTag::Set | TypeTag::SortedSet)))
+}
+
+pub fn native_sorted_q(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    type_predicate(args, ctx, "sorted?", |v| matches!(heap_tag(v), Some(TypeTag::SortedMap | TypeTag::SortedSet)))
+}
+
+pub fn native_seq_q(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    type_predicate(args, ctx, "seq?", |v| matches!(heap_tag(v), Some(TypeTag::List | TypeTag::LazySeq)))
+}
+
+pub fn native_coll_q(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    type_predicate(args, ctx, "coll?", |v| {
+        matches!(
+            heap_tag(v),
+            Some(
+                TypeTag::List
+                    | TypeTag::Vector
+                    | TypeTag::Map
+                    | TypeTag::Set
+                    | TypeTag::LazySeq
+                    | TypeTag::SortedMap
+                    | TypeTag::SortedSet
+            )
+        )
+    })
+}
+
+pub fn native_fn_q(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // Natives, compiled functions and closures; not macros or callable collections
+    type_predicate(args, ctx, "fn?", |v| {
+        v.is_native_fn() || matches!(heap_tag(v), Some(TypeTag::UserDefinedFunction | TypeTag::Closure))
+    })
+}
+
+// The full name of a keyword or symbol, without a keyword's leading colon
+fn symbolic_name(ctx: &NativeContext, value: ValueRef) -> Option<String> {
+    if let Some(id) = value.get_keyword() {
+        let name = ctx.symbol_name(id)?;
+        return Some(name.strip_prefix(':').unwrap_or(&name).to_string());
+    }
+    ctx.symbol_name(value.get_symbol()?)
+}
+
+// Split "module/name" into its parts. "/" on its own is a plain name.
+fn split_qualified(full_name: &str) -> (Option<&str>, &str) {
+    match full_name.split_once('/') {
+        Some((module, name)) if !module.is_empty() && !name.is_empty() => (Some(module), name),
+        _ => (None, full_name),
+    }
+}
+
+// The name to intern for (keyword ...) or (symbol ...), from one name or a module and a name
+fn coercion_name(ctx: &NativeContext, args: &[ValueRef], function_name: &str) -> Result<String, ValueRef> {
+    let part = |value: ValueRef| {
+        value
+            .get_string()
+            .or_else(|| symbolic_name(ctx, value))
+            .ok_or_else(|| ctx.type_error("a string, keyword or symbol", ctx.type_name(value), function_name))
+    };
+    let full_name = match args {
+        [name] => part(*name)?,
+        [module, name] if ctx.is_nil(*module) => part(*name)?,
+        [module, name] => format!("{}/{}", part(*module)?, part(*name)?),
+        _ => return Err(ctx.arity_error(1, args.len(), &format!("{} (or 2)", function_name))),
+    };
+    if full_name.is_empty() || (full_name != "/" && full_name.matches('/').count() > 1) {
+        return Err(ctx.eval_error(&format!("{}: cannot make a name from \"{}\"", function_name, full_name)));
+    }
+    Ok(full_name)
+}
+
+pub fn native_keyword(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (keyword name) or (keyword module name)
+    if let [value] = args[..] {
+        if value.get_keyword().is_some() {
+            return EvalResult::Value(value);
+        }
+    }
+    let name = try_value!(coercion_name(ctx, &args, "keyword"));
+    EvalResult::Value(ctx.keyword(&name))
+}
+
+pub fn native_symbol(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (symbol name) or (symbol module name)
+    if let [value] = args[..] {
+        if value.get_symbol().is_some() {
+            return EvalResult::Value(value);
+        }
+    }
+    let name = try_value!(coercion_name(ctx, &args, "symbol"));
+    EvalResult::Value(ctx.symbol(&name))
+}
+
+pub fn native_gensym(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (gensym) or (gensym "prefix") is a symbol no other code has used, prefix__N
+    let prefix = match args[..] {
+        [] => "G".to_string(),
+        [prefix] => match symbolic_name(ctx, prefix).or_else(|| prefix.get_string()) {
+            Some(prefix) => prefix,
+            None => return EvalResult::Value(ctx.type_error("a string or symbol prefix", ctx.type_name(prefix), "gensym")),
+        },
+        _ => return EvalResult::Value(ctx.arity_error(1, args.len(), "gensym")),
+    };
+    EvalResult::Value(ValueRef::symbol(ctx.vm().gensym(&prefix)))
+}
+
+pub fn native_name(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (name x) is a string's text, or a keyword's or symbol's name without its module
+    try_value!(ctx.require_arity(&args, 1, "name"));
+    if let Some(s) = args[0].get_string() {
+        return EvalResult::Value(ctx.string(&s));
+    }
+    match symbolic_name(ctx, args[0]) {
+        Some(full_name) => EvalResult::Value(ctx.string(split_qualified(&full_name).1)),
+        None => EvalResult::Value(ctx.type_error("a string, keyword or symbol", ctx.type_name(args[0]), "name")),
+    }
+}
+
+pub fn native_namespace(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (namespace x) is the module of a qualified keyword or symbol, or nil
+    try_value!(ctx.require_arity(&args, 1, "namespace"));
+    match symbolic_name(ctx, args[0]) {
+        Some(full_name) => match split_qualified(&full_name).0 {
+            Some(module) => EvalResult::Value(ctx.string(module)),
+            None => EvalResult::Value(ctx.nil()),
+        },
+        None => EvalResult::Value(ctx.type_error("a keyword or symbol", ctx.type_name(args[0]), "namespace")),
+    }
+}
+
+pub fn native_str_to_number(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (str->number "1.5"); surrounding whitespace is allowed
+    try_value!(ctx.require_arity(&args, 1, "str->number"));
+    let Some(s) = args[0].get_string() else {
+        return EvalResult::Value(ctx.type_error("a string", ctx.type_name(args[0]), "str->number"));
+    };
+    match s.trim().parse::<f64>() {
+        Ok(n) => EvalResult::Value(ctx.number(n)),
+        Err(_) => EvalResult::Value(ctx.eval_error(&format!("str->number: cannot convert \"{}\" to a number", s))),
+    }
+}
+
+pub fn native_vec(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (vec coll) is a vector of the items of any collection
+    try_value!(ctx.require_arity(&args, 1, "vec"));
+    if args[0].is_vec() {
+        return EvalResult::Value(args[0]);
+    }
+    match ctx.seq_to_vec(args[0]) {
+        Ok(items) => EvalResult::Value(ctx.vector(items)),
+        Err(_) if !ctx.is_lazy_seq(args[0]) => {
+            EvalResult::Value(ctx.type_error("a collection", ctx.type_name(args[0]), "vec"))
+        }
+        Err(error) => EvalResult::Value(error),
+    }
+}
+
+pub fn native_set(args: Vec<ValueRef>, ctx: &mut NativeContext) -> EvalResult {
+    // (set coll) is a hash set of the distinct items of any collection
+    try_value!(ctx.require_arity(&args, 1, "set"));
+    if heap_tag(args[0]) == Some(TypeTag::Set) {
+        return EvalResult::Value(args[0]);
+    }
+    match ctx.seq_to_vec(args[0]) {
+        Ok(items) => EvalResult::Value(ctx.hash_set(items)),
+        Err(_) if !ctx.is_lazy_seq(args[0]) => {
+            EvalResult::Value(ctx.type_error("a collection", ctx.type_name(args[0]), "set"))
+        }
+        Err(error) => EvalResult::Value(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::ExecutionContext;
+    use crate::test_support::{context, eval, try_eval};
+
+    // Whether the code evaluates to a value equal to `expected`
+    fn returns(ctx: &mut ExecutionContext, code: &str, expected: &str) -> bool {
+        eval(ctx, &format!("(= {} {})", code, expected)) == "true"
+    }
+
+    // The message of an error, whether it was raised or returned as a value
+    fn error_message(ctx: &mut ExecutionContext, code: &str) -> String {
+        try_eval(ctx, code).unwrap_or_else(|error| error)
+    }
+
+    // One value of each kind, by name
+    const SAMPLES: &[(&str, &str)] = &[
+        ("nil", "nil"),
+        ("true", "true"),
+        ("false", "false"),
+        ("int", "3"),
+        ("float", "2.5"),
+        ("string", "\"s\""),
+        ("keyword", ":k"),
+        ("symbol", "'sym"),
+        ("list", "'(1 2)"),
+        ("vector", "[1 2]"),
+        ("map", "{:a 1}"),
+        ("set", "(set [1])"),
+        ("sorted-map", "(sorted-map 1 2)"),
+        ("sorted-set", "(sorted-set 1)"),
+        ("lazy-seq", "(range 3)"),
+        ("native", "+"),
+        ("fn", "(fn [x] x)"),
+        ("closure", "(let [y 1] (fn [x] (+ x y)))"),
+    ];
+
+    #[test]
+    fn predicates_hold_for_exactly_their_kinds() {
+        let mut ctx = context("type-predicates");
+        let expectations: &[(&str, &[&str])] = &[
+            ("nil?", &["nil"]),
+            ("some?", &["true", "false", "int", "float", "string", "keyword", "symbol", "list", "vector", "map", "set",
+                "sorted-map", "sorted-set", "lazy-seq", "native", "fn", "closure"]),
+            ("true?", &["true"]),
+            ("false?", &["false"]),
+            ("boolean?", &["true", "false"]),
+            ("number?", &["int", "float"]),
+            ("int?", &["int"]),
+            ("string?", &["string"]),
+            ("keyword?", &["keyword"]),
+            ("symbol?", &["symbol"]),
+            ("list?", &["list"]),
+            ("vector?", &["vector"]),
+            ("map?", &["map", "sorted-map"]),
+            ("set?", &["set", "sorted-set"]),
+            ("sorted?", &["sorted-map", "sorted-set"]),
+            ("seq?", &["list", "lazy-seq"]),
+            ("coll?", &["list", "vector", "map", "set", "sorted-map", "sorted-set", "lazy-seq"]),
+            ("fn?", &["native", "fn", "closure"]),
+        ];
+        for (predicate, holds_for) in expectations {
+            for (kind, code) in SAMPLES {
+                let expected = holds_for.contains(kind).to_string();
+                assert_eq!(eval(&mut ctx, &format!("({} {})", predicate, code)), expected, "({} {})", predicate, kind);
+            }
+        }
+        // Whole numbers only, and not infinity
+        assert_eq!(eval(&mut ctx, "(int? (str->number \"inf\"))"), "false");
+        assert_eq!(eval(&mut ctx, "(int? -4)"), "true");
+        // Keywords and maps can be called but are not functions
+        assert_eq!(eval(&mut ctx, "(fn? :k)"), "false");
+        assert!(error_message(&mut ctx, "(nil?)").contains("nil?"));
+    }
+
+    #[test]
+    fn keywords_and_symbols_convert_between_each_other() {
+        let mut ctx = context("type-names");
+        assert!(returns(&mut ctx, "(keyword \"a\")", ":a"));
+        assert!(returns(&mut ctx, "(keyword 'a)", ":a"));
+        assert!(returns(&mut ctx, "(keyword :a)", ":a"));
+        assert!(returns(&mut ctx, "(keyword \"mod\" \"a\")", ":mod/a"));
+        assert!(returns(&mut ctx, "(keyword nil \"a\")", ":a"));
+        assert!(returns(&mut ctx, "(symbol \"b\")", "'b"));
+        assert!(returns(&mut ctx, "(symbol :b)", "'b"));
+        assert!(returns(&mut ctx, "(symbol 'mod \"b\")", "'mod/b"));
+        assert!(error_message(&mut ctx, "(keyword \"\")").contains("cannot make a name"));
+        assert!(error_message(&mut ctx, "(keyword \"a/b/c\")").contains("cannot make a name"));
+        assert!(error_message(&mut ctx, "(symbol 5)").contains("string, keyword or symbol"));
+
+        assert!(returns(&mut ctx, "(name :mod/a)", "\"a\""));
+        assert!(returns(&mut ctx, "(name 'b)", "\"b\""));
+        assert!(returns(&mut ctx, "(name \"text\")", "\"text\""));
+        assert!(returns(&mut ctx, "(name '/)", "\"/\""));
+        assert!(returns(&mut ctx, "(namespace :mod/a)", "\"mod\""));
+        assert_eq!(eval(&mut ctx, "(namespace :a)"), "nil");
+        assert!(error_message(&mut ctx, "(namespace \"mod/a\")").contains("keyword or symbol"));
+
+        assert_eq!(eval(&mut ctx, "(= (gensym) (gensym))"), "false");
+        assert!(eval(&mut ctx, "(name (gensym \"tmp\"))").starts_with("tmp__"));
+    }
+
+    #[test]
+    fn strings_and_collections_are_coerced() {
+        let mut ctx = context("type-coercions");
+        assert_eq!(eval(&mut ctx, "(str->number \"1.5\")"), "1.5");
+        assert_eq!(eval(&mut ctx, "(str->number \" -2 \")"), "-2");
+        assert_eq!(eval(&mut ctx, "(str->number \"1e3\")"), "1000");
+        assert!(error_message(&mut ctx, "(str->number \"12abc\")").contains("cannot convert"));
+        assert!(error_message(&mut ctx, "(str->number 12)").contains("string"));
+
+        assert!(returns(&mut ctx, "(vec '(1 2))", "[1 2]"));
+        assert!(returns(&mut ctx, "(vec (range 3))", "[0 1 2]"));
+        assert!(returns(&mut ctx, "(vec nil)", "[]"));
+        assert!(returns(&mut ctx, "(vec (sorted-set 2 1))", "[1 2]"));
+        assert_eq!(eval(&mut ctx, "(vector? (vec {:a 1}))"), "true");
+        assert!(returns(&mut ctx, "(set [1 2 1])", "(set [2 1])"));
+        assert_eq!(eval(&mut ctx, "(count (set (map (fn [x] (mod x 3)) (range 10))))"), "3");
+        assert!(error_message(&mut ctx, "(vec 5)").contains("collection"));
+        assert!(error_message(&mut ctx, "(set :k)").contains("collection"));
+        // A lazy seq that fails while realizing reports its own error
+        let message = error_message(&mut ctx, "(vec (map (fn [x] (err \"bad item\")) (range 1)))");
+        assert!(message.contains("bad item"), "{}", message);
+    }
+}
@@ -6,6 +6,7 @@ use crate::ValueRef;
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ChannelHandle {
     pub id: u64,
+    pub generation: u32, // CHKFIX
 }
 
 #[derive(Debug)]
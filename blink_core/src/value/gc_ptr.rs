@@ -4,9 +4,9 @@ use std::sync::Arc;
 
 use mmtk::util::ObjectReference;
 use parking_lot::RwLock;
-use crate::error::{BlinkError, BlinkErrorType, ParseErrorType};
+use crate::error::{BlinkError, BlinkErrorType, LimitKind, ParseErrorType};
 use crate::module::{Module, SerializedModuleSource};
-use crate::runtime::{BlinkObjectModel, ClosureObject, CompiledFunction, Macro, GLOBAL_VM};
+use crate::runtime::{BlinkObjectModel, ClosureObject, CompiledFunction, LazySeqPrefix, LineTableEntry, LocalVarEntry, Macro, GLOBAL_VM};
 use crate::value::{Callable, SourceRange};
 use crate::env::Env;
 use crate::{collections::{BlinkHashMap, BlinkHashSet}, value::ValueRef};
@@ -26,12 +26,18 @@ impl Hash for GcPtr {
 
 impl PartialEq for GcPtr {
     fn eq(&self, other: &Self) -> bool {
+        if self.0 == other.0 {
+            return true;
+        }
         let type_tag: TypeTag = self.type_tag();
         match type_tag {
-            // ref equality for error, user defined function, macro, future, env
-            TypeTag::Error => self.0 == other.0,
-            TypeTag::UserDefinedFunction => self.0 == other.0,
-            TypeTag::Env => self.0 == other.0,
+            // ref equality for error, user defined function, closure, macro, env and lazy seq
+            TypeTag::Error => false,
+            TypeTag::UserDefinedFunction => false,
+            TypeTag::Closure => false,
+            TypeTag::Macro => false,
+            TypeTag::Env => false,
+            TypeTag::LazySeq => false,
             _ => {
                 let heap_val = self.to_heap_value();
                 let other_heap_val = other.to_heap_value();
@@ -76,45 +82,37 @@ impl GcPtr {
             TypeTag::Closure => HeapValue::Closure(self.read_closure()),
             TypeTag::Macro => HeapValue::Macro(self.read_macro()),
             TypeTag::ListNode => unreachable!(), // should not happen but if I want to support it it'd need to create a new header
+            TypeTag::TrieNode => unreachable!(), // internal node of a vector, map or set
+            TypeTag::LazySeq => HeapValue::LazySeq(self.read_lazy_seq_prefix()),
+            TypeTag::Transducer => {
+                let vm = GLOBAL_VM.get().expect("BlinkVM not initialized");
+                HeapValue::Transducer(vm.transducer_kind(self.0), vm.transducer_arg(self.0))
+            }
+            TypeTag::SortedMap => {
+                let vm = GLOBAL_VM.get().expect("BlinkVM not initialized");
+                HeapValue::SortedMap(vm.sorted_comparator(self.0), vm.sorted_entries(vm.sorted_root(self.0)))
+            }
+            TypeTag::SortedSet => {
+                let vm = GLOBAL_VM.get().expect("BlinkVM not initialized");
+                let items = vm.sorted_entries(vm.sorted_root(self.0)).into_iter().map(|(key, _)| key).collect();
+                HeapValue::SortedSet(vm.sorted_comparator(self.0), items)
+            }
+            TypeTag::TreeNode => unreachable!(), // internal node of a sorted map or set
         }
     }
 
     pub fn read_map(&self) -> Vec<(ValueRef, ValueRef)> {
-        unsafe {
-            let base_ptr = self.0.to_raw_address().as_usize() as *mut u8;
-            let (_header, type_tag) = BlinkObjectModel::get_header(self.0);
-            
-            // Verify this is actually a map
-            debug_assert_eq!(type_tag, TypeTag::Map);
-            
-            let data_start = base_ptr;
-            
-            // Read metadata
-            let bucket_count_ptr = data_start as *const usize;
-            let bucket_count = std::ptr::read(bucket_count_ptr);
-            
-            let item_count_ptr = data_start.add(std::mem::size_of::<usize>()) as *const usize;
-            let item_count = std::ptr::read(item_count_ptr);
-            
-            // Calculate layout
-            let header_size = std::mem::size_of::<usize>() * 2;
-            let buckets_size = bucket_count * std::mem::size_of::<u32>();
-            
-            // Read all pairs
-            let pairs_ptr = data_start.add(header_size + buckets_size) as *const ValueRef;
-            let mut pairs = Vec::with_capacity(item_count);
-            
-            for i in 0..item_count {
-                let key = std::ptr::read(pairs_ptr.add(i * 2));
-                let val = std::ptr::read(pairs_ptr.add(i * 2 + 1));
-                pairs.push((key, val));
-            }
-
-            pairs
-        }
+        debug_assert_eq!(self.type_tag(), TypeTag::Map);
+        let vm = GLOBAL_VM.get().expect("BlinkVM not initialized");
+        vm.hashmap_entries(self.0)
     }
 
 
+    pub fn read_lazy_seq_prefix(&self) -> LazySeqPrefix {
+        let vm = GLOBAL_VM.get().expect("BlinkVM not initialized");
+        vm.lazy_seq_prefix(self.0)
+    }
+
     pub fn read_blink_hash_map(&self) -> BlinkHashMap {
         let pairs = self.read_map();
         BlinkHashMap::from_pairs(pairs)
@@ -178,6 +176,7 @@ impl GcPtr {
                 message,
                 pos,
                 error_type,
+                stack_trace: Vec::new(), // Stack traces are not stored on the heap
             }
         }
     }
@@ -245,6 +244,17 @@ impl GcPtr {
                 
                 BlinkErrorType::UserDefined { data }
             },
+            7 => {
+                let limit_discriminant = std::ptr::read_unaligned(ptr.add(offset) as *const u8);
+                let limit = match limit_discriminant {
+                    0 => LimitKind::Fuel,
+                    1 => LimitKind::Deadline,
+                    2 => LimitKind::CallDepth,
+                    _ => LimitKind::Allocation,
+                };
+                BlinkErrorType::LimitExceeded { limit }
+            },
+            8 => BlinkErrorType::Interrupted,
             _ => BlinkErrorType::Eval, // fallback
         }
     }
@@ -278,38 +288,9 @@ impl GcPtr {
     }
 
     pub fn read_set(&self) -> Vec<ValueRef> {
-        unsafe {
-            let base_ptr = self.0.to_raw_address().as_usize() as *mut u8;
-            let header_ptr = base_ptr as *mut ObjectHeader;
-            let header = std::ptr::read(header_ptr);
-
-            // Verify this is actually a set
-            debug_assert_eq!(header.type_tag, TypeTag::Set as i8);
-            
-            let data_start = base_ptr.add(std::mem::size_of::<ObjectHeader>());
-            
-            // Read metadata
-            let bucket_count_ptr = data_start as *const usize;
-            let bucket_count = std::ptr::read(bucket_count_ptr);
-            
-            let item_count_ptr = data_start.add(std::mem::size_of::<usize>()) as *const usize;
-            let item_count = std::ptr::read(item_count_ptr);
-            
-            // Calculate layout
-            let header_size = std::mem::size_of::<usize>() * 2;
-            let buckets_size = bucket_count * std::mem::size_of::<u32>();
-            
-            // Read all items
-            let items_ptr = data_start.add(header_size + buckets_size) as *const ValueRef;
-            let mut items = Vec::with_capacity(item_count);
-            
-            for i in 0..item_count {
-                let item = std::ptr::read(items_ptr.add(i));
-                items.push(item);
-            }
-            
-            items
-        }
+        debug_assert_eq!(self.type_tag(), TypeTag::Set);
+        let vm = GLOBAL_VM.get().expect("BlinkVM not initialized");
+        vm.hashset_to_vec(self.0)
     }
 
     pub fn read_blink_hash_set(&self) -> BlinkHashSet {
@@ -363,17 +344,7 @@ impl GcPtr {
             
             // Use the VM's safe API
             if let Some(vm) = GLOBAL_VM.get() {
-                let length = vm.vector_get_length(*obj_ref);
-                let mut items = Vec::with_capacity(length as usize);
-                
-                for i in 0..length {
-                    match vm.vector_get_at(*obj_ref, i) {
-                        Ok(item) => items.push(item),
-                        Err(_) => return None,
-                    }
-                }
-                
-                Some(items)
+                Some(vm.vector_to_vec(*obj_ref))
             } else {
                 None
             }
@@ -427,16 +398,12 @@ impl GcPtr {
             let data_ptr = self.0.to_raw_address().as_usize() as *const u8;
             let mut offset = 0;
 
-            let body_count = std::ptr::read_unaligned(data_ptr.add(offset) as *const u32) as usize;
-            offset += std::mem::size_of::<u32>();
+            let expander = std::ptr::read_unaligned(data_ptr.add(offset) as *const ValueRef);
+            offset += std::mem::size_of::<ValueRef>();
 
             let params_count = std::ptr::read_unaligned(data_ptr.add(offset) as *const u32) as usize;
             offset += std::mem::size_of::<u32>();
 
-            let body = std::slice::from_raw_parts(data_ptr.add(offset) as *const ValueRef, body_count);
-            offset += std::mem::size_of::<ValueRef>() * body_count;
-            
-
             let params = std::slice::from_raw_parts(data_ptr.add(offset) as *const u32, params_count);
             offset += std::mem::size_of::<u32>() * params_count;
             
@@ -446,7 +413,7 @@ impl GcPtr {
             let module = std::ptr::read_unaligned(data_ptr.add(offset) as *const u32);
             
 
-            Macro { params: params.to_vec(), body: body.to_vec(), is_variadic: is_variadic != 0, module }
+            Macro { params: params.to_vec(), expander, is_variadic: is_variadic != 0, module }
         }
         
     }
@@ -498,6 +465,32 @@ impl GcPtr {
                 bytecode_len
             );
             bytecode.set_len(bytecode_len);
+            offset += bytecode_len;
+
+            // Read debug info
+            let has_name = std::ptr::read_unaligned(data_ptr.add(offset) as *const u8);
+            offset += std::mem::size_of::<u8>();
+
+            let name_id = std::ptr::read_unaligned(data_ptr.add(offset) as *const u32);
+            offset += std::mem::size_of::<u32>();
+
+            let line_table_len = std::ptr::read_unaligned(data_ptr.add(offset) as *const u32) as usize;
+            offset += std::mem::size_of::<u32>();
+
+            let mut line_table = Vec::with_capacity(line_table_len);
+            for _ in 0..line_table_len {
+                line_table.push(std::ptr::read_unaligned(data_ptr.add(offset) as *const LineTableEntry));
+                offset += std::mem::size_of::<LineTableEntry>();
+            }
+
+            let locals_len = std::ptr::read_unaligned(data_ptr.add(offset) as *const u32) as usize;
+            offset += std::mem::size_of::<u32>();
+
+            let mut locals = Vec::with_capacity(locals_len);
+            for _ in 0..locals_len {
+                locals.push(std::ptr::read_unaligned(data_ptr.add(offset) as *const LocalVarEntry));
+                offset += std::mem::size_of::<LocalVarEntry>();
+            }
             
             CompiledFunction {
                 bytecode,
@@ -507,6 +500,9 @@ impl GcPtr {
                 module,
                 register_start,
                 has_self_reference: has_self_reference == 1,
+                name: if has_name == 1 { Some(name_id) } else { None },
+                line_table,
+                locals,
             }
         }
     }
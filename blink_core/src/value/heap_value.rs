@@ -1,7 +1,7 @@
 use std::{fmt::{self, Display}, hash::{Hash, Hasher}};
 
 use crate::{
-    collections::{BlinkHashMap, BlinkHashSet}, env::Env, error::BlinkError, runtime::{ClosureObject, CompiledFunction, Macro}, value::ValueRef
+    collections::{BlinkHashMap, BlinkHashSet}, env::Env, error::BlinkError, runtime::{ClosureObject, CompiledFunction, LazySeqPrefix, Macro, TransducerKind}, value::{value_hash, ValueRef}
 };
 
 #[derive(Debug)]
@@ -16,6 +16,11 @@ pub enum HeapValue {
     Macro(Macro),
     Closure(ClosureObject),
     Env(Env),
+    LazySeq(LazySeqPrefix),
+    Transducer(TransducerKind, ValueRef),
+    /// Comparator (nil for natural order) and the entries in order
+    SortedMap(ValueRef, Vec<(ValueRef, ValueRef)>),
+    SortedSet(ValueRef, Vec<ValueRef>),
 }
 
 impl Display for HeapValue {
@@ -74,6 +79,37 @@ impl Display for HeapValue {
                                                         write!(f, "macro")?;
                                                         Ok(())
                                                     },
+            HeapValue::LazySeq(prefix) => {
+                                                        write!(f, "(")?;
+                                                        for value_ref in &prefix.realized {
+                                                            write!(f, "{} ", value_ref)?;
+                                                        }
+                                                        if prefix.pending {
+                                                            write!(f, "...")?;
+                                                        }
+                                                        write!(f, ")")?;
+                                                        Ok(())
+                                                    },
+            HeapValue::Transducer(_, _) => {
+                                                        write!(f, "transducer")?;
+                                                        Ok(())
+                                                    },
+            HeapValue::SortedMap(_, entries) => {
+                                                        write!(f, "{{")?;
+                                                        for (key, value) in entries {
+                                                            write!(f, "{}: {}\n", key, value)?;
+                                                        }
+                                                        write!(f, "}}")?;
+                                                        Ok(())
+                                                    },
+            HeapValue::SortedSet(_, items) => {
+                                                        write!(f, "#{{")?;
+                                                        for value in items {
+                                                            write!(f, "{} ", value)?;
+                                                        }
+                                                        write!(f, "}}")?;
+                                                        Ok(())
+                                                    },
         }
     }
 }
@@ -85,34 +121,24 @@ impl Hash for HeapValue {
                                 "string".hash(state);
                                 s.hash(state);
                             }
-            HeapValue::List(value_refs) => {
-                                "list".hash(state);
-                                value_refs.len().hash(state);
-                                for item in value_refs {
-                                    item.hash(state);
-                                }
-                            }
-            HeapValue::Vector(value_refs) => {
-                                "vector".hash(state);
+            // Lists and vectors are equal when their items are, so they hash alike
+            HeapValue::List(value_refs) | HeapValue::Vector(value_refs) => {
+                                "sequential".hash(state);
                                 value_refs.len().hash(state);
                                 for item in value_refs {
                                     item.hash(state);
                                 }
                             }
+            // Maps and sets iterate in no particular order, so their parts are combined with a sum
             HeapValue::Map(blink_hash_map) => {
                                 "map".hash(state);
                                 blink_hash_map.len().hash(state);
-                                for (key, value) in blink_hash_map.iter() {
-                                    key.hash(state);
-                                    value.hash(state);
-                                }
+                                unordered_hash(blink_hash_map.iter().map(|(key, value)| entry_hash(key, value))).hash(state);
                             }
             HeapValue::Set(blink_hash_set) => {
                                 "set".hash(state);
                                 blink_hash_set.len().hash(state);
-                                for item in blink_hash_set.iter() {
-                                    item.hash(state);
-                                }
+                                unordered_hash(blink_hash_set.iter().map(value_hash)).hash(state);
                             }
             HeapValue::Error(blink_error) => {
                                 "error".hash(state);
@@ -132,7 +158,7 @@ impl Hash for HeapValue {
             HeapValue::Macro(macro_fn) => {
                                 "macro".hash(state);
                                 macro_fn.params.hash(state);
-                                macro_fn.body.len().hash(state);
+                                macro_fn.expander.hash(state);
                                 macro_fn.is_variadic.hash(state);
                                 macro_fn.module.hash(state);
                             }
@@ -144,6 +170,26 @@ impl Hash for HeapValue {
                                     upvalue.hash(state);
                                 }
                             }
+            HeapValue::LazySeq(_) => {
+                                // Lazy seqs compare by identity and realising one must not change its hash
+                                "lazy-seq".hash(state);
+                            }
+            HeapValue::Transducer(kind, arg) => {
+                                "transducer".hash(state);
+                                (*kind as u32).hash(state);
+                                arg.hash(state);
+                            }
+            // Sorted maps and sets equal hash maps and sets with the same entries
+            HeapValue::SortedMap(_, entries) => {
+                                "map".hash(state);
+                                entries.len().hash(state);
+                                unordered_hash(entries.iter().map(|(key, value)| entry_hash(key, value))).hash(state);
+                            }
+            HeapValue::SortedSet(_, items) => {
+                                "set".hash(state);
+                                items.len().hash(state);
+                                unordered_hash(items.iter().map(value_hash)).hash(state);
+                            }
             HeapValue::Env(env) => {
                                 "env".hash(state);
                                 env.vars.len().hash(state);
@@ -161,15 +207,21 @@ impl PartialEq for HeapValue {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (HeapValue::Str(s), HeapValue::Str(other_s)) => s == other_s,
-            (HeapValue::List(value_refs), HeapValue::List(other_value_refs)) => value_refs == other_value_refs,
-            (HeapValue::Vector(value_refs), HeapValue::Vector(other_value_refs)) => {
-                value_refs.len() == other_value_refs.len() && value_refs.iter().zip(other_value_refs.iter()).all(|(a, b)| a == b)
+            (HeapValue::List(value_refs) | HeapValue::Vector(value_refs), HeapValue::List(other_value_refs) | HeapValue::Vector(other_value_refs)) => {
+                value_refs == other_value_refs
+            },
+            (HeapValue::Transducer(kind, arg), HeapValue::Transducer(other_kind, other_arg)) => {
+                kind == other_kind && arg == other_arg
             },
-            (HeapValue::Map(blink_hash_map), HeapValue::Map(other_blink_hash_map)) => {
-                blink_hash_map.len() == other_blink_hash_map.len() && blink_hash_map.iter().zip(other_blink_hash_map.iter()).all(|(a, b)| a == b)
+            // Look each entry up: hash maps and sets have no order, and sorted ones
+            // with different comparators hold the same entries in different orders
+            (HeapValue::Map(_) | HeapValue::SortedMap(_, _), HeapValue::Map(_) | HeapValue::SortedMap(_, _)) => {
+                let (entries, other_map) = (self.map_entries(), other.to_hash_map());
+                entries.len() == other_map.len() && entries.iter().all(|(key, value)| other_map.get(key) == Some(value))
             },
-            (HeapValue::Set(blink_hash_set), HeapValue::Set(other_blink_hash_set)) => {
-                blink_hash_set.len() == other_blink_hash_set.len() && blink_hash_set.iter().zip(other_blink_hash_set.iter()).all(|(a, b)| a == b)
+            (HeapValue::Set(_) | HeapValue::SortedSet(_, _), HeapValue::Set(_) | HeapValue::SortedSet(_, _)) => {
+                let (items, other_set) = (self.set_items(), other.to_hash_set());
+                items.len() == other_set.len() && items.iter().all(|item| other_set.contains(item))
             },
             (HeapValue::Error(_arc_mod), HeapValue::Error(_)) => {
                 panic!("Should have happened already")
@@ -188,7 +240,48 @@ impl PartialEq for HeapValue {
 
 impl Eq for HeapValue {}
 
+// Sum of the hashes of the parts, which does not depend on their order
+fn unordered_hash(hashes: impl Iterator<Item = u64>) -> u64 {
+    hashes.fold(0, u64::wrapping_add)
+}
+
+fn entry_hash(key: &ValueRef, value: &ValueRef) -> u64 {
+    value_hash(key).rotate_left(1) ^ value_hash(value)
+}
+
 impl HeapValue {
+    // Entries of a hash or sorted map
+    fn map_entries(&self) -> Vec<(ValueRef, ValueRef)> {
+        match self {
+            HeapValue::Map(map) => map.iter().map(|(key, value)| (*key, *value)).collect(),
+            HeapValue::SortedMap(_, entries) => entries.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn to_hash_map(&self) -> BlinkHashMap {
+        match self {
+            HeapValue::Map(map) => map.clone(),
+            _ => BlinkHashMap::from_pairs(self.map_entries()),
+        }
+    }
+
+    // Members of a hash or sorted set
+    fn set_items(&self) -> Vec<ValueRef> {
+        match self {
+            HeapValue::Set(set) => set.to_vec(),
+            HeapValue::SortedSet(_, items) => items.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn to_hash_set(&self) -> BlinkHashSet {
+        match self {
+            HeapValue::Set(set) => set.clone(),
+            _ => BlinkHashSet::from_values(&self.set_items()),
+        }
+    }
+
     pub fn type_tag(&self) -> &'static str {
         match self {
             HeapValue::List(_) => "list",
@@ -201,6 +294,10 @@ impl HeapValue {
             HeapValue::Closure(_) => "closure",
             HeapValue::Env(_) => "env",
             HeapValue::Macro(_) => "macro",
+            HeapValue::LazySeq(_) => "lazy-seq",
+            HeapValue::Transducer(_, _) => "transducer",
+            HeapValue::SortedMap(_, _) => "sorted-map",
+            HeapValue::SortedSet(_, _) => "sorted-set",
         }
     }
 
@@ -12,13 +12,19 @@ const SYMBOL_TAG: u64 = 2;
 const NIL_TAG: u64 = 3;
 const KEYWORD_TAG: u64 = 4;
 
+// Infinities and NaN have the exponent bits of NAN_MASK too. Tagged values keep
+// the sign bit clear and use a non-zero tag, which leaves +inf (tag 0) and every
+// pattern with the sign bit set to numbers. NaN is always stored as this one
+// negative NaN, so equal NaNs have equal bits.
+const SIGN_BIT: u64 = 0x8000_0000_0000_0000;
+const CANONICAL_NAN: u64 = 0xFFF8_0000_0000_0000;
+
 // Packing functions    
 pub fn pack_number(n: f64) -> u64 {
-    let bits = n.to_bits();
-    if (bits & NAN_MASK) == NAN_MASK {
-        panic!("Cannot pack NaN");
+    if n.is_nan() {
+        return CANONICAL_NAN;
     }
-    bits
+    n.to_bits()
 }
 
 pub fn pack_bool(b: bool) -> u64 {
@@ -76,7 +82,7 @@ impl ImmediateValue {
 }
 
 pub fn unpack_immediate(packed: u64) -> ImmediateValue {
-    if (packed & NAN_MASK) != NAN_MASK {
+    if is_number(packed) {
         // Regular number
         ImmediateValue::Number(f64::from_bits(packed))
     } else {
@@ -93,7 +99,7 @@ pub fn unpack_immediate(packed: u64) -> ImmediateValue {
 
 // Convenient type checking
 pub fn is_number(packed: u64) -> bool {
-    (packed & NAN_MASK) != NAN_MASK
+    (packed & NAN_MASK) != NAN_MASK || (packed & SIGN_BIT) != 0 || (packed & TAG_MASK) == 0
 }
 
 pub fn is_bool(packed: u64) -> bool {
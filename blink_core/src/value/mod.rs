@@ -5,6 +5,8 @@ mod isolated_value;
 mod native_fn;
 mod parsed_value;
 mod plugin;
+mod seq;
+mod transducer;
 mod value_ref;
 mod future_handle;
 mod channel_handle;
@@ -16,10 +18,11 @@ pub use isolated_value::*;
 pub use native_fn::*;
 pub use parsed_value::*;
 pub use plugin::*;
+pub use transducer::*;
 pub use value_ref::*;
 pub use future_handle::*;
 pub use channel_handle::*;
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct SourcePos {
     pub line: usize,
     pub col: usize,
@@ -31,7 +34,7 @@ impl std::fmt::Display for SourcePos {
     }
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct SourceRange {
     pub start: SourcePos,
     pub end: SourcePos,
@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use crate::{
     error::BlinkError, 
-    runtime::{BlinkVM, ContextualBoundary, ValueBoundary, GLOBAL_VM, EvalResult}, value::{GcPtr, IsolatedValue, SourceRange, ValueRef}, collections::{BlinkHashMap, BlinkHashSet}
+    runtime::{BlinkVM, ContextualBoundary, ValueBoundary, GLOBAL_VM, EvalResult, TypeTag}, value::{GcPtr, IsolatedValue, SourceRange, ValueRef}, collections::{BlinkHashMap, BlinkHashSet}
 };
 
 pub type IsolatedNativeFn =
@@ -64,15 +64,39 @@ impl NativeFn {
 }
 
 
+/// Runs Blink functions on behalf of a native, on the caller's execution state
+pub trait FunctionCaller {
+    fn call_function(&mut self, func: ValueRef, args: Vec<ValueRef>) -> Result<ValueRef, BlinkError>;
+
+    /// Keep values alive for the GC until the native returns. Returns the slot of the first.
+    fn root_values(&mut self, values: &[ValueRef]) -> usize;
+
+    /// Replace the value in a slot returned by `root_values`
+    fn set_root(&mut self, slot: usize, value: ValueRef);
+
+    /// The module of the code that called the native
+    fn current_module(&self) -> u32;
+}
+
+/// A value rooted with `NativeContext::root_slot`
+#[derive(Clone, Copy, Debug)]
+pub struct RootSlot(Option<usize>);
+
 /// Lightweight context for native functions - provides safe access to VM operations
 /// without exposing execution state or environments
 pub struct NativeContext<'a> {
     vm: &'a Arc<BlinkVM>,
+    caller: Option<&'a mut dyn FunctionCaller>,
 }
 
 impl<'a> NativeContext<'a> {
     pub fn new(vm: &'a Arc<BlinkVM>) -> Self {
-        Self { vm }
+        Self { vm, caller: None }
+    }
+
+    /// Context for a native called from running code, which it can call back into
+    pub fn with_caller(vm: &'a Arc<BlinkVM>, caller: &'a mut dyn FunctionCaller) -> Self {
+        Self { vm, caller: Some(caller) }
     }
 
     pub fn get_pos(&self, value: ValueRef) -> Option<SourceRange> {
@@ -84,6 +108,14 @@ impl<'a> NativeContext<'a> {
         self.vm
     }
 
+    /// The module names are resolved in: the caller's, or core when there is none
+    pub fn current_module(&self) -> u32 {
+        match &self.caller {
+            Some(caller) => caller.current_module(),
+            None => self.vm.core_module.unwrap_or_default(),
+        }
+    }
+
     // === VALUE CREATION ===
     
     /// Create a number value
@@ -115,7 +147,7 @@ impl<'a> NativeContext<'a> {
     
     /// Allocate a vector value
     pub fn vector(&self, items: Vec<ValueRef>) -> ValueRef {
-        let object_ref = self.vm.alloc_vec(items, None);
+        let object_ref = self.vm.alloc_vec(items);
         ValueRef::Heap(GcPtr::new(object_ref))
     }
     
@@ -175,6 +207,61 @@ impl<'a> NativeContext<'a> {
         None
     }
 
+    // === CALLING FUNCTIONS ===
+
+    /// Check if a value can be called: a native, compiled function or closure,
+    /// or a keyword, map or set, which look keys up
+    pub fn is_callable(&self, value: ValueRef) -> bool {
+        match value {
+            ValueRef::Handle(_) => value.is_native_fn(),
+            ValueRef::Heap(gc_ptr) => matches!(
+                gc_ptr.type_tag(),
+                TypeTag::UserDefinedFunction
+                    | TypeTag::Closure
+                    | TypeTag::Map
+                    | TypeTag::Set
+                    | TypeTag::SortedMap
+                    | TypeTag::SortedSet
+            ),
+            _ => value.get_keyword().is_some(),
+        }
+    }
+
+    /// Call a Blink function and wait for its result.
+    /// A failed call, or one returning an error value, yields that error as `Err`
+    /// so natives can hand it straight back.
+    pub fn call(&mut self, func: ValueRef, args: Vec<ValueRef>) -> Result<ValueRef, ValueRef> {
+        let Some(caller) = self.caller.as_mut() else {
+            return Err(self.eval_error("Cannot call functions from this native context"));
+        };
+        match caller.call_function(func, args) {
+            Ok(value) if value.is_error() => Err(value),
+            Ok(value) => Ok(value),
+            Err(error) => Err(self.error(error)),
+        }
+    }
+
+    /// Keep values the native holds on to, such as items it allocated or results
+    /// it collects, alive across calls back into Blink. They stay rooted until the
+    /// native returns. Without a caller no Blink code runs, so there is nothing to do.
+    pub fn root(&mut self, values: &[ValueRef]) {
+        if let Some(caller) = self.caller.as_mut() {
+            caller.root_values(values);
+        }
+    }
+
+    /// Root a value the native keeps replacing, like the accumulator of a reduction,
+    /// so only its latest value is held
+    pub fn root_slot(&mut self, value: ValueRef) -> RootSlot {
+        RootSlot(self.caller.as_mut().map(|caller| caller.root_values(&[value])))
+    }
+
+    pub fn set_root_slot(&mut self, slot: RootSlot, value: ValueRef) {
+        if let (Some(caller), RootSlot(Some(index))) = (self.caller.as_mut(), slot) {
+            caller.set_root(index, value);
+        }
+    }
+
     // === ERROR HANDLING ===
 
     pub fn error(&self, error: BlinkError) -> ValueRef {
@@ -257,6 +344,9 @@ impl<'a> NativeContext<'a> {
     /// Get the length of a collection (list, vector, string, map, set)
     pub fn count(&self, value: ValueRef) -> Option<usize> {
         match value {
+            ValueRef::Heap(gc_ptr) if matches!(gc_ptr.type_tag(), TypeTag::SortedMap | TypeTag::SortedSet) => {
+                Some(self.vm.sorted_count(gc_ptr.0) as usize)
+            }
             ValueRef::Heap(gc_ptr) => {
                 match gc_ptr.to_heap_value() {
                     crate::value::HeapValue::List(items) => Some(items.len()),
@@ -276,6 +366,41 @@ impl<'a> NativeContext<'a> {
         self.count(value).map_or(false, |c| c == 0)
     }
     
+    /// Elements of any collection in iteration order: list and vector items, set
+    /// members, `[key value]` vectors for map entries and one-character strings.
+    /// Sorted maps and sets yield their entries in order.
+    /// nil is the empty sequence.
+    pub fn seq_items(&self, value: ValueRef) -> Option<Vec<ValueRef>> {
+        if self.is_nil(value) {
+            return Some(Vec::new());
+        }
+        let ValueRef::Heap(gc_ptr) = value else {
+            return None;
+        };
+        match gc_ptr.to_heap_value() {
+            crate::value::HeapValue::List(items) => Some(items),
+            crate::value::HeapValue::Vector(items) => Some(items),
+            crate::value::HeapValue::Set(set) => Some(set.to_vec()),
+            crate::value::HeapValue::Map(map) => Some(
+                map.iter()
+                    .map(|(key, value)| self.vector(vec![*key, *value]))
+                    .collect(),
+            ),
+            crate::value::HeapValue::SortedSet(_, items) => Some(items),
+            crate::value::HeapValue::SortedMap(_, entries) => Some(
+                entries.into_iter()
+                    .map(|(key, value)| self.vector(vec![key, value]))
+                    .collect(),
+            ),
+            crate::value::HeapValue::Str(s) => Some(
+                s.chars()
+                    .map(|c| self.string(c.encode_utf8(&mut [0; 4])))
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
     /// Get first element of a list/vector
     pub fn first(&self, value: ValueRef) -> ValueRef {
         if let Some(list) = value.get_list() {
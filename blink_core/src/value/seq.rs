@@ -0,0 +1,362 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+use mmtk::util::ObjectReference;
+
+use crate::runtime::{LazySeqState, NoGcSection, SeqGenerator, TypeTag};
+use crate::value::{GcPtr, NativeContext, ValueRef};
+
+// The seq protocol. Every collection can be walked one cell at a time with
+// `seq_step`, which yields the first item and the rest of the sequence, or None
+// once it is exhausted. Lazy seqs are realised here: the first step runs the
+// generator, caches the cell and later steps read the cache.
+
+// Bytes read from a file per realised chunk of `line-seq`
+const LINE_CHUNK_SIZE: usize = 64 * 1024;
+
+impl<'a> NativeContext<'a> {
+    pub fn is_lazy_seq(&self, value: ValueRef) -> bool {
+        value.heap_object(TypeTag::LazySeq).is_some()
+    }
+
+    /// A pending lazy seq whose cells are produced by `generator` from `source`
+    pub fn lazy_seq(&self, generator: SeqGenerator, source: ValueRef) -> ValueRef {
+        ValueRef::Heap(GcPtr::new(self.vm().alloc_lazy_seq(generator, source)))
+    }
+
+    /// A realised cell, e.g. `first` consed onto a lazy `rest`
+    pub fn seq_cell(&self, first: ValueRef, rest: ValueRef) -> ValueRef {
+        ValueRef::Heap(GcPtr::new(self.vm().alloc_seq_cell(first, rest)))
+    }
+
+    /// Split a seqable into its first item and the rest, or None when it is empty
+    pub fn seq_step(&mut self, coll: ValueRef) -> Result<Option<(ValueRef, ValueRef)>, ValueRef> {
+        if self.is_nil(coll) {
+            return Ok(None);
+        }
+        if let Some(seq) = coll.heap_object(TypeTag::LazySeq) {
+            return self.realize(seq);
+        }
+        // Concrete collections are walked through an index so each step is O(1)
+        let items = match coll.heap_object(TypeTag::Vector) {
+            Some(_) => coll,
+            None => {
+                let items = self
+                    .seq_items(coll)
+                    .ok_or_else(|| self.type_error("a collection", self.type_name(coll), "seq"))?;
+                self.vector(items)
+            }
+        };
+        self.step_items(items, 0)
+    }
+
+    /// Realise up to `n` items
+    pub fn seq_take(&mut self, coll: ValueRef, n: usize) -> Result<Vec<ValueRef>, ValueRef> {
+        let mut items = Vec::new();
+        let mut current = coll;
+        let current_root = self.root_slot(current);
+        while items.len() < n {
+            match self.seq_step(current)? {
+                Some((first, rest)) => {
+                    self.root(&[first]);
+                    items.push(first);
+                    current = rest;
+                    self.set_root_slot(current_root, current);
+                }
+                None => break,
+            }
+        }
+        Ok(items)
+    }
+
+    /// Realise every item. Does not return on an infinite seq.
+    pub fn seq_to_vec(&mut self, coll: ValueRef) -> Result<Vec<ValueRef>, ValueRef> {
+        if !self.is_lazy_seq(coll) {
+            return self
+                .seq_items(coll)
+                .ok_or_else(|| self.type_error("a collection", self.type_name(coll), "seq"));
+        }
+        self.seq_take(coll, usize::MAX)
+    }
+
+    /// Step past `n` items and return what remains, nil once exhausted
+    pub fn seq_drop(&mut self, coll: ValueRef, n: usize) -> Result<ValueRef, ValueRef> {
+        let mut current = coll;
+        let current_root = self.root_slot(current);
+        for _ in 0..n {
+            match self.seq_step(current)? {
+                Some((_, rest)) => {
+                    current = rest;
+                    self.set_root_slot(current_root, current);
+                }
+                None => return Ok(self.nil()),
+            }
+        }
+        Ok(current)
+    }
+
+    /// `=`: structural equality, except that lazy seqs, also inside lists and
+    /// vectors, are realised as far as needed and compared item by item with
+    /// lists, vectors and other seqs. Does not return on two equal infinite seqs.
+    pub fn values_equal(&mut self, a: ValueRef, b: ValueRef) -> Result<bool, ValueRef> {
+        if a == b {
+            return Ok(true);
+        }
+        let sequential = |value: &ValueRef| value.is_list() || value.is_vec();
+        let (a_lazy, b_lazy) = (self.is_lazy_seq(a), self.is_lazy_seq(b));
+        if a_lazy || b_lazy {
+            if !(a_lazy || sequential(&a)) || !(b_lazy || sequential(&b)) {
+                return Ok(false);
+            }
+            return self.seqs_equal(a, b);
+        }
+        // Unequal lists and vectors may still hold lazy seqs equal to their counterparts
+        let items = |value: ValueRef| value.get_list().or_else(|| value.get_vec());
+        match (items(a), items(b)) {
+            (Some(items), Some(other_items)) if items.len() == other_items.len() => {
+                for (item, other_item) in items.into_iter().zip(other_items) {
+                    if !self.values_equal(item, other_item)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn seqs_equal(&mut self, a: ValueRef, b: ValueRef) -> Result<bool, ValueRef> {
+        let (mut current, mut other_current) = (a, b);
+        let current_roots = [self.root_slot(current), self.root_slot(other_current)];
+        let first_roots = [self.root_slot(self.nil()), self.root_slot(self.nil())];
+        loop {
+            let step = self.seq_step(current)?;
+            if let Some((first, _)) = step {
+                self.set_root_slot(first_roots[0], first);
+            }
+            match (step, self.seq_step(other_current)?) {
+                (None, None) => return Ok(true),
+                (Some((first, rest)), Some((other_first, other_rest))) => {
+                    self.set_root_slot(first_roots[1], other_first);
+                    if !self.values_equal(first, other_first)? {
+                        return Ok(false);
+                    }
+                    (current, other_current) = (rest, other_rest);
+                    self.set_root_slot(current_roots[0], current);
+                    self.set_root_slot(current_roots[1], other_current);
+                }
+                _ => return Ok(false),
+            }
+        }
+    }
+
+    fn realize(&mut self, seq: ObjectReference) -> Result<Option<(ValueRef, ValueRef)>, ValueRef> {
+        match self.vm().lazy_seq_state(seq) {
+            LazySeqState::Cell(first, rest) => Ok(Some((first, rest))),
+            LazySeqState::Empty => Ok(None),
+            LazySeqState::Pending(generator, source) => {
+                let cell = self.generate(generator, source)?;
+                self.vm().lazy_seq_realize_as(seq, cell);
+                Ok(cell)
+            }
+        }
+    }
+
+    fn step_items(&mut self, items: ValueRef, index: u32) -> Result<Option<(ValueRef, ValueRef)>, ValueRef> {
+        let vector = items.heap_object(TypeTag::Vector).expect("Items generator over a vector");
+        let vm = self.vm().clone();
+        if index >= vm.vector_get_length(vector) {
+            return Ok(None);
+        }
+        let first = vm
+            .vector_get_at(vector, index)
+            .map_err(|message| self.eval_error(&message))?;
+        let state = self.vector(vec![items, self.number((index + 1) as f64)]);
+        Ok(Some((first, self.lazy_seq(SeqGenerator::Items, state))))
+    }
+
+    fn generate(&mut self, generator: SeqGenerator, source: ValueRef) -> Result<Option<(ValueRef, ValueRef)>, ValueRef> {
+        if generator == SeqGenerator::Thunk {
+            // The thunk returns any seqable; its first step becomes this cell
+            let body = self.call(source, vec![])?;
+            self.root(&[body]);
+            return self.seq_step(body);
+        }
+
+        let state = source.get_vec().unwrap_or_default();
+        let number = |index: usize| state.get(index).and_then(|value| value.get_number());
+        match generator {
+            SeqGenerator::Thunk => unreachable!(),
+            SeqGenerator::Range => {
+                let (start, step) = (number(0).unwrap_or(0.0), number(2).unwrap_or(1.0));
+                let done = match number(1) {
+                    Some(end) => (step > 0.0 && start >= end) || (step < 0.0 && start <= end),
+                    None => false,
+                };
+                if done {
+                    return Ok(None);
+                }
+                let next = self.vector(vec![self.number(start + step), state[1], state[2]]);
+                Ok(Some((self.number(start), self.lazy_seq(SeqGenerator::Range, next))))
+            }
+            SeqGenerator::Iterate => {
+                let value = self.call(state[0], vec![state[1]])?;
+                self.root(&[value]);
+                let next = self.vector(vec![state[0], value]);
+                Ok(Some((value, self.lazy_seq(SeqGenerator::Iterate, next))))
+            }
+            SeqGenerator::Repeat => {
+                let remaining = match number(1) {
+                    Some(n) if n <= 0.0 => return Ok(None),
+                    Some(n) => self.number(n - 1.0),
+                    None => self.nil(),
+                };
+                let next = self.vector(vec![state[0], remaining]);
+                Ok(Some((state[0], self.lazy_seq(SeqGenerator::Repeat, next))))
+            }
+            SeqGenerator::Cycle => {
+                let items = state[0].get_vec().unwrap_or_default();
+                if items.is_empty() {
+                    return Ok(None);
+                }
+                let index = number(1).unwrap_or(0.0) as usize % items.len();
+                let next = self.vector(vec![state[0], self.number(((index + 1) % items.len()) as f64)]);
+                Ok(Some((items[index], self.lazy_seq(SeqGenerator::Cycle, next))))
+            }
+            SeqGenerator::Concat => {
+                for (i, seqable) in state.iter().enumerate() {
+                    if let Some((first, rest)) = self.seq_step(*seqable)? {
+                        let mut remaining = vec![rest];
+                        remaining.extend_from_slice(&state[i + 1..]);
+                        let next = self.vector(remaining);
+                        return Ok(Some((first, self.lazy_seq(SeqGenerator::Concat, next))));
+                    }
+                }
+                Ok(None)
+            }
+            SeqGenerator::Lines => {
+                let path = state[0].get_string().unwrap_or_default();
+                let offset = number(1).unwrap_or(0.0) as u64;
+                self.read_lines(&path, offset)
+            }
+            SeqGenerator::Items => self.step_items(state[0], number(1).unwrap_or(0.0) as u32),
+            SeqGenerator::Map => {
+                let seqables = state[1].get_vec().unwrap_or_default();
+                let mut firsts = Vec::with_capacity(seqables.len());
+                let mut rests = Vec::with_capacity(seqables.len());
+                for seqable in seqables {
+                    match self.seq_step(seqable)? {
+                        Some((first, rest)) => {
+                            // Held while the other seqables step and f runs
+                            self.root(&[first, rest]);
+                            firsts.push(first);
+                            rests.push(rest);
+                        }
+                        None => return Ok(None),
+                    }
+                }
+                let value = self.call(state[0], firsts)?;
+                let rests = self.vector(rests);
+                let next = self.vector(vec![state[0], rests]);
+                Ok(Some((value, self.lazy_seq(SeqGenerator::Map, next))))
+            }
+            SeqGenerator::Filter => {
+                let mut current = state[1];
+                let current_root = self.root_slot(current);
+                while let Some((item, rest)) = self.seq_step(current)? {
+                    self.set_root_slot(current_root, rest);
+                    if self.call(state[0], vec![item])?.is_truthy() {
+                        let next = self.vector(vec![state[0], rest]);
+                        return Ok(Some((item, self.lazy_seq(SeqGenerator::Filter, next))));
+                    }
+                    current = rest;
+                }
+                Ok(None)
+            }
+            SeqGenerator::Transduce => {
+                let mut stages = self.transducer_stages(state[0], "sequence")?;
+                let remaining = state[2].get_vec().unwrap_or_default();
+                for (stage, count) in stages.iter_mut().zip(remaining) {
+                    stage.remaining = count.get_number().unwrap_or(0.0) as usize;
+                }
+                if self.transducer_exhausted(&stages) {
+                    return Ok(None);
+                }
+                let mut current = state[1];
+                let current_root = self.root_slot(current);
+                let value_root = self.root_slot(self.nil());
+                while let Some((item, rest)) = self.seq_step(current)? {
+                    self.set_root_slot(current_root, rest);
+                    let (output, finished) = self.transducer_step(&mut stages, value_root, item)?;
+                    if let Some(output) = output {
+                        if finished {
+                            return Ok(Some((output, self.nil())));
+                        }
+                        let counts = stages.iter().map(|stage| self.number(stage.remaining as f64)).collect();
+                        let counts = self.vector(counts);
+                        let next = self.vector(vec![state[0], rest, counts]);
+                        return Ok(Some((output, self.lazy_seq(SeqGenerator::Transduce, next))));
+                    }
+                    if finished {
+                        return Ok(None);
+                    }
+                    current = rest;
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    // Read the lines of one chunk of a file starting at `offset`. The lines are
+    // chained as realised cells, and the last one's rest is a pending read of the
+    // next chunk, so a large file is never held in memory at once.
+    fn read_lines(&mut self, path: &str, offset: u64) -> Result<Option<(ValueRef, ValueRef)>, ValueRef> {
+        let io_error = |ctx: &Self, error: std::io::Error| ctx.eval_error(&format!("line-seq: {}: {}", path, error));
+        let mut file = File::open(path).map_err(|error| io_error(self, error))?;
+        file.seek(SeekFrom::Start(offset)).map_err(|error| io_error(self, error))?;
+
+        // Read whole chunks until at least one complete line is buffered
+        let mut buffer = Vec::new();
+        let mut at_eof = false;
+        loop {
+            let mut chunk = vec![0; LINE_CHUNK_SIZE];
+            let read = file.read(&mut chunk).map_err(|error| io_error(self, error))?;
+            buffer.extend_from_slice(&chunk[..read]);
+            if read == 0 {
+                at_eof = true;
+                break;
+            }
+            if chunk[..read].contains(&b'\n') {
+                break;
+            }
+        }
+        if buffer.is_empty() {
+            return Ok(None);
+        }
+
+        // A trailing partial line is left for the next chunk unless the file ended
+        let consumed = match buffer.iter().rposition(|byte| *byte == b'\n') {
+            Some(newline) if !at_eof => newline + 1,
+            _ => buffer.len(),
+        };
+        let text = String::from_utf8_lossy(&buffer[..consumed]);
+        let lines: Vec<&str> = text
+            .split_terminator('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+            .collect();
+
+        // The cells are only held here until the caller caches them, and building
+        // them runs no Blink code
+        let _no_gc = NoGcSection::enter();
+        let mut rest = if at_eof {
+            self.nil()
+        } else {
+            let next = self.vector(vec![self.string(path), self.number((offset + consumed as u64) as f64)]);
+            self.lazy_seq(SeqGenerator::Lines, next)
+        };
+        for line in lines[1..].iter().rev() {
+            rest = self.seq_cell(self.string(line), rest);
+        }
+        Ok(Some((self.string(lines[0]), rest)))
+    }
+}
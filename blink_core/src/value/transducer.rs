@@ -0,0 +1,131 @@
+use crate::runtime::{TransducerKind, TypeTag};
+use crate::value::{GcPtr, NativeContext, RootSlot, ValueRef};
+
+// Running a transducer pipeline. Each input item is pushed through every stage
+// in turn and either comes out the end or is dropped on the way, so a pipeline
+// makes one pass over its input and builds no intermediate collections.
+
+/// One stage of a pipeline with its running state
+#[derive(Clone, Copy, Debug)]
+pub struct TransducerStage {
+    pub kind: TransducerKind,
+    pub arg: ValueRef,
+    /// Items still to take or to drop
+    pub remaining: usize,
+}
+
+impl<'a> NativeContext<'a> {
+    pub fn is_transducer(&self, value: ValueRef) -> bool {
+        value.heap_object(TypeTag::Transducer).is_some()
+    }
+
+    pub fn transducer(&self, kind: TransducerKind, arg: ValueRef) -> ValueRef {
+        ValueRef::Heap(GcPtr::new(self.vm().alloc_transducer(kind, arg)))
+    }
+
+    /// The stages of a transducer in order, with fresh state
+    pub fn transducer_stages(&self, xform: ValueRef, function_name: &str) -> Result<Vec<TransducerStage>, ValueRef> {
+        let Some(transducer) = xform.heap_object(TypeTag::Transducer) else {
+            return Err(self.type_error("a transducer", self.type_name(xform), function_name));
+        };
+        let vm = self.vm();
+        let kind = vm.transducer_kind(transducer);
+        let arg = vm.transducer_arg(transducer);
+        if kind == TransducerKind::Comp {
+            let mut stages = Vec::new();
+            for stage in arg.get_vec().unwrap_or_default() {
+                stages.extend(self.transducer_stages(stage, function_name)?);
+            }
+            return Ok(stages);
+        }
+        let remaining = self.get_number(arg).map_or(0, |n| n.max(0.0) as usize);
+        Ok(vec![TransducerStage { kind, arg, remaining }])
+    }
+
+    /// A `take` stage that has run out ends the pipeline before any more input is read
+    pub fn transducer_exhausted(&self, stages: &[TransducerStage]) -> bool {
+        stages.iter().any(|stage| stage.kind == TransducerKind::Take && stage.remaining == 0)
+    }
+
+    /// Push one item through the stages. Returns the item that comes out, if
+    /// any, and whether the pipeline has finished and should read no more input.
+    /// `value_root` holds the item as each stage replaces it, while the later
+    /// stages call back into Blink; the caller roots it once per pipeline.
+    pub fn transducer_step(&mut self, stages: &mut [TransducerStage], value_root: RootSlot, item: ValueRef) -> Result<(Option<ValueRef>, bool), ValueRef> {
+        let mut value = item;
+        self.set_root_slot(value_root, value);
+        let mut finished = false;
+        for stage in stages.iter_mut() {
+            match stage.kind {
+                TransducerKind::Map => {
+                    value = self.call(stage.arg, vec![value])?;
+                    self.set_root_slot(value_root, value);
+                }
+                TransducerKind::Filter => {
+                    if !self.call(stage.arg, vec![value])?.is_truthy() {
+                        return Ok((None, finished));
+                    }
+                }
+                TransducerKind::Take => {
+                    if stage.remaining == 0 {
+                        return Ok((None, true));
+                    }
+                    stage.remaining -= 1;
+                    finished |= stage.remaining == 0;
+                }
+                TransducerKind::Drop => {
+                    if stage.remaining > 0 {
+                        stage.remaining -= 1;
+                        return Ok((None, finished));
+                    }
+                }
+                TransducerKind::Comp => unreachable!("stages are flattened"),
+            }
+        }
+        Ok((Some(value), finished))
+    }
+
+    /// Run `coll` through the stages, handing each item that comes out to `emit`.
+    /// Concrete collections are copied to a rooted list of their items first;
+    /// lazy seqs are realised only as far as the pipeline reads.
+    pub fn transduce_each<F>(&mut self, stages: &mut [TransducerStage], coll: ValueRef, function_name: &str, mut emit: F) -> Result<(), ValueRef>
+    where
+        F: FnMut(&mut Self, ValueRef) -> Result<(), ValueRef>,
+    {
+        if self.transducer_exhausted(stages) {
+            return Ok(());
+        }
+        let value_root = self.root_slot(self.nil());
+        if self.is_lazy_seq(coll) {
+            let mut current = coll;
+            let current_root = self.root_slot(current);
+            while let Some((item, rest)) = self.seq_step(current)? {
+                self.set_root_slot(current_root, rest);
+                let (output, finished) = self.transducer_step(stages, value_root, item)?;
+                if let Some(output) = output {
+                    emit(self, output)?;
+                }
+                if finished {
+                    break;
+                }
+                current = rest;
+            }
+            return Ok(());
+        }
+
+        let items = self
+            .seq_items(coll)
+            .ok_or_else(|| self.type_error("a collection", self.type_name(coll), function_name))?;
+        self.root(&items);
+        for item in items {
+            let (output, finished) = self.transducer_step(stages, value_root, item)?;
+            if let Some(output) = output {
+                emit(self, output)?;
+            }
+            if finished {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 use core::fmt;
 use std::{
     fmt::Display,
-    hash::{Hash, Hasher},
+    hash::{BuildHasher, BuildHasherDefault, DefaultHasher, Hash, Hasher},
 };
 
 use mmtk::util::ObjectReference;
@@ -62,9 +62,35 @@ impl Display for ValueRef {
     }
 }
 
+// Equality is structural and `=` uses it:
+// - numbers compare numerically, so 0.0 equals -0.0; NaN equals NaN, which
+//   keeps equality reflexive and lets NaN be a map key
+// - lists and vectors are equal when their items are, whichever kind each is
+// - maps, and sets, are equal with the same entries, hash or sorted and
+//   whatever their order
+// - transducers are equal when they are the same kind of stage with equal arguments
+// - functions, closures, macros, errors and handles are equal only to themselves.
+//   Lazy seqs are too here, since comparing them may run code: `=` realises
+//   them and compares them item by item, see `NativeContext::values_equal`
+// `identical?` compares representations instead. Hashing agrees with equality:
+// values that are equal hash alike.
+
+/// Hasher for `BlinkHashMap` and `BlinkHashSet`, the same one `value_hash` uses
+pub type ValueHashBuilder = BuildHasherDefault<DefaultHasher>;
+
+/// The hash used for map keys and set members. It has fixed keys, so a value
+/// hashes the same in every map and set and in every run.
+pub fn value_hash(value: &ValueRef) -> u64 {
+    ValueHashBuilder::default().hash_one(value)
+}
+
 impl Hash for ValueRef {
     fn hash<H: Hasher>(&self, state: &mut H) {
         match self {
+            // -0.0 hashes as 0.0; NaN is packed canonically already
+            ValueRef::Immediate(packed) if is_number(*packed) && f64::from_bits(*packed) == 0.0 => {
+                0u64.hash(state)
+            }
             ValueRef::Immediate(packed) => packed.hash(state),
             ValueRef::Heap(gc_ptr) => gc_ptr.hash(state),
             ValueRef::Handle(n) => {
@@ -79,7 +105,12 @@ impl PartialEq for ValueRef {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (ValueRef::Immediate(packed), ValueRef::Immediate(other_packed)) => {
-                packed == other_packed
+                if is_number(*packed) && is_number(*other_packed) {
+                    // NaN is packed canonically, so NaNs have equal bits
+                    packed == other_packed || f64::from_bits(*packed) == f64::from_bits(*other_packed)
+                } else {
+                    packed == other_packed
+                }
             }
             (ValueRef::Heap(gc_ptr), ValueRef::Heap(other_gc_ptr)) => gc_ptr == other_gc_ptr,
             (ValueRef::Handle(n), ValueRef::Handle(other_n)) => n == other_n,
@@ -90,6 +121,19 @@ impl PartialEq for ValueRef {
 
 impl Eq for ValueRef {}
 
+impl ValueRef {
+    /// The same representation: bit-identical immediates, so NaN is identical
+    /// to itself and -0.0 is not identical to 0.0, or the same heap object or handle
+    pub fn identical(&self, other: &ValueRef) -> bool {
+        match (self, other) {
+            (ValueRef::Immediate(packed), ValueRef::Immediate(other_packed)) => packed == other_packed,
+            (ValueRef::Heap(gc_ptr), ValueRef::Heap(other_gc_ptr)) => gc_ptr.0 == other_gc_ptr.0,
+            (ValueRef::Handle(n), ValueRef::Handle(other_n)) => n == other_n,
+            _ => false,
+        }
+    }
+}
+
 impl ValueRef {
     // ------------------------------------------------------------
     // Constructors
@@ -224,6 +268,14 @@ impl ValueRef {
         }
     }
 
+    /// The heap object behind this value, if it has the given type
+    pub fn heap_object(&self, type_tag: TypeTag) -> Option<ObjectReference> {
+        match self {
+            ValueRef::Heap(gc_ptr) if gc_ptr.type_tag() == type_tag => Some(gc_ptr.0),
+            _ => None,
+        }
+    }
+
     pub fn is_map(&self) -> bool {
         match self {
             ValueRef::Heap(gc_ptr) => gc_ptr.type_tag() == TypeTag::Map,
@@ -484,3 +536,190 @@ impl ValueRef {
 
 
 }
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::test_support::vm;
+
+    fn heap(object: ObjectReference) -> ValueRef {
+        ValueRef::Heap(GcPtr::new(object))
+    }
+
+    // A value described independently of how it is represented
+    #[derive(Clone, Debug)]
+    enum Shape {
+        Number(f64),
+        Keyword(u32),
+        Bool(bool),
+        Nil,
+        Str(String),
+        Seq(Vec<Shape>),
+        // Keys and members are distinct whole numbers, so the same shape can
+        // also be built as a sorted map or set
+        Map(Vec<(u32, Shape)>),
+        Set(Vec<u32>),
+    }
+
+    fn number() -> impl Strategy<Value = f64> {
+        prop_oneof![
+            Just(0.0),
+            Just(-0.0),
+            Just(f64::INFINITY),
+            Just(f64::NEG_INFINITY),
+            Just(f64::NAN),
+            -5e5..5e5,
+            (0..10u32).prop_map(f64::from),
+        ]
+    }
+
+    fn distinct_ids() -> impl Strategy<Value = Vec<u32>> {
+        prop::collection::btree_set(0..20u32, 0..5).prop_map(|ids| ids.into_iter().collect())
+    }
+
+    fn shape() -> impl Strategy<Value = Shape> {
+        let leaf = prop_oneof![
+            number().prop_map(Shape::Number),
+            (0..4u32).prop_map(Shape::Keyword),
+            any::<bool>().prop_map(Shape::Bool),
+            Just(Shape::Nil),
+            prop::sample::select(vec!["", "a", "ab", "b"]).prop_map(|s| Shape::Str(s.to_string())),
+            distinct_ids().prop_map(Shape::Set),
+        ];
+        leaf.prop_recursive(3, 32, 4, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..4).prop_map(Shape::Seq),
+                (distinct_ids(), prop::collection::vec(inner, 4))
+                    .prop_map(|(keys, values)| Shape::Map(keys.into_iter().zip(values).collect())),
+            ]
+        })
+    }
+
+    // The representation choices `build` makes, drawn with the shape so a
+    // failing case shrinks to the plainest representations too
+    struct Picks(std::vec::IntoIter<u32>);
+
+    impl Picks {
+        fn below(&mut self, n: u32) -> u32 {
+            self.0.next().map_or(0, |pick| pick % n)
+        }
+    }
+
+    fn picks() -> impl Strategy<Value = Vec<u32>> {
+        prop::collection::vec(any::<u32>(), 0..64)
+    }
+
+    fn shuffled<T>(picks: &mut Picks, mut items: Vec<T>) -> Vec<T> {
+        for i in (1..items.len()).rev() {
+            items.swap(i, picks.below(i as u32 + 1) as usize);
+        }
+        items
+    }
+
+    // Build a value for the shape, choosing among the representations that
+    // should all be equal: 0 or -0, list or vector, hash or sorted collection,
+    // and any insertion order
+    fn build(picks: &mut Picks, shape: &Shape) -> ValueRef {
+        let vm = vm();
+        match shape {
+            Shape::Number(n) if *n == 0.0 && picks.below(2) == 1 => ValueRef::number(-n),
+            Shape::Number(n) => ValueRef::number(*n),
+            Shape::Keyword(id) => ValueRef::keyword(*id),
+            Shape::Bool(b) => ValueRef::boolean(*b),
+            Shape::Nil => ValueRef::nil(),
+            Shape::Str(s) => heap(vm.alloc_str(s)),
+            Shape::Seq(items) => {
+                let items = items.iter().map(|item| build(picks, item)).collect();
+                if picks.below(2) == 1 {
+                    heap(vm.alloc_list_from_items(items))
+                } else {
+                    heap(vm.alloc_vec(items))
+                }
+            }
+            Shape::Map(entries) => {
+                let entries: Vec<(ValueRef, ValueRef)> = entries
+                    .iter()
+                    .map(|(key, value)| (ValueRef::number(*key as f64), build(picks, value)))
+                    .collect();
+                if picks.below(2) == 1 {
+                    heap(vm.alloc_sorted_from_entries(TypeTag::SortedMap, ValueRef::nil(), &entries))
+                } else {
+                    heap(vm.alloc_map(shuffled(picks, entries)))
+                }
+            }
+            Shape::Set(members) => {
+                let members: Vec<ValueRef> = members.iter().map(|m| ValueRef::number(*m as f64)).collect();
+                if picks.below(2) == 1 {
+                    let entries: Vec<_> = members.iter().map(|m| (*m, *m)).collect();
+                    heap(vm.alloc_sorted_from_entries(TypeTag::SortedSet, ValueRef::nil(), &entries))
+                } else {
+                    heap(vm.alloc_set(shuffled(picks, members)))
+                }
+            }
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(256))]
+
+        #[test]
+        fn prop_equal_values_hash_alike(shape in shape(), first in picks(), second in picks()) {
+            let a = build(&mut Picks(first.into_iter()), &shape);
+            let b = build(&mut Picks(second.into_iter()), &shape);
+            prop_assert!(a == b, "{} should equal {}", a, b);
+            prop_assert!(b == a, "{} should equal {}", b, a);
+            prop_assert_eq!(value_hash(&a), value_hash(&b), "{} and {} hash differently", a, b);
+        }
+
+        #[test]
+        fn prop_equality_is_symmetric(first in shape(), second in shape(), picks in picks()) {
+            let mut picks = Picks(picks.into_iter());
+            let a = build(&mut picks, &first);
+            let b = build(&mut picks, &second);
+            prop_assert_eq!(a == b, b == a, "{} and {}", a, b);
+            if a == b {
+                prop_assert_eq!(value_hash(&a), value_hash(&b), "{} and {} hash differently", a, b);
+            }
+        }
+
+        #[test]
+        fn prop_identical_numbers(n in number()) {
+            let a = ValueRef::number(n);
+            prop_assert!(a.identical(&ValueRef::number(n)));
+            prop_assert!(a == a);
+            prop_assert!(a.is_number());
+        }
+
+        #[test]
+        fn prop_hash_set_holds_one_of_equal_values(shape in shape(), first in picks(), second in picks()) {
+            let mut set = BlinkHashSet::new();
+            prop_assert!(set.insert(build(&mut Picks(first.into_iter()), &shape)));
+            let twin = build(&mut Picks(second.into_iter()), &shape);
+            prop_assert!(set.contains(&twin));
+            prop_assert!(!set.insert(twin));
+            prop_assert_eq!(set.len(), 1);
+        }
+    }
+
+    #[test]
+    fn zero_equals_negative_zero_without_being_identical() {
+        let zero = ValueRef::number(0.0);
+        let negative_zero = ValueRef::number(-0.0);
+        assert!(zero == negative_zero);
+        assert!(!zero.identical(&negative_zero));
+        assert_eq!(value_hash(&zero), value_hash(&negative_zero));
+    }
+
+    #[test]
+    fn nan_is_equal_to_itself_and_usable_as_a_key() {
+        let nan = ValueRef::number(f64::NAN);
+        assert!(nan == ValueRef::number(-f64::NAN));
+        assert!(nan != ValueRef::number(0.0));
+        let mut set = BlinkHashSet::new();
+        assert!(set.insert(nan));
+        assert!(set.contains(&ValueRef::number(f64::NAN)));
+        assert!(!set.insert(ValueRef::number(0.0 / 0.0)));
+    }
+}
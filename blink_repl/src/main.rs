@@ -1,7 +1,31 @@
-use blink_core::repl::start_repl;
+use blink_core::project::{find_project_root, resolve_project, LOCK_FILE, MANIFEST_FILE};
+use blink_core::repl::{start_repl, ReplOptions};
 
 #[tokio::main]
 async fn main() {
+    if std::env::args().any(|arg| arg == "--resolve") {
+        match resolve() {
+            Ok(message) => println!("{}", message),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     println!("Blink REPL");
-    start_repl().await;
+    let options = ReplOptions {
+        watch: std::env::args().any(|arg| arg == "--watch"),
+    };
+    start_repl(options).await;
+}
+
+// Resolve the project of the working directory and write its blink.lock
+fn resolve() -> Result<String, String> {
+    let cwd = std::env::current_dir().map_err(|e| format!("Could not read the working directory: {}", e))?;
+    let root = find_project_root(&cwd)
+        .ok_or_else(|| format!("No {} in {} or its parents", MANIFEST_FILE, cwd.display()))?;
+    let project = resolve_project(&root)?;
+    Ok(format!("Resolved {} packages into {}", project.packages.len() - 1, root.join(LOCK_FILE).display()))
 }
@@ -0,0 +1,785 @@
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+};
+
+use anyhow::{anyhow, Context, Result};
+use blink_core::{
+    error::StackFrame,
+    module::{Module, SerializedModuleSource},
+    parser::parse_all,
+    runtime::{
+        BlinkVM, Breakpoint, BreakpointId, CancellationToken, DebugEvent, Debugger,
+        ExecutionContext, PauseReason, StepMode,
+    },
+    value::ParsedValueWithPos,
+};
+use serde_json::{json, Value};
+use tokio::{
+    io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    sync::{mpsc::{unbounded_channel, UnboundedSender}, oneshot},
+};
+
+use crate::dap_messages::DapMessage;
+
+// The debuggee runs on one thread, so every request refers to the same thread id
+const THREAD_ID: u64 = 1;
+
+// Numbers the debug sessions, whose programs load into modules of their own
+static NEXT_SESSION: AtomicU64 = AtomicU64::new(1);
+
+/// Commands sent from the adapter to the thread running the program.
+/// The thread only reads them while the program is paused or not yet started.
+enum DebugCommand {
+    SetBreakpoints {
+        file: String,
+        lines: Vec<usize>,
+        reply: oneshot::Sender<Vec<BreakpointId>>,
+    },
+    SetFunctionBreakpoints {
+        names: Vec<String>,
+        reply: oneshot::Sender<Vec<BreakpointId>>,
+    },
+    StackTrace {
+        reply: oneshot::Sender<Vec<StackFrame>>,
+    },
+    Variables {
+        frame: usize,
+        reply: oneshot::Sender<Vec<(String, String)>>,
+    },
+    Evaluate {
+        frame: usize,
+        expression: String,
+        reply: oneshot::Sender<Result<String, String>>,
+    },
+    Resume(StepMode),
+    Disconnect,
+}
+
+struct LaunchConfig {
+    program: String,
+    stop_on_entry: bool,
+}
+
+/// Debug Adapter Protocol endpoint. Each connection debugs one program file.
+pub struct DapHandler<R> {
+    reader: BufReader<R>,
+    outgoing: UnboundedSender<DapMessage>,
+    vm: Arc<BlinkVM>,
+    commands: Option<mpsc::Sender<DebugCommand>>,
+    // Whether the debuggee thread is reading commands: before the program starts and while it is paused
+    stopped: Arc<AtomicBool>,
+    cancel_token: CancellationToken,
+}
+
+impl<R> DapHandler<R>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    pub fn new<W>(reader: BufReader<R>, mut writer: W, vm: Arc<BlinkVM>) -> Self
+    where
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        // Responses and events from the debuggee thread share one writer,
+        // which numbers them in the order they go out
+        let (outgoing, mut pending) = unbounded_channel::<DapMessage>();
+        tokio::spawn(async move {
+            let mut seq = 0;
+            while let Some(mut message) = pending.recv().await {
+                seq += 1;
+                message.seq = seq;
+                if let Err(e) = write_message(&mut writer, &message).await {
+                    eprintln!("DAP write error: {:?}", e);
+                    break;
+                }
+            }
+        });
+
+        Self {
+            reader,
+            outgoing,
+            vm,
+            commands: None,
+            stopped: Arc::new(AtomicBool::new(false)),
+            cancel_token: CancellationToken::new(),
+        }
+    }
+
+    pub async fn process(&mut self) -> Result<()> {
+        loop {
+            let Some(request) = self.read_message().await? else {
+                return Ok(()); // Client disconnected
+            };
+            if request.message_type != "request" {
+                continue;
+            }
+
+            let command = request.command.clone().unwrap_or_default();
+            let arguments = request.arguments.clone().unwrap_or(Value::Null);
+
+            let result = match command.as_str() {
+                "initialize" => Ok(Some(json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsFunctionBreakpoints": true,
+                    "supportsEvaluateForHovers": true,
+                }))),
+                "launch" => self.handle_launch(&arguments),
+                "setBreakpoints" => self.handle_set_breakpoints(&arguments).await,
+                "setFunctionBreakpoints" => self.handle_set_function_breakpoints(&arguments).await,
+                "configurationDone" | "next" | "stepIn" | "stepOut" => self.stopped_program().map(|_| None),
+                "continue" => self
+                    .stopped_program()
+                    .map(|_| Some(json!({ "allThreadsContinued": true }))),
+                "pause" => {
+                    // A stopped program has nothing to interrupt
+                    if !self.stopped.load(Ordering::SeqCst) {
+                        self.cancel_token.cancel();
+                    }
+                    Ok(None)
+                }
+                "threads" => Ok(Some(json!({
+                    "threads": [{ "id": THREAD_ID, "name": "main" }]
+                }))),
+                "stackTrace" => self.handle_stack_trace().await,
+                "scopes" => {
+                    let frame_id = arguments.get("frameId").and_then(Value::as_u64).unwrap_or(0);
+                    // One scope per frame; variable references are frame ids shifted by one
+                    Ok(Some(json!({
+                        "scopes": [{
+                            "name": "Locals",
+                            "variablesReference": frame_id + 1,
+                            "expensive": false,
+                        }]
+                    })))
+                }
+                "variables" => self.handle_variables(&arguments).await,
+                "evaluate" => self.handle_evaluate(&arguments).await,
+                "disconnect" | "terminate" => {
+                    self.cancel_token.cancel();
+                    self.send_command(DebugCommand::Disconnect).ok();
+                    self.commands = None;
+                    self.send(DapMessage::response(&request, None));
+                    return Ok(());
+                }
+                other => Err(anyhow!("Unsupported request: {}", other)),
+            };
+
+            let succeeded = result.is_ok();
+            match result {
+                Ok(body) => self.send(DapMessage::response(&request, body)),
+                Err(e) => self.send(DapMessage::error_response(&request, e.to_string())),
+            }
+
+            // Resume only after responding, so the response precedes the next stopped event
+            let resume_mode = match command.as_str() {
+                "configurationDone" | "continue" => Some(StepMode::Run),
+                "next" => Some(StepMode::StepOver),
+                "stepIn" => Some(StepMode::StepIn),
+                "stepOut" => Some(StepMode::StepOut),
+                _ => None,
+            };
+            if let (Some(mode), true) = (resume_mode, succeeded) {
+                self.stopped.store(false, Ordering::SeqCst);
+                self.send_command(DebugCommand::Resume(mode)).ok();
+            }
+
+            // Breakpoints may only be set once the program is loaded
+            if command == "launch" && self.commands.is_some() {
+                self.send(DapMessage::event("initialized", None));
+            }
+        }
+    }
+
+    fn send(&self, message: DapMessage) {
+        self.outgoing.send(message).ok();
+    }
+
+    fn launched(&self) -> Result<&mpsc::Sender<DebugCommand>> {
+        self.commands.as_ref().context("No program has been launched")
+    }
+
+    /// The debuggee thread's commands, if it is stopped and reading them
+    fn stopped_program(&self) -> Result<&mpsc::Sender<DebugCommand>> {
+        let commands = self.launched()?;
+        if !self.stopped.load(Ordering::SeqCst) {
+            return Err(anyhow!("The program is running; pause it first"));
+        }
+        Ok(commands)
+    }
+
+    fn send_command(&self, command: DebugCommand) -> Result<()> {
+        self.launched()?
+            .send(command)
+            .map_err(|_| anyhow!("The debugged program has exited"))
+    }
+
+    /// Ask the debuggee thread for something it can only answer while the program is stopped
+    async fn request<T>(&self, make: impl FnOnce(oneshot::Sender<T>) -> DebugCommand) -> Result<T> {
+        let (reply, response) = oneshot::channel();
+        self.stopped_program()?
+            .send(make(reply))
+            .map_err(|_| anyhow!("The debugged program has exited"))?;
+        response
+            .await
+            .map_err(|_| anyhow!("The debugged program has exited"))
+    }
+
+    fn handle_launch(&mut self, arguments: &Value) -> Result<Option<Value>> {
+        if self.commands.is_some() {
+            return Err(anyhow!("A program is already running"));
+        }
+        let program = arguments
+            .get("program")
+            .and_then(Value::as_str)
+            .context("launch requires a program path")?
+            .to_string();
+        let stop_on_entry = arguments
+            .get("stopOnEntry")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+
+        let (commands, command_receiver) = mpsc::channel();
+        let launch = LaunchConfig { program, stop_on_entry };
+        let vm = self.vm.clone();
+        let outgoing = self.outgoing.clone();
+        let cancel_token = self.cancel_token.clone();
+        let stopped = self.stopped.clone();
+        stopped.store(true, Ordering::SeqCst);
+        thread::spawn(move || run_debuggee(vm, launch, command_receiver, outgoing, stopped, cancel_token));
+
+        self.commands = Some(commands);
+        Ok(None)
+    }
+
+    async fn handle_set_breakpoints(&self, arguments: &Value) -> Result<Option<Value>> {
+        let file = arguments
+            .pointer("/source/path")
+            .and_then(Value::as_str)
+            .context("setBreakpoints requires a source path")?
+            .to_string();
+        let lines: Vec<usize> = arguments
+            .get("breakpoints")
+            .and_then(Value::as_array)
+            .map(|breakpoints| {
+                breakpoints
+                    .iter()
+                    .filter_map(|bp| bp.get("line").and_then(Value::as_u64))
+                    .map(|line| line as usize)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let ids = self
+            .request(|reply| DebugCommand::SetBreakpoints { file, lines: lines.clone(), reply })
+            .await?;
+        let breakpoints: Vec<Value> = ids
+            .iter()
+            .zip(&lines)
+            .map(|(id, line)| json!({ "id": id, "verified": true, "line": line }))
+            .collect();
+        Ok(Some(json!({ "breakpoints": breakpoints })))
+    }
+
+    async fn handle_set_function_breakpoints(&self, arguments: &Value) -> Result<Option<Value>> {
+        let names: Vec<String> = arguments
+            .get("breakpoints")
+            .and_then(Value::as_array)
+            .map(|breakpoints| {
+                breakpoints
+                    .iter()
+                    .filter_map(|bp| bp.get("name").and_then(Value::as_str))
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let ids = self
+            .request(|reply| DebugCommand::SetFunctionBreakpoints { names, reply })
+            .await?;
+        let breakpoints: Vec<Value> = ids
+            .iter()
+            .map(|id| json!({ "id": id, "verified": true }))
+            .collect();
+        Ok(Some(json!({ "breakpoints": breakpoints })))
+    }
+
+    async fn handle_stack_trace(&self) -> Result<Option<Value>> {
+        let frames = self.request(|reply| DebugCommand::StackTrace { reply }).await?;
+        let stack_frames: Vec<Value> = frames
+            .iter()
+            .enumerate()
+            .map(|(index, frame)| {
+                let mut value = json!({
+                    "id": index,
+                    "name": frame.function.clone().unwrap_or_else(|| "<anonymous>".to_string()),
+                    "line": frame.line.unwrap_or(0),
+                    "column": frame.col.unwrap_or(0),
+                });
+                if let Some(file) = &frame.file {
+                    value["source"] = json!({ "path": file });
+                }
+                value
+            })
+            .collect();
+        Ok(Some(json!({
+            "stackFrames": stack_frames,
+            "totalFrames": frames.len(),
+        })))
+    }
+
+    async fn handle_variables(&self, arguments: &Value) -> Result<Option<Value>> {
+        let reference = arguments
+            .get("variablesReference")
+            .and_then(Value::as_u64)
+            .context("variables requires a variablesReference")?;
+        let frame = reference.checked_sub(1).context("Invalid variablesReference")? as usize;
+
+        let locals = self.request(|reply| DebugCommand::Variables { frame, reply }).await?;
+        let variables: Vec<Value> = locals
+            .into_iter()
+            .map(|(name, value)| json!({ "name": name, "value": value, "variablesReference": 0 }))
+            .collect();
+        Ok(Some(json!({ "variables": variables })))
+    }
+
+    async fn handle_evaluate(&self, arguments: &Value) -> Result<Option<Value>> {
+        let expression = arguments
+            .get("expression")
+            .and_then(Value::as_str)
+            .context("evaluate requires an expression")?
+            .to_string();
+        let frame = arguments.get("frameId").and_then(Value::as_u64).unwrap_or(0) as usize;
+
+        let result = self
+            .request(|reply| DebugCommand::Evaluate { frame, expression, reply })
+            .await?
+            .map_err(|e| anyhow!(e))?;
+        Ok(Some(json!({ "result": result, "variablesReference": 0 })))
+    }
+
+    async fn read_message(&mut self) -> io::Result<Option<DapMessage>> {
+        // Headers end with an empty line; only Content-Length matters
+        let mut content_length: Option<usize> = None;
+        let mut line = Vec::new();
+        loop {
+            let mut byte = [0u8; 1];
+            if let Err(e) = self.reader.read_exact(&mut byte).await {
+                if e.kind() == io::ErrorKind::UnexpectedEof {
+                    return Ok(None);
+                }
+                return Err(e);
+            }
+            if byte[0] != b'\n' {
+                line.push(byte[0]);
+                continue;
+            }
+
+            let header = String::from_utf8_lossy(&line).trim_end_matches('\r').to_string();
+            line.clear();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(len) = header.strip_prefix("Content-Length:") {
+                content_length = len.trim().parse().ok();
+            }
+        }
+
+        let len = content_length.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "Missing Content-Length header")
+        })?;
+        let mut content = vec![0; len];
+        self.reader.read_exact(&mut content).await?;
+
+        serde_json::from_slice::<DapMessage>(&content)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+async fn write_message<W: AsyncWrite + Unpin>(writer: &mut W, message: &DapMessage) -> io::Result<()> {
+    let content = serde_json::to_string(message)?;
+    let header = format!("Content-Length: {}\r\n\r\n", content.len());
+
+    writer.write_all(header.as_bytes()).await?;
+    writer.write_all(content.as_bytes()).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Body of the debuggee thread: load the program, then serve commands until
+/// the program ends or the client disconnects
+fn run_debuggee(
+    vm: Arc<BlinkVM>,
+    launch: LaunchConfig,
+    commands: mpsc::Receiver<DebugCommand>,
+    outgoing: UnboundedSender<DapMessage>,
+    stopped: Arc<AtomicBool>,
+    cancel_token: CancellationToken,
+) {
+    let (module_name, forms) = match load_program(&vm, &launch.program) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            send_output(&outgoing, "stderr", format!("{}\n", e));
+            outgoing.send(DapMessage::event("terminated", None)).ok();
+            return;
+        }
+    };
+
+    let mut ctx = ExecutionContext::new(vm.clone(), module_name);
+    ctx.cancel_token = cancel_token;
+    let mut debugger = Debugger::new();
+    debugger.set_stop_on_entry(launch.stop_on_entry);
+    let mut forms = forms.into_iter();
+
+    while let Ok(command) = commands.recv() {
+        match command {
+            DebugCommand::SetBreakpoints { file, lines, reply } => {
+                debugger.clear_breakpoints(|bp| {
+                    matches!(bp, Breakpoint::Line { file: Some(existing), .. } if *existing == file)
+                });
+                let ids = lines
+                    .into_iter()
+                    .map(|line| {
+                        debugger.add_breakpoint(Breakpoint::Line { file: Some(file.clone()), line })
+                    })
+                    .collect();
+                reply.send(ids).ok();
+            }
+            DebugCommand::SetFunctionBreakpoints { names, reply } => {
+                debugger.clear_breakpoints(|bp| matches!(bp, Breakpoint::Function { .. }));
+                let ids = names
+                    .into_iter()
+                    .map(|name| debugger.add_breakpoint(Breakpoint::Function { name }))
+                    .collect();
+                reply.send(ids).ok();
+            }
+            DebugCommand::StackTrace { reply } => {
+                reply.send(ctx.capture_stack_trace()).ok();
+            }
+            DebugCommand::Variables { frame, reply } => {
+                let locals = ctx
+                    .frame_locals(frame)
+                    .into_iter()
+                    .map(|(name, value)| (name, value.to_string()))
+                    .collect();
+                reply.send(locals).ok();
+            }
+            DebugCommand::Evaluate { frame, expression, reply } => {
+                reply.send(evaluate(&vm, &mut ctx, frame, &expression)).ok();
+            }
+            DebugCommand::Resume(mode) => {
+                debugger.set_step_mode(mode, ctx.call_stack.len());
+                if !run_until_stop(&vm, &mut ctx, &mut debugger, &mut forms, mode, &stopped, &outgoing) {
+                    break;
+                }
+            }
+            DebugCommand::Disconnect => break,
+        }
+    }
+
+    if !ctx.call_stack.is_empty() {
+        ctx.debug_abort(&mut debugger);
+    }
+}
+
+/// Run top-level forms until the debugger pauses. Returns false once the program is over.
+fn run_until_stop(
+    vm: &Arc<BlinkVM>,
+    ctx: &mut ExecutionContext,
+    debugger: &mut Debugger,
+    forms: &mut impl Iterator<Item = ParsedValueWithPos>,
+    mode: StepMode,
+    stopped: &AtomicBool,
+    outgoing: &UnboundedSender<DapMessage>,
+) -> bool {
+    loop {
+        if ctx.call_stack.is_empty() {
+            let Some(form) = forms.next() else {
+                outgoing.send(DapMessage::event("terminated", None)).ok();
+                return false;
+            };
+            let ast = vm.alloc_parsed_value(form);
+            if let Err(e) = ctx.debug_start(ast) {
+                send_output(outgoing, "stderr", format!("{}\n", e));
+                outgoing.send(DapMessage::event("terminated", None)).ok();
+                return false;
+            }
+            // A step that runs off the end of one form stops at the start of the next
+            if mode != StepMode::Run {
+                debugger.set_step_mode(StepMode::StepIn, 0);
+            }
+        }
+
+        match ctx.debug_resume(debugger) {
+            DebugEvent::Paused { reason, .. } => {
+                let (reason, hit) = match reason {
+                    PauseReason::Breakpoint(id) => ("breakpoint", vec![id]),
+                    PauseReason::Step => ("step", Vec::new()),
+                    PauseReason::Entry => ("entry", Vec::new()),
+                    PauseReason::Interrupted => ("pause", Vec::new()),
+                };
+                // Requests about the paused program are served once the client hears it stopped
+                stopped.store(true, Ordering::SeqCst);
+                outgoing
+                    .send(DapMessage::event(
+                        "stopped",
+                        Some(json!({
+                            "reason": reason,
+                            "threadId": THREAD_ID,
+                            "hitBreakpointIds": hit,
+                            "allThreadsStopped": true,
+                        })),
+                    ))
+                    .ok();
+                return true;
+            }
+            DebugEvent::Finished(_) => continue,
+            DebugEvent::Failed(e) => {
+                send_output(outgoing, "stderr", format!("{}\n", e));
+                outgoing.send(DapMessage::event("terminated", None)).ok();
+                return false;
+            }
+        }
+    }
+}
+
+fn evaluate(vm: &Arc<BlinkVM>, ctx: &mut ExecutionContext, frame: usize, expression: &str) -> Result<String, String> {
+    let mut forms = {
+        let mut symbol_table = vm.symbol_table.write();
+        let mut reader_macros = vm.reader_macros.write();
+        parse_all(expression, &mut reader_macros, &mut symbol_table).map_err(|e| e.to_string())?
+    };
+    if forms.len() != 1 {
+        return Err("Expected a single expression".to_string());
+    }
+    let expr = vm.alloc_parsed_value(forms.remove(0));
+
+    let result = if ctx.call_stack.is_empty() {
+        ctx.compile_and_execute(expr)
+    } else {
+        ctx.eval_in_frame(frame, expr)
+    };
+    result.map(|value| value.to_string()).map_err(|e| e.to_string())
+}
+
+/// Parse a program file and register it as a module named after the file and
+/// the session, so sessions debugging the same file do not share globals
+fn load_program(vm: &Arc<BlinkVM>, program: &str) -> Result<(u32, Vec<ParsedValueWithPos>)> {
+    let code = std::fs::read_to_string(program)
+        .with_context(|| format!("Could not read {}", program))?;
+
+    let module_name = Path::new(program)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .context("Program path has no file name")?;
+
+    let mut symbol_table = vm.symbol_table.write();
+    let mut reader_macros = vm.reader_macros.write();
+    let forms = parse_all(&code, &mut reader_macros, &mut symbol_table)
+        .map_err(|e| anyhow!("{}", e))?;
+
+    let session = NEXT_SESSION.fetch_add(1, Ordering::Relaxed);
+    let module_id = symbol_table.intern(&format!("{}-{}", module_name, session));
+    let path_id = symbol_table.intern_simple(program);
+    drop(reader_macros);
+    drop(symbol_table);
+
+    vm.module_registry
+        .write()
+        .register_module(Module::new(module_id, SerializedModuleSource::BlinkFile(path_id)));
+
+    Ok((module_id, forms))
+}
+
+fn send_output(outgoing: &UnboundedSender<DapMessage>, category: &str, output: String) {
+    outgoing
+        .send(DapMessage::event("output", Some(json!({ "category": category, "output": output }))))
+        .ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{duplex, split, AsyncBufReadExt, DuplexStream, ReadHalf, WriteHalf};
+
+    use super::*;
+    use crate::test_support::test_vm;
+
+    struct Client {
+        reader: BufReader<ReadHalf<DuplexStream>>,
+        writer: WriteHalf<DuplexStream>,
+        seq: u64,
+    }
+
+    impl Client {
+        fn connect() -> Self {
+            let (client, server) = duplex(64 * 1024);
+            let (server_reader, server_writer) = split(server);
+            let mut handler = DapHandler::new(BufReader::new(server_reader), server_writer, test_vm());
+            tokio::spawn(async move { handler.process().await });
+
+            let (reader, writer) = split(client);
+            Client { reader: BufReader::new(reader), writer, seq: 0 }
+        }
+
+        async fn send(&mut self, command: &str, arguments: Value) {
+            self.seq += 1;
+            let request = json!({
+                "seq": self.seq,
+                "type": "request",
+                "command": command,
+                "arguments": arguments,
+            });
+            let content = request.to_string();
+            let header = format!("Content-Length: {}\r\n\r\n", content.len());
+            self.writer.write_all(header.as_bytes()).await.unwrap();
+            self.writer.write_all(content.as_bytes()).await.unwrap();
+        }
+
+        async fn receive(&mut self) -> DapMessage {
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                self.reader.read_line(&mut line).await.unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some(value) = line.strip_prefix("Content-Length:") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            let mut content = vec![0; length];
+            self.reader.read_exact(&mut content).await.unwrap();
+            serde_json::from_slice(&content).unwrap()
+        }
+
+        // The response to a request, with the events sent before it
+        async fn request(&mut self, command: &str, arguments: Value) -> (DapMessage, Vec<DapMessage>) {
+            self.send(command, arguments).await;
+            let mut events = Vec::new();
+            loop {
+                let message = self.receive().await;
+                if message.message_type == "response" {
+                    assert_eq!(message.command.as_deref(), Some(command));
+                    return (message, events);
+                }
+                events.push(message);
+            }
+        }
+
+        async fn event(&mut self, name: &str) -> DapMessage {
+            loop {
+                let message = self.receive().await;
+                if message.event.as_deref() == Some(name) {
+                    return message;
+                }
+            }
+        }
+
+        async fn launch(&mut self, name: &str, code: &str) -> String {
+            let program = std::env::temp_dir().join(format!("blink-dap-{}-{}.bl", name, std::process::id()));
+            std::fs::write(&program, code).unwrap();
+            self.request("initialize", json!({})).await;
+            let (response, _) = self.request("launch", json!({ "program": program })).await;
+            assert_eq!(response.success, Some(true), "{:?}", response.message);
+            self.event("initialized").await;
+            program.to_string_lossy().into_owned()
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn breakpoints_stop_with_the_stack_and_locals() {
+        let mut client = Client::connect();
+        let program = client
+            .launch(
+                "breakpoints",
+                "(def add2 (fn [x]
+  (+ x 2)))
+(add2 40)",
+            )
+            .await;
+
+        let (response, _) = client
+            .request("setBreakpoints", json!({ "source": { "path": program }, "breakpoints": [{ "line": 2 }] }))
+            .await;
+        assert_eq!(response.body.unwrap()["breakpoints"][0]["verified"], true);
+        client.request("configurationDone", json!({})).await;
+
+        let stopped = client.event("stopped").await;
+        assert_eq!(stopped.body.unwrap()["reason"], "breakpoint");
+
+        let (response, _) = client.request("stackTrace", json!({ "threadId": THREAD_ID })).await;
+        let frames = response.body.unwrap()["stackFrames"].clone();
+        assert_eq!(frames[0]["name"], "add2");
+        assert_eq!(frames[0]["line"], 2);
+
+        let (response, _) = client.request("scopes", json!({ "frameId": 0 })).await;
+        let reference = response.body.unwrap()["scopes"][0]["variablesReference"].clone();
+        let (response, _) = client.request("variables", json!({ "variablesReference": reference })).await;
+        let variables = response.body.unwrap()["variables"].clone();
+        assert_eq!((variables[0]["name"].clone(), variables[0]["value"].clone()), (json!("x"), json!("40")));
+
+        let (response, _) = client.request("evaluate", json!({ "expression": "(+ x 1)", "frameId": 0 })).await;
+        assert_eq!(response.body.unwrap()["result"], "41");
+
+        client.request("continue", json!({ "threadId": THREAD_ID })).await;
+        client.event("terminated").await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn requests_about_a_running_program_fail_until_it_pauses() {
+        let mut client = Client::connect();
+        client
+            .launch(
+                "running",
+                "(def spin (fn [n]
+  (loop [i n] (recur (+ i 1)))))
+(spin 0)",
+            )
+            .await;
+        client.request("configurationDone", json!({})).await;
+
+        for (command, arguments) in [
+            ("stackTrace", json!({ "threadId": THREAD_ID })),
+            ("variables", json!({ "variablesReference": 1 })),
+            ("evaluate", json!({ "expression": "1", "frameId": 0 })),
+            ("continue", json!({ "threadId": THREAD_ID })),
+        ] {
+            let (response, _) = client.request(command, arguments).await;
+            assert_eq!(response.success, Some(false), "{} should fail while running", command);
+        }
+
+        client.request("pause", json!({ "threadId": THREAD_ID })).await;
+        let stopped = client.event("stopped").await;
+        assert_eq!(stopped.body.unwrap()["reason"], "pause");
+
+        let (response, _) = client.request("stackTrace", json!({ "threadId": THREAD_ID })).await;
+        assert_eq!(response.body.unwrap()["stackFrames"][0]["name"], "spin");
+        client.request("disconnect", json!({})).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn sessions_debug_in_modules_of_their_own() {
+        let code = "(def counter 1)\n(def counter (+ counter 1))\n(+ counter 0)";
+        let mut first = Client::connect();
+        let program = first.launch("isolated", code).await;
+        let mut second = Client::connect();
+        second.launch("isolated", code).await;
+
+        for client in [&mut first, &mut second] {
+            client
+                .request("setBreakpoints", json!({ "source": { "path": program }, "breakpoints": [{ "line": 3 }] }))
+                .await;
+            client.request("configurationDone", json!({})).await;
+            client.event("stopped").await;
+            let (response, _) = client.request("evaluate", json!({ "expression": "counter", "frameId": 0 })).await;
+            assert_eq!(response.body.unwrap()["result"], "2");
+            client.request("continue", json!({ "threadId": THREAD_ID })).await;
+            client.event("terminated").await;
+        }
+    }
+}
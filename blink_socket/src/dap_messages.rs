@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A Debug Adapter Protocol message: a request, a response or an event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DapMessage {
+    /// Sequence number, assigned by the sender
+    #[serde(default)]
+    pub seq: u64,
+
+    /// "request", "response" or "event"
+    #[serde(rename = "type")]
+    pub message_type: String,
+
+    /// The request name (e.g., "setBreakpoints")
+    /// - Present in requests and responses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+
+    /// Request arguments
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<Value>,
+
+    /// Sequence number of the request a response answers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_seq: Option<u64>,
+
+    /// Whether a request succeeded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub success: Option<bool>,
+
+    /// Error message of a failed request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+
+    /// The event name (e.g., "stopped")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<String>,
+
+    /// Response or event payload
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+}
+
+impl DapMessage {
+    fn new(message_type: &str) -> Self {
+        DapMessage {
+            seq: 0,
+            message_type: message_type.to_string(),
+            command: None,
+            arguments: None,
+            request_seq: None,
+            success: None,
+            message: None,
+            event: None,
+            body: None,
+        }
+    }
+
+    pub fn response(request: &DapMessage, body: Option<Value>) -> Self {
+        DapMessage {
+            command: request.command.clone(),
+            request_seq: Some(request.seq),
+            success: Some(true),
+            body,
+            ..DapMessage::new("response")
+        }
+    }
+
+    pub fn error_response(request: &DapMessage, message: impl Into<String>) -> Self {
+        DapMessage {
+            command: request.command.clone(),
+            request_seq: Some(request.seq),
+            success: Some(false),
+            message: Some(message.into()),
+            ..DapMessage::new("response")
+        }
+    }
+
+    pub fn event(event: &str, body: Option<Value>) -> Self {
+        DapMessage {
+            event: Some(event.to_string()),
+            body,
+            ..DapMessage::new("event")
+        }
+    }
+}
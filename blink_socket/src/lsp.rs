@@ -4,8 +4,7 @@ use crate::{
     helpers::collect_symbols_from_forms, lsp_messages::{create_server_capabilities, CompletionItem, CompletionParams, Diagnostic, DiagnosticsParams, DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams, DocumentSymbolParams, GotoDefinitionParams, HoverParams, LspError, LspMessage, Position, Range}, session::{Document, Session, SymbolSource}, session_manager::SessionManager
 };
 use anyhow::{anyhow, Context, Result};
-use blink_core::{ error::{BlinkError, BlinkErrorType, ParseErrorType}, parser::{parse_all, ReaderContext}, runtime::SymbolTable, value::SourcePos};
-use parking_lot::RwLock;
+use blink_core::{ error::{BlinkError, BlinkErrorType, ParseErrorType}, parser::parse_all, runtime::BlinkVM, value::SourcePos};
 use serde_json::{json, Value};
 use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
 
@@ -30,11 +29,15 @@ pub enum LspHandlerResult {
 
 
 
+// MessageType of window/showMessage notifications
+const MESSAGE_TYPE_WARNING: u8 = 2;
+
 // Updated LspHandler implementation with error handling
 pub struct LspHandler<R, W> {
     reader: BufReader<R>,
     writer: W,
-    session: Option<Arc<Session>>
+    session: Option<Arc<Session>>,
+    vm: Arc<BlinkVM>,
 }
 
 
@@ -43,11 +46,12 @@ where
     R: AsyncRead + Unpin + Send + 'static,
     W: AsyncWrite + Unpin + Send + 'static,
 {
-    pub fn new(reader: BufReader<R>, writer: W) -> Self {
+    pub fn new(reader: BufReader<R>, writer: W, vm: Arc<BlinkVM>) -> Self {
         Self {
             reader,
             writer,
             session: None,
+            vm,
         }
     }
 
@@ -80,8 +84,7 @@ where
                 .with_context(|| format!("Session '{}' not found", session_id))?
         } else {
             let new_id = uuid::Uuid::new_v4().to_string();
-            let symbol_table = Arc::new(RwLock::new(SymbolTable::new()));
-            let session = Session::new(new_id.clone(), symbol_table);
+            let session = Session::new(new_id.clone(), self.vm.clone());
             session.features.write().lsp = true;
             let arc_session = Arc::new(session);
             session_manager.register(arc_session.clone()).await;
@@ -195,23 +198,39 @@ where
             let message_id = message.id.clone();
 
             let result = self.handle_message(message).await;
-            match result {
+            let messages = match result {
                 LspHandlerResult::Exit => {
                                 return Ok(());
                             },
-                LspHandlerResult::NoResponse => {
-                                continue;
-                            },
-                _ => {
-                    let messages = process_handler_result(result, message_id).await;
-                    for message in messages {
-                        self.write_message(&message).await?;
-                    }
-                }
+                LspHandlerResult::NoResponse => Vec::new(),
+                _ => process_handler_result(result, message_id).await,
+            };
+            for message in messages.into_iter().chain(self.warning_messages()) {
+                self.write_message(&message).await?;
             }
         }
     }
 
+    // Warnings from the VM, such as a project that could not be resolved, and
+    // from REPL evaluations on this session, shown by the editor
+    fn warning_messages(&self) -> Vec<LspMessage> {
+        let mut warnings = self.vm.take_warnings();
+        if let Some(session) = &self.session {
+            warnings.append(&mut session.warnings.lock());
+        }
+        warnings
+            .into_iter()
+            .map(|warning| LspMessage {
+                jsonrpc: "2.0".to_string(),
+                id: None,
+                method: Some("window/showMessage".to_string()),
+                params: Some(json!({ "type": MESSAGE_TYPE_WARNING, "message": warning })),
+                result: None,
+                error: None,
+            })
+            .collect()
+    }
+
     async fn handle_message(&mut self, message: LspMessage) -> LspHandlerResult {
         let method = match message.method.as_deref() {
             Some(m) => m,
@@ -402,20 +421,6 @@ where
         }
     }
 
-    fn get_reader_ctx(&self) -> Arc<RwLock<ReaderContext>> {
-        let mut reader_ctx = match &self.session {
-            Some(session) => {
-                let eval_ctx_guard = session.eval_ctx.read();
-                match eval_ctx_guard.as_ref() {
-                    Some(eval_ctx) => eval_ctx.reader_macros.clone(),
-                    None => Arc::new(RwLock::new(ReaderContext::new())),
-                }
-            },
-            None => Arc::new(RwLock::new(ReaderContext::new())),
-        }; 
-        reader_ctx
-    }
-
     async fn handle_text_document_did_open(
         &mut self,
         params: Value
@@ -432,11 +437,9 @@ where
         let uri_clone = uri.clone();
         let source = SymbolSource::File(uri_clone.clone());
     
-        let mut reader_ctx = self.get_reader_ctx();
-        let symbol_table = self.session.as_ref().unwrap().eval_ctx.read().as_deref().unwrap().symbol_table.clone();
         let parsed_result = {
-            let mut symbol_table_guard = symbol_table.write();
-            let mut reader_ctx_guard = reader_ctx.write();
+            let mut symbol_table_guard = self.vm.symbol_table.write();
+            let mut reader_ctx_guard = self.vm.reader_macros.write();
             parse_all(&text, &mut *reader_ctx_guard, &mut *symbol_table_guard)
         };
         match parsed_result {
@@ -453,8 +456,7 @@ where
                     {
                         let mut symbols = session.symbols.write();
                         
-                        let symbol_table = session.symbol_table.clone();
-                        let symbol_table_guard = symbol_table.read();
+                        let symbol_table_guard = session.vm.symbol_table.read();
                         collect_symbols_from_forms(&mut symbols, &new_doc.forms, source, &symbol_table_guard);
                     }
                     {
@@ -526,11 +528,9 @@ where
             }
         }
         let source = SymbolSource::File(uri.clone());
-        let mut reader_ctx = self.get_reader_ctx();
         let forms = {
-            let symbol_table = session.eval_ctx.read().as_deref().unwrap().symbol_table.clone();
-            let mut symbol_table_guard = symbol_table.write();
-            let mut reader_ctx_guard = reader_ctx.write();
+            let mut symbol_table_guard = self.vm.symbol_table.write();
+            let mut reader_ctx_guard = self.vm.reader_macros.write();
             parse_all(&current_text, &mut *reader_ctx_guard, &mut *symbol_table_guard)
         };
         match forms {
@@ -553,8 +553,7 @@ where
 
                         
                         {
-                            let symbol_table = session.symbol_table.clone();
-                            let symbol_table_guard = symbol_table.read();
+                            let symbol_table_guard = session.vm.symbol_table.read();
                             collect_symbols_from_forms(&mut symbols, &new_doc.forms, source, &symbol_table_guard);
                         }
                         documents.insert(new_doc.uri.clone(), new_doc);
@@ -664,9 +663,7 @@ where
         let prefix_str = self.get_prefix_at_position(&document.text, &position);
     
         // Add built-in special forms
-        for &special_form in &[
-            "def", "fn", "if", "quote", "do", "let", "and", "or", "try", "imp", "apply",
-        ] {
+        for &special_form in SPECIAL_FORMS {
             completion_items.push(CompletionItem {
                 label: special_form.to_string(),
                 kind: Some(3), // Function
@@ -696,18 +693,19 @@ where
             });
         }
     
-        // Add native functions
-        for (func, doc) in get_native_function_docs() {
+        // Add the public definitions of every loaded module
+        for global in global_names(&self.vm) {
             if let Some(prefix) = &prefix_str {
-                if !func.starts_with(prefix) {
+                if !global.label.starts_with(prefix) {
                     continue;
                 }
             }
+            let module = self.vm.symbol_table.read().get_symbol(global.module_id).unwrap_or_default();
             completion_items.push(CompletionItem {
-                label: func.to_string(),
+                documentation: definition_markdown(&self.vm, global.module_id, global.name_id),
+                label: global.label,
                 kind: Some(3), // Function
-                detail: Some("Built-in Function".into()),
-                documentation: Some(doc),
+                detail: Some(format!("{} in {}", global.type_name, module)),
                 insert_text: None,
             });
         }
@@ -751,8 +749,8 @@ where
                 if let Some(doc) = get_special_form_doc(&symbol) {
                     create_hover_response(id, &doc)
                 }
-                // Check for native functions
-                else if let Some(doc) = get_native_function_doc(&symbol) {
+                // Check definitions in the loaded modules
+                else if let Some(doc) = describe_global(&self.vm, &symbol) {
                     create_hover_response(id, &doc)
                 }
                 // Check user-defined symbols
@@ -857,10 +855,81 @@ fn find_symbol_at_position(text: &str, position: &Position) -> Option<String> {
     Some(line[start_idx..end_idx].to_string())
 }
 
+/// Forms the compiler handles itself; they are not definitions in any module
+const SPECIAL_FORMS: &[&str] = &[
+    "def", "defonce", "fn", "macro", "rmac", "if", "quote", "var", "do", "let", "and", "or", "try", "imp", "load", "reload", "apply",
+];
+
+/// A public definition of a loaded module, as completion lists it
+struct GlobalName {
+    label: String,
+    module_id: u32,
+    name_id: u32,
+    type_name: &'static str,
+}
+
+/// Public definitions of the loaded modules: core names as they are, the rest
+/// qualified with their module
+fn global_names(vm: &BlinkVM) -> Vec<GlobalName> {
+    let Some(core_module) = vm.core_module else {
+        return Vec::new();
+    };
+    let registry = vm.module_registry.read();
+    let symbol_table = vm.symbol_table.read();
+    let mut names = Vec::new();
+    for &module_id in registry.modules.keys() {
+        let module_name = symbol_table.get_symbol(module_id).unwrap_or_default();
+        for (name_id, value) in registry.public_exports(module_id) {
+            let name = symbol_table.get_symbol(name_id).unwrap_or_default();
+            // Reader macros such as quote and deref are registered as symbols naming their form
+            if SPECIAL_FORMS.contains(&name.as_str()) || value.get_symbol().is_some() {
+                continue;
+            }
+            let label = if module_id == core_module { name } else { format!("{}/{}", module_name, name) };
+            names.push(GlobalName { label, module_id, name_id, type_name: value.type_name() });
+        }
+    }
+    names.sort_by(|a, b| a.label.cmp(&b.label));
+    names.dedup_by(|a, b| a.label == b.label);
+    names
+}
+
+/// Markdown for a definition: its docstring, arglists and deprecation from
+/// its metadata. Natives are given theirs when they are registered.
+fn definition_markdown(vm: &BlinkVM, module_id: u32, name_id: u32) -> Option<String> {
+    vm.definition_doc(module_id, name_id).map(|definition| definition.to_markdown())
+}
+
+/// Hover text for a name defined in a loaded module, looked up as core code would
+fn describe_global(vm: &BlinkVM, symbol: &str) -> Option<String> {
+    let core_module = vm.core_module?;
+    let located = match symbol.split_once('/') {
+        Some((module, name)) if !module.is_empty() && !name.is_empty() => {
+            let (module_id, name_id) = {
+                let symbol_table = vm.symbol_table.read();
+                (symbol_table.lookup_symbol(module)?, symbol_table.lookup_symbol(name)?)
+            };
+            vm.module_registry.read().resolve_public(module_id, name_id).map(|_| (module_id, name_id))
+        }
+        _ => {
+            let symbol_id = vm.symbol_table.read().lookup_symbol(symbol)?;
+            vm.locate_global_symbol(core_module, symbol_id)
+        }
+    };
+    let (module_id, name_id) = located?;
+
+    let value = vm.module_registry.read().resolve_symbol(module_id, name_id)?;
+    let markdown = definition_markdown(vm, module_id, name_id)?;
+    Some(format!("{}\n\nType: {}", markdown, value.type_name()))
+}
+
 fn get_special_form_doc(symbol: &str) -> Option<String> {
     match symbol {
-        "def" => Some("**def** - Define a variable\n\n```blink\n(def name value)\n```\n\nAssigns `value` to `name` in the current environment.".to_string()),
+        "def" => Some("**def** - Define a variable\n\n```blink\n(def name value)\n(def name \"doc\" value)\n(def ^{:private true} name value)\n```\n\nAssigns `value` to `name` in the current module. A docstring and a `^{...}` or `^:flag` metadata map are kept with the definition; `:private` hides it from other modules and `:deprecated` marks it in docs.".to_string()),
+        "defonce" => Some("**defonce** - Define once\n\n```blink\n(defonce name value)\n```\n\nLike `def`, but when `name` is already defined in the module `value` is not evaluated and the existing value is kept, so reloading a file keeps its state.".to_string()),
         "fn" => Some("**fn** - Create a function\n\n```blink\n(fn [param1 param2 ...] body)\n```\n\nCreates a new function with the specified parameters and body expressions.".to_string()),
+        "macro" => Some("**macro** - Create a macro\n\n```blink\n(macro [param1 param2 & rest] body)\n```\n\nThe body runs when a call to the macro is compiled, with the unevaluated argument forms, and returns the code to compile in its place. It is ordinary code: it can use `let`, loops and helper functions. `&form` is the whole call and `&env` a map of the locals in scope at the call.".to_string()),
+        "rmac" => Some("**rmac** - Remove a macro\n\n```blink\n(rmac name)\n```\n\nRemoves a macro defined in the current module. Definitions compiled with its expansion keep it; they are listed in a warning, as they are when the macro is redefined.".to_string()),
         "if" => Some("**if** - Conditional expression\n\n```blink\n(if condition then-expr else-expr)\n```\n\nEvaluates `condition` and returns `then-expr` if truthy, otherwise returns `else-expr`.".to_string()),
         "do" => Some("**do** - Sequence of expressions\n\n```blink\n(do expr1 expr2 ... exprN)\n```\n\nEvaluates each expression in order and returns the value of the last one.".to_string()),
         "let" => Some("**let** - Local bindings\n\n```blink\n(let [name1 value1, name2 value2 ...] body)\n```\n\nCreates local bindings that are available within the body expressions.".to_string()),
@@ -869,61 +938,17 @@ fn get_special_form_doc(symbol: &str) -> Option<String> {
         "or" => Some("**or** - Logical OR\n\n```blink\n(or expr1 expr2 ... exprN)\n```\n\nReturns the first truthy value or the last value if all are falsy.".to_string()),
         "try" => Some("**try** - Error handling\n\n```blink\n(try expr recovery-expr)\n```\n\nEvaluates `expr` and returns its value. If an error occurs, evaluates and returns `recovery-expr`.".to_string()),
         "apply" => Some("**apply** - Apply function to arguments\n\n```blink\n(apply fn arg-list)\n```\n\nApplies the function to the list of arguments.".to_string()),
-        "imp" => Some("**imp** - Import module\n\n```blink\n(imp \"module-path\")\n```\n\nImports the specified module, evaluating all forms in it.".to_string()),
+        "imp" => Some("**imp** - Import module\n\n```blink\n(imp name)\n(imp name :as alias)\n(imp name :only [a b])\n(imp name :refer-all)\n```\n\nMakes the public names of module `name` available as `name/a`, or `alias/a` with `:as`. `:only` also makes the listed names usable unqualified, and `:refer-all` every public name.\n\nA module that is not defined yet is loaded from its file on the module path: `a.b.c` from `a/b/c.blink`.".to_string()),
+        "var" => Some("**var** - Name a definition\n\n```blink\n(var name)\n#'name\n```\n\nReturns the qualified symbol `module/name` of the definition `name` resolves to, for `meta`.".to_string()),
+        "reload" => Some("**reload** - Reload a module\n\n```blink\n(reload 'name)\n```\n\nEvaluates the module's file again, then the files of the modules that import it, each after the modules it imports. Definitions are replaced in place and `defonce` values are kept. Returns the reloaded module names in order.".to_string()),
+        "load" => Some("**load** - Evaluate a source file\n\n```blink\n(load \"path\")\n```\n\nEvaluates the forms of a file once and returns the value of the last one. The path is relative to the file doing the load, and `.blink` may be left off; otherwise the module path is searched.".to_string()),
+        "lazy-seq" => Some("**lazy-seq** - Delay a sequence\n\n```blink\n(lazy-seq body)\n```\n\nReturns a lazy seq whose body runs the first time the seq is used. The result is cached.".to_string()),
+        "lazy-cat" => Some("**lazy-cat** - Lazily concatenate\n\n```blink\n(lazy-cat coll1 coll2 ...)\n```\n\nReturns a lazy seq of the items of each collection. No argument is evaluated until the seq reaches it.".to_string()),
         "nimp" => Some("**nimp** - Native import\n\n```blink\n(nimp \"library-name\")\n```\n\nImports a native Rust library (.so/.dll) and registers its functions.".to_string()),
         _ => None,
     }
 }
 
-fn get_native_function_doc(symbol: &str) -> Option<String> {
-    match symbol {
-        "+" => Some("**+** - Addition\n\n```blink\n(+ num1 num2 ...)\n```\n\nAdds numbers together.".to_string()),
-        "-" => Some("**-** - Subtraction\n\n```blink\n(- num1 num2 ...)\n```\n\nSubtracts numbers from the first one.".to_string()),
-        "*" => Some("***** - Multiplication\n\n```blink\n(* num1 num2 ...)\n```\n\nMultiplies numbers together.".to_string()),
-        "/" => Some("**/** - Division\n\n```blink\n(/ num1 num2 ...)\n```\n\nDivides the first number by the rest.".to_string()),
-        "=" => Some("**=** - Equality\n\n```blink\n(= val1 val2 ...)\n```\n\nChecks if all values are equal.".to_string()),
-        "not" => Some("**not** - Logical NOT\n\n```blink\n(not expr)\n```\n\nReturns true if expr is falsy, false otherwise.".to_string()),
-        "map" => Some("**map** - Apply function to each item\n\n```blink\n(map fn coll)\n```\n\nApplies the function to each item in the collection.".to_string()),
-        "reduce" => Some("**reduce** - Reduce collection to a value\n\n```blink\n(reduce fn init coll)\n```\n\nReduces the collection to a single value using the function.".to_string()),
-        "list" => Some("**list** - Create a list\n\n```blink\n(list item1 item2 ...)\n```\n\nCreates a new list containing the given items.".to_string()),
-        "vector" => Some("**vector** - Create a vector\n\n```blink\n(vector item1 item2 ...)\n```\n\nCreates a new vector containing the given items.".to_string()),
-        "hash-map" => Some("**hash-map** - Create a map\n\n```blink\n(hash-map key1 val1 key2 val2 ...)\n```\n\nCreates a new hash map with the given keys and values.".to_string()),
-        "print" => Some("**print** - Output values\n\n```blink\n(print val1 val2 ...)\n```\n\nPrints values to standard output.".to_string()),
-        "type-of" => Some("**type-of** - Get type\n\n```blink\n(type-of value)\n```\n\nReturns the type of the given value as a string.".to_string()),
-        "cons" => Some("**cons** - Prepend to list\n\n```blink\n(cons item list)\n```\n\nPrepends an item to the beginning of a list.".to_string()),
-        "car" => Some("**car** - First item\n\n```blink\n(car list)\n```\n\nReturns the first item of a list.".to_string()),
-        "cdr" => Some("**cdr** - Rest of list\n\n```blink\n(cdr list)\n```\n\nReturns all items except the first one from a list.".to_string()),
-        "first" => Some("**first** - First item\n\n```blink\n(first list)\n```\n\nAlias for car. Returns the first item of a list.".to_string()),
-        "rest" => Some("**rest** - Rest of list\n\n```blink\n(rest list)\n```\n\nAlias for cdr. Returns all items except the first one from a list.".to_string()),
-        "get" => Some("**get** - Get by key/index\n\n```blink\n(get collection key [default])\n```\n\nGets the value at the key/index in the collection, or returns default if not found.".to_string()),
-        _ => None,
-    }
-}
-
-fn get_native_function_docs() -> Vec<(&'static str, String)> {
-    vec![
-        ("+", get_native_function_doc("+").unwrap()),
-        ("-", get_native_function_doc("-").unwrap()),
-        ("*", get_native_function_doc("*").unwrap()),
-        ("/", get_native_function_doc("/").unwrap()),
-        ("=", get_native_function_doc("=").unwrap()),
-        ("not", get_native_function_doc("not").unwrap()),
-        ("map", get_native_function_doc("map").unwrap()),
-        ("reduce", get_native_function_doc("reduce").unwrap()),
-        ("list", get_native_function_doc("list").unwrap()),
-        ("vector", get_native_function_doc("vector").unwrap()),
-        ("hash-map", get_native_function_doc("hash-map").unwrap()),
-        ("print", get_native_function_doc("print").unwrap()),
-        ("type-of", get_native_function_doc("type-of").unwrap()),
-        ("cons", get_native_function_doc("cons").unwrap()),
-        ("car", get_native_function_doc("car").unwrap()),
-        ("cdr", get_native_function_doc("cdr").unwrap()),
-        ("first", get_native_function_doc("first").unwrap()),
-        ("rest", get_native_function_doc("rest").unwrap()),
-        ("get", get_native_function_doc("get").unwrap()),
-    ]
-}
-
 // Helper function to compute byte position in string from line/column
 fn compute_position(text: &str, line: u32, character: u32) -> usize {
     let lines: Vec<&str> = text.lines().collect();
@@ -976,49 +1001,6 @@ fn create_hover_response(id: Value, content: &str) -> LspMessage {
     }
 }
 
-/// Get documentation for special forms
-fn get_special_forms() -> Vec<(&'static str, String)> {
-    vec![
-        ("def", "**def** - Define a variable\n\n```blink\n(def name value)\n```\n\nAssigns `value` to `name` in the current environment.".to_string()),
-        ("fn", "**fn** - Create a function\n\n```blink\n(fn [param1 param2 ...] body)\n```\n\nCreates a new function with the specified parameters and body expressions.".to_string()),
-        ("if", "**if** - Conditional expression\n\n```blink\n(if condition then-expr else-expr)\n```\n\nEvaluates `condition` and returns `then-expr` if truthy, otherwise returns `else-expr`.".to_string()),
-        ("do", "**do** - Sequence of expressions\n\n```blink\n(do expr1 expr2 ... exprN)\n```\n\nEvaluates each expression in order and returns the value of the last one.".to_string()),
-        ("let", "**let** - Local bindings\n\n```blink\n(let [name1 value1, name2 value2 ...] body)\n```\n\nCreates local bindings that are available within the body expressions.".to_string()),
-        ("quote", "**quote** - Prevent evaluation\n\n```blink\n(quote expr)\n'expr\n```\n\nReturns the expression without evaluating it.".to_string()),
-        ("and", "**and** - Logical AND\n\n```blink\n(and expr1 expr2 ... exprN)\n```\n\nReturns the first falsy value or the last value if all are truthy.".to_string()),
-        ("or", "**or** - Logical OR\n\n```blink\n(or expr1 expr2 ... exprN)\n```\n\nReturns the first truthy value or the last value if all are falsy.".to_string()),
-        ("try", "**try** - Error handling\n\n```blink\n(try expr recovery-expr)\n```\n\nEvaluates `expr` and returns its value. If an error occurs, evaluates and returns `recovery-expr`.".to_string()),
-        ("apply", "**apply** - Apply function to arguments\n\n```blink\n(apply fn arg-list)\n```\n\nApplies the function to the list of arguments.".to_string()),
-        ("imp", "**imp** - Import module\n\n```blink\n(imp \"module-path\")\n```\n\nImports the specified module, evaluating all forms in it.".to_string()),
-        ("nimp", "**nimp** - Native import\n\n```blink\n(nimp \"library-name\")\n```\n\nImports a native Rust library (.so/.dll) and registers its functions.".to_string()),
-    ]
-}
-
-/// Get documentation for built-in functions
-fn get_builtin_functions() -> Vec<(&'static str, String)> {
-    vec![
-        ("+", "**+** - Addition\n\n```blink\n(+ num1 num2 ...)\n```\n\nAdds numbers together.".to_string()),
-        ("-", "**-** - Subtraction\n\n```blink\n(- num1 num2 ...)\n```\n\nSubtracts numbers from the first one.".to_string()),
-        ("*", "***** - Multiplication\n\n```blink\n(* num1 num2 ...)\n```\n\nMultiplies numbers together.".to_string()),
-        ("/", "**/** - Division\n\n```blink\n(/ num1 num2 ...)\n```\n\nDivides the first number by the rest.".to_string()),
-        ("=", "**=** - Equality\n\n```blink\n(= val1 val2 ...)\n```\n\nChecks if all values are equal.".to_string()),
-        ("not", "**not** - Logical NOT\n\n```blink\n(not expr)\n```\n\nReturns true if expr is falsy, false otherwise.".to_string()),
-        ("map", "**map** - Apply function to each item\n\n```blink\n(map fn coll)\n```\n\nApplies the function to each item in the collection.".to_string()),
-        ("reduce", "**reduce** - Reduce collection to a value\n\n```blink\n(reduce fn init coll)\n```\n\nReduces the collection to a single value using the function.".to_string()),
-        ("list", "**list** - Create a list\n\n```blink\n(list item1 item2 ...)\n```\n\nCreates a new list containing the given items.".to_string()),
-        ("vector", "**vector** - Create a vector\n\n```blink\n(vector item1 item2 ...)\n```\n\nCreates a new vector containing the given items.".to_string()),
-        ("hash-map", "**hash-map** - Create a map\n\n```blink\n(hash-map key1 val1 key2 val2 ...)\n```\n\nCreates a new hash map with the given keys and values.".to_string()),
-        ("print", "**print** - Output values\n\n```blink\n(print val1 val2 ...)\n```\n\nPrints values to standard output.".to_string()),
-        ("type-of", "**type-of** - Get type\n\n```blink\n(type-of value)\n```\n\nReturns the type of the given value as a string.".to_string()),
-        ("cons", "**cons** - Prepend to list\n\n```blink\n(cons item list)\n```\n\nPrepends an item to the beginning of a list.".to_string()),
-        ("car", "**car** - First item\n\n```blink\n(car list)\n```\n\nReturns the first item of a list.".to_string()),
-        ("cdr", "**cdr** - Rest of list\n\n```blink\n(cdr list)\n```\n\nReturns all items except the first one from a list.".to_string()),
-        ("first", "**first** - First item\n\n```blink\n(first list)\n```\n\nAlias for car. Returns the first item of a list.".to_string()),
-        ("rest", "**rest** - Rest of list\n\n```blink\n(rest list)\n```\n\nAlias for cdr. Returns all items except the first one from a list.".to_string()),
-        ("get", "**get** - Get by key/index\n\n```blink\n(get collection key [default])\n```\n\nGets the value at the key/index in the collection, or returns default if not found.".to_string()),
-    ]
-}
-
 /// Convert a Blink error to an LSP diagnostic
 fn error_to_diagnostic(err: &blink_core::error::BlinkError, uri: &str) -> Diagnostic {
 
@@ -1053,6 +1035,9 @@ fn error_to_diagnostic(err: &blink_core::error::BlinkError, uri: &str) -> Diagno
         BlinkErrorType::UserDefined { data } => {
             (message.clone(), range)    
         }
+        BlinkErrorType::LimitExceeded { .. } | BlinkErrorType::Interrupted => {
+            (err.to_string(), range)
+        }
     };
 
     Diagnostic {
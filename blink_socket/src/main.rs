@@ -1,3 +1,5 @@
+mod dap;
+mod dap_messages;
 mod lsp;
 mod lsp_messages;
 mod repl_message;
@@ -5,7 +7,11 @@ mod repl;
 mod session;
 mod session_manager;
 mod helpers;
+#[cfg(test)]
+mod test_support;
+use blink_core::runtime::BlinkVM;
 use clap::Parser;
+use dap::DapHandler;
 use lsp::LspHandler;
 use repl::ReplHandler;
 
@@ -31,14 +37,23 @@ async fn main() -> std::io::Result<()> {
 
     let repl_port = opts.port;
     let lsp_port = opts.port + 1;
+    let dap_port = opts.port + 2;
 
     let repl_listener = TcpListener::bind(("127.0.0.1", repl_port)).await?;
     let lsp_listener = TcpListener::bind(("127.0.0.1", lsp_port)).await?;
+    let dap_listener = TcpListener::bind(("127.0.0.1", dap_port)).await?;
 
     println!("Blink REPL listening on 127.0.0.1:{}", repl_port);
     println!("Blink LSP listening on 127.0.0.1:{}", lsp_port);
+    println!("Blink DAP listening on 127.0.0.1:{}", dap_port);
+
+    // The process VM. REPL and debug sessions evaluate in modules of their own;
+    // the LSP reads it for completion and hover
+    let vm = BlinkVM::new_arc();
+    vm.use_project();
 
     // Spawn REPL server
+    let repl_vm = vm.clone();
     tokio::spawn(async move {
         loop {
             match repl_listener.accept().await {
@@ -48,7 +63,7 @@ async fn main() -> std::io::Result<()> {
                     let reader = BufReader::new(reader);
                     let writer = BufWriter::new(writer);
                     
-                    let mut handler = ReplHandler::new(reader, writer);
+                    let mut handler = ReplHandler::new(reader, writer, repl_vm.clone());
                     let result = handler.init(repl_manager.clone()).await;
                     if result.is_err() {
                         eprintln!("Failed to initialize REPL handler: {:?}", result.err().unwrap());
@@ -67,6 +82,30 @@ async fn main() -> std::io::Result<()> {
         }
     });
 
+    // Spawn DAP server
+    let dap_vm = vm.clone();
+    tokio::spawn(async move {
+        loop {
+            match dap_listener.accept().await {
+                Ok((socket, addr)) => {
+                    println!("DAP client {} connected.", addr);
+                    let (reader, writer) = socket.into_split();
+                    let reader = BufReader::new(reader);
+                    let writer = BufWriter::new(writer);
+
+                    let mut handler = DapHandler::new(reader, writer, dap_vm.clone());
+                    tokio::spawn(async move {
+                        let result = handler.process().await;
+                        if result.is_err() {
+                            eprintln!("DAP handler process error: {:?}", result.err().unwrap());
+                        }
+                    });
+                }
+                Err(e) => eprintln!("DAP accept error: {:?}", e),
+            }
+        }
+    });
+
     // Spawn LSP server
     loop {
         match lsp_listener.accept().await {
@@ -76,7 +115,7 @@ async fn main() -> std::io::Result<()> {
                 let reader = BufReader::new(reader);
                 let writer = BufWriter::new(writer);
                 
-                let mut handler = LspHandler::new(reader, writer);
+                let mut handler = LspHandler::new(reader, writer, vm.clone());
                 let result = handler.init(manager.clone()).await;
                 if result.is_err() {
                     eprintln!("Failed to initialize LSP handler: {:?}", result.err().unwrap());
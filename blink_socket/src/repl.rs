@@ -1,30 +1,32 @@
-use std::{future::Future, io::BufRead, pin::Pin, sync::Arc};
+use std::{sync::Arc, time::Duration};
 
 use anyhow::Context;
 use blink_core::{
-
+    error::{BlinkError, BlinkErrorType},
+    module::{Module, SerializedModuleSource},
     parser::{parse, tokenize_at},
-    runtime::SymbolTable,
+    runtime::{BlinkVM, ExecutionContext, ExecutionLimits},
     value::SourcePos,
-    Env,
 };
-use parking_lot::RwLock;
-use rmp_serde::{from_slice, to_vec, Deserializer, Serializer};
+use rmp_serde::{from_slice, Serializer};
 use serde::{de::DeserializeOwned, Serialize};
-use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::{
+    io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    sync::Mutex,
+};
 
 use crate::{
-    helpers::collect_symbols_from_forms,
     repl_message::{ReplRequest, ReplResponse},
-    session::{Session, SymbolSource},
+    session::Session,
     session_manager::SessionManager,
 };
-use std::boxed::Box;
 
 pub struct ReplHandler<R, W> {
     reader: BufReader<R>,
-    writer: W,
+    // Shared with running evaluations, which reply when they finish
+    writer: Arc<Mutex<W>>,
     session: Option<Arc<Session>>,
+    vm: Arc<BlinkVM>,
 }
 
 impl<R, W> ReplHandler<R, W>
@@ -36,7 +38,7 @@ where
         self.read_msgpack_frame::<ReplRequest>().await
     }
     async fn write_message(&mut self, msg: &ReplResponse) -> io::Result<()> {
-        self.write_msgpack_frame(msg).await
+        write_msgpack_frame(&self.writer, msg).await
     }
 
     pub async fn read_msgpack_frame<T>(&mut self) -> io::Result<Option<T>>
@@ -63,30 +65,12 @@ where
         Ok(Some(msg))
     }
 
-    pub async fn write_msgpack_frame(&mut self, msg: &ReplResponse) -> io::Result<()> {
-        println!("DEBUG: Type is {}", std::any::type_name::<ReplResponse>());
-        println!(
-            "DEBUG: JSON representation = {}",
-            serde_json::to_string(msg).unwrap()
-        );
-        let mut buf = Vec::new();
-        {
-            let mut serializer = Serializer::new(&mut buf).with_struct_map(); // force map encoding
-            msg.serialize(&mut serializer)
-                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        }
-        let len = (buf.len() as u32).to_be_bytes();
-        self.writer.write_all(&len).await?;
-        self.writer.write_all(&buf).await?;
-        self.writer.flush().await?;
-        Ok(())
-    }
-
-    pub fn new(reader: BufReader<R>, writer: W) -> Self {
+    pub fn new(reader: BufReader<R>, writer: W, vm: Arc<BlinkVM>) -> Self {
         Self {
             reader,
-            writer,
+            writer: Arc::new(Mutex::new(writer)),
             session: None,
+            vm,
         }
     }
 
@@ -101,8 +85,7 @@ where
                         .with_context(|| format!("Session '{}' not found", session_id))?
                 } else {
                     let new_id = uuid::Uuid::new_v4().to_string();
-                    let symbol_table = Arc::new(RwLock::new(SymbolTable::new()));
-                    let arc_session = Arc::new(Session::new(new_id.clone(), symbol_table));
+                    let arc_session = Arc::new(Session::new(new_id.clone(), self.vm.clone()));
 
                     // Register the session
                     session_manager.register(arc_session.clone()).await;
@@ -112,20 +95,10 @@ where
                     arc_session
                 };
                 {
-                    let mut ctx = session.eval_ctx.write();
+                    let mut ctx = session.eval_ctx.lock().await;
                     // Check if the context is null
                     if ctx.is_none() {
-                        let global_env = Arc::new(RwLock::new(Env::new()));
-
-                        let mut eval_ctx =
-                            EvalContext::new(global_env.clone(), session.symbol_table.clone());
-
-                        register_builtins(&mut eval_ctx);
-                        register_builtin_macros(&mut eval_ctx);
-                        register_complex_macros(&mut eval_ctx);
-
-                        preload_builtin_reader_macros(&mut eval_ctx);
-                        *ctx = Some(Box::new(eval_ctx));
+                        *ctx = Some(session_context(&session));
                     }
                 }
                 session.features.write().repl = true;
@@ -142,13 +115,67 @@ where
     pub async fn process(&mut self) -> anyhow::Result<()> {
         let session = self.session.as_ref().cloned().context("No session found")?;
         println!("Session pointer at process: {:?}", Arc::as_ptr(&session));
+        let mut previous_eval: Option<tokio::task::JoinHandle<()>> = None;
         loop {
             let message = self.read_message().await?.context("No message received")?;
             match message {
                 ReplRequest::Eval { id, code, pos } => {
-                    let response = self.handle_eval(id, code, pos).await?;
-
-                    self.write_message(&response).await?;
+                    // Evaluate off the connection task, so an Interrupt can be read while it runs.
+                    // Evaluations still run in the order they were sent.
+                    session.pending.lock().evals += 1;
+                    let session = session.clone();
+                    let writer = self.writer.clone();
+                    let previous = previous_eval.take();
+                    previous_eval = Some(tokio::spawn(async move {
+                        if let Some(previous) = previous {
+                            previous.await.ok();
+                        }
+                        if let Err(e) = run_eval(session, writer, id, code, pos).await {
+                            eprintln!("REPL eval error: {:?}", e);
+                        }
+                    }));
+                }
+                ReplRequest::SetLimits { id, fuel, timeout_ms, max_call_depth, max_alloc_bytes } => {
+                    let limits = ExecutionLimits {
+                        fuel,
+                        timeout: timeout_ms.map(Duration::from_millis),
+                        max_call_depth,
+                        max_alloc_bytes,
+                    };
+                    // Applies from the next evaluation, after the ones already sent
+                    let session = session.clone();
+                    let writer = self.writer.clone();
+                    let previous = previous_eval.take();
+                    previous_eval = Some(tokio::spawn(async move {
+                        if let Some(previous) = previous {
+                            previous.await.ok();
+                        }
+                        if let Some(ctx) = session.eval_ctx.lock().await.as_mut() {
+                            ctx.set_limits(limits);
+                        }
+                        if let Err(e) = write_msgpack_frame(&writer, &ReplResponse::LimitsSet { id }).await {
+                            eprintln!("REPL write error: {:?}", e);
+                        }
+                    }));
+                }
+                ReplRequest::Interrupt { id } => {
+                    // Answered by the evaluation it stops once that unwinds, whether it
+                    // is running or still queued
+                    let accepted = {
+                        let mut pending = session.pending.lock();
+                        if pending.evals > 0 {
+                            pending.interrupts.push(id.clone());
+                            session.cancel_token.cancel();
+                        }
+                        pending.evals > 0
+                    };
+                    if !accepted {
+                        let response = ReplResponse::Error {
+                            id,
+                            message: "No evaluation is running".to_string(),
+                        };
+                        self.write_message(&response).await?;
+                    }
                 }
                 ReplRequest::Close => {
                     break;
@@ -158,99 +185,328 @@ where
         }
         Ok(())
     }
+}
 
-    pub async fn handle_eval(
-        &mut self,
-        id: String,
-        code: String,
-        pos: Option<SourcePos>,
-    ) -> anyhow::Result<ReplResponse> {
-        println!("Received code literal: {:?}", &code);
+/// A context evaluating in the session's own module, interrupted through the session's token
+fn session_context(session: &Session) -> ExecutionContext<'static> {
+    let vm = session.vm.clone();
+    let module_id = vm.symbol_table.write().intern(&format!("user-{}", session.id));
+    vm.module_registry
+        .write()
+        .register_module(Module::new(module_id, SerializedModuleSource::Repl));
 
-        let source_pos = pos.unwrap_or(SourcePos { line: 0, col: 0 });
-        let session = self.session.as_ref().cloned().context("No session found")?;
+    let mut ctx = ExecutionContext::new(vm, module_id);
+    ctx.cancel_token = session.cancel_token.clone();
+    ctx
+}
 
-        // Parse (need context briefly)
-        let parsed = {
-            let mut ctx_guard = session.eval_ctx.write();
-            let ctx = ctx_guard
-                .as_deref_mut()
-                .ok_or(anyhow::anyhow!("No eval context found."))?;
-
-            let mut rctx = ctx.reader_macros.clone();
-            let mut rctx_guard = rctx.write();
-            let mut tokens = tokenize_at(&code, Some(source_pos))
-                .map_err(|e| anyhow::anyhow!("Failed to tokenize code: {}", e))?;
-            let symbol_table = ctx.symbol_table.clone();
-            let mut symbol_table_guard = symbol_table.write();
-            parse(&mut tokens, &mut *rctx_guard, &mut *symbol_table_guard)
-                .map_err(|e| anyhow::anyhow!("Failed to parse code: {}", e))?
-        }; // ctx_guard dropped here
-
-        let ast = {
-            let mut ctx_guard = session.eval_ctx.write();
-            let ctx = ctx_guard.as_deref_mut().unwrap();
-            ctx.alloc_parsed_value(parsed)
+/// Evaluate one request and write its response, then answer the Interrupt
+/// requests that arrived since it was accepted
+async fn run_eval<W>(
+    session: Arc<Session>,
+    writer: Arc<Mutex<W>>,
+    id: String,
+    code: String,
+    pos: Option<SourcePos>,
+) -> anyhow::Result<()>
+where
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    println!("Received code literal: {:?}", &code);
+
+    let mut ctx = session.eval_ctx.clone().lock_owned().await;
+
+    let finished = tokio::task::spawn_blocking(move || {
+        let result = match ctx.as_mut() {
+            Some(eval_ctx) => eval_code(eval_ctx, &code, pos),
+            None => Err(BlinkError::eval("No eval context found.")),
         };
+        (result, ctx)
+    })
+    .await;
+    // Reset under the lock, so an Interrupt that came after the evaluation finished
+    // is answered here and does not stop the next one
+    let interrupts = {
+        let mut pending = session.pending.lock();
+        pending.evals -= 1;
+        session.cancel_token.reset();
+        std::mem::take(&mut pending.interrupts)
+    };
+    let (result, mut ctx) = finished?;
+    let updates = ctx.as_mut().map(|ctx| profile_updates(ctx, &id)).unwrap_or_default();
+    let warnings = ctx.as_mut().map(|ctx| ctx.take_warnings()).unwrap_or_default();
+    drop(ctx);
+    if session.features.read().lsp {
+        session.warnings.lock().extend(warnings.iter().cloned());
+    }
 
-        // Start evaluation
-        let mut current_result = {
-            let mut ctx_guard = session.eval_ctx.write();
-            let ctx = ctx_guard.as_deref_mut().unwrap();
-
-            eval::eval(ast, ctx)
-        }; // ctx_guard dropped here
-
-        // Handle suspension iteratively - no locks held during await
-        let final_value = loop {
-            match current_result {
-                EvalResult::Value(value) => break value,
-
-                EvalResult::Suspended { future, resume } => {
-                    // Await the future (no locks held - this is Send safe!)
-                    let val = future.await;
-
-                    // Re-acquire lock only for resume
-                    current_result = {
-                        let mut ctx_guard = session.eval_ctx.write();
-                        let ctx = ctx_guard.as_deref_mut().unwrap();
-                        if ctx.is_err(&val) {
-                            let context = ValueContext::new(ctx.shared_arena.clone());
-                            let contextual_val = ContextualValueRef::new(val, context);
-                            return Err(anyhow::anyhow!("Error: {}", contextual_val));
-                        }
-                        resume(val, ctx)
-                    }; // ctx_guard dropped again
-                }
+    let interrupted = matches!(&result, Err(BlinkError { error_type: BlinkErrorType::Interrupted, .. }));
+    let response = match result {
+        Ok(value) => ReplResponse::EvalResult { id: id.clone(), value },
+        Err(e) => ReplResponse::Error { id: id.clone(), message: e.to_string() },
+    };
+    write_msgpack_frame(&writer, &response).await?;
+
+    for message in warnings {
+        write_msgpack_frame(&writer, &ReplResponse::Warning { id: id.clone(), message }).await?;
+    }
+
+    // Stream the functions whose profile changed during this evaluation
+    for update in updates {
+        write_msgpack_frame(&writer, &update).await?;
+    }
+
+    for interrupt_id in interrupts {
+        let response = if interrupted {
+            ReplResponse::Interrupted { id: interrupt_id }
+        } else {
+            ReplResponse::Error {
+                id: interrupt_id,
+                message: "The evaluation finished before it could be interrupted".to_string(),
             }
         };
+        write_msgpack_frame(&writer, &response).await?;
+    }
+    Ok(())
+}
+
+/// Parse and evaluate every form of the code, returning the printed value of the last
+fn eval_code(ctx: &mut ExecutionContext, code: &str, pos: Option<SourcePos>) -> Result<String, BlinkError> {
+    let source_pos = pos.unwrap_or(SourcePos { line: 0, col: 0 });
+    let vm = ctx.vm.clone();
+    let mut tokens = tokenize_at(code, Some(source_pos))?;
+
+    let mut value = String::new();
+    while !tokens.is_empty() {
+        let parsed = {
+            let mut symbol_table = vm.symbol_table.write();
+            let reader_macros = vm.reader_macros.read();
+            parse(&mut tokens, &reader_macros, &mut symbol_table)?
+        };
+        let ast = vm.alloc_parsed_value(parsed);
+        value = ctx.compile_and_execute(ast)?.to_string();
+    }
+    Ok(value)
+}
 
-        let ctx = session.eval_ctx.clone();
-        let ctx_guard = ctx.read();
-        let ctx_guard_ref = ctx_guard.as_deref().unwrap();
-        let symbol_table = session.symbol_table.clone();
-        let mut symbol_table_guard = symbol_table.write();
-        // Update symbols after successful evaluation
-        {
-            // TODO need a fn to collect symbols from evaled forms
-            let mut symbols = session.symbols.write();
-            //collect_symbols_from_forms(&mut symbols, &vec![ast], SymbolSource::Repl, &mut *symbol_table_guard);
+pub async fn write_msgpack_frame<W>(writer: &Mutex<W>, msg: &ReplResponse) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    println!("DEBUG: Type is {}", std::any::type_name::<ReplResponse>());
+    println!(
+        "DEBUG: JSON representation = {}",
+        serde_json::to_string(msg).unwrap()
+    );
+    let mut buf = Vec::new();
+    {
+        let mut serializer = Serializer::new(&mut buf).with_struct_map(); // force map encoding
+        msg.serialize(&mut serializer)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    }
+    let len = (buf.len() as u32).to_be_bytes();
+    let mut writer = writer.lock().await;
+    writer.write_all(&len).await?;
+    writer.write_all(&buf).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// The profile changes of the functions the session's evaluation ran,
+/// including those made before the evaluation stopped the profiler
+fn profile_updates(ctx: &mut ExecutionContext, id: &str) -> Vec<ReplResponse> {
+    ctx.take_profile_updates()
+        .into_iter()
+        .map(|(function, data)| ReplResponse::ProfileUpdate {
+            id: id.to_string(),
+            function,
+            calls: data.calls,
+            avg_time_us: (data.total_time.as_micros() / data.calls.max(1) as u128) as u64,
+            max_time_us: data.max_time.as_micros() as u64,
+            self_time_us: data.self_time.as_micros() as u64,
+            total_time_us: data.total_time.as_micros() as u64,
+            alloc_bytes: data.alloc_bytes,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{duplex, split, DuplexStream, ReadHalf, WriteHalf};
+
+    use super::*;
+    use crate::test_support::test_vm;
+
+    struct Client {
+        reader: ReadHalf<DuplexStream>,
+        writer: WriteHalf<DuplexStream>,
+    }
+
+    impl Client {
+        async fn send(&mut self, request: &ReplRequest) {
+            let buf = rmp_serde::to_vec_named(request).unwrap();
+            self.writer.write_all(&(buf.len() as u32).to_be_bytes()).await.unwrap();
+            self.writer.write_all(&buf).await.unwrap();
         }
 
-        let contextual_val = ctx_guard_ref.get_contextual_value(final_value);
-        // Create response
-        let response = if ctx_guard_ref.is_err(&final_value) {
-            ReplResponse::Error {
-                id,
-                message: contextual_val.to_string(),
+        async fn receive(&mut self) -> ReplResponse {
+            let mut len = [0u8; 4];
+            self.reader.read_exact(&mut len).await.unwrap();
+            let mut buf = vec![0u8; u32::from_be_bytes(len) as usize];
+            self.reader.read_exact(&mut buf).await.unwrap();
+            from_slice(&buf).unwrap()
+        }
+    }
+
+    // A handler serving one connection, with a session already initialized
+    async fn connect() -> Client {
+        connect_with_session().await.0
+    }
+
+    async fn connect_with_session() -> (Client, Arc<Session>) {
+        let (client, server) = duplex(64 * 1024);
+        let (server_reader, server_writer) = split(server);
+        let (reader, writer) = split(client);
+        let mut client = Client { reader, writer };
+
+        client.send(&ReplRequest::Init { id: "init".to_string(), session_id: None }).await;
+        let mut handler = ReplHandler::new(BufReader::new(server_reader), server_writer, test_vm());
+        handler.init(Arc::new(SessionManager::new())).await.unwrap();
+        let session = handler.session.clone().unwrap();
+        tokio::spawn(async move { handler.process().await });
+        assert!(matches!(client.receive().await, ReplResponse::Initialized { .. }));
+        (client, session)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn interrupt_stops_a_running_loop() {
+        let mut client = connect().await;
+        let code = "(loop [i 0] (recur (+ i 1)))".to_string();
+        client.send(&ReplRequest::Eval { id: "eval".to_string(), code, pos: None }).await;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        client.send(&ReplRequest::Interrupt { id: "interrupt".to_string() }).await;
+
+        match client.receive().await {
+            ReplResponse::Error { id, message } => {
+                assert_eq!(id, "eval");
+                assert!(message.starts_with("Interrupted"), "{}", message);
             }
-        } else {
-            ReplResponse::EvalResult {
-                id,
-                value: format!("{}", contextual_val),
+            other => panic!("expected the eval to fail, got {:?}", other),
+        }
+        assert!(matches!(client.receive().await, ReplResponse::Interrupted { id } if id == "interrupt"));
+
+        // The session is usable afterwards
+        client.send(&ReplRequest::Eval { id: "next".to_string(), code: "(+ 1 2)".to_string(), pos: None }).await;
+        assert!(matches!(client.receive().await, ReplResponse::EvalResult { value, .. } if value == "3"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn interrupt_stops_an_eval_still_queued() {
+        let (mut client, session) = connect_with_session().await;
+        // Another connection's evaluation holds the context
+        let running = session.eval_ctx.clone().lock_owned().await;
+        let code = "(def started 1) (loop [i 0] (recur (+ i 1)))".to_string();
+        client.send(&ReplRequest::Eval { id: "eval".to_string(), code, pos: None }).await;
+        client.send(&ReplRequest::Interrupt { id: "interrupt".to_string() }).await;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        drop(running);
+
+        // Sent while the eval was queued, the interrupt is not cleared as each of its forms starts
+        match client.receive().await {
+            ReplResponse::Error { id, message } => {
+                assert_eq!(id, "eval");
+                assert!(message.starts_with("Interrupted"), "{}", message);
             }
+            other => panic!("expected the eval to fail, got {:?}", other),
+        }
+        assert!(matches!(client.receive().await, ReplResponse::Interrupted { id } if id == "interrupt"));
+
+        // The next eval is not interrupted
+        client.send(&ReplRequest::Eval { id: "next".to_string(), code: "(+ 1 2)".to_string(), pos: None }).await;
+        assert!(matches!(client.receive().await, ReplResponse::EvalResult { value, .. } if value == "3"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn session_limits_abort_runaway_evals() {
+        let mut client = connect().await;
+        let limits = ReplRequest::SetLimits {
+            id: "limits".to_string(),
+            fuel: Some(10_000),
+            timeout_ms: None,
+            max_call_depth: None,
+            max_alloc_bytes: None,
         };
+        client.send(&limits).await;
+        assert!(matches!(client.receive().await, ReplResponse::LimitsSet { id } if id == "limits"));
+
+        let code = "(loop [i 0] (recur (+ i 1)))".to_string();
+        client.send(&ReplRequest::Eval { id: "eval".to_string(), code, pos: None }).await;
+        match client.receive().await {
+            ReplResponse::Error { id, message } => {
+                assert_eq!(id, "eval");
+                assert!(message.contains("instruction fuel"), "{}", message);
+            }
+            other => panic!("expected the eval to run out of fuel, got {:?}", other),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn profiled_evals_stream_updates_and_collapsed_stacks() {
+        let mut client = connect().await;
+        let code = "(def work (fn [n] (loop [i 0 acc 0] (if (< i n) (recur (+ i 1) (+ acc i)) acc))))".to_string();
+        client.send(&ReplRequest::Eval { id: "def".to_string(), code, pos: None }).await;
+        assert!(matches!(client.receive().await, ReplResponse::EvalResult { .. }));
+
+        // Stopping the profiler in the same evaluation keeps its updates
+        let code = "(do (profile-start) (work 5000) (profile-stop) (profile-collapsed))".to_string();
+        client.send(&ReplRequest::Eval { id: "profile".to_string(), code, pos: None }).await;
+        match client.receive().await {
+            ReplResponse::EvalResult { value, .. } => {
+                let stack = value.lines().find(|line| line.contains(";work ")).unwrap_or_else(|| panic!("{}", value));
+                let count: u64 = stack.rsplit(' ').next().unwrap().trim_matches('"').parse().unwrap();
+                assert!(count > 0, "{}", stack);
+            }
+            other => panic!("expected the collapsed stacks, got {:?}", other),
+        }
+        match client.receive().await {
+            ReplResponse::ProfileUpdate { id, function, calls, total_time_us, self_time_us, .. } => {
+                assert_eq!((id.as_str(), function.as_str(), calls), ("profile", "work", 1));
+                assert!(self_time_us <= total_time_us);
+            }
+            other => panic!("expected a profile update, got {:?}", other),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn interrupt_without_a_running_eval_is_an_error() {
+        let mut client = connect().await;
+        client.send(&ReplRequest::Interrupt { id: "interrupt".to_string() }).await;
+        assert!(matches!(client.receive().await, ReplResponse::Error { id, .. } if id == "interrupt"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn evals_report_stale_macro_expansions_as_warnings() {
+        let mut client = connect().await;
+        let code = "(def twice (macro [x] `(* 2 ~x))) (def f (fn [] (twice 5)))".to_string();
+        client.send(&ReplRequest::Eval { id: "def".to_string(), code, pos: None }).await;
+        assert!(matches!(client.receive().await, ReplResponse::EvalResult { .. }));
+
+        let code = "(def twice (macro [x] `(* 3 ~x)))".to_string();
+        client.send(&ReplRequest::Eval { id: "redef".to_string(), code, pos: None }).await;
+        assert!(matches!(client.receive().await, ReplResponse::EvalResult { id, .. } if id == "redef"));
+        match client.receive().await {
+            ReplResponse::Warning { id, message } => {
+                assert_eq!(id, "redef");
+                assert!(message.contains("twice was redefined"), "{}", message);
+                assert!(message.contains("/f were compiled"), "{}", message);
+            }
+            other => panic!("expected a warning, got {:?}", other),
+        }
 
-        Ok(response)
+        // Each warning is sent once
+        client.send(&ReplRequest::Eval { id: "next".to_string(), code: "(f)".to_string(), pos: None }).await;
+        assert!(matches!(client.receive().await, ReplResponse::EvalResult { value, .. } if value == "10"));
+        client.send(&ReplRequest::Interrupt { id: "interrupt".to_string() }).await;
+        assert!(matches!(client.receive().await, ReplResponse::Error { id, .. } if id == "interrupt"));
     }
 }
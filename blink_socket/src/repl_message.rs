@@ -10,6 +10,15 @@ pub enum ReplRequest {
     },
     Close,
     Eval { id: String, code: String, pos: Option<SourcePos> },
+    Interrupt { id: String },
+    // Limits for the session's later evaluations; a missing field is unlimited
+    SetLimits {
+        id: String,
+        fuel: Option<u64>,
+        timeout_ms: Option<u64>,
+        max_call_depth: Option<usize>,
+        max_alloc_bytes: Option<usize>,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -26,14 +35,29 @@ pub enum ReplResponse {
         id: String,
         message: String,
     },
+    Interrupted {
+        id: String,
+    },
+    // Sent after the result of the evaluation that recorded it
+    Warning {
+        id: String,
+        message: String,
+    },
+    LimitsSet {
+        id: String,
+    },
     Telemetry {
         id: String,
         event: TelemetryEvent,
     },
     ProfileUpdate {
         id: String,
+        function: String,
         calls: u64,
-        avg_time_us: u128,
-        max_time_us: u128,
+        avg_time_us: u64,
+        max_time_us: u64,
+        self_time_us: u64,
+        total_time_us: u64,
+        alloc_bytes: usize,
     },
 }
@@ -1,8 +1,8 @@
 use core::fmt;
 use std::{collections::HashMap, fmt::Display, sync::Arc, time::Instant};
 
-use blink_core::{ eval::EvalContext, runtime::SymbolTable, value::{ParsedValue, ParsedValueWithPos, SourceRange}};
-use parking_lot::RwLock;
+use blink_core::{ runtime::{BlinkVM, CancellationToken, ExecutionContext}, value::{ParsedValue, ParsedValueWithPos, SourceRange}};
+use parking_lot::{Mutex, RwLock};
 use serde::{Deserialize, Serialize};
 
 use crate::{lsp_messages::LspMessage, repl_message::ReplResponse};
@@ -100,30 +100,48 @@ pub struct Document {
     pub forms: Vec<ParsedValueWithPos>,
 }
 
+/// Evaluations a session accepted, counted from the request until its response,
+/// so an Interrupt also reaches one still queued behind another
+#[derive(Default)]
+pub struct PendingEvals {
+    pub evals: usize,
+    // Ids of the Interrupt requests, answered by the evaluation they stop
+    pub interrupts: Vec<String>,
+}
+
 pub struct Session {
     pub id: String,
     pub features: RwLock<SessionFeatures>,
     pub documents: RwLock<HashMap<String, Document>>,
-    // Only filled after REPL attached
-    pub eval_ctx: Arc<RwLock<Option<Box<EvalContext>>>>,
+    // Only filled after REPL attached. Held by the running evaluation, so evaluations run one at a time
+    pub eval_ctx: Arc<tokio::sync::Mutex<Option<ExecutionContext<'static>>>>,
     pub connected_at: RwLock<Instant>,
     pub last_activity: RwLock<Instant>,
     pub symbols: RwLock<HashMap<String, SymbolInfo>>,
-    pub symbol_table: Arc<RwLock<SymbolTable>>,
+    pub vm: Arc<BlinkVM>,
+    // Shared with the running evaluation so any connection on this session can interrupt it
+    pub cancel_token: CancellationToken,
+    // Evaluations sent on any connection and not answered yet, with the Interrupts waiting for them
+    pub pending: Mutex<PendingEvals>,
+    // Warnings from evaluations the LSP connection has not shown yet; only kept
+    // while one is attached
+    pub warnings: Mutex<Vec<String>>,
 }
 
 impl Session {
-    pub fn new(id: String, symbol_table: Arc<RwLock<SymbolTable>>) -> Self {
+    pub fn new(id: String, vm: Arc<BlinkVM>) -> Self {
         Self {
             id: id,
             features: RwLock::new(SessionFeatures::default()),
             documents: RwLock::new(HashMap::new()),
-            eval_ctx: Arc::new(RwLock::new(None)),
+            eval_ctx: Arc::new(tokio::sync::Mutex::new(None)),
             symbols: RwLock::new(HashMap::new()),   
             connected_at: RwLock::new(Instant::now()),
             last_activity: RwLock::new(Instant::now()),
-            symbol_table: symbol_table,
-            
+            vm,
+            cancel_token: CancellationToken::new(),
+            pending: Mutex::new(PendingEvals::default()),
+            warnings: Mutex::new(Vec::new()),
         }
     }
 }
@@ -1,5 +1,5 @@
 use crate::session::Session;
-use blink_core::eval::EvalContext;
+use blink_core::runtime::ExecutionContext;
 use parking_lot::RwLock;
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -8,7 +8,7 @@ use std::sync::Arc;
 #[derive(Default)]
 pub struct SessionManager {
     sessions: RwLock<HashMap<String, Arc<Session>>>,
-    saved_repl_sessions: RwLock<HashMap<String, Arc<RwLock<Option<Box<EvalContext>>>>>>
+    saved_repl_sessions: RwLock<HashMap<String, Arc<tokio::sync::Mutex<Option<ExecutionContext<'static>>>>>>
 
 }
 
@@ -56,7 +56,7 @@ impl SessionManager {
         Ok(())
     }
 
-    pub async fn get_persisted(&self, id: &str) -> Option<Arc<RwLock<Option<Box<EvalContext>>>>> {
+    pub async fn get_persisted(&self, id: &str) -> Option<Arc<tokio::sync::Mutex<Option<ExecutionContext<'static>>>>> {
         let saved = self.saved_repl_sessions.read();
         saved.get(id).cloned()
     }
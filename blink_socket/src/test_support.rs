@@ -0,0 +1,10 @@
+use std::sync::{Arc, OnceLock};
+
+use blink_core::runtime::BlinkVM;
+
+static VM: OnceLock<Arc<BlinkVM>> = OnceLock::new();
+
+/// The process VM, created once for every test in the binary
+pub fn test_vm() -> Arc<BlinkVM> {
+    VM.get_or_init(BlinkVM::new_arc).clone()
+}